@@ -0,0 +1,198 @@
+//! Procedural macros for `ceres-solver` crate, use them via `ceres_solver::autodiff` module with
+//! `derive` Cargo feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Error, Expr, FnArg, GenericParam, ImplItem, ImplItemFn, ItemImpl,
+    ReturnType, Type,
+};
+
+/// Implement `AutoDiffCostFunctor` for a type from its inherent `residuals` method.
+///
+/// The method must be generic over a single scalar type `T`, take `&self` and references to
+/// fixed-size arrays `&[T; N]`, one per parameter block, and return an array `[T; R]` of
+/// residuals. The array sizes define the parameter block sizes and the number of residuals.
+///
+/// The macro also adds `into_cost_function(self)` method returning
+/// `AutoDiffCostFunction<Self, _>` with the total number of parameter components. The total
+/// is a sum of the array sizes, so the sizes must not depend on generic parameters of the type
+/// when there are several parameter blocks.
+///
+/// See `ceres_solver::autodiff` documentation for an example.
+#[proc_macro_attribute]
+pub fn residual(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return Error::new(
+            Span::call_site(),
+            "#[residual] attribute doesn't accept arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let item_impl = parse_macro_input!(item as ItemImpl);
+    expand(item_impl)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(item_impl: ItemImpl) -> syn::Result<proc_macro2::TokenStream> {
+    if let Some((_, path, _)) = &item_impl.trait_ {
+        return Err(Error::new(
+            path.span(),
+            "#[residual] must be applied to an inherent impl block",
+        ));
+    }
+    let method = item_impl
+        .items
+        .iter()
+        .find_map(|item| match item {
+            ImplItem::Fn(method) if method.sig.ident == "residuals" => Some(method),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            Error::new(
+                item_impl.self_ty.span(),
+                "#[residual] impl block must have `residuals` method",
+            )
+        })?;
+    let signature = parse_signature(method)?;
+
+    let (impl_generics, _, where_clause) = item_impl.generics.split_for_impl();
+    let self_ty = &item_impl.self_ty;
+    let num_residuals = &signature.num_residuals;
+    let parameter_sizes = &signature.parameter_sizes;
+    let indices = 0..parameter_sizes.len();
+    let blocks: Vec<_> = (0..parameter_sizes.len())
+        .map(|index| syn::Ident::new(&format!("block{index}"), Span::call_site()))
+        .collect();
+    let total_size = if parameter_sizes.is_empty() {
+        quote!(0)
+    } else {
+        quote!(#(#parameter_sizes)+*)
+    };
+
+    Ok(quote! {
+        #item_impl
+
+        impl #impl_generics ::ceres_solver::autodiff::AutoDiffCostFunctor for #self_ty #where_clause {
+            fn residuals<__T: ::ceres_solver::autodiff::Scalar>(
+                &self,
+                parameters: &[&[__T]],
+                residuals: &mut [__T],
+            ) -> bool {
+                #(
+                    let #blocks: &[__T; #parameter_sizes] =
+                        match ::std::convert::TryInto::try_into(parameters[#indices]) {
+                            Ok(block) => block,
+                            Err(_) => return false,
+                        };
+                )*
+                let output: [__T; #num_residuals] = Self::residuals::<__T>(self, #(#blocks),*);
+                residuals.copy_from_slice(&output);
+                true
+            }
+
+            fn num_residuals(&self) -> usize {
+                #num_residuals
+            }
+
+            fn parameter_sizes(&self) -> ::std::vec::Vec<usize> {
+                ::std::vec![#(#parameter_sizes),*]
+            }
+        }
+
+        impl #impl_generics #self_ty #where_clause {
+            /// Wrap into a cost function differentiated automatically, see
+            /// [AutoDiffCostFunction](::ceres_solver::autodiff::AutoDiffCostFunction).
+            pub fn into_cost_function(
+                self,
+            ) -> ::ceres_solver::autodiff::AutoDiffCostFunction<Self, { #total_size }> {
+                ::ceres_solver::autodiff::AutoDiffCostFunction::new(self)
+            }
+        }
+    })
+}
+
+struct Signature {
+    parameter_sizes: Vec<Expr>,
+    num_residuals: Expr,
+}
+
+fn parse_signature(method: &ImplItemFn) -> syn::Result<Signature> {
+    let sig = &method.sig;
+    let mut type_params = sig.generics.params.iter().filter_map(|param| match param {
+        GenericParam::Type(param) => Some(param),
+        _ => None,
+    });
+    let scalar =
+        match (type_params.next(), type_params.next()) {
+            (Some(param), None) if sig.generics.params.len() == 1 => param.ident.clone(),
+            _ => return Err(Error::new(
+                sig.generics.span(),
+                "`residuals` must have a single generic scalar type parameter, e.g. `<T: Scalar>`",
+            )),
+        };
+
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(receiver))
+            if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+        _ => {
+            return Err(Error::new(
+                sig.inputs.span(),
+                "`residuals` must take `&self` as the first argument",
+            ))
+        }
+    }
+    let parameter_sizes = inputs
+        .map(|input| {
+            let FnArg::Typed(typed) = input else {
+                unreachable!("receiver can be the first argument only")
+            };
+            match &*typed.ty {
+                Type::Reference(reference) if reference.mutability.is_none() => {
+                    array_size(&reference.elem, &scalar)
+                }
+                _ => None,
+            }
+            .ok_or_else(|| {
+                Error::new(
+                    typed.ty.span(),
+                    format!("parameter block must have `&[{scalar}; N]` type"),
+                )
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let num_residuals = match &sig.output {
+        ReturnType::Type(_, ty) => array_size(ty, &scalar),
+        ReturnType::Default => None,
+    }
+    .ok_or_else(|| {
+        Error::new(
+            sig.output.span(),
+            format!("`residuals` must return `[{scalar}; R]`"),
+        )
+    })?;
+
+    Ok(Signature {
+        parameter_sizes,
+        num_residuals,
+    })
+}
+
+/// Size of `[scalar; N]` array type.
+fn array_size(ty: &Type, scalar: &syn::Ident) -> Option<Expr> {
+    let Type::Array(array) = ty else {
+        return None;
+    };
+    match &*array.elem {
+        Type::Path(path) if path.qself.is_none() && path.path.is_ident(scalar) => {
+            Some(array.len.clone())
+        }
+        _ => None,
+    }
+}
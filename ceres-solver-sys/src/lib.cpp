@@ -2,6 +2,14 @@
 // Created by Konstantin Malanchev on 2023-01-24.
 //
 
+#include <cstring>
+#include <limits>
+#include <mutex>
+#include <unordered_set>
+
+#include <ceres/line_manifold.h>
+#include <ceres/product_manifold.h>
+#include <ceres/sphere_manifold.h>
 #include <glog/logging.h>
 
 #include "ceres-solver-sys/src/lib.h"
@@ -57,10 +65,110 @@ namespace ceres {
     std::unique_ptr<LossFunction> new_tukey_loss(double a) {
         return std::make_unique<TukeyLoss>(a);
     }
+    std::unique_ptr<LossFunction> new_scaled_loss(std::unique_ptr<LossFunction> loss_function, double a) {
+        return std::make_unique<ScaledLoss>(loss_function.release(), a, TAKE_OWNERSHIP);
+    }
+    std::unique_ptr<LossFunctionWrapper> new_loss_function_wrapper(std::unique_ptr<LossFunction> loss_function) {
+        return std::make_unique<LossFunctionWrapper>(loss_function.release(), TAKE_OWNERSHIP);
+    }
+    void reset_loss_function_wrapper(LossFunctionWrapper& wrapper, std::unique_ptr<LossFunction> loss_function) {
+        wrapper.Reset(loss_function.release(), TAKE_OWNERSHIP);
+    }
+    LossFunction* loss_function_wrapper_as_loss_function(LossFunctionWrapper* wrapper) {
+        return wrapper;
+    }
+
+    std::unique_ptr<Manifold> new_subset_manifold(int size, rust::Slice<const int32_t> constant_parameters) {
+        return std::make_unique<SubsetManifold>(
+            size, std::vector<int>(constant_parameters.begin(), constant_parameters.end()));
+    }
+    std::unique_ptr<Manifold> new_quaternion_manifold() {
+        return std::make_unique<QuaternionManifold>();
+    }
+    std::unique_ptr<Manifold> new_eigen_quaternion_manifold() {
+        return std::make_unique<EigenQuaternionManifold>();
+    }
+    std::unique_ptr<Manifold> new_sphere_manifold(int size) {
+        return std::make_unique<SphereManifold<DYNAMIC>>(size);
+    }
+    std::unique_ptr<Manifold> new_line_manifold(int size) {
+        return std::make_unique<LineManifold<DYNAMIC>>(size);
+    }
+    std::unique_ptr<Manifold> new_euclidean_manifold(int size) {
+        return std::make_unique<EuclideanManifold<DYNAMIC>>(size);
+    }
+    std::unique_ptr<Manifold> new_product_manifold(std::unique_ptr<Manifold> first, std::unique_ptr<Manifold> second) {
+        return std::make_unique<ProductManifold<std::unique_ptr<Manifold>, std::unique_ptr<Manifold>>>(
+            std::move(first), std::move(second));
+    }
+    CallbackManifold::CallbackManifold(rust::Box<RustManifold> inner, int ambient_size, int tangent_size):
+        inner(std::move(inner)), ambient_size(ambient_size), tangent_size(tangent_size) {}
+    int CallbackManifold::AmbientSize() const {
+        return ambient_size;
+    }
+    int CallbackManifold::TangentSize() const {
+        return tangent_size;
+    }
+    bool CallbackManifold::Plus(const double* x, const double* delta, double* x_plus_delta) const {
+        return inner->plus(x, delta, x_plus_delta);
+    }
+    bool CallbackManifold::PlusJacobian(const double* x, double* jacobian) const {
+        return inner->plus_jacobian(x, jacobian);
+    }
+    bool CallbackManifold::Minus(const double* y, const double* x, double* y_minus_x) const {
+        return inner->minus(y, x, y_minus_x);
+    }
+    bool CallbackManifold::MinusJacobian(const double* x, double* jacobian) const {
+        return inner->minus_jacobian(x, jacobian);
+    }
+    std::unique_ptr<Manifold> new_callback_manifold(rust::Box<RustManifold> inner, int ambient_size, int tangent_size) {
+        return std::make_unique<CallbackManifold>(std::move(inner), ambient_size, tangent_size);
+    }
+
+    CallbackEvaluationCallback::CallbackEvaluationCallback(rust::Box<RustEvaluationCallback> inner):
+        inner(std::move(inner)) {}
+    void CallbackEvaluationCallback::PrepareForEvaluation(bool evaluate_jacobians, bool new_evaluation_point) {
+        inner->prepare_for_evaluation(evaluate_jacobians, new_evaluation_point);
+    }
+    std::unique_ptr<CallbackEvaluationCallback> new_callback_evaluation_callback(rust::Box<RustEvaluationCallback> inner) {
+        return std::make_unique<CallbackEvaluationCallback>(std::move(inner));
+    }
 
     std::unique_ptr<Problem> new_problem() {
         return std::make_unique<Problem>();
     }
+    std::unique_ptr<Context> new_context() {
+        return std::unique_ptr<Context>(Context::Create());
+    }
+    std::unique_ptr<Problem> new_problem_with_options(const RustProblemOptions& options,
+                                                      CallbackEvaluationCallback* evaluation_callback,
+                                                      Context* context) {
+        Problem::Options problem_options;
+        problem_options.cost_function_ownership = options.cost_function_ownership;
+        problem_options.loss_function_ownership = options.loss_function_ownership;
+        problem_options.manifold_ownership = options.manifold_ownership;
+        problem_options.enable_fast_removal = options.enable_fast_removal;
+        problem_options.disable_all_safety_checks = options.disable_all_safety_checks;
+        problem_options.evaluation_callback = evaluation_callback;
+        problem_options.context = context;
+        return std::make_unique<Problem>(problem_options);
+    }
+    void set_parameter_bounds(Problem& problem,
+                              double* values,
+                              rust::Slice<const double> lower_bounds,
+                              rust::Slice<const double> upper_bounds) {
+        constexpr auto unbounded = std::numeric_limits<double>::max();
+        for (size_t i = 0; i < lower_bounds.size(); ++i) {
+            if (lower_bounds[i] != -unbounded) {
+                problem.SetParameterLowerBound(values, i, lower_bounds[i]);
+            }
+        }
+        for (size_t i = 0; i < upper_bounds.size(); ++i) {
+            if (upper_bounds[i] != unbounded) {
+                problem.SetParameterUpperBound(values, i, upper_bounds[i]);
+            }
+        }
+    }
     std::shared_ptr<ResidualBlockId> add_residual_block(Problem& problem,
                                                         std::unique_ptr<CallbackCostFunction> cost_function,
                                                         std::unique_ptr<LossFunction> loss_function,
@@ -72,6 +180,244 @@ namespace ceres {
                                                  num_parameter_blocks);
         return std::make_shared<ResidualBlockId>(block_id);
     }
+    std::shared_ptr<ResidualBlockId> add_residual_block_not_owned(Problem& problem,
+                                                                  CallbackCostFunction* cost_function,
+                                                                  LossFunction* loss_function,
+                                                                  double* const* const parameter_blocks,
+                                                                  int num_parameter_blocks) {
+        auto block_id = problem.AddResidualBlock(cost_function,
+                                                 loss_function,
+                                                 parameter_blocks,
+                                                 num_parameter_blocks);
+        return std::make_shared<ResidualBlockId>(block_id);
+    }
+    void add_residual_blocks_not_owned(Problem& problem,
+                                       CallbackCostFunction* const* cost_functions,
+                                       LossFunction* const* loss_functions,
+                                       double* const* parameter_blocks,
+                                       rust::Slice<const int32_t> num_parameter_blocks,
+                                       rust::Slice<std::shared_ptr<ResidualBlockId>> residual_block_ids) {
+        for (size_t i = 0; i < num_parameter_blocks.size(); ++i) {
+            auto block_id = problem.AddResidualBlock(cost_functions[i],
+                                                     loss_functions[i],
+                                                     parameter_blocks,
+                                                     num_parameter_blocks[i]);
+            residual_block_ids[i] = std::make_shared<ResidualBlockId>(block_id);
+            parameter_blocks += num_parameter_blocks[i];
+        }
+    }
+    void remove_residual_block(Problem& problem, const std::shared_ptr<ResidualBlockId>& residual_block) {
+        problem.RemoveResidualBlock(*residual_block);
+    }
+    double ProblemEvaluation::cost() const {
+        return cost_;
+    }
+    const std::vector<double>& ProblemEvaluation::residuals() const {
+        return residuals_;
+    }
+    const std::vector<double>& ProblemEvaluation::gradient() const {
+        return gradient_;
+    }
+    int ProblemEvaluation::jacobian_num_rows() const {
+        return jacobian_.num_rows;
+    }
+    int ProblemEvaluation::jacobian_num_cols() const {
+        return jacobian_.num_cols;
+    }
+    const std::vector<int>& ProblemEvaluation::jacobian_rows() const {
+        return jacobian_.rows;
+    }
+    const std::vector<int>& ProblemEvaluation::jacobian_cols() const {
+        return jacobian_.cols;
+    }
+    const std::vector<double>& ProblemEvaluation::jacobian_values() const {
+        return jacobian_.values;
+    }
+    std::unique_ptr<ProblemEvaluation> evaluate_problem(Problem& problem,
+                                                        double* const* const parameter_blocks,
+                                                        int num_parameter_blocks,
+                                                        rust::Slice<const std::shared_ptr<ResidualBlockId>> residual_blocks,
+                                                        bool apply_loss_function,
+                                                        int num_threads,
+                                                        bool compute_jacobian) {
+        Problem::EvaluateOptions options;
+        options.parameter_blocks.assign(parameter_blocks, parameter_blocks + num_parameter_blocks);
+        for (const auto& block : residual_blocks) {
+            options.residual_blocks.push_back(*block);
+        }
+        options.apply_loss_function = apply_loss_function;
+        options.num_threads = num_threads;
+        auto evaluation = std::make_unique<ProblemEvaluation>();
+        if (!problem.Evaluate(options, &evaluation->cost_, &evaluation->residuals_, &evaluation->gradient_,
+                              compute_jacobian ? &evaluation->jacobian_ : nullptr)) {
+            return nullptr;
+        }
+        return evaluation;
+    }
+    double ResidualBlockEvaluation::cost() const {
+        return cost_;
+    }
+    const std::vector<double>& ResidualBlockEvaluation::residuals() const {
+        return residuals_;
+    }
+    size_t ResidualBlockEvaluation::num_jacobians() const {
+        return jacobians_.size();
+    }
+    bool ResidualBlockEvaluation::has_jacobian(size_t index) const {
+        return has_jacobians_.at(index);
+    }
+    const std::vector<double>& ResidualBlockEvaluation::jacobian(size_t index) const {
+        return jacobians_.at(index);
+    }
+    std::unique_ptr<ResidualBlockEvaluation> evaluate_residual_block(const Problem& problem,
+                                                                     const std::shared_ptr<ResidualBlockId>& residual_block,
+                                                                     bool apply_loss_function) {
+        const auto num_residuals = problem.GetCostFunctionForResidualBlock(*residual_block)->num_residuals();
+        std::vector<double*> parameter_blocks;
+        problem.GetParameterBlocksForResidualBlock(*residual_block, &parameter_blocks);
+        auto evaluation = std::make_unique<ResidualBlockEvaluation>();
+        evaluation->residuals_.resize(num_residuals);
+        std::vector<double*> jacobians;
+        for (const auto block : parameter_blocks) {
+            // Ceres fails if a jacobian is requested for a constant parameter block.
+            if (problem.IsParameterBlockConstant(block)) {
+                evaluation->jacobians_.emplace_back();
+                evaluation->has_jacobians_.push_back(false);
+                jacobians.push_back(nullptr);
+                continue;
+            }
+            // Jacobians are computed with respect to the tangent space of the parameter block.
+            evaluation->jacobians_.emplace_back(num_residuals * problem.ParameterBlockTangentSize(block));
+            evaluation->has_jacobians_.push_back(true);
+            jacobians.push_back(evaluation->jacobians_.back().data());
+        }
+        if (!problem.EvaluateResidualBlock(*residual_block, apply_loss_function, &evaluation->cost_,
+                                           evaluation->residuals_.data(), jacobians.data())) {
+            return nullptr;
+        }
+        return evaluation;
+    }
+    std::unique_ptr<std::vector<size_t>> residual_blocks_for_parameter_block(const Problem& problem,
+                                                                             const double* values,
+                                                                             rust::Slice<const std::shared_ptr<ResidualBlockId>> residual_blocks) {
+        std::vector<ResidualBlockId> dependent_blocks;
+        problem.GetResidualBlocksForParameterBlock(values, &dependent_blocks);
+        const std::unordered_set<ResidualBlockId> dependent_set(dependent_blocks.begin(), dependent_blocks.end());
+        auto indices = std::make_unique<std::vector<size_t>>();
+        for (size_t i = 0; i < residual_blocks.size(); ++i) {
+            const auto id = *residual_blocks[i];
+            if (dependent_set.count(id) != 0) {
+                indices->push_back(i);
+            }
+        }
+        return indices;
+    }
+    std::unique_ptr<std::vector<double>> compute_covariance(Problem& problem,
+                                                            const RustCovarianceOptions& options,
+                                                            double* const* const parameter_blocks,
+                                                            int num_parameter_blocks) {
+        Covariance::Options covariance_options;
+        covariance_options.algorithm_type = options.algorithm_type;
+        covariance_options.min_reciprocal_condition_number = options.min_reciprocal_condition_number;
+        covariance_options.null_space_rank = options.null_space_rank;
+        covariance_options.num_threads = options.num_threads;
+        covariance_options.apply_loss_function = options.apply_loss_function;
+        Covariance covariance(covariance_options);
+        const std::vector<const double*> blocks(parameter_blocks, parameter_blocks + num_parameter_blocks);
+        if (!covariance.Compute(blocks, &problem)) {
+            return nullptr;
+        }
+        int size = 0;
+        for (const auto block : blocks) {
+            size += problem.ParameterBlockSize(block);
+        }
+        auto matrix = std::make_unique<std::vector<double>>(size * size);
+        if (!covariance.GetCovarianceMatrix(blocks, matrix->data())) {
+            return nullptr;
+        }
+        return matrix;
+    }
+
+    bool GradientCheckResults::passed() const {
+        return passed_;
+    }
+    double GradientCheckResults::maximum_relative_error() const {
+        return maximum_relative_error_;
+    }
+    const std::vector<double>& GradientCheckResults::residuals() const {
+        return residuals_;
+    }
+    size_t GradientCheckResults::num_jacobians() const {
+        return jacobians_.size();
+    }
+    const std::vector<double>& GradientCheckResults::jacobian(size_t index) const {
+        return jacobians_.at(index);
+    }
+    const std::vector<double>& GradientCheckResults::numeric_jacobian(size_t index) const {
+        return numeric_jacobians_.at(index);
+    }
+    const std::string& GradientCheckResults::error_log() const {
+        return error_log_;
+    }
+    template <typename M>
+    static std::vector<double> row_major_values(const M& matrix) {
+        std::vector<double> values;
+        values.reserve(matrix.rows() * matrix.cols());
+        for (Eigen::Index row = 0; row < matrix.rows(); ++row) {
+            for (Eigen::Index col = 0; col < matrix.cols(); ++col) {
+                values.push_back(matrix(row, col));
+            }
+        }
+        return values;
+    }
+    std::unique_ptr<GradientCheckResults> check_gradients(const CallbackCostFunction& cost_function,
+                                                          const double* const* parameters,
+                                                          double relative_precision) {
+        const NumericDiffOptions options;
+        const GradientChecker checker(&cost_function, nullptr, options);
+        GradientChecker::ProbeResults probe;
+        const bool passed = checker.Probe(parameters, relative_precision, &probe);
+        if (!probe.return_value) {
+            return nullptr;
+        }
+        auto results = std::make_unique<GradientCheckResults>();
+        results->passed_ = passed;
+        results->maximum_relative_error_ = probe.maximum_relative_error;
+        results->residuals_.assign(probe.residuals.data(), probe.residuals.data() + probe.residuals.size());
+        for (size_t i = 0; i < probe.jacobians.size(); ++i) {
+            results->jacobians_.push_back(row_major_values(probe.jacobians[i]));
+            results->numeric_jacobians_.push_back(row_major_values(probe.numeric_jacobians[i]));
+        }
+        results->error_log_ = probe.error_log;
+        return results;
+    }
+
+    CallbackIterationCallback::CallbackIterationCallback(rust::Box<RustIterationCallback> inner):
+        inner(std::move(inner)) {}
+    CallbackReturnType CallbackIterationCallback::operator()(const IterationSummary& summary) {
+        RustIterationSummary rust_summary;
+        rust_summary.iteration = summary.iteration;
+        rust_summary.step_is_valid = summary.step_is_valid;
+        rust_summary.step_is_nonmonotonic = summary.step_is_nonmonotonic;
+        rust_summary.step_is_successful = summary.step_is_successful;
+        rust_summary.cost = summary.cost;
+        rust_summary.cost_change = summary.cost_change;
+        rust_summary.gradient_max_norm = summary.gradient_max_norm;
+        rust_summary.gradient_norm = summary.gradient_norm;
+        rust_summary.step_norm = summary.step_norm;
+        rust_summary.relative_decrease = summary.relative_decrease;
+        rust_summary.trust_region_radius = summary.trust_region_radius;
+        rust_summary.eta = summary.eta;
+        rust_summary.step_size = summary.step_size;
+        rust_summary.line_search_function_evaluations = summary.line_search_function_evaluations;
+        rust_summary.line_search_gradient_evaluations = summary.line_search_gradient_evaluations;
+        rust_summary.line_search_iterations = summary.line_search_iterations;
+        rust_summary.linear_solver_iterations = summary.linear_solver_iterations;
+        rust_summary.iteration_time_in_seconds = summary.iteration_time_in_seconds;
+        rust_summary.step_solver_time_in_seconds = summary.step_solver_time_in_seconds;
+        rust_summary.cumulative_time_in_seconds = summary.cumulative_time_in_seconds;
+        return inner->call(rust_summary);
+    }
 
     SolverOptions::SolverOptions():
         inner(Solver::Options()) {}
@@ -182,8 +528,10 @@ namespace ceres {
     }
     void SolverOptions::set_residual_blocks_for_subset_preconditioner(rust::Slice<const std::shared_ptr<ResidualBlockId>> residual_blocks) {
         inner.residual_blocks_for_subset_preconditioner.clear();
+        residual_blocks_for_subset_preconditioner.clear();
         for (auto &block : residual_blocks) {
             inner.residual_blocks_for_subset_preconditioner.insert(*block);
+            residual_blocks_for_subset_preconditioner.push_back(block);
         }
     }
     void SolverOptions::set_dense_linear_algebra_library_type(DenseLinearAlgebraLibraryType dense_linear_algebra_library_type) {
@@ -192,6 +540,27 @@ namespace ceres {
     void SolverOptions::set_sparse_linear_algebra_library_type(SparseLinearAlgebraLibraryType sparse_linear_algebra_library_type) {
         inner.sparse_linear_algebra_library_type = sparse_linear_algebra_library_type;
     }
+    void SolverOptions::set_linear_solver_ordering_type(LinearSolverOrderingType linear_solver_ordering_type) {
+        inner.linear_solver_ordering_type = linear_solver_ordering_type;
+    }
+    void SolverOptions::set_use_mixed_precision_solves(bool yes) {
+        inner.use_mixed_precision_solves = yes;
+    }
+    void SolverOptions::set_max_num_refinement_iterations(int max_num_refinement_iterations) {
+        inner.max_num_refinement_iterations = max_num_refinement_iterations;
+    }
+    void SolverOptions::set_min_linear_solver_iterations(int min_linear_solver_iterations) {
+        inner.min_linear_solver_iterations = min_linear_solver_iterations;
+    }
+    void SolverOptions::set_max_linear_solver_iterations(int max_linear_solver_iterations) {
+        inner.max_linear_solver_iterations = max_linear_solver_iterations;
+    }
+    void SolverOptions::set_eta(double eta) {
+        inner.eta = eta;
+    }
+    void SolverOptions::set_jacobi_scaling(bool yes) {
+        inner.jacobi_scaling = yes;
+    }
     void SolverOptions::set_logging_type(LoggingType logging_type) {
         inner.logging_type = logging_type;
     }
@@ -222,9 +591,179 @@ namespace ceres {
     void SolverOptions::set_update_state_every_iteration(bool yes) {
         inner.update_state_every_iteration = yes;
     }
+    void SolverOptions::add_iteration_callback(rust::Box<RustIterationCallback> callback) {
+        auto iteration_callback = std::make_shared<CallbackIterationCallback>(std::move(callback));
+        inner.callbacks.push_back(iteration_callback.get());
+        callbacks.push_back(std::move(iteration_callback));
+    }
+    MinimizerType SolverOptions::minimizer_type() const {
+        return inner.minimizer_type;
+    }
+    LineSearchDirectionType SolverOptions::line_search_direction_type() const {
+        return inner.line_search_direction_type;
+    }
+    LineSearchType SolverOptions::line_search_type() const {
+        return inner.line_search_type;
+    }
+    NonlinearConjugateGradientType SolverOptions::nonlinear_conjugate_gradient_type() const {
+        return inner.nonlinear_conjugate_gradient_type;
+    }
+    int SolverOptions::max_lbfgs_rank() const {
+        return inner.max_lbfgs_rank;
+    }
+    bool SolverOptions::use_approximate_eigenvalue_bfgs_scaling() const {
+        return inner.use_approximate_eigenvalue_bfgs_scaling;
+    }
+    LineSearchInterpolationType SolverOptions::line_search_interpolation_type() const {
+        return inner.line_search_interpolation_type;
+    }
+    double SolverOptions::min_line_search_step_size() const {
+        return inner.min_line_search_step_size;
+    }
+    double SolverOptions::line_search_sufficient_function_decrease() const {
+        return inner.line_search_sufficient_function_decrease;
+    }
+    double SolverOptions::max_line_search_step_contraction() const {
+        return inner.max_line_search_step_contraction;
+    }
+    double SolverOptions::min_line_search_step_contraction() const {
+        return inner.min_line_search_step_contraction;
+    }
+    int SolverOptions::max_num_line_search_direction_restarts() const {
+        return inner.max_num_line_search_direction_restarts;
+    }
+    double SolverOptions::line_search_sufficient_curvature_decrease() const {
+        return inner.line_search_sufficient_curvature_decrease;
+    }
+    double SolverOptions::max_line_search_step_expansion() const {
+        return inner.max_line_search_step_expansion;
+    }
+    TrustRegionStrategyType SolverOptions::trust_region_strategy_type() const {
+        return inner.trust_region_strategy_type;
+    }
+    DoglegType SolverOptions::dogleg_type() const {
+        return inner.dogleg_type;
+    }
+    bool SolverOptions::use_nonmonotonic_steps() const {
+        return inner.use_nonmonotonic_steps;
+    }
+    int SolverOptions::max_consecutive_nonmonotonic_steps() const {
+        return inner.max_consecutive_nonmonotonic_steps;
+    }
+    int SolverOptions::max_num_iterations() const {
+        return inner.max_num_iterations;
+    }
+    double SolverOptions::max_solver_time_in_seconds() const {
+        return inner.max_solver_time_in_seconds;
+    }
+    int SolverOptions::num_threads() const {
+        return inner.num_threads;
+    }
+    double SolverOptions::initial_trust_region_radius() const {
+        return inner.initial_trust_region_radius;
+    }
+    double SolverOptions::max_trust_region_radius() const {
+        return inner.max_trust_region_radius;
+    }
+    double SolverOptions::min_trust_region_radius() const {
+        return inner.min_trust_region_radius;
+    }
+    double SolverOptions::min_relative_decrease() const {
+        return inner.min_relative_decrease;
+    }
+    double SolverOptions::min_lm_diagonal() const {
+        return inner.min_lm_diagonal;
+    }
+    double SolverOptions::max_lm_diagonal() const {
+        return inner.max_lm_diagonal;
+    }
+    int SolverOptions::max_num_consecutive_invalid_steps() const {
+        return inner.max_num_consecutive_invalid_steps;
+    }
+    double SolverOptions::function_tolerance() const {
+        return inner.function_tolerance;
+    }
+    double SolverOptions::gradient_tolerance() const {
+        return inner.gradient_tolerance;
+    }
+    double SolverOptions::parameter_tolerance() const {
+        return inner.parameter_tolerance;
+    }
+    LinearSolverType SolverOptions::linear_solver_type() const {
+        return inner.linear_solver_type;
+    }
+    PreconditionerType SolverOptions::preconditioner_type() const {
+        return inner.preconditioner_type;
+    }
+    VisibilityClusteringType SolverOptions::visibility_clustering_type() const {
+        return inner.visibility_clustering_type;
+    }
+    size_t SolverOptions::num_residual_blocks_for_subset_preconditioner() const {
+        return residual_blocks_for_subset_preconditioner.size();
+    }
+    std::shared_ptr<ResidualBlockId> SolverOptions::residual_block_for_subset_preconditioner(size_t index) const {
+        return residual_blocks_for_subset_preconditioner.at(index);
+    }
+    DenseLinearAlgebraLibraryType SolverOptions::dense_linear_algebra_library_type() const {
+        return inner.dense_linear_algebra_library_type;
+    }
+    SparseLinearAlgebraLibraryType SolverOptions::sparse_linear_algebra_library_type() const {
+        return inner.sparse_linear_algebra_library_type;
+    }
+    LinearSolverOrderingType SolverOptions::linear_solver_ordering_type() const {
+        return inner.linear_solver_ordering_type;
+    }
+    bool SolverOptions::use_mixed_precision_solves() const {
+        return inner.use_mixed_precision_solves;
+    }
+    int SolverOptions::max_num_refinement_iterations() const {
+        return inner.max_num_refinement_iterations;
+    }
+    int SolverOptions::min_linear_solver_iterations() const {
+        return inner.min_linear_solver_iterations;
+    }
+    int SolverOptions::max_linear_solver_iterations() const {
+        return inner.max_linear_solver_iterations;
+    }
+    double SolverOptions::eta() const {
+        return inner.eta;
+    }
+    bool SolverOptions::jacobi_scaling() const {
+        return inner.jacobi_scaling;
+    }
+    LoggingType SolverOptions::logging_type() const {
+        return inner.logging_type;
+    }
+    bool SolverOptions::minimizer_progress_to_stdout() const {
+        return inner.minimizer_progress_to_stdout;
+    }
+    const std::vector<int>& SolverOptions::trust_region_minimizer_iterations_to_dump() const {
+        return inner.trust_region_minimizer_iterations_to_dump;
+    }
+    const std::string& SolverOptions::trust_region_problem_dump_directory() const {
+        return inner.trust_region_problem_dump_directory;
+    }
+    DumpFormatType SolverOptions::trust_region_problem_dump_format_type() const {
+        return inner.trust_region_problem_dump_format_type;
+    }
+    bool SolverOptions::check_gradients() const {
+        return inner.check_gradients;
+    }
+    double SolverOptions::gradient_check_relative_precision() const {
+        return inner.gradient_check_relative_precision;
+    }
+    double SolverOptions::gradient_check_numeric_derivative_relative_step_size() const {
+        return inner.gradient_check_numeric_derivative_relative_step_size;
+    }
+    bool SolverOptions::update_state_every_iteration() const {
+        return inner.update_state_every_iteration;
+    }
     std::unique_ptr<SolverOptions> new_solver_options() {
         return std::make_unique<SolverOptions>();
     }
+    std::unique_ptr<SolverOptions> clone_solver_options(const SolverOptions& options) {
+        return std::make_unique<SolverOptions>(options);
+    }
 
     SolverSummary::SolverSummary():
         inner(Solver::Summary()) {}
@@ -237,6 +776,12 @@ namespace ceres {
     bool SolverSummary::is_solution_usable() const {
         return inner.IsSolutionUsable();
     }
+    TerminationType SolverSummary::termination_type() const {
+        return inner.termination_type;
+    }
+    std::unique_ptr<std::string> SolverSummary::message() const {
+        return std::make_unique<std::string>(inner.message);
+    }
     double SolverSummary::initial_cost() const {
         return inner.initial_cost;
     }
@@ -265,4 +810,71 @@ namespace ceres {
     void solve(const SolverOptions& options, Problem& problem, SolverSummary& summary) {
         Solve(options.inner, &problem, &summary.inner);
     }
+
+    CallbackLogSink::CallbackLogSink(rust::Box<RustLogSink> inner):
+        inner(std::move(inner)) {}
+    void CallbackLogSink::send(google::LogSeverity severity, const char* full_filename,
+                               const char* base_filename, int line,
+                               const struct ::tm* tm_time,
+                               const char* message, size_t message_len) {
+        inner->send(
+            static_cast<int32_t>(severity),
+            rust::Slice<const uint8_t>(reinterpret_cast<const uint8_t*>(base_filename), std::strlen(base_filename)),
+            line,
+            rust::Slice<const uint8_t>(reinterpret_cast<const uint8_t*>(message), message_len)
+        );
+    }
+
+    static std::mutex logging_mutex;
+    static std::unique_ptr<CallbackLogSink> log_sink;
+
+    static void init_google_logging_once(const std::string& program_name) {
+        // glog keeps the pointer to the program name, so it must outlive the logging.
+        static std::string program_name_storage;
+        if (!google::IsGoogleLoggingInitialized()) {
+            program_name_storage = program_name;
+            google::InitGoogleLogging(program_name_storage.c_str());
+        }
+    }
+
+    static void disable_stderr_and_file_logging() {
+        FLAGS_logtostderr = false;
+        FLAGS_alsologtostderr = false;
+        google::SetStderrLogging(static_cast<google::LogSeverity>(google::NUM_SEVERITIES));
+        for (int severity = 0; severity < google::NUM_SEVERITIES; ++severity) {
+            google::SetLogDestination(static_cast<google::LogSeverity>(severity), "");
+        }
+    }
+
+    void set_log_sink(rust::Box<RustLogSink> inner) {
+        std::lock_guard<std::mutex> lock(logging_mutex);
+        // Messages are written to stderr before the initialization regardless of the flags.
+        init_google_logging_once("ceres-solver-rs");
+        disable_stderr_and_file_logging();
+        // Per-iteration progress is logged with VLOG(1).
+        if (FLAGS_v < 1) {
+            FLAGS_v = 1;
+        }
+        if (log_sink) {
+            google::RemoveLogSink(log_sink.get());
+        }
+        log_sink = std::make_unique<CallbackLogSink>(std::move(inner));
+        google::AddLogSink(log_sink.get());
+    }
+
+    void init_logging(rust::Str program_name, int32_t min_level, bool log_to_stderr) {
+        std::lock_guard<std::mutex> lock(logging_mutex);
+        init_google_logging_once(std::string(program_name));
+        FLAGS_minloglevel = min_level;
+        if (log_to_stderr) {
+            FLAGS_logtostderr = true;
+            google::SetStderrLogging(static_cast<google::LogSeverity>(min_level));
+        } else {
+            disable_stderr_and_file_logging();
+        }
+    }
+
+    void log_warning(rust::Str message) {
+        LOG(WARNING) << std::string(message);
+    }
 }
@@ -6,6 +6,8 @@
 #define CERES_SOLVER_RS_LIB_H
 
 #include <ceres/ceres.h>
+#include <ceres/gradient_checker.h>
+#include <glog/logging.h>
 #include <rust/cxx.h>
 
 namespace ceres {
@@ -39,16 +41,153 @@ namespace ceres {
     std::unique_ptr<LossFunction> new_arctan_loss(double a);
     std::unique_ptr<LossFunction> new_tolerant_loss(double a, double b);
     std::unique_ptr<LossFunction> new_tukey_loss(double a);
+    std::unique_ptr<LossFunction> new_scaled_loss(std::unique_ptr<LossFunction> loss_function, double a);
+    std::unique_ptr<LossFunctionWrapper> new_loss_function_wrapper(std::unique_ptr<LossFunction> loss_function);
+    void reset_loss_function_wrapper(LossFunctionWrapper& wrapper, std::unique_ptr<LossFunction> loss_function);
+    LossFunction* loss_function_wrapper_as_loss_function(LossFunctionWrapper* wrapper);
+
+    std::unique_ptr<Manifold> new_subset_manifold(int size, rust::Slice<const int32_t> constant_parameters);
+    std::unique_ptr<Manifold> new_quaternion_manifold();
+    std::unique_ptr<Manifold> new_eigen_quaternion_manifold();
+    std::unique_ptr<Manifold> new_sphere_manifold(int size);
+    std::unique_ptr<Manifold> new_line_manifold(int size);
+    std::unique_ptr<Manifold> new_euclidean_manifold(int size);
+    std::unique_ptr<Manifold> new_product_manifold(std::unique_ptr<Manifold> first, std::unique_ptr<Manifold> second);
+    struct RustManifold;
+    struct CallbackManifold final : public Manifold {
+        rust::Box<RustManifold> inner;
+        int ambient_size;
+        int tangent_size;
+        CallbackManifold(rust::Box<RustManifold> inner, int ambient_size, int tangent_size);
+        // Manifold impl
+        virtual int AmbientSize() const override;
+        virtual int TangentSize() const override;
+        virtual bool Plus(const double* x, const double* delta, double* x_plus_delta) const override;
+        virtual bool PlusJacobian(const double* x, double* jacobian) const override;
+        virtual bool Minus(const double* y, const double* x, double* y_minus_x) const override;
+        virtual bool MinusJacobian(const double* x, double* jacobian) const override;
+    };
+    std::unique_ptr<Manifold> new_callback_manifold(rust::Box<RustManifold> inner, int ambient_size, int tangent_size);
+
+    struct RustEvaluationCallback;
+    struct CallbackEvaluationCallback final : public EvaluationCallback {
+        rust::Box<RustEvaluationCallback> inner;
+        CallbackEvaluationCallback(rust::Box<RustEvaluationCallback> inner);
+        // EvaluationCallback impl
+        virtual void PrepareForEvaluation(bool evaluate_jacobians, bool new_evaluation_point) override;
+    };
+    std::unique_ptr<CallbackEvaluationCallback> new_callback_evaluation_callback(rust::Box<RustEvaluationCallback> inner);
 
     std::unique_ptr<Problem> new_problem();
+    struct RustProblemOptions;
+    std::unique_ptr<Context> new_context();
+    std::unique_ptr<Problem> new_problem_with_options(const RustProblemOptions& options,
+                                                      CallbackEvaluationCallback* evaluation_callback,
+                                                      Context* context);
+    void set_parameter_bounds(Problem& problem,
+                              double* values,
+                              rust::Slice<const double> lower_bounds,
+                              rust::Slice<const double> upper_bounds);
     std::shared_ptr<ResidualBlockId> add_residual_block(Problem& problem,
                                                         std::unique_ptr<CallbackCostFunction> cost_function,
                                                         std::unique_ptr<LossFunction> loss_function,
                                                         double* const* const parameter_blocks,
                                                         int num_parameter_blocks);
+    std::shared_ptr<ResidualBlockId> add_residual_block_not_owned(Problem& problem,
+                                                                  CallbackCostFunction* cost_function,
+                                                                  LossFunction* loss_function,
+                                                                  double* const* const parameter_blocks,
+                                                                  int num_parameter_blocks);
+    void add_residual_blocks_not_owned(Problem& problem,
+                                       CallbackCostFunction* const* cost_functions,
+                                       LossFunction* const* loss_functions,
+                                       double* const* parameter_blocks,
+                                       rust::Slice<const int32_t> num_parameter_blocks,
+                                       rust::Slice<std::shared_ptr<ResidualBlockId>> residual_block_ids);
+    void remove_residual_block(Problem& problem, const std::shared_ptr<ResidualBlockId>& residual_block);
+
+    struct ProblemEvaluation {
+        double cost_;
+        std::vector<double> residuals_;
+        std::vector<double> gradient_;
+        CRSMatrix jacobian_;
+        double cost() const;
+        const std::vector<double>& residuals() const;
+        const std::vector<double>& gradient() const;
+        int jacobian_num_rows() const;
+        int jacobian_num_cols() const;
+        const std::vector<int>& jacobian_rows() const;
+        const std::vector<int>& jacobian_cols() const;
+        const std::vector<double>& jacobian_values() const;
+    };
+    // Returns nullptr if any of the cost functions fails.
+    std::unique_ptr<ProblemEvaluation> evaluate_problem(Problem& problem,
+                                                        double* const* const parameter_blocks,
+                                                        int num_parameter_blocks,
+                                                        rust::Slice<const std::shared_ptr<ResidualBlockId>> residual_blocks,
+                                                        bool apply_loss_function,
+                                                        int num_threads,
+                                                        bool compute_jacobian);
+
+    struct ResidualBlockEvaluation {
+        double cost_;
+        std::vector<double> residuals_;
+        std::vector<std::vector<double>> jacobians_;
+        std::vector<bool> has_jacobians_;
+        double cost() const;
+        const std::vector<double>& residuals() const;
+        size_t num_jacobians() const;
+        bool has_jacobian(size_t index) const;
+        const std::vector<double>& jacobian(size_t index) const;
+    };
+    // Returns nullptr if the cost function fails.
+    std::unique_ptr<ResidualBlockEvaluation> evaluate_residual_block(const Problem& problem,
+                                                                     const std::shared_ptr<ResidualBlockId>& residual_block,
+                                                                     bool apply_loss_function);
+    std::unique_ptr<std::vector<size_t>> residual_blocks_for_parameter_block(const Problem& problem,
+                                                                             const double* values,
+                                                                             rust::Slice<const std::shared_ptr<ResidualBlockId>> residual_blocks);
+    struct RustCovarianceOptions;
+    // Returns nullptr if the covariance cannot be computed.
+    std::unique_ptr<std::vector<double>> compute_covariance(Problem& problem,
+                                                            const RustCovarianceOptions& options,
+                                                            double* const* const parameter_blocks,
+                                                            int num_parameter_blocks);
+
+    struct GradientCheckResults {
+        bool passed_;
+        double maximum_relative_error_;
+        std::vector<double> residuals_;
+        std::vector<std::vector<double>> jacobians_;
+        std::vector<std::vector<double>> numeric_jacobians_;
+        std::string error_log_;
+        bool passed() const;
+        double maximum_relative_error() const;
+        const std::vector<double>& residuals() const;
+        size_t num_jacobians() const;
+        const std::vector<double>& jacobian(size_t index) const;
+        const std::vector<double>& numeric_jacobian(size_t index) const;
+        const std::string& error_log() const;
+    };
+    // Returns nullptr if the cost function fails.
+    std::unique_ptr<GradientCheckResults> check_gradients(const CallbackCostFunction& cost_function,
+                                                          const double* const* parameters,
+                                                          double relative_precision);
+
+    struct RustIterationCallback;
+    struct CallbackIterationCallback final : public IterationCallback {
+        rust::Box<RustIterationCallback> inner;
+        CallbackIterationCallback(rust::Box<RustIterationCallback> inner);
+        // IterationCallback impl
+        virtual CallbackReturnType operator()(const IterationSummary& summary) override;
+    };
 
     struct SolverOptions {
         Solver::Options inner;
+        // Solver::Options doesn't own callbacks, so we do.
+        std::vector<std::shared_ptr<IterationCallback>> callbacks;
+        // Solver::Options holds raw pointers, we keep the shared pointers to read them back.
+        std::vector<std::shared_ptr<ResidualBlockId>> residual_blocks_for_subset_preconditioner;
         SolverOptions();
         bool is_valid(std::string& error) const;
         void set_minimizer_type(MinimizerType minimizer_type);
@@ -88,6 +227,13 @@ namespace ceres {
         void set_residual_blocks_for_subset_preconditioner(rust::Slice<const std::shared_ptr<ResidualBlockId>> residual_blocks);
         void set_dense_linear_algebra_library_type(DenseLinearAlgebraLibraryType dense_linear_algebra_library_type);
         void set_sparse_linear_algebra_library_type(SparseLinearAlgebraLibraryType sparse_linear_algebra_library_type);
+        void set_linear_solver_ordering_type(LinearSolverOrderingType linear_solver_ordering_type);
+        void set_use_mixed_precision_solves(bool yes);
+        void set_max_num_refinement_iterations(int max_num_refinement_iterations);
+        void set_min_linear_solver_iterations(int min_linear_solver_iterations);
+        void set_max_linear_solver_iterations(int max_linear_solver_iterations);
+        void set_eta(double eta);
+        void set_jacobi_scaling(bool yes);
         // We skip bundle adjustment specific options.
         void set_logging_type(LoggingType logging_type);
         void set_minimizer_progress_to_stdout(bool yes);
@@ -98,9 +244,65 @@ namespace ceres {
         void set_gradient_check_relative_precision(double relative_precision);
         void set_gradient_check_numeric_derivative_relative_step_size(double relative_step_size);
         void set_update_state_every_iteration(bool yes);
-        // Callbacks are skipped for now.
+        void add_iteration_callback(rust::Box<RustIterationCallback> callback);
+        // Getters
+        MinimizerType minimizer_type() const;
+        LineSearchDirectionType line_search_direction_type() const;
+        LineSearchType line_search_type() const;
+        NonlinearConjugateGradientType nonlinear_conjugate_gradient_type() const;
+        int max_lbfgs_rank() const;
+        bool use_approximate_eigenvalue_bfgs_scaling() const;
+        LineSearchInterpolationType line_search_interpolation_type() const;
+        double min_line_search_step_size() const;
+        double line_search_sufficient_function_decrease() const;
+        double max_line_search_step_contraction() const;
+        double min_line_search_step_contraction() const;
+        int max_num_line_search_direction_restarts() const;
+        double line_search_sufficient_curvature_decrease() const;
+        double max_line_search_step_expansion() const;
+        TrustRegionStrategyType trust_region_strategy_type() const;
+        DoglegType dogleg_type() const;
+        bool use_nonmonotonic_steps() const;
+        int max_consecutive_nonmonotonic_steps() const;
+        int max_num_iterations() const;
+        double max_solver_time_in_seconds() const;
+        int num_threads() const;
+        double initial_trust_region_radius() const;
+        double max_trust_region_radius() const;
+        double min_trust_region_radius() const;
+        double min_relative_decrease() const;
+        double min_lm_diagonal() const;
+        double max_lm_diagonal() const;
+        int max_num_consecutive_invalid_steps() const;
+        double function_tolerance() const;
+        double gradient_tolerance() const;
+        double parameter_tolerance() const;
+        LinearSolverType linear_solver_type() const;
+        PreconditionerType preconditioner_type() const;
+        VisibilityClusteringType visibility_clustering_type() const;
+        size_t num_residual_blocks_for_subset_preconditioner() const;
+        std::shared_ptr<ResidualBlockId> residual_block_for_subset_preconditioner(size_t index) const;
+        DenseLinearAlgebraLibraryType dense_linear_algebra_library_type() const;
+        SparseLinearAlgebraLibraryType sparse_linear_algebra_library_type() const;
+        LinearSolverOrderingType linear_solver_ordering_type() const;
+        bool use_mixed_precision_solves() const;
+        int max_num_refinement_iterations() const;
+        int min_linear_solver_iterations() const;
+        int max_linear_solver_iterations() const;
+        double eta() const;
+        bool jacobi_scaling() const;
+        LoggingType logging_type() const;
+        bool minimizer_progress_to_stdout() const;
+        const std::vector<int>& trust_region_minimizer_iterations_to_dump() const;
+        const std::string& trust_region_problem_dump_directory() const;
+        DumpFormatType trust_region_problem_dump_format_type() const;
+        bool check_gradients() const;
+        double gradient_check_relative_precision() const;
+        double gradient_check_numeric_derivative_relative_step_size() const;
+        bool update_state_every_iteration() const;
     };
     std::unique_ptr<SolverOptions> new_solver_options();
+    std::unique_ptr<SolverOptions> clone_solver_options(const SolverOptions& options);
 
     struct SolverSummary {
         Solver::Summary inner;
@@ -108,6 +310,8 @@ namespace ceres {
         std::unique_ptr<std::string> brief_report() const;
         std::unique_ptr<std::string> full_report() const;
         bool is_solution_usable() const;
+        TerminationType termination_type() const;
+        std::unique_ptr<std::string> message() const;
         double initial_cost() const;
         double final_cost() const;
         double fixed_cost() const;
@@ -120,6 +324,23 @@ namespace ceres {
     std::unique_ptr<SolverSummary> new_solver_summary();
 
     void solve(const SolverOptions& options, Problem& problem, SolverSummary& summary);
+
+    struct RustLogSink;
+    struct CallbackLogSink final : public google::LogSink {
+        rust::Box<RustLogSink> inner;
+        CallbackLogSink(rust::Box<RustLogSink> inner);
+        // LogSink impl
+        virtual void send(google::LogSeverity severity, const char* full_filename,
+                          const char* base_filename, int line,
+                          const struct ::tm* tm_time,
+                          const char* message, size_t message_len) override;
+    };
+    // Replaces the previously set sink, if any, and stops glog from writing to stderr and files.
+    void set_log_sink(rust::Box<RustLogSink> inner);
+    // Initializes glog if it is not initialized yet and sets the minimum logged severity, messages go
+    // either to stderr or to the log sink only.
+    void init_logging(rust::Str program_name, int32_t min_level, bool log_to_stderr);
+    void log_warning(rust::Str message);
 }
 
 #endif //CERES_SOLVER_RS_LIB_H
@@ -9,12 +9,14 @@ pub mod ffi {
     // False positive, I believe
     #![allow(clippy::missing_safety_doc)]
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum MinimizerType {
         LINE_SEARCH,
         TRUST_REGION,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum LineSearchDirectionType {
         STEEPEST_DESCENT,
@@ -23,12 +25,14 @@ pub mod ffi {
         BFGS,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum LineSearchType {
         ARMIJO,
         WOLFE,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum NonlinearConjugateGradientType {
         FLETCHER_REEVES,
@@ -36,6 +40,7 @@ pub mod ffi {
         HESTENES_STIEFEL,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum LineSearchInterpolationType {
         BISECTION,
@@ -43,18 +48,21 @@ pub mod ffi {
         CUBIC,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum TrustRegionStrategyType {
         LEVENBERG_MARQUARDT,
         DOGLEG,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum DoglegType {
         TRADITIONAL_DOGLEG,
         SUBSPACE_DOGLEG,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum LinearSolverType {
         DENSE_NORMAL_CHOLESKY,
@@ -66,6 +74,7 @@ pub mod ffi {
         CGNR,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum PreconditionerType {
         IDENTITY,
@@ -77,12 +86,14 @@ pub mod ffi {
         SUBSET,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum VisibilityClusteringType {
         CANONICAL_VIEWS,
         SINGLE_LINKAGE,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum DenseLinearAlgebraLibraryType {
         EIGEN,
@@ -90,6 +101,7 @@ pub mod ffi {
         CUDA,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum SparseLinearAlgebraLibraryType {
         SUITE_SPARSE,
@@ -99,18 +111,107 @@ pub mod ffi {
         NO_SPARSE,
     }
 
+    #[derive(Debug)]
+    #[repr(u32)]
+    enum LinearSolverOrderingType {
+        AMD,
+        NESDIS,
+    }
+
+    #[derive(Debug)]
     #[repr(u32)]
     enum LoggingType {
         SILENT,
         PER_MINIMIZER_ITERATION,
     }
 
+    #[derive(Debug)]
     #[repr(u32)]
     enum DumpFormatType {
         CONSOLE,
         TEXTFILE,
     }
 
+    #[derive(Debug)]
+    #[repr(u32)]
+    enum TerminationType {
+        CONVERGENCE,
+        NO_CONVERGENCE,
+        FAILURE,
+        USER_SUCCESS,
+        USER_FAILURE,
+    }
+
+    #[derive(Debug)]
+    #[repr(u32)]
+    enum CallbackReturnType {
+        SOLVER_CONTINUE,
+        SOLVER_ABORT,
+        SOLVER_TERMINATE_SUCCESSFULLY,
+    }
+
+    #[derive(Debug)]
+    #[repr(u32)]
+    enum Ownership {
+        DO_NOT_TAKE_OWNERSHIP,
+        TAKE_OWNERSHIP,
+    }
+
+    #[derive(Debug)]
+    #[repr(u32)]
+    enum CovarianceAlgorithmType {
+        DENSE_SVD,
+        SPARSE_QR,
+    }
+
+    /// Subset of ceres::Problem::Options, evaluation callback is passed separately.
+    #[cxx_name = "RustProblemOptions"]
+    #[derive(Clone, Copy, Debug)]
+    struct ProblemOptions {
+        cost_function_ownership: Ownership,
+        loss_function_ownership: Ownership,
+        manifold_ownership: Ownership,
+        enable_fast_removal: bool,
+        disable_all_safety_checks: bool,
+    }
+
+    /// Subset of ceres::Covariance::Options.
+    #[cxx_name = "RustCovarianceOptions"]
+    #[derive(Clone, Copy, Debug)]
+    struct CovarianceOptions {
+        algorithm_type: CovarianceAlgorithmType,
+        min_reciprocal_condition_number: f64,
+        null_space_rank: i32,
+        num_threads: i32,
+        apply_loss_function: bool,
+    }
+
+    /// A copy of ceres::IterationSummary passed to iteration callbacks.
+    #[cxx_name = "RustIterationSummary"]
+    #[derive(Clone, Copy, Debug)]
+    struct IterationSummary {
+        iteration: i32,
+        step_is_valid: bool,
+        step_is_nonmonotonic: bool,
+        step_is_successful: bool,
+        cost: f64,
+        cost_change: f64,
+        gradient_max_norm: f64,
+        gradient_norm: f64,
+        step_norm: f64,
+        relative_decrease: f64,
+        trust_region_radius: f64,
+        eta: f64,
+        step_size: f64,
+        line_search_function_evaluations: i32,
+        line_search_gradient_evaluations: i32,
+        line_search_iterations: i32,
+        linear_solver_iterations: i32,
+        iteration_time_in_seconds: f64,
+        step_solver_time_in_seconds: f64,
+        cumulative_time_in_seconds: f64,
+    }
+
     extern "Rust" {
         type RustCostFunction<'cost>;
         unsafe fn evaluate(
@@ -122,6 +223,36 @@ pub mod ffi {
 
         type RustLossFunction;
         unsafe fn evaluate(self: &RustLossFunction, sq_norm: f64, out: *mut f64);
+
+        type RustManifold;
+        unsafe fn plus(
+            self: &RustManifold,
+            x: *const f64,
+            delta: *const f64,
+            x_plus_delta: *mut f64,
+        ) -> bool;
+        unsafe fn plus_jacobian(self: &RustManifold, x: *const f64, jacobian: *mut f64) -> bool;
+        unsafe fn minus(
+            self: &RustManifold,
+            y: *const f64,
+            x: *const f64,
+            y_minus_x: *mut f64,
+        ) -> bool;
+        unsafe fn minus_jacobian(self: &RustManifold, x: *const f64, jacobian: *mut f64) -> bool;
+
+        type RustEvaluationCallback<'cost>;
+        fn prepare_for_evaluation(
+            self: &RustEvaluationCallback,
+            evaluate_jacobians: bool,
+            new_evaluation_point: bool,
+        );
+
+        type RustIterationCallback;
+        fn call(self: &mut RustIterationCallback, summary: &IterationSummary)
+            -> CallbackReturnType;
+
+        type RustLogSink;
+        fn send(self: &RustLogSink, severity: i32, base_filename: &[u8], line: i32, message: &[u8]);
     }
 
     unsafe extern "C++" {
@@ -139,8 +270,13 @@ pub mod ffi {
         type VisibilityClusteringType;
         type DenseLinearAlgebraLibraryType;
         type SparseLinearAlgebraLibraryType;
+        type LinearSolverOrderingType;
         type LoggingType;
         type DumpFormatType;
+        type TerminationType;
+        type CallbackReturnType;
+        type Ownership;
+        type CovarianceAlgorithmType;
 
         type CallbackCostFunction<'cost>;
         /// Creates new C++ cost function from Rust cost function;
@@ -151,6 +287,11 @@ pub mod ffi {
         ) -> UniquePtr<CallbackCostFunction<'cost>>;
 
         type LossFunction;
+        /// Evaluates the loss function and its first two derivatives at the squared norm.
+        ///
+        /// # Safety
+        /// `out` must point to an array of three elements.
+        unsafe fn Evaluate(self: &LossFunction, sq_norm: f64, out: *mut f64);
         /// Creates new C++ loss function from Rust loss function;
         fn new_callback_loss_function(inner: Box<RustLossFunction>) -> UniquePtr<LossFunction>;
         /// Creates stock TrivialLoss.
@@ -167,9 +308,74 @@ pub mod ffi {
         fn new_tolerant_loss(a: f64, b: f64) -> UniquePtr<LossFunction>;
         /// Creates stock TukeyLoss.
         fn new_tukey_loss(a: f64) -> UniquePtr<LossFunction>;
+        /// Creates stock ScaledLoss owning the wrapped loss function, null means trivial loss.
+        fn new_scaled_loss(
+            loss_function: UniquePtr<LossFunction>,
+            a: f64,
+        ) -> UniquePtr<LossFunction>;
+
+        type LossFunctionWrapper;
+        /// Creates LossFunctionWrapper owning the loss function, null means no loss function.
+        fn new_loss_function_wrapper(
+            loss_function: UniquePtr<LossFunction>,
+        ) -> UniquePtr<LossFunctionWrapper>;
+        /// Replaces the wrapped loss function, null means no loss function.
+        fn reset_loss_function_wrapper(
+            wrapper: Pin<&mut LossFunctionWrapper>,
+            loss_function: UniquePtr<LossFunction>,
+        );
+        /// Converts the wrapper pointer to the base class pointer.
+        ///
+        /// # Safety
+        /// `wrapper` must be a valid pointer.
+        unsafe fn loss_function_wrapper_as_loss_function(
+            wrapper: *mut LossFunctionWrapper,
+        ) -> *mut LossFunction;
+
+        type Manifold;
+        /// Dimension of the ambient space.
+        fn AmbientSize(self: &Manifold) -> i32;
+        /// Dimension of the tangent space.
+        fn TangentSize(self: &Manifold) -> i32;
+        /// Creates stock SubsetManifold which keeps `constant_parameters` components constant.
+        fn new_subset_manifold(size: i32, constant_parameters: &[i32]) -> UniquePtr<Manifold>;
+        /// Creates stock QuaternionManifold, the quaternion is stored as `[w, x, y, z]`.
+        fn new_quaternion_manifold() -> UniquePtr<Manifold>;
+        /// Creates stock EigenQuaternionManifold, the quaternion is stored as `[x, y, z, w]`.
+        fn new_eigen_quaternion_manifold() -> UniquePtr<Manifold>;
+        /// Creates stock SphereManifold of unit-norm vectors of the given size.
+        fn new_sphere_manifold(size: i32) -> UniquePtr<Manifold>;
+        /// Creates stock LineManifold of lines in the space of the given dimension, the line is
+        /// stored as an origin point followed by a unit direction, so the ambient size is
+        /// `2 * size`.
+        fn new_line_manifold(size: i32) -> UniquePtr<Manifold>;
+        /// Creates stock EuclideanManifold of the given size.
+        fn new_euclidean_manifold(size: i32) -> UniquePtr<Manifold>;
+        /// Creates stock ProductManifold owning both manifolds, the ambient and tangent spaces
+        /// are concatenations of the ones of `first` and `second`.
+        fn new_product_manifold(
+            first: UniquePtr<Manifold>,
+            second: UniquePtr<Manifold>,
+        ) -> UniquePtr<Manifold>;
+        /// Creates new C++ manifold from Rust manifold.
+        fn new_callback_manifold(
+            inner: Box<RustManifold>,
+            ambient_size: i32,
+            tangent_size: i32,
+        ) -> UniquePtr<Manifold>;
+
+        type CallbackEvaluationCallback<'cost>;
+        /// Creates new C++ evaluation callback from Rust function.
+        fn new_callback_evaluation_callback<'cost>(
+            inner: Box<RustEvaluationCallback<'cost>>,
+        ) -> UniquePtr<CallbackEvaluationCallback<'cost>>;
 
         type ResidualBlockId;
 
+        type Context;
+        /// Creates new Context holding resources shared by problems, such as thread pool.
+        fn new_context() -> UniquePtr<Context>;
+
         type Problem<'cost>;
         /// Set parameter to be constant.
         ///
@@ -206,6 +412,22 @@ pub mod ffi {
             index: i32,
             upper_bound: f64,
         );
+        /// Lower bound for a component of a parameter block, `-f64::MAX` if it is unbounded.
+        ///
+        /// # Safety
+        /// `values` must point to already added parameter block.
+        unsafe fn GetParameterLowerBound(self: &Problem, values: *const f64, index: i32) -> f64;
+        /// Upper bound for a component of a parameter block, `f64::MAX` if it is unbounded.
+        ///
+        /// # Safety
+        /// `values` must point to already added parameter block.
+        unsafe fn GetParameterUpperBound(self: &Problem, values: *const f64, index: i32) -> f64;
+        /// Set manifold of a parameter block, null `manifold` removes it.
+        ///
+        /// # Safety
+        /// `values` must point to already added parameter block. `manifold` must be null or outlive
+        /// the problem if the problem doesn't own manifolds.
+        unsafe fn SetManifold(self: Pin<&mut Problem>, values: *mut f64, manifold: *mut Manifold);
         fn NumParameterBlocks(self: &Problem) -> i32;
         fn NumParameters(self: &Problem) -> i32;
         fn NumResidualBlocks(self: &Problem) -> i32;
@@ -215,6 +437,12 @@ pub mod ffi {
         /// # Safety
         /// `values` must point to already added parameter block.
         unsafe fn ParameterBlockSize(self: &Problem, values: *const f64) -> i32;
+        /// Size of the tangent space of the parameter, it differs from the number of components if
+        /// the parameter has a manifold.
+        ///
+        /// # Safety
+        /// `values` must point to already added parameter block.
+        unsafe fn ParameterBlockTangentSize(self: &Problem, values: *const f64) -> i32;
         /// Checks if problem has a given parameter.
         ///
         /// # Safety
@@ -222,6 +450,29 @@ pub mod ffi {
         unsafe fn HasParameterBlock(self: &Problem, values: *const f64) -> bool;
         /// Creates new Problem.
         fn new_problem<'cost>() -> UniquePtr<Problem<'cost>>;
+        /// Creates new Problem with given options, optional evaluation callback and optional
+        /// context, null context means a context owned by the problem.
+        ///
+        /// # Safety
+        /// `evaluation_callback` and `context` must be null or outlive the problem. Functions which
+        /// problem doesn't own must outlive it.
+        unsafe fn new_problem_with_options<'cost>(
+            options: &ProblemOptions,
+            evaluation_callback: *mut CallbackEvaluationCallback<'cost>,
+            context: *mut Context,
+        ) -> UniquePtr<Problem<'cost>>;
+        /// Sets bounds of all components of a parameter block at once. Empty slice means no bounds,
+        /// `-f64::MAX` lower and `f64::MAX` upper bounds mean an unbounded component.
+        ///
+        /// # Safety
+        /// `values` must point to already added parameter block, non-empty slices must have its
+        /// size.
+        unsafe fn set_parameter_bounds(
+            problem: Pin<&mut Problem>,
+            values: *mut f64,
+            lower_bounds: &[f64],
+            upper_bounds: &[f64],
+        );
         /// Adds a residual block to the problem.
         ///
         /// # Safety
@@ -233,6 +484,140 @@ pub mod ffi {
             parameter_blocks: *const *mut f64,
             num_parameter_blocks: i32,
         ) -> SharedPtr<ResidualBlockId>;
+        /// Adds a residual block to the problem without transferring ownership of the functions,
+        /// so they can be shared by several problems. `loss_function` may be null.
+        ///
+        /// # Safety
+        /// `problem` must be created with [Ownership::DO_NOT_TAKE_OWNERSHIP] for cost and loss
+        /// functions, and the functions and `parameter_blocks` must outlive `problem`.
+        unsafe fn add_residual_block_not_owned<'cost>(
+            problem: Pin<&mut Problem<'cost>>,
+            cost_function: *mut CallbackCostFunction<'cost>,
+            loss_function: *mut LossFunction,
+            parameter_blocks: *const *mut f64,
+            num_parameter_blocks: i32,
+        ) -> SharedPtr<ResidualBlockId>;
+        /// Adds several residual blocks to the problem at once, without transferring ownership of
+        /// the functions. `num_parameter_blocks` holds the number of parameter blocks of every
+        /// residual block and `parameter_blocks` holds their pointers concatenated. Elements of
+        /// `loss_functions` may be null. Ids of the new residual blocks are written into
+        /// `residual_block_ids`.
+        ///
+        /// # Safety
+        /// `problem` must be created with [Ownership::DO_NOT_TAKE_OWNERSHIP] for cost and loss
+        /// functions, `cost_functions` and `loss_functions` must point to arrays having the same
+        /// size as `num_parameter_blocks` and `residual_block_ids`, and the functions and
+        /// `parameter_blocks` must outlive `problem`.
+        unsafe fn add_residual_blocks_not_owned<'cost>(
+            problem: Pin<&mut Problem<'cost>>,
+            cost_functions: *const *mut CallbackCostFunction<'cost>,
+            loss_functions: *const *mut LossFunction,
+            parameter_blocks: *const *mut f64,
+            num_parameter_blocks: &[i32],
+            residual_block_ids: &mut [SharedPtr<ResidualBlockId>],
+        );
+        /// Removes the residual block from the problem, the last residual block takes its place.
+        /// Parameter blocks are kept, even if no other residual block depends on them.
+        ///
+        /// # Safety
+        /// `residual_block` must belong to `problem`, Ceres aborts otherwise. The functions of the
+        /// residual block are deleted if the problem owns them.
+        unsafe fn remove_residual_block(
+            problem: Pin<&mut Problem>,
+            residual_block: &SharedPtr<ResidualBlockId>,
+        );
+
+        type ProblemEvaluation;
+        fn cost(self: &ProblemEvaluation) -> f64;
+        fn residuals(self: &ProblemEvaluation) -> &CxxVector<f64>;
+        fn gradient(self: &ProblemEvaluation) -> &CxxVector<f64>;
+        fn jacobian_num_rows(self: &ProblemEvaluation) -> i32;
+        fn jacobian_num_cols(self: &ProblemEvaluation) -> i32;
+        /// Row offsets of the jacobian in compressed row sparse format.
+        fn jacobian_rows(self: &ProblemEvaluation) -> &CxxVector<i32>;
+        /// Column indices of the jacobian in compressed row sparse format.
+        fn jacobian_cols(self: &ProblemEvaluation) -> &CxxVector<i32>;
+        fn jacobian_values(self: &ProblemEvaluation) -> &CxxVector<f64>;
+        /// Evaluates the problem at the current parameter values, returns null if any cost
+        /// function fails. Empty `residual_blocks` means all residual blocks of the problem.
+        ///
+        /// # Safety
+        /// `parameter_blocks` must point to `num_parameter_blocks` already added parameter blocks.
+        unsafe fn evaluate_problem(
+            problem: Pin<&mut Problem>,
+            parameter_blocks: *const *mut f64,
+            num_parameter_blocks: i32,
+            residual_blocks: &[SharedPtr<ResidualBlockId>],
+            apply_loss_function: bool,
+            num_threads: i32,
+            compute_jacobian: bool,
+        ) -> UniquePtr<ProblemEvaluation>;
+
+        type ResidualBlockEvaluation;
+        fn cost(self: &ResidualBlockEvaluation) -> f64;
+        fn residuals(self: &ResidualBlockEvaluation) -> &CxxVector<f64>;
+        fn num_jacobians(self: &ResidualBlockEvaluation) -> usize;
+        /// Whether the jacobian was computed, it is not for constant parameter blocks.
+        fn has_jacobian(self: &ResidualBlockEvaluation, index: usize) -> bool;
+        /// Row-major jacobian with respect to the tangent space of the parameter block, empty if
+        /// it wasn't computed.
+        fn jacobian(self: &ResidualBlockEvaluation, index: usize) -> &CxxVector<f64>;
+        /// Evaluates a single residual block at the current parameter values, returns null if the
+        /// cost function fails.
+        ///
+        /// # Safety
+        /// `residual_block` must belong to `problem`.
+        unsafe fn evaluate_residual_block(
+            problem: &Problem,
+            residual_block: &SharedPtr<ResidualBlockId>,
+            apply_loss_function: bool,
+        ) -> UniquePtr<ResidualBlockEvaluation>;
+        /// Indices of `residual_blocks` elements which depend on the parameter block.
+        ///
+        /// # Safety
+        /// `values` must point to a parameter block already added to `problem`.
+        unsafe fn residual_blocks_for_parameter_block(
+            problem: &Problem,
+            values: *const f64,
+            residual_blocks: &[SharedPtr<ResidualBlockId>],
+        ) -> UniquePtr<CxxVector<usize>>;
+        /// Computes the covariance matrix of the parameter blocks, row-major with the components
+        /// of the blocks concatenated in their ambient spaces. Returns null if the computation
+        /// fails, e.g. the jacobian is rank deficient or a cost function fails.
+        ///
+        /// # Safety
+        /// `parameter_blocks` must point to `num_parameter_blocks` distinct parameter blocks
+        /// already added to `problem`.
+        unsafe fn compute_covariance(
+            problem: Pin<&mut Problem>,
+            options: &CovarianceOptions,
+            parameter_blocks: *const *mut f64,
+            num_parameter_blocks: i32,
+        ) -> UniquePtr<CxxVector<f64>>;
+
+        type GradientCheckResults;
+        /// Whether the analytic and numeric jacobians agree within the relative precision.
+        fn passed(self: &GradientCheckResults) -> bool;
+        fn maximum_relative_error(self: &GradientCheckResults) -> f64;
+        fn residuals(self: &GradientCheckResults) -> &CxxVector<f64>;
+        fn num_jacobians(self: &GradientCheckResults) -> usize;
+        /// Row-major jacobian with respect to the parameter block computed by the cost function.
+        fn jacobian(self: &GradientCheckResults, index: usize) -> &CxxVector<f64>;
+        /// Row-major jacobian with respect to the parameter block computed with central finite
+        /// differences.
+        fn numeric_jacobian(self: &GradientCheckResults, index: usize) -> &CxxVector<f64>;
+        fn error_log(self: &GradientCheckResults) -> &CxxString;
+        /// Compares jacobians of the cost function with finite differences using
+        /// `ceres::GradientChecker`, returns null if the cost function fails.
+        ///
+        /// # Safety
+        /// `parameters` must point to an array of the cost function's parameter block count, every
+        /// element must point to an array of the corresponding parameter block size.
+        unsafe fn check_gradients(
+            cost_function: &CallbackCostFunction,
+            parameters: *const *const f64,
+            relative_precision: f64,
+        ) -> UniquePtr<GradientCheckResults>;
 
         type SolverOptions;
         fn is_valid(self: &SolverOptions, error: Pin<&mut CxxString>) -> bool;
@@ -333,6 +718,25 @@ pub mod ffi {
             self: Pin<&mut SolverOptions>,
             sparse_linear_algebra_library_type: SparseLinearAlgebraLibraryType,
         );
+        fn set_linear_solver_ordering_type(
+            self: Pin<&mut SolverOptions>,
+            linear_solver_ordering_type: LinearSolverOrderingType,
+        );
+        fn set_use_mixed_precision_solves(self: Pin<&mut SolverOptions>, yes: bool);
+        fn set_max_num_refinement_iterations(
+            self: Pin<&mut SolverOptions>,
+            max_num_refinement_iterations: i32,
+        );
+        fn set_min_linear_solver_iterations(
+            self: Pin<&mut SolverOptions>,
+            min_linear_solver_iterations: i32,
+        );
+        fn set_max_linear_solver_iterations(
+            self: Pin<&mut SolverOptions>,
+            max_linear_solver_iterations: i32,
+        );
+        fn set_eta(self: Pin<&mut SolverOptions>, eta: f64);
+        fn set_jacobi_scaling(self: Pin<&mut SolverOptions>, yes: bool);
         fn set_logging_type(self: Pin<&mut SolverOptions>, logging_type: LoggingType);
         fn set_minimizer_progress_to_stdout(self: Pin<&mut SolverOptions>, yes: bool);
         fn set_trust_region_minimizer_iterations_to_dump(
@@ -357,14 +761,85 @@ pub mod ffi {
             gradient_check_numeric_derivative_relative_step_size: f64,
         );
         fn set_update_state_every_iteration(self: Pin<&mut SolverOptions>, yes: bool);
+        /// Adds a callback to be called after every minimizer iteration, options own it.
+        fn add_iteration_callback(
+            self: Pin<&mut SolverOptions>,
+            callback: Box<RustIterationCallback>,
+        );
+        fn minimizer_type(self: &SolverOptions) -> MinimizerType;
+        fn line_search_direction_type(self: &SolverOptions) -> LineSearchDirectionType;
+        fn line_search_type(self: &SolverOptions) -> LineSearchType;
+        fn nonlinear_conjugate_gradient_type(
+            self: &SolverOptions,
+        ) -> NonlinearConjugateGradientType;
+        fn max_lbfgs_rank(self: &SolverOptions) -> i32;
+        fn use_approximate_eigenvalue_bfgs_scaling(self: &SolverOptions) -> bool;
+        fn line_search_interpolation_type(self: &SolverOptions) -> LineSearchInterpolationType;
+        fn min_line_search_step_size(self: &SolverOptions) -> f64;
+        fn line_search_sufficient_function_decrease(self: &SolverOptions) -> f64;
+        fn max_line_search_step_contraction(self: &SolverOptions) -> f64;
+        fn min_line_search_step_contraction(self: &SolverOptions) -> f64;
+        fn max_num_line_search_direction_restarts(self: &SolverOptions) -> i32;
+        fn line_search_sufficient_curvature_decrease(self: &SolverOptions) -> f64;
+        fn max_line_search_step_expansion(self: &SolverOptions) -> f64;
+        fn trust_region_strategy_type(self: &SolverOptions) -> TrustRegionStrategyType;
+        fn dogleg_type(self: &SolverOptions) -> DoglegType;
+        fn use_nonmonotonic_steps(self: &SolverOptions) -> bool;
+        fn max_consecutive_nonmonotonic_steps(self: &SolverOptions) -> i32;
+        fn max_num_iterations(self: &SolverOptions) -> i32;
+        fn max_solver_time_in_seconds(self: &SolverOptions) -> f64;
+        fn num_threads(self: &SolverOptions) -> i32;
+        fn initial_trust_region_radius(self: &SolverOptions) -> f64;
+        fn max_trust_region_radius(self: &SolverOptions) -> f64;
+        fn min_trust_region_radius(self: &SolverOptions) -> f64;
+        fn min_relative_decrease(self: &SolverOptions) -> f64;
+        fn min_lm_diagonal(self: &SolverOptions) -> f64;
+        fn max_lm_diagonal(self: &SolverOptions) -> f64;
+        fn max_num_consecutive_invalid_steps(self: &SolverOptions) -> i32;
+        fn function_tolerance(self: &SolverOptions) -> f64;
+        fn gradient_tolerance(self: &SolverOptions) -> f64;
+        fn parameter_tolerance(self: &SolverOptions) -> f64;
+        fn linear_solver_type(self: &SolverOptions) -> LinearSolverType;
+        fn preconditioner_type(self: &SolverOptions) -> PreconditionerType;
+        fn visibility_clustering_type(self: &SolverOptions) -> VisibilityClusteringType;
+        fn num_residual_blocks_for_subset_preconditioner(self: &SolverOptions) -> usize;
+        fn residual_block_for_subset_preconditioner(
+            self: &SolverOptions,
+            index: usize,
+        ) -> SharedPtr<ResidualBlockId>;
+        fn dense_linear_algebra_library_type(self: &SolverOptions)
+            -> DenseLinearAlgebraLibraryType;
+        fn sparse_linear_algebra_library_type(
+            self: &SolverOptions,
+        ) -> SparseLinearAlgebraLibraryType;
+        fn linear_solver_ordering_type(self: &SolverOptions) -> LinearSolverOrderingType;
+        fn use_mixed_precision_solves(self: &SolverOptions) -> bool;
+        fn max_num_refinement_iterations(self: &SolverOptions) -> i32;
+        fn min_linear_solver_iterations(self: &SolverOptions) -> i32;
+        fn max_linear_solver_iterations(self: &SolverOptions) -> i32;
+        fn eta(self: &SolverOptions) -> f64;
+        fn jacobi_scaling(self: &SolverOptions) -> bool;
+        fn logging_type(self: &SolverOptions) -> LoggingType;
+        fn minimizer_progress_to_stdout(self: &SolverOptions) -> bool;
+        fn trust_region_minimizer_iterations_to_dump(self: &SolverOptions) -> &CxxVector<i32>;
+        fn trust_region_problem_dump_directory(self: &SolverOptions) -> &CxxString;
+        fn trust_region_problem_dump_format_type(self: &SolverOptions) -> DumpFormatType;
+        fn check_gradients(self: &SolverOptions) -> bool;
+        fn gradient_check_relative_precision(self: &SolverOptions) -> f64;
+        fn gradient_check_numeric_derivative_relative_step_size(self: &SolverOptions) -> f64;
+        fn update_state_every_iteration(self: &SolverOptions) -> bool;
 
         /// Create an instance wrapping Solver::Options.
         fn new_solver_options() -> UniquePtr<SolverOptions>;
+        /// Copy Solver::Options, callbacks are shared between the copies.
+        fn clone_solver_options(options: &SolverOptions) -> UniquePtr<SolverOptions>;
 
         type SolverSummary;
         fn brief_report(self: &SolverSummary) -> UniquePtr<CxxString>;
         fn full_report(self: &SolverSummary) -> UniquePtr<CxxString>;
         fn is_solution_usable(self: &SolverSummary) -> bool;
+        fn termination_type(self: &SolverSummary) -> TerminationType;
+        fn message(self: &SolverSummary) -> UniquePtr<CxxString>;
         fn initial_cost(self: &SolverSummary) -> f64;
         fn final_cost(self: &SolverSummary) -> f64;
         fn fixed_cost(self: &SolverSummary) -> f64;
@@ -381,9 +856,24 @@ pub mod ffi {
             problem: Pin<&mut Problem>,
             summary: Pin<&mut SolverSummary>,
         );
+
+        /// Sets a glog sink replacing the previous one, glog stops writing to stderr and files.
+        fn set_log_sink(inner: Box<RustLogSink>);
+        /// Initializes glog if needed, sets minimum severity (0 is INFO, 3 is FATAL) and whether
+        /// to log to stderr.
+        fn init_logging(program_name: &str, min_level: i32, log_to_stderr: bool);
+        /// Logs a message with glog WARNING severity.
+        fn log_warning(message: &str);
     }
 }
 
+/// Call `f` catching a panic, unwinding into C++ is undefined behaviour, `on_panic` gives the
+/// value to return to C++ instead.
+fn catch_panic<R>(f: impl FnOnce() -> R, on_panic: impl FnOnce() -> R) -> R {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|_| on_panic())
+}
+
+/// Rust cost function called by `CallbackCostFunction`, a panic is reported as a failed evaluation.
 pub struct RustCostFunction<'cost>(
     pub Box<dyn Fn(*const *const f64, *mut f64, *mut *mut f64) -> bool + 'cost>,
 );
@@ -395,7 +885,7 @@ impl RustCostFunction<'_> {
         residuals: *mut f64,
         jacobians: *mut *mut f64,
     ) -> bool {
-        (self.0)(parameters, residuals, jacobians)
+        catch_panic(|| (self.0)(parameters, residuals, jacobians), || false)
     }
 }
 
@@ -409,11 +899,15 @@ impl<'cost> From<Box<dyn Fn(*const *const f64, *mut f64, *mut *mut f64) -> bool
     }
 }
 
+/// Rust loss function called by `CallbackLossFunction`, a panic makes all three outputs NaN.
 pub struct RustLossFunction(pub Box<dyn Fn(f64, *mut f64)>);
 
 impl RustLossFunction {
     pub fn evaluate(&self, sq_norm: f64, out: *mut f64) {
-        (self.0)(sq_norm, out)
+        catch_panic(
+            || (self.0)(sq_norm, out),
+            || unsafe { *(out as *mut [f64; 3]) = [f64::NAN; 3] },
+        )
     }
 }
 
@@ -423,6 +917,97 @@ impl From<Box<dyn Fn(f64, *mut f64)>> for RustLossFunction {
     }
 }
 
+/// Operations of a manifold implemented in Rust, see `ceres::Manifold` for the arguments. The
+/// jacobians are row-major, they return [false] if the operation fails.
+pub trait RawManifold {
+    fn plus(&self, x: *const f64, delta: *const f64, x_plus_delta: *mut f64) -> bool;
+    fn plus_jacobian(&self, x: *const f64, jacobian: *mut f64) -> bool;
+    fn minus(&self, y: *const f64, x: *const f64, y_minus_x: *mut f64) -> bool;
+    fn minus_jacobian(&self, x: *const f64, jacobian: *mut f64) -> bool;
+}
+
+/// Rust manifold called by `CallbackManifold`, a panic is reported as a failed operation.
+pub struct RustManifold(pub Box<dyn RawManifold>);
+
+impl RustManifold {
+    pub fn plus(&self, x: *const f64, delta: *const f64, x_plus_delta: *mut f64) -> bool {
+        catch_panic(|| self.0.plus(x, delta, x_plus_delta), || false)
+    }
+
+    pub fn plus_jacobian(&self, x: *const f64, jacobian: *mut f64) -> bool {
+        catch_panic(|| self.0.plus_jacobian(x, jacobian), || false)
+    }
+
+    pub fn minus(&self, y: *const f64, x: *const f64, y_minus_x: *mut f64) -> bool {
+        catch_panic(|| self.0.minus(y, x, y_minus_x), || false)
+    }
+
+    pub fn minus_jacobian(&self, x: *const f64, jacobian: *mut f64) -> bool {
+        catch_panic(|| self.0.minus_jacobian(x, jacobian), || false)
+    }
+}
+
+impl From<Box<dyn RawManifold>> for RustManifold {
+    fn from(value: Box<dyn RawManifold>) -> Self {
+        Self(value)
+    }
+}
+
+/// Rust function called by `CallbackEvaluationCallback`, a panic is ignored.
+pub struct RustEvaluationCallback<'cost>(pub Box<dyn Fn(bool, bool) + 'cost>);
+
+impl RustEvaluationCallback<'_> {
+    pub fn prepare_for_evaluation(&self, evaluate_jacobians: bool, new_evaluation_point: bool) {
+        catch_panic(|| (self.0)(evaluate_jacobians, new_evaluation_point), || ())
+    }
+}
+
+impl<'cost> From<Box<dyn Fn(bool, bool) + 'cost>> for RustEvaluationCallback<'cost> {
+    fn from(value: Box<dyn Fn(bool, bool) + 'cost>) -> Self {
+        Self(value)
+    }
+}
+
+/// Rust function called by `CallbackIterationCallback`, a panic aborts the solver.
+pub struct RustIterationCallback(
+    pub Box<dyn FnMut(&ffi::IterationSummary) -> ffi::CallbackReturnType>,
+);
+
+impl RustIterationCallback {
+    pub fn call(&mut self, summary: &ffi::IterationSummary) -> ffi::CallbackReturnType {
+        catch_panic(
+            || (self.0)(summary),
+            || ffi::CallbackReturnType::SOLVER_ABORT,
+        )
+    }
+}
+
+impl From<Box<dyn FnMut(&ffi::IterationSummary) -> ffi::CallbackReturnType>>
+    for RustIterationCallback
+{
+    fn from(value: Box<dyn FnMut(&ffi::IterationSummary) -> ffi::CallbackReturnType>) -> Self {
+        Self(value)
+    }
+}
+
+/// Function receiving glog messages: severity, base file name, line and message.
+pub type LogSinkFunction = Box<dyn Fn(i32, &[u8], i32, &[u8]) + Send + Sync>;
+
+/// Rust function called by the glog sink, a panic is ignored.
+pub struct RustLogSink(pub LogSinkFunction);
+
+impl RustLogSink {
+    pub fn send(&self, severity: i32, base_filename: &[u8], line: i32, message: &[u8]) {
+        catch_panic(|| (self.0)(severity, base_filename, line, message), || ())
+    }
+}
+
+impl From<LogSinkFunction> for RustLogSink {
+    fn from(value: LogSinkFunction) -> Self {
+        Self(value)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -517,4 +1102,20 @@ mod tests {
     fn end_to_end_stock_loss() {
         end_to_end(ffi::new_arctan_loss(1.0));
     }
+
+    #[test]
+    fn end_to_end_scaled_loss() {
+        end_to_end(ffi::new_scaled_loss(ffi::new_arctan_loss(1.0), 2.0));
+    }
+
+    #[test]
+    fn panics_are_caught() {
+        let cost = RustCostFunction(Box::new(|_, _, _| panic!("cost")));
+        assert!(!cost.evaluate(std::ptr::null(), std::ptr::null_mut(), std::ptr::null_mut()));
+
+        let loss = RustLossFunction(Box::new(|_, _| panic!("loss")));
+        let mut out = [0.0; 3];
+        loss.evaluate(1.0, out.as_mut_ptr());
+        assert!(out.iter().all(|value| value.is_nan()));
+    }
 }
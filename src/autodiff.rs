@@ -0,0 +1,740 @@
+//! Automatic differentiation of cost functions, the Rust counterpart of
+//! `ceres::AutoDiffCostFunction`.
+//!
+//! Write residuals once, generically over [Scalar], and wrap the functor into
+//! [AutoDiffCostFunction], which implements [CostFunction]. The functor is called with [f64]
+//! values when the solver needs residuals only, and with [Jet] dual numbers carrying the
+//! derivatives otherwise, so the jacobians are exact. Manifolds can be differentiated the same
+//! way with [AutoDiffManifold].
+//!
+//! ```rust
+//! use ceres_solver::autodiff::{AutoDiffCostFunction, AutoDiffCostFunctor, Scalar};
+//! // Math functions of Scalar
+//! use num_traits::Float;
+//! use ceres_solver::{NllsProblem, SolverOptions};
+//!
+//! // Model y = a * exp(-b * x)
+//! struct Decay {
+//!     x: Vec<f64>,
+//!     y: Vec<f64>,
+//! }
+//!
+//! impl AutoDiffCostFunctor for Decay {
+//!     fn residuals<T: Scalar>(&self, parameters: &[&[T]], residuals: &mut [T]) -> bool {
+//!         let (a, b) = (parameters[0][0], parameters[0][1]);
+//!         for ((residual, &x), &y) in residuals.iter_mut().zip(&self.x).zip(&self.y) {
+//!             *residual = a * (-b * x).exp() - y;
+//!         }
+//!         true
+//!     }
+//!
+//!     fn num_residuals(&self) -> usize {
+//!         self.x.len()
+//!     }
+//!
+//!     fn parameter_sizes(&self) -> Vec<usize> {
+//!         vec![2]
+//!     }
+//! }
+//!
+//! let x: Vec<_> = (0..10).map(|i| i as f64).collect();
+//! let y = x.iter().map(|&x| 3.0 * f64::exp(-0.5 * x)).collect();
+//! // 2 is the total number of parameter components
+//! let cost = AutoDiffCostFunction::<_, 2>::new(Decay { x, y });
+//! let solution = NllsProblem::new()
+//!     .residual_block_builder()
+//!     .set_cost_fn(cost)
+//!     .set_parameters([vec![1.0, 1.0]])
+//!     .build_into_problem()
+//!     .unwrap()
+//!     .0
+//!     .solve(&SolverOptions::default())
+//!     .unwrap();
+//! assert!((solution.parameters[0][0] - 3.0).abs() < 1e-6);
+//! assert!((solution.parameters[0][1] - 0.5).abs() < 1e-6);
+//! ```
+
+use crate::cost::CostFunction;
+use crate::jet::Jet;
+use crate::manifold::CustomManifold;
+use crate::types::JacobianType;
+
+use num_traits::Float;
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
+
+/// Scalar type of generic residual functions, implemented by [f64] and by [Jet] used for
+/// automatic differentiation.
+///
+/// Math functions come from [Float], so generic code written for [num_traits] can be called with
+/// any [Scalar]. Comparison operators of [Jet] compare the values only.
+pub trait Scalar:
+    Float
+    + Debug
+    + Add<f64, Output = Self>
+    + Sub<f64, Output = Self>
+    + Mul<f64, Output = Self>
+    + Div<f64, Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+{
+    /// Constant, its derivatives are zero.
+    fn from_f64(value: f64) -> Self;
+
+    /// Value without derivatives.
+    fn value(self) -> f64;
+}
+
+impl Scalar for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn value(self) -> f64 {
+        self
+    }
+}
+
+/// Residual function generic over [Scalar], see [AutoDiffCostFunction].
+pub trait AutoDiffCostFunctor {
+    /// Find residuals for the given parameters, return [false] if they cannot be computed.
+    ///
+    /// The slice sizes are the same as for [CostFunction::evaluate].
+    fn residuals<T: Scalar>(&self, parameters: &[&[T]], residuals: &mut [T]) -> bool;
+
+    /// Length of the residual vector.
+    fn num_residuals(&self) -> usize;
+
+    /// Sizes of the parameter blocks.
+    fn parameter_sizes(&self) -> Vec<usize>;
+}
+
+/// Attribute macro implementing [AutoDiffCostFunctor] from an inherent `residuals` method working
+/// with fixed-size arrays, available with `derive` Cargo feature.
+///
+/// ```rust
+/// use ceres_solver::autodiff::{residual, Scalar};
+/// use ceres_solver::residual_block::ResidualBlockSpec;
+/// use ceres_solver::{NllsProblem, SolverOptions};
+/// // Math functions of Scalar
+/// use num_traits::Float;
+///
+/// // Measured distance from a station to the point
+/// struct Distance {
+///     station: [f64; 2],
+///     distance: f64,
+/// }
+///
+/// #[residual]
+/// impl Distance {
+///     fn residuals<T: Scalar>(&self, point: &[T; 2]) -> [T; 1] {
+///         let dx = point[0] - self.station[0];
+///         let dy = point[1] - self.station[1];
+///         [(dx * dx + dy * dy).sqrt() - self.distance]
+///     }
+/// }
+///
+/// let cost = |station: [f64; 2]| {
+///     let distance = f64::hypot(3.0 - station[0], 4.0 - station[1]);
+///     Distance { station, distance }.into_cost_function()
+/// };
+/// let (mut problem, _) = NllsProblem::new()
+///     .residual_block_builder()
+///     .set_cost_fn(cost([0.0, 0.0]))
+///     .set_parameters([vec![1.0, 1.0]])
+///     .build_into_problem()
+///     .unwrap();
+/// problem
+///     .add_residual_blocks([
+///         ResidualBlockSpec::from_cost_fn(cost([10.0, 0.0]), [0_usize]),
+///         ResidualBlockSpec::from_cost_fn(cost([0.0, 10.0]), [0_usize]),
+///     ])
+///     .unwrap();
+/// let solution = problem.solve(&SolverOptions::default()).unwrap();
+/// assert!((solution.parameters[0][0] - 3.0).abs() < 1e-6);
+/// assert!((solution.parameters[0][1] - 4.0).abs() < 1e-6);
+/// ```
+#[cfg(feature = "derive")]
+pub use ceres_solver_derive::residual;
+
+/// Cost function with the jacobians found by automatic differentiation of
+/// [AutoDiffCostFunctor].
+///
+/// `N` is the total number of parameter components, i.e. the sum of
+/// [AutoDiffCostFunctor::parameter_sizes]. It is a compile-time constant, so the derivatives of
+/// the dual numbers are fixed-size arrays and need no allocation.
+pub struct AutoDiffCostFunction<F, const N: usize> {
+    functor: F,
+    num_residuals: usize,
+    parameter_sizes: Vec<usize>,
+}
+
+impl<F, const N: usize> AutoDiffCostFunction<F, N>
+where
+    F: AutoDiffCostFunctor,
+{
+    /// Wrap the functor.
+    ///
+    /// # Panics
+    /// If `N` is not equal to the total number of parameter components of the functor.
+    pub fn new(functor: F) -> Self {
+        let parameter_sizes = functor.parameter_sizes();
+        let num_parameters: usize = parameter_sizes.iter().sum();
+        assert_eq!(
+            num_parameters, N,
+            "AutoDiffCostFunction<_, {N}> is used for a functor with {num_parameters} parameter components"
+        );
+        Self {
+            num_residuals: functor.num_residuals(),
+            parameter_sizes,
+            functor,
+        }
+    }
+
+    /// The wrapped functor.
+    pub fn functor(&self) -> &F {
+        &self.functor
+    }
+}
+
+impl<F, const N: usize> CostFunction for AutoDiffCostFunction<F, N>
+where
+    F: AutoDiffCostFunctor,
+{
+    fn evaluate(
+        &self,
+        parameters: &[&[f64]],
+        residuals: &mut [f64],
+        jacobians: JacobianType<'_>,
+    ) -> bool {
+        let Some(jacobians) = jacobians else {
+            return self.functor.residuals(parameters, residuals);
+        };
+
+        let mut offset = 0;
+        let jet_parameters: Vec<Vec<Jet<N>>> = parameters
+            .iter()
+            .map(|block| {
+                let jets = block
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &value)| Jet::variable(value, offset + i))
+                    .collect();
+                offset += block.len();
+                jets
+            })
+            .collect();
+        let jet_parameter_refs: Vec<&[Jet<N>]> = jet_parameters.iter().map(|v| &v[..]).collect();
+        let mut jet_residuals = vec![Jet::constant(0.0); self.num_residuals];
+        if !self
+            .functor
+            .residuals(&jet_parameter_refs, &mut jet_residuals)
+        {
+            return false;
+        }
+
+        for (residual, jet) in residuals.iter_mut().zip(&jet_residuals) {
+            *residual = jet.a;
+        }
+        let mut offset = 0;
+        for (jacobian, &size) in jacobians.iter_mut().zip(&self.parameter_sizes) {
+            if let Some(jacobian) = jacobian {
+                for (row, jet) in jacobian.iter_mut().zip(&jet_residuals) {
+                    row.copy_from_slice(&jet.v[offset..offset + size]);
+                }
+            }
+            offset += size;
+        }
+        true
+    }
+
+    fn num_residuals(&self) -> usize {
+        self.num_residuals
+    }
+
+    fn parameter_sizes(&self) -> Vec<usize> {
+        self.parameter_sizes.clone()
+    }
+}
+
+/// Residual function generic over [Scalar] with the sizes defined at runtime, see
+/// [DynamicAutoDiffCostFunction].
+pub trait DynamicAutoDiffCostFunctor {
+    /// Find residuals for the given parameters, return [false] if they cannot be computed.
+    ///
+    /// The slice sizes are set by [DynamicAutoDiffCostFunction::add_parameter_block] and
+    /// [DynamicAutoDiffCostFunction::set_num_residuals].
+    fn residuals<T: Scalar>(&self, parameters: &[&[T]], residuals: &mut [T]) -> bool;
+}
+
+/// Cost function with the parameter block sizes and the number of residuals set at runtime and
+/// the jacobians found by automatic differentiation, the Rust counterpart of
+/// `ceres::DynamicAutoDiffCostFunction`.
+///
+/// The derivatives are found in several passes, each pass differentiates `STRIDE` parameter
+/// components, so the functor is called `ceil(num_parameters / STRIDE)` times per jacobian
+/// evaluation. Parameter blocks without requested jacobian, e.g. constant ones, are not
+/// differentiated.
+pub struct DynamicAutoDiffCostFunction<F, const STRIDE: usize = 4> {
+    functor: F,
+    num_residuals: usize,
+    parameter_sizes: Vec<usize>,
+}
+
+impl<F, const STRIDE: usize> DynamicAutoDiffCostFunction<F, STRIDE>
+where
+    F: DynamicAutoDiffCostFunctor,
+{
+    /// Wrap the functor, add parameter blocks with
+    /// [DynamicAutoDiffCostFunction::add_parameter_block] and set the number of residuals with
+    /// [DynamicAutoDiffCostFunction::set_num_residuals].
+    pub fn new(functor: F) -> Self {
+        Self {
+            functor,
+            num_residuals: 0,
+            parameter_sizes: Vec::new(),
+        }
+    }
+
+    /// Add a parameter block of the given size.
+    pub fn add_parameter_block(&mut self, size: usize) {
+        self.parameter_sizes.push(size);
+    }
+
+    /// Set the length of the residual vector.
+    pub fn set_num_residuals(&mut self, num_residuals: usize) {
+        self.num_residuals = num_residuals;
+    }
+
+    /// The wrapped functor.
+    pub fn functor(&self) -> &F {
+        &self.functor
+    }
+}
+
+impl<F, const STRIDE: usize> CostFunction for DynamicAutoDiffCostFunction<F, STRIDE>
+where
+    F: DynamicAutoDiffCostFunctor,
+{
+    fn evaluate(
+        &self,
+        parameters: &[&[f64]],
+        residuals: &mut [f64],
+        jacobians: JacobianType<'_>,
+    ) -> bool {
+        // (block index, component index) of the components to differentiate
+        let variables: Vec<(usize, usize)> = match &jacobians {
+            Some(jacobians) => jacobians
+                .iter()
+                .zip(&self.parameter_sizes)
+                .enumerate()
+                .filter(|(_, (jacobian, _))| jacobian.is_some())
+                .flat_map(|(block, (_, &size))| (0..size).map(move |i| (block, i)))
+                .collect(),
+            None => Vec::new(),
+        };
+        let Some(jacobians) = jacobians.filter(|_| !variables.is_empty()) else {
+            return self.functor.residuals(parameters, residuals);
+        };
+
+        let mut jet_parameters: Vec<Vec<Jet<STRIDE>>> = parameters
+            .iter()
+            .map(|block| block.iter().map(|&value| Jet::constant(value)).collect())
+            .collect();
+        let mut jet_residuals = vec![Jet::constant(0.0); self.num_residuals];
+        for chunk in variables.chunks(STRIDE) {
+            for (index, &(block, i)) in chunk.iter().enumerate() {
+                jet_parameters[block][i] = Jet::variable(parameters[block][i], index);
+            }
+            let jet_parameter_refs: Vec<&[Jet<STRIDE>]> =
+                jet_parameters.iter().map(|v| &v[..]).collect();
+            if !self
+                .functor
+                .residuals(&jet_parameter_refs, &mut jet_residuals)
+            {
+                return false;
+            }
+            for (index, &(block, i)) in chunk.iter().enumerate() {
+                jet_parameters[block][i] = Jet::constant(parameters[block][i]);
+                let jacobian = jacobians[block]
+                    .as_mut()
+                    .expect("Only blocks with requested jacobian are differentiated");
+                for (row, jet) in jacobian.iter_mut().zip(&jet_residuals) {
+                    row[i] = jet.v[index];
+                }
+            }
+        }
+        for (residual, jet) in residuals.iter_mut().zip(&jet_residuals) {
+            *residual = jet.a;
+        }
+        true
+    }
+
+    fn num_residuals(&self) -> usize {
+        self.num_residuals
+    }
+
+    fn parameter_sizes(&self) -> Vec<usize> {
+        self.parameter_sizes.clone()
+    }
+}
+
+/// Plus and minus operations of a manifold generic over [Scalar], see [AutoDiffManifold].
+pub trait AutoDiffManifoldFunctor {
+    /// Move the point `x` along the tangent vector `delta`, return [false] if it fails. See
+    /// [CustomManifold::plus].
+    fn plus<T: Scalar>(&self, x: &[T], delta: &[T], x_plus_delta: &mut [T]) -> bool;
+
+    /// Tangent vector moving `x` to `y`, return [false] if it fails. See [CustomManifold::minus].
+    fn minus<T: Scalar>(&self, y: &[T], x: &[T], y_minus_x: &mut [T]) -> bool;
+}
+
+/// Manifold with the jacobians found by automatic differentiation of
+/// [AutoDiffManifoldFunctor], the Rust counterpart of `ceres::AutoDiffManifold`.
+///
+/// `AMBIENT` and `TANGENT` are the dimensions of the ambient and tangent spaces. Wrap it into
+/// [Manifold](crate::manifold::Manifold) with
+/// [Manifold::custom](crate::manifold::Manifold::custom).
+///
+/// ```rust
+/// use ceres_solver::autodiff::{AutoDiffManifold, AutoDiffManifoldFunctor, Scalar};
+/// use ceres_solver::manifold::Manifold;
+/// use ceres_solver::{CostFunctionType, NllsProblem, SolverOptions};
+/// // Math functions of Scalar
+/// use num_traits::Float;
+///
+/// // Positive numbers, the solver steps in the logarithm
+/// struct Positive;
+///
+/// impl AutoDiffManifoldFunctor for Positive {
+///     fn plus<T: Scalar>(&self, x: &[T], delta: &[T], x_plus_delta: &mut [T]) -> bool {
+///         x_plus_delta[0] = x[0] * delta[0].exp();
+///         true
+///     }
+///
+///     fn minus<T: Scalar>(&self, y: &[T], x: &[T], y_minus_x: &mut [T]) -> bool {
+///         y_minus_x[0] = (y[0] / x[0]).ln();
+///         true
+///     }
+/// }
+///
+/// // r = x - (-1), the minimum is out of the manifold
+/// let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+///     residuals[0] = parameters[0][0] + 1.0;
+///     if let Some(jacobians) = jacobians {
+///         if let Some(d_dx) = &mut jacobians[0] {
+///             d_dx[0][0] = 1.0;
+///         }
+///     }
+///     true
+/// });
+/// let (mut problem, _block_id) = NllsProblem::new()
+///     .residual_block_builder()
+///     .set_cost(cost, 1)
+///     .set_parameters([vec![1.0]])
+///     .build_into_problem()
+///     .unwrap();
+/// let manifold = AutoDiffManifold::<_, 1, 1>::new(Positive);
+/// problem
+///     .set_parameter_block_manifold(0, Some(Manifold::custom_threadsafe(manifold)))
+///     .unwrap();
+/// let solution = problem.solve(&SolverOptions::default()).unwrap();
+/// assert!(solution.parameters[0][0] > 0.0);
+/// ```
+pub struct AutoDiffManifold<F, const AMBIENT: usize, const TANGENT: usize> {
+    functor: F,
+}
+
+impl<F, const AMBIENT: usize, const TANGENT: usize> AutoDiffManifold<F, AMBIENT, TANGENT>
+where
+    F: AutoDiffManifoldFunctor,
+{
+    /// Wrap the functor.
+    pub fn new(functor: F) -> Self {
+        Self { functor }
+    }
+
+    /// The wrapped functor.
+    pub fn functor(&self) -> &F {
+        &self.functor
+    }
+}
+
+impl<F, const AMBIENT: usize, const TANGENT: usize> CustomManifold
+    for AutoDiffManifold<F, AMBIENT, TANGENT>
+where
+    F: AutoDiffManifoldFunctor,
+{
+    fn ambient_size(&self) -> usize {
+        AMBIENT
+    }
+
+    fn tangent_size(&self) -> usize {
+        TANGENT
+    }
+
+    fn plus(&self, x: &[f64], delta: &[f64], x_plus_delta: &mut [f64]) -> bool {
+        self.functor.plus(x, delta, x_plus_delta)
+    }
+
+    fn plus_jacobian(&self, x: &[f64], jacobian: &mut [f64]) -> bool {
+        let x: Vec<Jet<TANGENT>> = x.iter().map(|&x| Jet::constant(x)).collect();
+        let delta: [Jet<TANGENT>; TANGENT] = std::array::from_fn(|i| Jet::variable(0.0, i));
+        let mut x_plus_delta = [Jet::constant(0.0); AMBIENT];
+        if !self.functor.plus(&x, &delta, &mut x_plus_delta) {
+            return false;
+        }
+        for (row, jet) in jacobian.chunks_exact_mut(TANGENT).zip(&x_plus_delta) {
+            row.copy_from_slice(&jet.v);
+        }
+        true
+    }
+
+    fn minus(&self, y: &[f64], x: &[f64], y_minus_x: &mut [f64]) -> bool {
+        self.functor.minus(y, x, y_minus_x)
+    }
+
+    fn minus_jacobian(&self, x: &[f64], jacobian: &mut [f64]) -> bool {
+        let y: [Jet<AMBIENT>; AMBIENT] = std::array::from_fn(|i| Jet::variable(x[i], i));
+        let x: Vec<Jet<AMBIENT>> = x.iter().map(|&x| Jet::constant(x)).collect();
+        let mut y_minus_x = [Jet::constant(0.0); TANGENT];
+        if !self.functor.minus(&y, &x, &mut y_minus_x) {
+            return false;
+        }
+        for (row, jet) in jacobian.chunks_exact_mut(AMBIENT).zip(&y_minus_x) {
+            row.copy_from_slice(&jet.v);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::nlls_problem::NllsProblem;
+    use crate::solver::SolverOptions;
+
+    use approx::assert_abs_diff_eq;
+    use std::f64::consts::PI;
+
+    /// Residual of Rosenbrock function, (1 - x, 10 (y - x^2)).
+    struct Rosenbrock;
+
+    impl AutoDiffCostFunctor for Rosenbrock {
+        fn residuals<T: Scalar>(&self, parameters: &[&[T]], residuals: &mut [T]) -> bool {
+            let (x, y) = (parameters[0][0], parameters[1][0]);
+            residuals[0] = -x + 1.0;
+            residuals[1] = (y - x * x) * 10.0;
+            true
+        }
+
+        fn num_residuals(&self) -> usize {
+            2
+        }
+
+        fn parameter_sizes(&self) -> Vec<usize> {
+            vec![1, 1]
+        }
+    }
+
+    #[test]
+    fn jacobian() {
+        let cost = AutoDiffCostFunction::<_, 2>::new(Rosenbrock);
+        let mut residuals = [0.0; 2];
+        let mut d_dx = [[0.0]; 2];
+        let mut d_dy = [[0.0]; 2];
+        let mut d_dx_rows: Vec<&mut [f64]> = d_dx.iter_mut().map(|row| &mut row[..]).collect();
+        let mut d_dy_rows: Vec<&mut [f64]> = d_dy.iter_mut().map(|row| &mut row[..]).collect();
+        let mut jacobians = [Some(&mut d_dx_rows[..]), Some(&mut d_dy_rows[..])];
+        assert!(cost.evaluate(&[&[2.0], &[3.0]], &mut residuals, Some(&mut jacobians)));
+        assert_abs_diff_eq!(residuals[..], [-1.0, -10.0]);
+        assert_abs_diff_eq!(d_dx[0][0], -1.0);
+        assert_abs_diff_eq!(d_dx[1][0], -40.0);
+        assert_abs_diff_eq!(d_dy[0][0], 0.0);
+        assert_abs_diff_eq!(d_dy[1][0], 10.0);
+    }
+
+    #[test]
+    fn num_traits_code() {
+        // Generic code which knows nothing about autodiff
+        fn gaussian<T: Float>(x: T, mu: T, sigma: T) -> T {
+            let z = (x - mu) / sigma;
+            (-z * z / T::from(2.0).unwrap()).exp()
+        }
+
+        struct Peak;
+
+        impl AutoDiffCostFunctor for Peak {
+            fn residuals<T: Scalar>(&self, parameters: &[&[T]], residuals: &mut [T]) -> bool {
+                let (mu, sigma) = (parameters[0][0], parameters[0][1]);
+                residuals[0] = gaussian(T::from_f64(1.0), mu, sigma);
+                true
+            }
+
+            fn num_residuals(&self) -> usize {
+                1
+            }
+
+            fn parameter_sizes(&self) -> Vec<usize> {
+                vec![2]
+            }
+        }
+
+        let cost = AutoDiffCostFunction::<_, 2>::new(Peak);
+        let (mu, sigma) = (0.5, 2.0);
+        let mut residuals = [0.0];
+        let mut d_dp = [[0.0; 2]];
+        let mut d_dp_rows: Vec<&mut [f64]> = d_dp.iter_mut().map(|row| &mut row[..]).collect();
+        let mut jacobians = [Some(&mut d_dp_rows[..])];
+        assert!(cost.evaluate(&[&[mu, sigma]], &mut residuals, Some(&mut jacobians)));
+        let z: f64 = (1.0 - mu) / sigma;
+        let value = f64::exp(-0.5 * z * z);
+        assert_abs_diff_eq!(residuals[0], value);
+        assert_abs_diff_eq!(d_dp[0][0], value * z / sigma, epsilon = 1e-12);
+        assert_abs_diff_eq!(d_dp[0][1], value * z * z / sigma, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn dynamic_jacobian() {
+        // Polynomial with runtime number of coefficients, y = sum_k c_k x^k
+        struct Polynomial {
+            x: Vec<f64>,
+        }
+
+        impl DynamicAutoDiffCostFunctor for Polynomial {
+            fn residuals<T: Scalar>(&self, parameters: &[&[T]], residuals: &mut [T]) -> bool {
+                for (residual, &x) in residuals.iter_mut().zip(&self.x) {
+                    *residual = parameters[0]
+                        .iter()
+                        .rev()
+                        .fold(T::from_f64(0.0), |acc, &c| acc * x + c)
+                        + parameters[1][0];
+                }
+                true
+            }
+        }
+
+        let x = vec![0.5, 2.0];
+        let coefficients = [1.0, 2.0, 3.0];
+        // Stride 2 makes two passes for three coefficients
+        let mut cost = DynamicAutoDiffCostFunction::<_, 2>::new(Polynomial { x: x.clone() });
+        cost.add_parameter_block(coefficients.len());
+        cost.add_parameter_block(1);
+        cost.set_num_residuals(x.len());
+        assert_eq!(cost.parameter_sizes(), [3, 1]);
+
+        let mut residuals = [0.0; 2];
+        let mut d_dc = [[0.0; 3]; 2];
+        let mut d_dc_rows: Vec<&mut [f64]> = d_dc.iter_mut().map(|row| &mut row[..]).collect();
+        // Offset is constant, its jacobian is not requested
+        let mut jacobians = [Some(&mut d_dc_rows[..]), None];
+        assert!(cost.evaluate(
+            &[&coefficients, &[10.0]],
+            &mut residuals,
+            Some(&mut jacobians)
+        ));
+        for (i, &x) in x.iter().enumerate() {
+            assert_abs_diff_eq!(residuals[i], 1.0 + 2.0 * x + 3.0 * x * x + 10.0);
+            assert_abs_diff_eq!(d_dc[i][..], [1.0, x, x * x]);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn wrong_number_of_parameters() {
+        AutoDiffCostFunction::<_, 3>::new(Rosenbrock);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn residual_macro() {
+        struct ArrayRosenbrock;
+
+        #[residual]
+        impl ArrayRosenbrock {
+            fn residuals<T: Scalar>(&self, x: &[T; 1], y: &[T; 1]) -> [T; 2] {
+                [-x[0] + 1.0, (y[0] - x[0] * x[0]) * 10.0]
+            }
+        }
+
+        assert_eq!(ArrayRosenbrock.num_residuals(), 2);
+        assert_eq!(ArrayRosenbrock.parameter_sizes(), [1, 1]);
+        let expected = AutoDiffCostFunction::<_, 2>::new(Rosenbrock)
+            .evaluate_at(&[&[2.0], &[3.0]], true)
+            .unwrap();
+        let actual = ArrayRosenbrock
+            .into_cost_function()
+            .evaluate_at(&[&[2.0], &[3.0]], true)
+            .unwrap();
+        assert_abs_diff_eq!(actual.residuals[..], expected.residuals[..]);
+        assert_eq!(actual.jacobians, expected.jacobians);
+        // Wrong parameter block size
+        assert!(!AutoDiffCostFunctor::residuals(
+            &ArrayRosenbrock,
+            &[&[2.0, 1.0], &[3.0]],
+            &mut [0.0; 2],
+        ));
+    }
+
+    #[test]
+    fn solve() {
+        let solution = NllsProblem::new()
+            .residual_block_builder()
+            .set_cost_fn(AutoDiffCostFunction::<_, 2>::new(Rosenbrock))
+            .set_parameters([vec![-1.2], vec![1.0]])
+            .build_into_problem()
+            .unwrap()
+            .0
+            .solve(&SolverOptions::default())
+            .unwrap();
+        assert_abs_diff_eq!(solution.parameters[0][0], 1.0, epsilon = 1e-8);
+        assert_abs_diff_eq!(solution.parameters[1][0], 1.0, epsilon = 1e-8);
+    }
+
+    /// Unit circle in the plane parametrized by the angle.
+    struct Circle;
+
+    impl AutoDiffManifoldFunctor for Circle {
+        fn plus<T: Scalar>(&self, x: &[T], delta: &[T], x_plus_delta: &mut [T]) -> bool {
+            let angle = x[1].atan2(x[0]) + delta[0];
+            x_plus_delta[0] = angle.cos();
+            x_plus_delta[1] = angle.sin();
+            true
+        }
+
+        fn minus<T: Scalar>(&self, y: &[T], x: &[T], y_minus_x: &mut [T]) -> bool {
+            // Angle between the vectors
+            let cross = x[0] * y[1] - x[1] * y[0];
+            let dot = x[0] * y[0] + x[1] * y[1];
+            y_minus_x[0] = cross.atan2(dot);
+            true
+        }
+    }
+
+    #[test]
+    fn manifold_jacobians() {
+        let manifold = AutoDiffManifold::<_, 2, 1>::new(Circle);
+        assert_eq!(manifold.ambient_size(), 2);
+        assert_eq!(manifold.tangent_size(), 1);
+        let (c, s) = (0.6, 0.8);
+
+        let mut x_plus_delta = [0.0; 2];
+        assert!(manifold.plus(&[c, s], &[0.5 * PI], &mut x_plus_delta));
+        assert_abs_diff_eq!(x_plus_delta[..], [-s, c], epsilon = 1e-12);
+        let mut y_minus_x = [0.0];
+        assert!(manifold.minus(&[-s, c], &[c, s], &mut y_minus_x));
+        assert_abs_diff_eq!(y_minus_x[0], 0.5 * PI, epsilon = 1e-12);
+
+        // Tangent to the circle
+        let mut plus_jacobian = [0.0; 2];
+        assert!(manifold.plus_jacobian(&[c, s], &mut plus_jacobian));
+        assert_abs_diff_eq!(plus_jacobian[..], [-s, c], epsilon = 1e-12);
+        // Projection onto the tangent
+        let mut minus_jacobian = [0.0; 2];
+        assert!(manifold.minus_jacobian(&[c, s], &mut minus_jacobian));
+        assert_abs_diff_eq!(minus_jacobian[..], [-s, c], epsilon = 1e-12);
+    }
+}
@@ -0,0 +1,155 @@
+//! Parallel fitting of many independent curves with [rayon], e.g. light curves of a survey.
+//!
+//! [CurveFitProblem1D] and [SolverOptions] are not [Send], because they may hold non-thread-safe
+//! model functions and callbacks. So [fit_many] moves the datasets to the worker threads and
+//! builds everything there: the problems with `model_factory`, and the solver options with
+//! `options`. The options are built once per chunk of work and reused by all its fits, so the
+//! per-fit overhead is building and solving the problem only.
+//!
+//! Let's fit a line to each of many small datasets:
+//!
+//! ```rust
+//! use ceres_solver::batch::fit_many;
+//! use ceres_solver::curve_fit::{CurveFitProblem1D, CurveFunctionType};
+//! use ceres_solver::SolverOptions;
+//!
+//! // y = a * x + b
+//! fn line(x: f64, parameters: &[f64], y: &mut f64, jacobians: Option<&mut [Option<f64>]>) -> bool {
+//!     *y = parameters[0] * x + parameters[1];
+//!     if let Some(jacobians) = jacobians {
+//!         if let Some(d_da) = &mut jacobians[0] {
+//!             *d_da = x;
+//!         }
+//!         if let Some(d_db) = &mut jacobians[1] {
+//!             *d_db = 1.0;
+//!         }
+//!     }
+//!     true
+//! }
+//!
+//! let x: Vec<_> = (0..20).map(|i| i as f64).collect();
+//! let datasets: Vec<Vec<f64>> = (0..1000)
+//!     .map(|i| x.iter().map(|&x| 0.01 * i as f64 * x + 1.0).collect())
+//!     .collect();
+//!
+//! let solutions = fit_many(
+//!     &datasets,
+//!     |y| {
+//!         let func: CurveFunctionType = Box::new(line);
+//!         CurveFitProblem1D::builder()
+//!             .func(func)
+//!             .x(&x)
+//!             .y(y)
+//!             .parameters(&[0.0, 0.0])
+//!             .build()
+//!     },
+//!     SolverOptions::default,
+//! );
+//!
+//! for (i, solution) in solutions.into_iter().enumerate() {
+//!     let solution = solution.unwrap();
+//!     assert!(f64::abs(solution.parameters[0] - 0.01 * i as f64) < 1e-8);
+//! }
+//! ```
+
+use crate::curve_fit::{CurveFitProblem1D, CurveFitProblemSolution};
+use crate::error::CurveFitProblemBuildError;
+use crate::solver::SolverOptions;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+/// Fits many independent datasets in parallel on the current [rayon] thread pool, see
+/// [batch](crate::batch) module docs.
+///
+/// # Arguments
+/// - datasets - a parallel iterator or a collection of the datasets, e.g. `&Vec<T>` or
+///   `Vec<T>`. The items are anything [Send] the problems are built from.
+/// - model_factory - builds a problem for a dataset on a worker thread, usually with
+///   [CurveFitProblem1D::builder].
+/// - options - builds the solver options on a worker thread, e.g. [SolverOptions::default].
+///
+/// Returns the solutions in the order of the datasets, or the errors of `model_factory` for the
+/// datasets which couldn't be built. Use [rayon::ThreadPool::install] to run it on a custom
+/// thread pool.
+pub fn fit_many<'a, I, F, O>(
+    datasets: I,
+    model_factory: F,
+    options: O,
+) -> Vec<Result<CurveFitProblemSolution, CurveFitProblemBuildError>>
+where
+    I: IntoParallelIterator,
+    F: Fn(I::Item) -> Result<CurveFitProblem1D<'a>, CurveFitProblemBuildError> + Send + Sync,
+    O: Fn() -> SolverOptions + Send + Sync,
+{
+    datasets
+        .into_par_iter()
+        .map_init(options, |options, dataset| {
+            Ok(model_factory(dataset)?.solve(options))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::autodiff::Scalar;
+    use crate::curve_fit::AutoDiffCurveModel;
+
+    use approx::assert_abs_diff_eq;
+
+    struct Exponent;
+
+    impl AutoDiffCurveModel for Exponent {
+        fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+            parameters[0] * (-x * parameters[1]).exp()
+        }
+    }
+
+    #[test]
+    fn fit_many_exponents() {
+        let x: Vec<_> = (0..30).map(|i| 0.1 * i as f64).collect();
+        // Owned datasets with their initial guesses, the fifth one is invalid
+        let datasets: Vec<(Vec<f64>, [f64; 2])> = (0..200)
+            .map(|i| {
+                let rate = 0.5 + 0.01 * i as f64;
+                let mut y: Vec<_> = x.iter().map(|&x| 2.0 * f64::exp(-rate * x)).collect();
+                if i == 5 {
+                    y.pop();
+                }
+                (y, [1.0, 1.0])
+            })
+            .collect();
+
+        let solutions = fit_many(
+            datasets,
+            |(y, parameters)| {
+                CurveFitProblem1D::builder()
+                    .autodiff_func(Exponent)
+                    .x(&x)
+                    .y(y)
+                    .parameters(&parameters)
+                    .build()
+            },
+            SolverOptions::default,
+        );
+
+        assert_eq!(solutions.len(), 200);
+        for (i, solution) in solutions.into_iter().enumerate() {
+            if i == 5 {
+                assert!(matches!(
+                    solution,
+                    Err(CurveFitProblemBuildError::DataSizesDontMatch)
+                ));
+                continue;
+            }
+            let solution = solution.unwrap();
+            assert!(solution.summary.is_solution_usable());
+            assert_abs_diff_eq!(
+                &solution.parameters[..],
+                &[2.0, 0.5 + 0.01 * i as f64][..],
+                epsilon = 1e-6
+            );
+        }
+    }
+}
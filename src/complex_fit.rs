@@ -0,0 +1,479 @@
+//! Wrapping [NllsProblem] for 1-D curve fit problems with complex-valued data.
+//!
+//! [ComplexCurveFitProblem] fits a model `y = f(x; parameters)` with complex `y` and real `x`
+//! and parameters, e.g. an equivalent circuit to an impedance spectrum or a damped oscillation
+//! to an NMR free induction decay. Every complex data point gives two residuals, for its real and
+//! imaginary parts, so the problem is the same as a real fit of the interleaved data. The problem
+//! returns [CurveFitProblemSolution], where [CurveFitProblemSolution::y_fit] and
+//! [CurveFitProblemSolution::residuals] are interleaved as `[re_0, im_0, re_1, im_1, ...]`, and
+//! the statistics count every complex point as two observations.
+//!
+//! Let's fit a resistor in series with a resistor-capacitor pair, `Z = R0 + R1 / (1 + i w R1 C)`,
+//! to an impedance spectrum:
+//!
+//! ```rust
+//! use ceres_solver::complex_fit::{ComplexCurveFitProblem, ComplexCurveFunctionType};
+//! use ceres_solver::SolverOptions;
+//! use num_complex::Complex;
+//!
+//! fn impedance(
+//!     omega: f64,
+//!     parameters: &[f64],
+//!     z: &mut Complex<f64>,
+//!     jacobians: Option<&mut [Option<Complex<f64>>]>,
+//! ) -> bool {
+//!     let &[r0, r1, c]: &[f64; 3] = parameters.try_into().unwrap();
+//!     let denominator = Complex::new(1.0, omega * r1 * c);
+//!     *z = r0 + r1 / denominator;
+//!     if let Some(jacobians) = jacobians {
+//!         let d2 = denominator * denominator;
+//!         let derivatives = [
+//!             Complex::new(1.0, 0.0),
+//!             1.0 / d2,
+//!             Complex::new(0.0, -omega * r1 * r1) / d2,
+//!         ];
+//!         for (jacobian, derivative) in jacobians.iter_mut().zip(derivatives) {
+//!             if let Some(jacobian) = jacobian {
+//!                 *jacobian = derivative;
+//!             }
+//!         }
+//!     }
+//!     true
+//! }
+//!
+//! let true_parameters = [10.0, 100.0, 1e-4];
+//!
+//! let omega: Vec<_> = (0..30).map(|i| f64::powf(10.0, 0.2 * i as f64)).collect();
+//! let z: Vec<_> = omega
+//!     .iter()
+//!     .map(|&omega| {
+//!         let mut z = Complex::new(0.0, 0.0);
+//!         impedance(omega, &true_parameters, &mut z, None);
+//!         z
+//!     })
+//!     .collect();
+//!
+//! let func: ComplexCurveFunctionType = Box::new(impedance);
+//! let solution = ComplexCurveFitProblem::builder()
+//!     .func(func)
+//!     .x(&omega)
+//!     .y(&z)
+//!     .parameters(&[8.0, 80.0, 1.5e-4])
+//!     .lower_bounds(&[Some(0.0), Some(0.0), Some(0.0)])
+//!     .build()
+//!     .unwrap()
+//!     .solve(&SolverOptions::default());
+//!
+//! for (true_value, value) in true_parameters.iter().zip(solution.parameters.iter()) {
+//!     assert!(f64::abs(true_value - value) / true_value < 1e-6);
+//! }
+//! ```
+
+use crate::cost::CostFunctionType;
+use crate::curve_fit::{solve_fit_problem, CurveFitProblemSolution};
+use crate::error::CurveFitProblemBuildError;
+use crate::loss::LossFunction;
+use crate::nlls_problem::NllsProblem;
+use crate::parameter_block::ParameterBlock;
+use crate::solver::SolverOptions;
+
+use num_complex::Complex;
+
+/// Model function of [ComplexCurveFitProblem], it accepts `x`, the parameters, a mutable
+/// reference to output the complex `y` value and optional complex jacobians, see
+/// [ComplexCurveFitProblem::new].
+pub type ComplexCurveFunctionType =
+    Box<dyn Fn(f64, &[f64], &mut Complex<f64>, Option<&mut [Option<Complex<f64>>]>) -> bool>;
+
+/// A wrapper for [NllsProblem] to fit a complex curve `y = f(x; parameters)` to the data. Create
+/// it with [ComplexCurveFitProblem::new] or with [ComplexCurveFitProblem::builder] for more
+/// options, and then call a destructive method [ComplexCurveFitProblem::solve].
+pub struct ComplexCurveFitProblem<'cost> {
+    problem: NllsProblem<'cost>,
+    data: Vec<f64>,
+    inverse_error: Option<Vec<f64>>,
+}
+
+impl<'cost> ComplexCurveFitProblem<'cost> {
+    /// Creates a new instance of the `ComplexCurveFitProblem`. If you need more control over the
+    /// problem, use [ComplexCurveFitProblem::builder] instead.
+    ///
+    /// # Arguments
+    /// - func - a function describing a complex curve. It must return [false] if it cannot
+    ///   calculate the value, or [true] otherwise. It accepts the following parameters:
+    ///   - x - an independent coordinate.
+    ///   - parameters - a slice for the current value of the problem parameters.
+    ///   - y - a mutable reference to output the function value.
+    ///   - jacobians - complex derivatives of the value over the real parameters, the slice has
+    ///     length of `parameters.len()`. It (or any of its component) can be [None], which means
+    ///     that the solver doesn't need it.
+    /// - x - independent coordinates of the data points.
+    /// - y - complex values of the data points.
+    /// - parameters - initial parameters.
+    ///
+    /// # Panics
+    /// Panics if `x` and `y` have different sizes.
+    pub fn new(
+        func: impl Into<ComplexCurveFunctionType>,
+        x: &'cost [f64],
+        y: &'cost [Complex<f64>],
+        parameters: &[f64],
+    ) -> Self {
+        assert_eq!(x.len(), y.len());
+        let nlls_parameters: Vec<_> = parameters.iter().map(|&p| vec![p]).collect();
+        let (problem, _block_id) = NllsProblem::new()
+            .residual_block_builder()
+            .set_cost(Self::cost_function(x, y, None, func.into()), 2 * y.len())
+            .set_parameters(nlls_parameters)
+            .build_into_problem()
+            .unwrap();
+        Self {
+            problem,
+            data: interleave(y),
+            inverse_error: None,
+        }
+    }
+
+    /// Create a [ComplexCurveFitProblemBuilder] instance, see its docs for the details.
+    pub fn builder<'param>() -> ComplexCurveFitProblemBuilder<'cost, 'param> {
+        ComplexCurveFitProblemBuilder::new()
+    }
+
+    fn cost_function(
+        x: &'cost [f64],
+        y: &'cost [Complex<f64>],
+        inverse_error: Option<&'cost [f64]>,
+        func: ComplexCurveFunctionType,
+    ) -> CostFunctionType<'cost> {
+        Box::new(move |parameters, residuals, mut jacobians| {
+            let parameters: Vec<_> = parameters.iter().map(|p| p[0]).collect();
+            let mut point_jacobians: Option<Vec<Option<Complex<f64>>>> =
+                jacobians.as_ref().map(|jacobians| {
+                    jacobians
+                        .iter()
+                        .map(|jacobian| jacobian.as_ref().map(|_| Complex::new(0.0, 0.0)))
+                        .collect()
+                });
+            let mut value = Complex::new(0.0, 0.0);
+            for (i, (&x, &y)) in x.iter().zip(y).enumerate() {
+                if !func(x, &parameters, &mut value, point_jacobians.as_deref_mut()) {
+                    return false;
+                }
+                let w = inverse_error.map_or(1.0, |inverse_error| inverse_error[i]);
+                let residual = w * (y - value);
+                residuals[2 * i] = residual.re;
+                residuals[2 * i + 1] = residual.im;
+                if let (Some(jacobians), Some(point_jacobians)) =
+                    (jacobians.as_mut(), point_jacobians.as_ref())
+                {
+                    for (d_out, &d_in) in jacobians.iter_mut().zip(point_jacobians) {
+                        if let (Some(d_out), Some(d_in)) = (d_out.as_mut(), d_in) {
+                            d_out[2 * i][0] = -w * d_in.re;
+                            d_out[2 * i + 1][0] = -w * d_in.im;
+                        }
+                    }
+                }
+            }
+            true
+        })
+    }
+
+    /// Solves the problem and returns a solution for the parameters.
+    pub fn solve(self, options: &SolverOptions) -> CurveFitProblemSolution {
+        solve_fit_problem(
+            self.problem,
+            &self.data,
+            self.inverse_error.as_deref(),
+            options,
+            false,
+        )
+    }
+
+    /// Solves the problem like [ComplexCurveFitProblem::solve] and also fills
+    /// [CurveFitProblemSolution::y_fit] with the model values and
+    /// [CurveFitProblemSolution::residuals] with the residuals at the solution, both with
+    /// interleaved real and imaginary parts.
+    pub fn solve_with_residuals(self, options: &SolverOptions) -> CurveFitProblemSolution {
+        solve_fit_problem(
+            self.problem,
+            &self.data,
+            self.inverse_error.as_deref(),
+            options,
+            true,
+        )
+    }
+}
+
+fn interleave(y: &[Complex<f64>]) -> Vec<f64> {
+    y.iter().flat_map(|y| [y.re, y.im]).collect()
+}
+
+/// Builder for [ComplexCurveFitProblem], see [complex_fit](crate::complex_fit) module docs for
+/// an example.
+pub struct ComplexCurveFitProblemBuilder<'cost, 'param> {
+    /// Model function
+    pub func: Option<ComplexCurveFunctionType>,
+    /// Independent coordinates for data
+    pub x: Option<&'cost [f64]>,
+    /// Complex values for data
+    pub y: Option<&'cost [Complex<f64>]>,
+    /// Optional inverse errors - square root of the weight, the same for real and imaginary parts
+    pub inverse_error: Option<&'cost [f64]>,
+    /// Initial parameters' guess
+    pub parameters: Option<&'param [f64]>,
+    /// Optional lower bounds for parameters
+    pub lower_bounds: Option<&'param [Option<f64>]>,
+    /// Optional upper bounds for parameters
+    pub upper_bounds: Option<&'param [Option<f64>]>,
+    /// Constant parameters, they will not be optimized.
+    pub constant_parameters: Option<&'param [usize]>,
+    /// Optional loss function
+    pub loss: Option<LossFunction>,
+}
+
+impl<'cost, 'param> ComplexCurveFitProblemBuilder<'cost, 'param> {
+    pub fn new() -> Self {
+        Self {
+            func: None,
+            x: None,
+            y: None,
+            inverse_error: None,
+            parameters: None,
+            lower_bounds: None,
+            upper_bounds: None,
+            constant_parameters: None,
+            loss: None,
+        }
+    }
+
+    /// Add model function, see [ComplexCurveFitProblem::new] for its arguments.
+    pub fn func(mut self, func: impl Into<ComplexCurveFunctionType>) -> Self {
+        self.func = Some(func.into());
+        self
+    }
+
+    /// Add independent parameter values for the data points.
+    pub fn x(mut self, x: &'cost [f64]) -> Self {
+        self.x = Some(x);
+        self
+    }
+
+    /// Add complex values for the data points.
+    pub fn y(mut self, y: &'cost [Complex<f64>]) -> Self {
+        self.y = Some(y);
+        self
+    }
+
+    /// Add optional inverse errors for the data points. They must be positive and are applied to
+    /// both real and imaginary parts, the residual would be `(y - model(x)) * inverse_error`. If
+    /// not given, unity values are assumed.
+    pub fn inverse_error(mut self, inv_err: &'cost [f64]) -> Self {
+        self.inverse_error = Some(inv_err);
+        self
+    }
+
+    /// Add initial parameter guess slice, it is copied to the [ComplexCurveFitProblem] instance.
+    pub fn parameters(mut self, parameters: &'param [f64]) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+
+    /// Add optional lower bounds for parameters, in the same order as parameters themselves. If
+    /// some parameter has no lower bound, use [None].
+    pub fn lower_bounds(mut self, lower_bounds: &'param [Option<f64>]) -> Self {
+        self.lower_bounds = Some(lower_bounds);
+        self
+    }
+
+    /// Add optional upper bounds for parameters, in the same order as parameters themselves. If
+    /// some parameter has no upper bound, use [None].
+    pub fn upper_bounds(mut self, upper_bounds: &'param [Option<f64>]) -> Self {
+        self.upper_bounds = Some(upper_bounds);
+        self
+    }
+
+    /// Make parameters constant, i.e. they will not be fitted.
+    pub fn constant(mut self, indexes: &'param [usize]) -> Self {
+        self.constant_parameters = Some(indexes);
+        self
+    }
+
+    /// Add optional loss function, if not given the trivial loss is assumed. The loss is applied
+    /// to the whole problem, i.e. to all the residuals at once.
+    pub fn loss(mut self, loss: LossFunction) -> Self {
+        self.loss = Some(loss);
+        self
+    }
+
+    /// Build the [ComplexCurveFitProblem] instance. Returns [Err] if one of the mandatory fields
+    /// is missed or data slices have inconsistent lengths.
+    pub fn build(self) -> Result<ComplexCurveFitProblem<'cost>, CurveFitProblemBuildError> {
+        let func = self.func.ok_or(CurveFitProblemBuildError::FuncMissed)?;
+        let x = self.x.ok_or(CurveFitProblemBuildError::XMissed)?;
+        let y = self.y.ok_or(CurveFitProblemBuildError::YMissed)?;
+        if x.len() != y.len() {
+            return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+        }
+        if let Some(inverse_error) = self.inverse_error {
+            if inverse_error.len() != y.len() {
+                return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+            }
+        }
+        let mut nlls_parameters: Vec<ParameterBlock> = self
+            .parameters
+            .ok_or(CurveFitProblemBuildError::ParametersMissed)?
+            .iter()
+            .map(|&p| vec![p].into())
+            .collect();
+        if let Some(lower_bounds) = self.lower_bounds {
+            if lower_bounds.len() != nlls_parameters.len() {
+                return Err(CurveFitProblemBuildError::LowerBoundarySizeMismatch);
+            }
+            for (parameter, &lb) in nlls_parameters.iter_mut().zip(lower_bounds) {
+                if lb.is_some() {
+                    parameter.set_lower_bounds(vec![lb]);
+                }
+            }
+        }
+        if let Some(upper_bounds) = self.upper_bounds {
+            if upper_bounds.len() != nlls_parameters.len() {
+                return Err(CurveFitProblemBuildError::UpperBoundarySizeMismatch);
+            }
+            for (parameter, &ub) in nlls_parameters.iter_mut().zip(upper_bounds) {
+                if ub.is_some() {
+                    parameter.set_upper_bounds(vec![ub]);
+                }
+            }
+        }
+        let mut residual_block = NllsProblem::new().residual_block_builder().set_cost(
+            ComplexCurveFitProblem::cost_function(x, y, self.inverse_error, func),
+            2 * y.len(),
+        );
+        if let Some(loss) = self.loss {
+            residual_block = residual_block.set_loss(loss);
+        }
+        let (mut problem, _block_id) = residual_block
+            .set_parameters(nlls_parameters)
+            .build_into_problem()
+            .unwrap();
+        if let Some(indexes) = self.constant_parameters {
+            for &i_param in indexes {
+                problem.set_parameter_block_constant(i_param)?;
+            }
+        }
+        Ok(ComplexCurveFitProblem {
+            problem,
+            data: interleave(y),
+            inverse_error: self
+                .inverse_error
+                .map(|inverse_error| inverse_error.iter().flat_map(|&w| [w, w]).collect()),
+        })
+    }
+}
+
+impl Default for ComplexCurveFitProblemBuilder<'_, '_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    // y = a exp(-x / tau) exp(i omega x), a free induction decay
+    fn decay(
+        x: f64,
+        parameters: &[f64],
+        y: &mut Complex<f64>,
+        jacobians: Option<&mut [Option<Complex<f64>>]>,
+    ) -> bool {
+        let &[a, tau, omega]: &[f64; 3] = parameters.try_into().unwrap();
+        let e = Complex::new(-x / tau, omega * x).exp();
+        *y = a * e;
+        if let Some(jacobians) = jacobians {
+            let derivatives = [e, a * e * x / tau.powi(2), a * e * Complex::new(0.0, x)];
+            for (jacobian, derivative) in jacobians.iter_mut().zip(derivatives) {
+                if let Some(jacobian) = jacobian {
+                    *jacobian = derivative;
+                }
+            }
+        }
+        true
+    }
+
+    const TRUE_PARAMETERS: [f64; 3] = [3.0, 2.0, 5.0];
+
+    fn decay_value(x: f64) -> Complex<f64> {
+        let mut y = Complex::new(0.0, 0.0);
+        decay(x, &TRUE_PARAMETERS, &mut y, None);
+        y
+    }
+
+    #[test]
+    fn free_induction_decay() {
+        let x: Vec<_> = (0..100).map(|i| 0.05 * i as f64).collect();
+        let y: Vec<_> = x.iter().map(|&x| decay_value(x)).collect();
+        let inverse_error = vec![2.0; y.len()];
+
+        let func: ComplexCurveFunctionType = Box::new(decay);
+        let solution = ComplexCurveFitProblem::builder()
+            .func(func)
+            .x(&x)
+            .y(&y)
+            .inverse_error(&inverse_error)
+            .parameters(&[2.0, 1.5, 5.1])
+            .build()
+            .unwrap()
+            .solve_with_residuals(&SolverOptions::default());
+        assert_abs_diff_eq!(
+            &solution.parameters[..],
+            &TRUE_PARAMETERS[..],
+            epsilon = 1e-8
+        );
+        assert_eq!(solution.degrees_of_freedom, 197);
+        let y_fit = solution.y_fit.unwrap();
+        assert_eq!(y_fit.len(), 200);
+        for (fit, value) in y_fit.chunks(2).zip(&y) {
+            assert_abs_diff_eq!(fit[0], value.re, epsilon = 1e-8);
+            assert_abs_diff_eq!(fit[1], value.im, epsilon = 1e-8);
+        }
+    }
+
+    #[test]
+    fn build_errors() {
+        let x = [0.0, 1.0, 2.0];
+        let y: Vec<_> = x.iter().map(|&x| decay_value(x)).collect();
+
+        let func: ComplexCurveFunctionType = Box::new(decay);
+        let error = ComplexCurveFitProblem::builder()
+            .func(func)
+            .x(&x[..2])
+            .y(&y)
+            .parameters(&TRUE_PARAMETERS)
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            CurveFitProblemBuildError::DataSizesDontMatch
+        ));
+
+        let func: ComplexCurveFunctionType = Box::new(decay);
+        let error = ComplexCurveFitProblem::builder()
+            .func(func)
+            .x(&x)
+            .y(&y)
+            .inverse_error(&[1.0])
+            .parameters(&TRUE_PARAMETERS)
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            CurveFitProblemBuildError::DataSizesDontMatch
+        ));
+    }
+}
@@ -0,0 +1,70 @@
+//! Resources shared by several [NllsProblem](crate::nlls_problem::NllsProblem)s.
+//!
+//! Every problem creates its own `ceres::Context` by default, which includes the thread pool used
+//! for multi-threaded evaluation and linear algebra. Create a single [Context] and pass it via
+//! [ProblemOptions::context](crate::nlls_problem::ProblemOptions::context) to reuse these
+//! resources when solving many problems one after another.
+//!
+//! ```rust
+//! use ceres_solver::nlls_problem::ProblemOptions;
+//! use ceres_solver::{Context, CostFunctionType, NllsProblem, SolverOptions};
+//!
+//! let context = Context::new();
+//! for target in [1.0, 2.0, 3.0] {
+//!     let cost: CostFunctionType = Box::new(move |parameters, residuals, jacobians| {
+//!         residuals[0] = parameters[0][0] - target;
+//!         if let Some(jacobians) = jacobians {
+//!             if let Some(d_dx) = &mut jacobians[0] {
+//!                 d_dx[0][0] = 1.0;
+//!             }
+//!         }
+//!         true
+//!     });
+//!     let options = ProblemOptions {
+//!         context: Some(context.clone()),
+//!         ..Default::default()
+//!     };
+//!     let solution = NllsProblem::with_options(options)
+//!         .residual_block_builder()
+//!         .set_cost(cost, 1)
+//!         .set_parameters([vec![0.0]])
+//!         .build_into_problem()
+//!         .unwrap()
+//!         .0
+//!         .solve(&SolverOptions::default())
+//!         .unwrap();
+//!     assert!((solution.parameters[0][0] - target).abs() < 1e-8);
+//! }
+//! ```
+
+use ceres_solver_sys::cxx::UniquePtr;
+use ceres_solver_sys::ffi;
+use std::rc::Rc;
+
+/// Shared handle of `ceres::Context`, cloning it is cheap and gives a handle of the same context.
+///
+/// The context is alive while any handle or any problem using it is alive.
+#[derive(Clone)]
+pub struct Context(Rc<UniquePtr<ffi::Context>>);
+
+impl Context {
+    /// Create a new context.
+    pub fn new() -> Self {
+        Self(Rc::new(ffi::new_context()))
+    }
+
+    /// Pointer to the underlying C++ object, it is valid while `self` is alive.
+    pub(crate) fn as_ptr(&self) -> *mut ffi::Context {
+        self.0
+            .as_ref()
+            .as_ref()
+            .expect("Underlying C++ unique_ptr<Context> must hold non-null pointer")
+            as *const _ as *mut _
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,69 +1,1518 @@
 //! Cost function wrapper for [NllsProblem](crate::nlls_problem::NllsProblem).
 //!
 //! Box your cost function into [CostFunctionType] to add it to problem using
-//! [crate::nlls_problem::ResidualBlockBuilder::set_cost]
+//! [crate::nlls_problem::ResidualBlockBuilder::set_cost], or implement [CostFunction] trait for
+//! your type and use [crate::nlls_problem::ResidualBlockBuilder::set_cost_fn].
 
+use crate::error::CostFunctionEvaluationError;
+use crate::evaluation::DenseMatrix;
+use crate::panic_slot::PanicSlot;
 use crate::types::JacobianType;
 
 use ceres_solver_sys::cxx;
 use ceres_solver_sys::ffi;
+use std::ops::Range;
 use std::slice;
+use std::sync::{Arc, Mutex};
 
+/// Boxed cost function closure, its arguments and return value are the same as for
+/// [CostFunction::evaluate].
 pub type CostFunctionType<'a> = Box<dyn Fn(&[&[f64]], &mut [f64], JacobianType<'_>) -> bool + 'a>;
 
+/// Cost function which can be called from several threads at once, required by problems created
+/// with [NllsProblem::new_threadsafe](crate::nlls_problem::NllsProblem::new_threadsafe).
+pub type ThreadSafeCostFunctionType<'a> =
+    Box<dyn Fn(&[&[f64]], &mut [f64], JacobianType<'_>) -> bool + Send + Sync + 'a>;
+
+/// Error of a [FallibleCostFunctionType] function explaining why the evaluation failed.
+pub type CostFunctionError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Cost function which returns an error instead of [false] when it cannot be evaluated, see
+/// [ResidualBlockBuilder::set_fallible_cost](crate::nlls_problem::ResidualBlockBuilder::set_fallible_cost).
+/// The arguments are the same as for [CostFunction::evaluate].
+pub type FallibleCostFunctionType<'a> =
+    Box<dyn Fn(&[&[f64]], &mut [f64], JacobianType<'_>) -> Result<(), CostFunctionError> + 'a>;
+
+/// Storage for the last error of a [FallibleCostFunctionType] function, clones share the storage.
+#[derive(Clone, Default)]
+pub(crate) struct ErrorSlot(Arc<Mutex<Option<CostFunctionError>>>);
+
+impl ErrorSlot {
+    fn set(&self, error: CostFunctionError) {
+        *self.0.lock().unwrap_or_else(|error| error.into_inner()) = Some(error);
+    }
+
+    /// The last error, the slot is cleared.
+    pub(crate) fn take(&self) -> Option<CostFunctionError> {
+        self.0
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .take()
+    }
+}
+
+/// Cost function implemented by a Rust type, an alternative to [CostFunctionType] closures for
+/// cost functions which carry their own state, e.g. the observed data.
+///
+/// The function is called directly, without boxing into [CostFunctionType], so a generic
+/// implementation avoids double indirection.
+///
+/// ```rust
+/// use ceres_solver::{CostFunction, NllsProblem, SolverOptions};
+/// use ceres_solver::types::JacobianType;
+///
+/// // Residuals of a line y = a * x + b.
+/// struct Line {
+///     x: Vec<f64>,
+///     y: Vec<f64>,
+/// }
+///
+/// impl CostFunction for Line {
+///     fn evaluate(
+///         &self,
+///         parameters: &[&[f64]],
+///         residuals: &mut [f64],
+///         mut jacobians: JacobianType<'_>,
+///     ) -> bool {
+///         let (a, b) = (parameters[0][0], parameters[1][0]);
+///         for (i, (&x, &y)) in self.x.iter().zip(&self.y).enumerate() {
+///             residuals[i] = a * x + b - y;
+///             if let Some(jacobians) = jacobians.as_mut() {
+///                 if let Some(d_da) = &mut jacobians[0] {
+///                     d_da[i][0] = x;
+///                 }
+///                 if let Some(d_db) = &mut jacobians[1] {
+///                     d_db[i][0] = 1.0;
+///                 }
+///             }
+///         }
+///         true
+///     }
+///
+///     fn num_residuals(&self) -> usize {
+///         self.x.len()
+///     }
+///
+///     fn parameter_sizes(&self) -> Vec<usize> {
+///         vec![1, 1]
+///     }
+/// }
+///
+/// let line = Line {
+///     x: vec![0.0, 1.0, 2.0],
+///     y: vec![1.0, 3.0, 5.0],
+/// };
+/// let solution = NllsProblem::new()
+///     .residual_block_builder()
+///     .set_cost_fn(line)
+///     .set_parameters([vec![0.0], vec![0.0]])
+///     .build_into_problem()
+///     .unwrap()
+///     .0
+///     .solve(&SolverOptions::default())
+///     .unwrap();
+/// assert!((solution.parameters[0][0] - 2.0).abs() < 1e-8);
+/// assert!((solution.parameters[1][0] - 1.0).abs() < 1e-8);
+/// ```
+pub trait CostFunction {
+    /// Find residuals and Jacobian for the problem block. The function must return [false] if it
+    /// cannot compute Jacobian, [true] otherwise.
+    ///
+    /// # Arguments
+    /// - parameters - slice of [f64] slices representing the current values of the parameters.
+    ///   Each parameter is represented as a slice, the slice sizes are specified by
+    ///   [CostFunction::parameter_sizes].
+    /// - residuals - mutable slice of [f64] for residuals outputs, the size is specified by
+    ///   [CostFunction::num_residuals].
+    /// - jacobians: [JacobianType](crate::types::JacobianType) - represents a mutable structure
+    ///   to output the Jacobian. Sometimes the solver doesn't need the Jacobian or some of its
+    ///   components, in this case the corresponding value is [None]. For the required components
+    ///   it has a 3-D shape: top index is for the parameter index, middle index is for the
+    ///   residual index, and the most inner dimension is for the given parameter component index.
+    ///   So the size of top-level [Some] is defined by `parameter_sizes.len()`, second-level
+    ///   [Some]'s slice length is `num_residuals`, and the bottom-level slice has length of
+    ///   `parameter_sizes[i]`, where `i` is the top-level index.
+    fn evaluate(
+        &self,
+        parameters: &[&[f64]],
+        residuals: &mut [f64],
+        jacobians: JacobianType<'_>,
+    ) -> bool;
+
+    /// Length of the residual vector.
+    fn num_residuals(&self) -> usize;
+
+    /// Sizes of the parameter blocks, they must match the parameter blocks of the residual block.
+    fn parameter_sizes(&self) -> Vec<usize>;
+
+    /// Evaluate the cost function at the given parameter values without adding it to a problem,
+    /// which is useful for testing and debugging.
+    ///
+    /// Jacobians are computed for all the parameter blocks if `compute_jacobians` is [true].
+    /// Outputs which are not written by [CostFunction::evaluate] are zero.
+    ///
+    /// # Errors
+    /// - [CostFunctionEvaluationError::WrongParameterSizes] if `parameters` doesn't match
+    ///   [CostFunction::parameter_sizes].
+    /// - [CostFunctionEvaluationError::EvaluationFailed] if [CostFunction::evaluate] returns
+    ///   [false].
+    ///
+    /// ```rust
+    /// use ceres_solver::cost::DynamicCostFunction;
+    /// use ceres_solver::{CostFunction, CostFunctionType};
+    ///
+    /// let func: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+    ///     let (x, y) = (parameters[0][0], parameters[0][1]);
+    ///     residuals[0] = x * y;
+    ///     if let Some(jacobians) = jacobians {
+    ///         if let Some(d_dxy) = &mut jacobians[0] {
+    ///             d_dxy[0][0] = y;
+    ///             d_dxy[0][1] = x;
+    ///         }
+    ///     }
+    ///     true
+    /// });
+    /// let mut cost = DynamicCostFunction::new(func);
+    /// cost.add_parameter_block(2);
+    /// cost.set_num_residuals(1);
+    ///
+    /// let evaluation = cost.evaluate_at(&[&[2.0, 3.0]], true).unwrap();
+    /// assert_eq!(evaluation.residuals, [6.0]);
+    /// assert_eq!(evaluation.jacobians.unwrap()[0].values, [3.0, 2.0]);
+    /// ```
+    fn evaluate_at(
+        &self,
+        parameters: &[&[f64]],
+        compute_jacobians: bool,
+    ) -> Result<CostFunctionEvaluation, CostFunctionEvaluationError> {
+        let parameter_sizes = self.parameter_sizes();
+        let actual: Vec<_> = parameters.iter().map(|block| block.len()).collect();
+        if actual != parameter_sizes {
+            return Err(CostFunctionEvaluationError::WrongParameterSizes {
+                expected: parameter_sizes,
+                actual,
+            });
+        }
+        let num_residuals = self.num_residuals();
+        let mut residuals = vec![0.0; num_residuals];
+        let mut jacobians: Vec<_> = if compute_jacobians {
+            parameter_sizes
+                .iter()
+                .map(|&size| DenseMatrix::zeros(num_residuals, size))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut rows: Vec<Vec<&mut [f64]>> = jacobians
+            .iter_mut()
+            .map(|jacobian| {
+                jacobian
+                    .values
+                    .chunks_mut(jacobian.num_cols.max(1))
+                    .collect()
+            })
+            .collect();
+        let mut blocks: Vec<Option<&mut [&mut [f64]]>> =
+            rows.iter_mut().map(|rows| Some(&mut rows[..])).collect();
+        let success = self.evaluate(
+            parameters,
+            &mut residuals,
+            compute_jacobians.then_some(&mut blocks[..]),
+        );
+        if !success {
+            return Err(CostFunctionEvaluationError::EvaluationFailed);
+        }
+        Ok(CostFunctionEvaluation {
+            residuals,
+            jacobians: compute_jacobians.then_some(jacobians),
+        })
+    }
+}
+
+/// Result of [CostFunction::evaluate_at].
+#[derive(Clone, Debug)]
+pub struct CostFunctionEvaluation {
+    /// Residuals at the given parameter values.
+    pub residuals: Vec<f64>,
+    /// Jacobians with respect to the parameter blocks, [Some] if requested.
+    pub jacobians: Option<Vec<DenseMatrix>>,
+}
+
+/// Cost function closure with the parameter block sizes and the number of residuals set after
+/// construction, the Rust counterpart of `ceres::DynamicCostFunction`. It is useful when the sizes
+/// are known from the input data at runtime only.
+///
+/// ```rust
+/// use ceres_solver::cost::DynamicCostFunction;
+/// use ceres_solver::{CostFunctionType, NllsProblem, SolverOptions};
+///
+/// // Fit a constant to the data, the number of residuals is the data size.
+/// let data = vec![1.0, 2.0, 3.0, 4.0];
+/// let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+///     for (residual, &y) in residuals.iter_mut().zip(&data) {
+///         *residual = parameters[0][0] - y;
+///     }
+///     if let Some(jacobians) = jacobians {
+///         if let Some(d_dc) = &mut jacobians[0] {
+///             for row in d_dc.iter_mut() {
+///                 row[0] = 1.0;
+///             }
+///         }
+///     }
+///     true
+/// });
+/// let mut cost = DynamicCostFunction::new(cost);
+/// cost.add_parameter_block(1);
+/// cost.set_num_residuals(data.len());
+/// let solution = NllsProblem::new()
+///     .residual_block_builder()
+///     .set_cost_fn(cost)
+///     .set_parameters([vec![0.0]])
+///     .build_into_problem()
+///     .unwrap()
+///     .0
+///     .solve(&SolverOptions::default())
+///     .unwrap();
+/// assert!((solution.parameters[0][0] - 2.5).abs() < 1e-8);
+/// ```
+pub struct DynamicCostFunction<'a> {
+    func: CostFunctionType<'a>,
+    num_residuals: usize,
+    parameter_sizes: Vec<usize>,
+}
+
+impl<'a> DynamicCostFunction<'a> {
+    /// Cost function without parameter blocks and residuals, add them with
+    /// [DynamicCostFunction::add_parameter_block] and [DynamicCostFunction::set_num_residuals].
+    pub fn new(func: impl Into<CostFunctionType<'a>>) -> Self {
+        Self {
+            func: func.into(),
+            num_residuals: 0,
+            parameter_sizes: Vec::new(),
+        }
+    }
+
+    /// Add a parameter block of the given size.
+    pub fn add_parameter_block(&mut self, size: usize) {
+        self.parameter_sizes.push(size);
+    }
+
+    /// Set the length of the residual vector.
+    pub fn set_num_residuals(&mut self, num_residuals: usize) {
+        self.num_residuals = num_residuals;
+    }
+}
+
+impl CostFunction for DynamicCostFunction<'_> {
+    fn evaluate(
+        &self,
+        parameters: &[&[f64]],
+        residuals: &mut [f64],
+        jacobians: JacobianType<'_>,
+    ) -> bool {
+        (self.func)(parameters, residuals, jacobians)
+    }
+
+    fn num_residuals(&self) -> usize {
+        self.num_residuals
+    }
+
+    fn parameter_sizes(&self) -> Vec<usize> {
+        self.parameter_sizes.clone()
+    }
+}
+
+/// Transformation of a single residual, see [ConditionedCostFunction].
+pub enum Conditioner<'a> {
+    /// Residual is left unchanged.
+    Identity,
+    /// `scale * residual + offset`.
+    Linear { scale: f64, offset: f64 },
+    /// Function returning the transformed residual and its derivative with respect to the
+    /// original residual.
+    Custom(Box<dyn Fn(f64) -> (f64, f64) + 'a>),
+}
+
+impl Conditioner<'_> {
+    /// Transformed residual and its derivative.
+    fn apply(&self, residual: f64) -> (f64, f64) {
+        match self {
+            Self::Identity => (residual, 1.0),
+            Self::Linear { scale, offset } => (scale * residual + offset, *scale),
+            Self::Custom(func) => func(residual),
+        }
+    }
+}
+
+/// Cost function with every residual transformed by its own [Conditioner], the Rust counterpart
+/// of `ceres::ConditionedCostFunction`. It applies per-residual scaling or offset, e.g.
+/// whitening with per-measurement uncertainties, to an existing cost function without rewriting
+/// it.
+///
+/// ```rust
+/// use ceres_solver::cost::{Conditioner, ConditionedCostFunction, DynamicCostFunction};
+/// use ceres_solver::{CostFunctionType, NllsProblem, SolverOptions};
+///
+/// // Weighted mean of measurements with different uncertainties.
+/// let data = [(1.0, 1.0), (3.0, 0.5)];
+/// let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+///     for (residual, &(y, _sigma)) in residuals.iter_mut().zip(&data) {
+///         *residual = parameters[0][0] - y;
+///     }
+///     if let Some(jacobians) = jacobians {
+///         if let Some(d_dc) = &mut jacobians[0] {
+///             for row in d_dc.iter_mut() {
+///                 row[0] = 1.0;
+///             }
+///         }
+///     }
+///     true
+/// });
+/// let mut cost = DynamicCostFunction::new(cost);
+/// cost.add_parameter_block(1);
+/// cost.set_num_residuals(data.len());
+/// let conditioners = data.iter().map(|&(_y, sigma)| Conditioner::Linear {
+///     scale: 1.0 / sigma,
+///     offset: 0.0,
+/// });
+/// let cost = ConditionedCostFunction::new(cost, conditioners);
+/// let solution = NllsProblem::new()
+///     .residual_block_builder()
+///     .set_cost_fn(cost)
+///     .set_parameters([vec![0.0]])
+///     .build_into_problem()
+///     .unwrap()
+///     .0
+///     .solve(&SolverOptions::default())
+///     .unwrap();
+/// // (1 / 1^2 + 3 / 0.5^2) / (1 / 1^2 + 1 / 0.5^2)
+/// assert!((solution.parameters[0][0] - 2.6).abs() < 1e-8);
+/// ```
+pub struct ConditionedCostFunction<'a, C> {
+    cost: C,
+    conditioners: Vec<Conditioner<'a>>,
+}
+
+impl<'a, C> ConditionedCostFunction<'a, C>
+where
+    C: CostFunction,
+{
+    /// Wrap the cost function, `conditioners` are applied to the residuals in the same order.
+    ///
+    /// # Panics
+    /// If the number of conditioners differs from the number of residuals of the cost function.
+    pub fn new(cost: C, conditioners: impl IntoIterator<Item = Conditioner<'a>>) -> Self {
+        let conditioners: Vec<_> = conditioners.into_iter().collect();
+        assert_eq!(
+            conditioners.len(),
+            cost.num_residuals(),
+            "Number of conditioners must be equal to the number of residuals"
+        );
+        Self { cost, conditioners }
+    }
+
+    /// The wrapped cost function.
+    pub fn cost(&self) -> &C {
+        &self.cost
+    }
+}
+
+impl<C> CostFunction for ConditionedCostFunction<'_, C>
+where
+    C: CostFunction,
+{
+    fn evaluate(
+        &self,
+        parameters: &[&[f64]],
+        residuals: &mut [f64],
+        mut jacobians: JacobianType<'_>,
+    ) -> bool {
+        let num_residuals = residuals.len();
+        let success = with_reborrowed_jacobians(&mut jacobians, 0..num_residuals, |jacobians| {
+            self.cost.evaluate(parameters, residuals, jacobians)
+        });
+        if !success {
+            return false;
+        }
+        for (i, (residual, conditioner)) in residuals.iter_mut().zip(&self.conditioners).enumerate()
+        {
+            let (value, derivative) = conditioner.apply(*residual);
+            *residual = value;
+            for jacobian in jacobians.iter_mut().flat_map(|v| v.iter_mut()).flatten() {
+                for d in jacobian[i].iter_mut() {
+                    *d *= derivative;
+                }
+            }
+        }
+        true
+    }
+
+    fn num_residuals(&self) -> usize {
+        self.cost.num_residuals()
+    }
+
+    fn parameter_sizes(&self) -> Vec<usize> {
+        self.cost.parameter_sizes()
+    }
+}
+
+/// Whitening of the residuals of a residual block, see
+/// [ResidualBlockBuilder::set_whitening](crate::nlls_problem::ResidualBlockBuilder::set_whitening).
+///
+/// Residuals `r` and every jacobian block `J` of the cost function are replaced with `W r` and
+/// `W J` before Ceres sees them, so the cost function can work with the raw measurement
+/// differences.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Whitening {
+    /// Diagonal `W` given by per-residual weights, usually inverse standard deviations
+    /// `1 / sigma`.
+    Diagonal(Vec<f64>),
+    /// Square root of the information matrix, i.e. `W` such that `W^T W` is the inverse of the
+    /// residual covariance matrix, for example the inverse of its Cholesky factor. It must be
+    /// square with the size of the number of residuals.
+    SqrtInformation(DenseMatrix),
+    /// Lower triangular Cholesky factor `L` of the residual covariance matrix `C = L L^T`, `W` is
+    /// `L^-1` applied with forward substitution, so no matrix inversion is needed. It must be
+    /// square with the size of the number of residuals, the upper triangle is ignored.
+    Cholesky(DenseMatrix),
+    /// Banded lower triangular Cholesky factor, the fast path of [Whitening::Cholesky] for the
+    /// covariance matrices which are zero farther than `bandwidth` from the diagonal, e.g. for
+    /// autoregressive noise. Whitening costs `O(n bandwidth)` instead of `O(n^2)`. Every row `i`
+    /// holds `bandwidth + 1` values `L[i][i - bandwidth..=i]`, the elements outside of the matrix
+    /// are zero.
+    BandedCholesky { bandwidth: usize, values: Vec<f64> },
+}
+
+impl Whitening {
+    /// Whitening for the residual covariance matrix found with its Cholesky decomposition. It is
+    /// [Whitening::BandedCholesky] if the matrix is banded, and [Whitening::Cholesky] otherwise.
+    /// Only the lower triangle of the matrix is used.
+    ///
+    /// Returns [None] if the matrix is not square or not positive definite.
+    ///
+    /// ```rust
+    /// use ceres_solver::cost::Whitening;
+    /// use ceres_solver::evaluation::DenseMatrix;
+    ///
+    /// // Exponentially correlated noise truncated to the nearest neighbours
+    /// let covariance = DenseMatrix {
+    ///     num_rows: 3,
+    ///     num_cols: 3,
+    ///     values: vec![1.0, 0.5, 0.0, 0.5, 1.0, 0.5, 0.0, 0.5, 1.0],
+    /// };
+    /// let whitening = Whitening::from_covariance(&covariance).unwrap();
+    /// assert!(matches!(whitening, Whitening::BandedCholesky { bandwidth: 1, .. }));
+    /// ```
+    pub fn from_covariance(covariance: &DenseMatrix) -> Option<Self> {
+        let n = covariance.num_rows;
+        if covariance.num_cols != n || covariance.values.len() != n * n {
+            return None;
+        }
+        let bandwidth = (0..n)
+            .flat_map(|i| (0..i).map(move |j| (i, j)))
+            .filter(|&(i, j)| covariance.get(i, j) != 0.0)
+            .map(|(i, j)| i - j)
+            .max()
+            .unwrap_or(0);
+        let width = bandwidth + 1;
+        // Index of L[i][j] in the banded storage
+        let index = |i: usize, j: usize| i * width + j + bandwidth - i;
+        let mut values = vec![0.0; n * width];
+        for i in 0..n {
+            for j in i.saturating_sub(bandwidth)..=i {
+                let sum = covariance.get(i, j)
+                    - (i.saturating_sub(bandwidth)..j)
+                        .map(|k| values[index(i, k)] * values[index(j, k)])
+                        .sum::<f64>();
+                values[index(i, j)] = if i == j {
+                    if sum <= 0.0 || sum.is_nan() {
+                        return None;
+                    }
+                    sum.sqrt()
+                } else {
+                    sum / values[index(j, j)]
+                };
+            }
+        }
+        if width < n {
+            return Some(Self::BandedCholesky { bandwidth, values });
+        }
+        let mut factor = DenseMatrix::zeros(n, n);
+        for i in 0..n {
+            for j in 0..=i {
+                factor.values[i * n + j] = values[index(i, j)];
+            }
+        }
+        Some(Self::Cholesky(factor))
+    }
+
+    /// Whether the whitening can be applied to `num_residuals` residuals.
+    pub(crate) fn matches(&self, num_residuals: usize) -> bool {
+        match self {
+            Self::Diagonal(weights) => weights.len() == num_residuals,
+            Self::SqrtInformation(matrix) | Self::Cholesky(matrix) => {
+                matrix.num_rows == num_residuals
+                    && matrix.num_cols == num_residuals
+                    && matrix.values.len() == num_residuals * num_residuals
+            }
+            Self::BandedCholesky { bandwidth, values } => {
+                values.len() == num_residuals * (bandwidth + 1)
+            }
+        }
+    }
+
+    /// Restore the original residuals from the whitened ones, i.e. multiply them by `W^-1`.
+    /// Returns [None] if `W` is singular.
+    pub(crate) fn unwhiten(&self, mut residuals: Vec<f64>) -> Option<Vec<f64>> {
+        let n = residuals.len();
+        match self {
+            Self::Diagonal(weights) => {
+                for (residual, weight) in residuals.iter_mut().zip(weights) {
+                    *residual /= weight;
+                }
+            }
+            // Multiply by L, going from the bottom keeps the values needed for the next rows
+            Self::Cholesky(matrix) => {
+                for i in (0..n).rev() {
+                    residuals[i] = (0..=i).map(|k| matrix.get(i, k) * residuals[k]).sum();
+                }
+            }
+            Self::BandedCholesky { bandwidth, values } => {
+                for i in (0..n).rev() {
+                    let start = i.saturating_sub(*bandwidth);
+                    residuals[i] = (start..=i)
+                        .map(|k| values[i * (bandwidth + 1) + k + bandwidth - i] * residuals[k])
+                        .sum();
+                }
+            }
+            // Gaussian elimination with partial pivoting
+            Self::SqrtInformation(matrix) => {
+                let mut a = matrix.values.clone();
+                for col in 0..n {
+                    let pivot = (col..n)
+                        .max_by(|&i, &j| a[i * n + col].abs().total_cmp(&a[j * n + col].abs()))?;
+                    if a[pivot * n + col] == 0.0 {
+                        return None;
+                    }
+                    for k in 0..n {
+                        a.swap(col * n + k, pivot * n + k);
+                    }
+                    residuals.swap(col, pivot);
+                    for row in col + 1..n {
+                        let factor = a[row * n + col] / a[col * n + col];
+                        for k in col..n {
+                            a[row * n + k] -= factor * a[col * n + k];
+                        }
+                        residuals[row] -= factor * residuals[col];
+                    }
+                }
+                for row in (0..n).rev() {
+                    let sum: f64 = (row + 1..n).map(|k| a[row * n + k] * residuals[k]).sum();
+                    residuals[row] = (residuals[row] - sum) / a[row * n + row];
+                }
+            }
+        }
+        Some(residuals)
+    }
+
+    /// Whiten the outputs of the function after it succeeded.
+    fn wrap<'a>(
+        self,
+        func: RawCostFunctionType<'a>,
+        parameter_sizes: Vec<usize>,
+        num_residuals: usize,
+    ) -> RawCostFunctionType<'a> {
+        Box::new(move |parameters_ptr, residuals_ptr, jacobians_ptr| {
+            if !func(parameters_ptr, residuals_ptr, jacobians_ptr) {
+                return false;
+            }
+            for_each_output(
+                residuals_ptr,
+                jacobians_ptr,
+                &parameter_sizes,
+                num_residuals,
+                |_, values| self.apply(values, num_residuals),
+            );
+            true
+        })
+    }
+
+    /// Multiply a row-major matrix having `num_rows` rows by `W` from the left.
+    fn apply(&self, values: &mut [f64], num_rows: usize) {
+        if values.is_empty() {
+            return;
+        }
+        let num_cols = values.len() / num_rows;
+        match self {
+            Self::Diagonal(weights) => {
+                for (row, &weight) in values.chunks_exact_mut(num_cols).zip(weights) {
+                    row.iter_mut().for_each(|value| *value *= weight);
+                }
+            }
+            Self::Cholesky(matrix) => {
+                for i in 0..num_rows {
+                    let factor_row = matrix.row(i);
+                    for col in 0..num_cols {
+                        let sum: f64 = (0..i)
+                            .map(|k| factor_row[k] * values[k * num_cols + col])
+                            .sum();
+                        values[i * num_cols + col] =
+                            (values[i * num_cols + col] - sum) / factor_row[i];
+                    }
+                }
+            }
+            Self::BandedCholesky {
+                bandwidth,
+                values: factor,
+            } => {
+                let width = bandwidth + 1;
+                for i in 0..num_rows {
+                    let factor_row = &factor[i * width..(i + 1) * width];
+                    let start = i.saturating_sub(*bandwidth);
+                    for col in 0..num_cols {
+                        let sum: f64 = (start..i)
+                            .map(|k| factor_row[k + bandwidth - i] * values[k * num_cols + col])
+                            .sum();
+                        values[i * num_cols + col] =
+                            (values[i * num_cols + col] - sum) / factor_row[*bandwidth];
+                    }
+                }
+            }
+            Self::SqrtInformation(matrix) => {
+                let original = values.to_vec();
+                for (matrix_row, row) in matrix.rows().zip(values.chunks_exact_mut(num_cols)) {
+                    for (col, value) in row.iter_mut().enumerate() {
+                        *value = matrix_row
+                            .iter()
+                            .zip(original.iter().skip(col).step_by(num_cols))
+                            .map(|(&w, &x)| w * x)
+                            .sum();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Several cost functions of the same parameter blocks stacked into a single one, its residuals
+/// and jacobians are concatenations of the ones of the stacked functions.
+///
+/// It allows to express a model which is a sum of terms as a single residual block, reducing the
+/// number of FFI calls and the bookkeeping.
+///
+/// ```rust
+/// use ceres_solver::cost::{DynamicCostFunction, StackedCostFunction};
+/// use ceres_solver::{CostFunctionType, NllsProblem, SolverOptions};
+///
+/// // Residual of x - target
+/// fn term(target: f64) -> DynamicCostFunction<'static> {
+///     let cost: CostFunctionType = Box::new(move |parameters, residuals, jacobians| {
+///         residuals[0] = parameters[0][0] - target;
+///         if let Some(jacobians) = jacobians {
+///             if let Some(d_dx) = &mut jacobians[0] {
+///                 d_dx[0][0] = 1.0;
+///             }
+///         }
+///         true
+///     });
+///     let mut cost = DynamicCostFunction::new(cost);
+///     cost.add_parameter_block(1);
+///     cost.set_num_residuals(1);
+///     cost
+/// }
+///
+/// let cost = StackedCostFunction::new(term(1.0))
+///     .push(term(2.0))
+///     .push(term(6.0));
+/// let solution = NllsProblem::new()
+///     .residual_block_builder()
+///     .set_cost_fn(cost)
+///     .set_parameters([vec![0.0]])
+///     .build_into_problem()
+///     .unwrap()
+///     .0
+///     .solve(&SolverOptions::default())
+///     .unwrap();
+/// assert!((solution.parameters[0][0] - 3.0).abs() < 1e-8);
+/// ```
+pub struct StackedCostFunction<'a> {
+    costs: Vec<Box<dyn CostFunction + 'a>>,
+    num_residuals: usize,
+    parameter_sizes: Vec<usize>,
+}
+
+impl<'a> StackedCostFunction<'a> {
+    /// Start a stack with the given cost function, it defines the parameter block sizes.
+    pub fn new(cost: impl CostFunction + 'a) -> Self {
+        Self {
+            num_residuals: cost.num_residuals(),
+            parameter_sizes: cost.parameter_sizes(),
+            costs: vec![Box::new(cost)],
+        }
+    }
+
+    /// Add a cost function to the stack, its residuals follow the residuals of the already
+    /// stacked functions.
+    ///
+    /// # Panics
+    /// If the parameter block sizes of the cost function differ from the ones of the stack.
+    pub fn push(mut self, cost: impl CostFunction + 'a) -> Self {
+        assert_eq!(
+            cost.parameter_sizes(),
+            self.parameter_sizes,
+            "Stacked cost functions must have the same parameter block sizes"
+        );
+        self.num_residuals += cost.num_residuals();
+        self.costs.push(Box::new(cost));
+        self
+    }
+}
+
+impl CostFunction for StackedCostFunction<'_> {
+    fn evaluate(
+        &self,
+        parameters: &[&[f64]],
+        residuals: &mut [f64],
+        mut jacobians: JacobianType<'_>,
+    ) -> bool {
+        let mut start = 0;
+        for cost in self.costs.iter() {
+            let end = start + cost.num_residuals();
+            let success = with_reborrowed_jacobians(&mut jacobians, start..end, |jacobians| {
+                cost.evaluate(parameters, &mut residuals[start..end], jacobians)
+            });
+            if !success {
+                return false;
+            }
+            start = end;
+        }
+        true
+    }
+
+    fn num_residuals(&self) -> usize {
+        self.num_residuals
+    }
+
+    fn parameter_sizes(&self) -> Vec<usize> {
+        self.parameter_sizes.clone()
+    }
+}
+
+/// Cost function wrapper which computes the jacobian components left unwritten by the wrapped
+/// cost function with central finite differences, so analytic derivatives can be provided for
+/// the easy parameters only.
+///
+/// Requested jacobians are filled with a NaN sentinel before calling the wrapped function, the
+/// sentinel values remaining after the call are replaced by the numeric derivatives. Every
+/// parameter component with unwritten derivatives costs two extra evaluations of the residuals.
+///
+/// ```rust
+/// use ceres_solver::cost::{DynamicCostFunction, NumericFallbackCostFunction};
+/// use ceres_solver::{CostFunction, CostFunctionType};
+///
+/// // r = a * sin(b), only the derivative over a is written
+/// let func: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+///     let (a, b) = (parameters[0][0], parameters[1][0]);
+///     residuals[0] = a * b.sin();
+///     if let Some(jacobians) = jacobians {
+///         if let Some(d_da) = &mut jacobians[0] {
+///             d_da[0][0] = b.sin();
+///         }
+///     }
+///     true
+/// });
+/// let mut cost = DynamicCostFunction::new(func);
+/// cost.add_parameter_block(1);
+/// cost.add_parameter_block(1);
+/// cost.set_num_residuals(1);
+/// let cost = NumericFallbackCostFunction::new(cost);
+///
+/// let evaluation = cost.evaluate_at(&[&[2.0], &[0.5]], true).unwrap();
+/// let jacobians = evaluation.jacobians.unwrap();
+/// assert_eq!(jacobians[0].values[0], 0.5_f64.sin());
+/// assert!((jacobians[1].values[0] - 2.0 * 0.5_f64.cos()).abs() < 1e-8);
+/// ```
+pub struct NumericFallbackCostFunction<C> {
+    cost: C,
+    relative_step_size: f64,
+}
+
+impl<C> NumericFallbackCostFunction<C>
+where
+    C: CostFunction,
+{
+    /// Wrap the cost function, the relative step size of finite differences is `1e-6`.
+    pub fn new(cost: C) -> Self {
+        Self {
+            cost,
+            relative_step_size: 1e-6,
+        }
+    }
+
+    /// Set the step size relative to the parameter value, the step equals to the relative step
+    /// size for zero parameter values.
+    pub fn relative_step_size(mut self, relative_step_size: f64) -> Self {
+        self.relative_step_size = relative_step_size;
+        self
+    }
+
+    /// The wrapped cost function.
+    pub fn cost(&self) -> &C {
+        &self.cost
+    }
+}
+
+impl<C> CostFunction for NumericFallbackCostFunction<C>
+where
+    C: CostFunction,
+{
+    fn evaluate(
+        &self,
+        parameters: &[&[f64]],
+        residuals: &mut [f64],
+        mut jacobians: JacobianType<'_>,
+    ) -> bool {
+        let num_residuals = residuals.len();
+        for rows in jacobians
+            .iter_mut()
+            .flat_map(|jacobians| jacobians.iter_mut().flatten())
+        {
+            for row in rows.iter_mut() {
+                row.fill(f64::from_bits(UNWRITTEN_BITS));
+            }
+        }
+        let success = with_reborrowed_jacobians(&mut jacobians, 0..num_residuals, |jacobians| {
+            self.cost.evaluate(parameters, residuals, jacobians)
+        });
+        let (true, Some(jacobians)) = (success, jacobians) else {
+            return success;
+        };
+
+        let mut residuals_plus = vec![0.0; num_residuals];
+        let mut residuals_minus = vec![0.0; num_residuals];
+        for (block, rows) in jacobians.iter_mut().enumerate() {
+            let Some(rows) = rows else {
+                continue;
+            };
+            for component in 0..parameters[block].len() {
+                if !rows
+                    .iter()
+                    .any(|row| row[component].to_bits() == UNWRITTEN_BITS)
+                {
+                    continue;
+                }
+                let value = parameters[block][component];
+                let step = if value == 0.0 {
+                    self.relative_step_size
+                } else {
+                    value.abs() * self.relative_step_size
+                };
+                let mut shifted_block = parameters[block].to_vec();
+                for (shift, output) in [(step, &mut residuals_plus), (-step, &mut residuals_minus)]
+                {
+                    shifted_block[component] = value + shift;
+                    let mut shifted_parameters = parameters.to_vec();
+                    shifted_parameters[block] = &shifted_block;
+                    if !self.cost.evaluate(&shifted_parameters, output, None) {
+                        return false;
+                    }
+                }
+                for (row, (plus, minus)) in rows
+                    .iter_mut()
+                    .zip(residuals_plus.iter().zip(residuals_minus.iter()))
+                {
+                    if row[component].to_bits() == UNWRITTEN_BITS {
+                        row[component] = (plus - minus) / (2.0 * step);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn num_residuals(&self) -> usize {
+        self.cost.num_residuals()
+    }
+
+    fn parameter_sizes(&self) -> Vec<usize> {
+        self.cost.parameter_sizes()
+    }
+}
+
+/// Cost function with a single parameter block, the number of residuals `NR` and the parameter
+/// block size `N0` are compile-time constants, the counterpart of `ceres::SizedCostFunction`.
+///
+/// The function gets arrays instead of slices, and the jacobian is a row-major `NR x N0` array.
+/// When added with [ResidualBlockBuilder::set_sized_cost](crate::nlls_problem::ResidualBlockBuilder::set_sized_cost)
+/// the arrays point directly into the Ceres buffers, so no heap allocations are made per
+/// evaluation, which matters for tiny per-point problems. Use [SizedCostFunction2] for two
+/// parameter blocks.
+///
+/// ```rust
+/// use ceres_solver::cost::SizedCostFunction;
+/// use ceres_solver::{NllsProblem, SolverOptions};
+///
+/// // Residuals of a circle x^2 + y^2 = 1 and a line y = 2 x.
+/// let cost = SizedCostFunction::<_, 2, 2>::new(|p, residuals, jacobian| {
+///     let [x, y] = *p;
+///     *residuals = [x * x + y * y - 1.0, y - 2.0 * x];
+///     if let Some(jacobian) = jacobian {
+///         *jacobian = [[2.0 * x, 2.0 * y], [-2.0, 1.0]];
+///     }
+///     true
+/// });
+/// let solution = NllsProblem::new()
+///     .residual_block_builder()
+///     .set_sized_cost(cost)
+///     .set_parameters([vec![1.0, 1.0]])
+///     .build_into_problem()
+///     .unwrap()
+///     .0
+///     .solve(&SolverOptions::default())
+///     .unwrap();
+/// let x = 1.0 / 5.0_f64.sqrt();
+/// assert!((solution.parameters[0][0] - x).abs() < 1e-8);
+/// assert!((solution.parameters[0][1] - 2.0 * x).abs() < 1e-8);
+/// ```
+pub struct SizedCostFunction<F, const NR: usize, const N0: usize> {
+    func: F,
+}
+
+impl<F, const NR: usize, const N0: usize> SizedCostFunction<F, NR, N0>
+where
+    F: Fn(&[f64; N0], &mut [f64; NR], Option<&mut [[f64; N0]; NR]>) -> bool,
+{
+    pub fn new(func: F) -> Self {
+        Self { func }
+    }
+}
+
+impl<F, const NR: usize, const N0: usize> CostFunction for SizedCostFunction<F, NR, N0>
+where
+    F: Fn(&[f64; N0], &mut [f64; NR], Option<&mut [[f64; N0]; NR]>) -> bool,
+{
+    fn evaluate(
+        &self,
+        parameters: &[&[f64]],
+        residuals: &mut [f64],
+        mut jacobians: JacobianType<'_>,
+    ) -> bool {
+        let (Ok(p0), Ok(residuals)) = (parameters[0].try_into(), residuals.try_into()) else {
+            return false;
+        };
+        let rows0 = jacobians
+            .as_mut()
+            .and_then(|jacobians| jacobians[0].as_deref_mut());
+        let mut j0 = rows0.as_deref().map(jacobian_from_rows::<NR, N0>);
+        let success = (self.func)(p0, residuals, j0.as_mut());
+        if let (true, Some(rows0), Some(j0)) = (success, rows0, j0) {
+            jacobian_to_rows(&j0, rows0);
+        }
+        success
+    }
+
+    fn num_residuals(&self) -> usize {
+        NR
+    }
+
+    fn parameter_sizes(&self) -> Vec<usize> {
+        vec![N0]
+    }
+}
+
+impl<F, const NR: usize, const N0: usize> sealed::Sealed for SizedCostFunction<F, NR, N0>
+where
+    F: Fn(&[f64; N0], &mut [f64; NR], Option<&mut [[f64; N0]; NR]>) -> bool,
+{
+    fn into_raw<'a>(self) -> RawCostFunctionType<'a>
+    where
+        Self: 'a,
+    {
+        Box::new(move |parameters, residuals, jacobians| unsafe {
+            (self.func)(
+                &*(*parameters).cast(),
+                &mut *residuals.cast(),
+                raw_jacobian(jacobians, 0),
+            )
+        })
+    }
+}
+
+impl<F, const NR: usize, const N0: usize> SizedCost for SizedCostFunction<F, NR, N0> where
+    F: Fn(&[f64; N0], &mut [f64; NR], Option<&mut [[f64; N0]; NR]>) -> bool
+{
+}
+
+/// [SizedCostFunction] with two parameter blocks of sizes `N0` and `N1`.
+///
+/// ```rust
+/// use ceres_solver::cost::SizedCostFunction2;
+/// use ceres_solver::{NllsProblem, SolverOptions};
+///
+/// // Residual of a * b = 6 and a - b = 1
+/// let cost = SizedCostFunction2::<_, 2, 1, 1>::new(|&[a], &[b], residuals, d_da, d_db| {
+///     *residuals = [a * b - 6.0, a - b - 1.0];
+///     if let Some(d_da) = d_da {
+///         *d_da = [[b], [1.0]];
+///     }
+///     if let Some(d_db) = d_db {
+///         *d_db = [[a], [-1.0]];
+///     }
+///     true
+/// });
+/// let solution = NllsProblem::new()
+///     .residual_block_builder()
+///     .set_sized_cost(cost)
+///     .set_parameters([vec![2.0], vec![1.0]])
+///     .build_into_problem()
+///     .unwrap()
+///     .0
+///     .solve(&SolverOptions::default())
+///     .unwrap();
+/// assert!((solution.parameters[0][0] - 3.0).abs() < 1e-8);
+/// assert!((solution.parameters[1][0] - 2.0).abs() < 1e-8);
+/// ```
+pub struct SizedCostFunction2<F, const NR: usize, const N0: usize, const N1: usize> {
+    func: F,
+}
+
+impl<F, const NR: usize, const N0: usize, const N1: usize> SizedCostFunction2<F, NR, N0, N1>
+where
+    F: Fn(
+        &[f64; N0],
+        &[f64; N1],
+        &mut [f64; NR],
+        Option<&mut [[f64; N0]; NR]>,
+        Option<&mut [[f64; N1]; NR]>,
+    ) -> bool,
+{
+    pub fn new(func: F) -> Self {
+        Self { func }
+    }
+}
+
+impl<F, const NR: usize, const N0: usize, const N1: usize> CostFunction
+    for SizedCostFunction2<F, NR, N0, N1>
+where
+    F: Fn(
+        &[f64; N0],
+        &[f64; N1],
+        &mut [f64; NR],
+        Option<&mut [[f64; N0]; NR]>,
+        Option<&mut [[f64; N1]; NR]>,
+    ) -> bool,
+{
+    fn evaluate(
+        &self,
+        parameters: &[&[f64]],
+        residuals: &mut [f64],
+        mut jacobians: JacobianType<'_>,
+    ) -> bool {
+        let (Ok(p0), Ok(p1), Ok(residuals)) = (
+            parameters[0].try_into(),
+            parameters[1].try_into(),
+            residuals.try_into(),
+        ) else {
+            return false;
+        };
+        let (rows0, rows1) = match jacobians.as_mut() {
+            Some([rows0, rows1]) => (rows0.as_deref_mut(), rows1.as_deref_mut()),
+            _ => (None, None),
+        };
+        let mut j0 = rows0.as_deref().map(jacobian_from_rows::<NR, N0>);
+        let mut j1 = rows1.as_deref().map(jacobian_from_rows::<NR, N1>);
+        let success = (self.func)(p0, p1, residuals, j0.as_mut(), j1.as_mut());
+        if success {
+            if let (Some(rows0), Some(j0)) = (rows0, j0) {
+                jacobian_to_rows(&j0, rows0);
+            }
+            if let (Some(rows1), Some(j1)) = (rows1, j1) {
+                jacobian_to_rows(&j1, rows1);
+            }
+        }
+        success
+    }
+
+    fn num_residuals(&self) -> usize {
+        NR
+    }
+
+    fn parameter_sizes(&self) -> Vec<usize> {
+        vec![N0, N1]
+    }
+}
+
+impl<F, const NR: usize, const N0: usize, const N1: usize> sealed::Sealed
+    for SizedCostFunction2<F, NR, N0, N1>
+where
+    F: Fn(
+        &[f64; N0],
+        &[f64; N1],
+        &mut [f64; NR],
+        Option<&mut [[f64; N0]; NR]>,
+        Option<&mut [[f64; N1]; NR]>,
+    ) -> bool,
+{
+    fn into_raw<'a>(self) -> RawCostFunctionType<'a>
+    where
+        Self: 'a,
+    {
+        Box::new(move |parameters, residuals, jacobians| unsafe {
+            (self.func)(
+                &*(*parameters).cast(),
+                &*(*parameters.add(1)).cast(),
+                &mut *residuals.cast(),
+                raw_jacobian(jacobians, 0),
+                raw_jacobian(jacobians, 1),
+            )
+        })
+    }
+}
+
+impl<F, const NR: usize, const N0: usize, const N1: usize> SizedCost
+    for SizedCostFunction2<F, NR, N0, N1>
+where
+    F: Fn(
+        &[f64; N0],
+        &[f64; N1],
+        &mut [f64; NR],
+        Option<&mut [[f64; N0]; NR]>,
+        Option<&mut [[f64; N1]; NR]>,
+    ) -> bool,
+{
+}
+
+/// [CostFunction] with compile-time sizes which can be evaluated without marshaling through heap
+/// allocated slices, implemented by [SizedCostFunction] and [SizedCostFunction2].
+pub trait SizedCost: CostFunction + sealed::Sealed {}
+
+/// Cost function working with the Ceres arrays directly.
+pub(crate) type RawCostFunctionType<'a> =
+    Box<dyn Fn(*const *const f64, *mut f64, *mut *mut f64) -> bool + 'a>;
+
+mod sealed {
+    pub trait Sealed {
+        /// Function reading the parameters and writing the outputs right in the Ceres arrays.
+        #[allow(clippy::type_complexity)]
+        fn into_raw<'a>(
+            self,
+        ) -> Box<dyn Fn(*const *const f64, *mut f64, *mut *mut f64) -> bool + 'a>
+        where
+            Self: 'a;
+    }
+}
+
+/// Jacobian of the parameter block with the given index, [None] if it is not requested.
+///
+/// # Safety
+/// `jacobians` must be null or point to an array of at least `index + 1` pointers, each of them
+/// must be null or point to `NR * N` values.
+unsafe fn raw_jacobian<'a, const NR: usize, const N: usize>(
+    jacobians: *mut *mut f64,
+    index: usize,
+) -> Option<&'a mut [[f64; N]; NR]> {
+    if jacobians.is_null() {
+        return None;
+    }
+    let jacobian = *jacobians.add(index);
+    (!jacobian.is_null()).then(|| &mut *jacobian.cast())
+}
+
+fn jacobian_from_rows<const NR: usize, const N: usize>(rows: &[&mut [f64]]) -> [[f64; N]; NR] {
+    let mut jacobian = [[0.0; N]; NR];
+    for (values, row) in jacobian.iter_mut().zip(rows) {
+        values.copy_from_slice(row);
+    }
+    jacobian
+}
+
+fn jacobian_to_rows<const NR: usize, const N: usize>(
+    jacobian: &[[f64; N]; NR],
+    rows: &mut [&mut [f64]],
+) {
+    for (row, values) in rows.iter_mut().zip(jacobian) {
+        row.copy_from_slice(values);
+    }
+}
+
+/// Call `f` with the given residual rows of jacobians borrowed from `jacobians`, so they can be
+/// used again after the call.
+///
+/// [JacobianType] has a single invariant lifetime, so it cannot be reborrowed directly.
+pub(crate) fn with_reborrowed_jacobians<R>(
+    jacobians: &mut JacobianType<'_>,
+    residual_range: Range<usize>,
+    f: impl FnOnce(JacobianType<'_>) -> R,
+) -> R {
+    let mut rows: Option<Vec<Option<Vec<&mut [f64]>>>> = jacobians.as_mut().map(|jacobians| {
+        jacobians
+            .iter_mut()
+            .map(|jacobian| {
+                jacobian.as_mut().map(|rows| {
+                    rows[residual_range.clone()]
+                        .iter_mut()
+                        .map(|row| &mut row[..])
+                        .collect()
+                })
+            })
+            .collect()
+    });
+    let mut blocks: Option<Vec<Option<&mut [&mut [f64]]>>> = rows.as_mut().map(|rows| {
+        rows.iter_mut()
+            .map(|jacobian| jacobian.as_mut().map(|rows| &mut rows[..]))
+            .collect()
+    });
+    f(blocks.as_mut().map(|blocks| &mut blocks[..]))
+}
+
+/// Cost function of a residual block which is not passed to Ceres yet, because parameter block
+/// sizes and the validation mode are known only when the block is added to the problem.
+pub(crate) struct PendingCostFunction<'cost> {
+    pub(crate) num_residuals: usize,
+    /// Parameter block sizes declared by the cost function, [None] if they are defined by the
+    /// parameter blocks.
+    pub(crate) parameter_sizes: Option<Vec<usize>>,
+    #[allow(clippy::type_complexity)]
+    create: Box<dyn FnOnce(Vec<usize>, Option<String>) -> RawCostFunctionType<'cost> + 'cost>,
+    error: Option<ErrorSlot>,
+}
+
+impl<'cost> PendingCostFunction<'cost> {
+    pub(crate) fn from_closure(func: CostFunctionType<'cost>, num_residuals: usize) -> Self {
+        Self {
+            num_residuals,
+            parameter_sizes: None,
+            create: Box::new(move |parameter_sizes, validation_label| {
+                CallbackCostFunction::raw_from_closure(
+                    func,
+                    parameter_sizes,
+                    num_residuals,
+                    validation_label,
+                )
+            }),
+            error: None,
+        }
+    }
+
+    pub(crate) fn from_fallible_closure(
+        func: FallibleCostFunctionType<'cost>,
+        num_residuals: usize,
+    ) -> Self {
+        let error = ErrorSlot::default();
+        let error_clone = error.clone();
+        let func: CostFunctionType<'cost> = Box::new(move |parameters, residuals, jacobians| {
+            match func(parameters, residuals, jacobians) {
+                Ok(()) => true,
+                Err(error) => {
+                    error_clone.set(error);
+                    false
+                }
+            }
+        });
+        Self {
+            error: Some(error),
+            ..Self::from_closure(func, num_residuals)
+        }
+    }
+
+    /// The cost function is evaluated directly on the Ceres arrays unless validation is enabled.
+    pub(crate) fn from_sized_cost(cost: impl SizedCost + 'cost) -> Self {
+        let num_residuals = cost.num_residuals();
+        Self {
+            num_residuals,
+            parameter_sizes: Some(cost.parameter_sizes()),
+            create: Box::new(move |parameter_sizes, validation_label| {
+                if validation_label.is_some() {
+                    (Self::from_cost_fn(cost).create)(parameter_sizes, validation_label)
+                } else {
+                    cost.into_raw()
+                }
+            }),
+            error: None,
+        }
+    }
+
+    /// Cost function working with the Ceres arrays directly, it is never validated.
+    pub(crate) fn from_raw(
+        func: RawCostFunctionType<'cost>,
+        parameter_sizes: Vec<usize>,
+        num_residuals: usize,
+    ) -> Self {
+        Self {
+            num_residuals,
+            parameter_sizes: Some(parameter_sizes),
+            create: Box::new(move |_parameter_sizes, _validation_label| func),
+            error: None,
+        }
+    }
+
+    pub(crate) fn from_cost_fn(cost: impl CostFunction + 'cost) -> Self {
+        let num_residuals = cost.num_residuals();
+        Self {
+            num_residuals,
+            parameter_sizes: Some(cost.parameter_sizes()),
+            create: Box::new(move |parameter_sizes, validation_label| {
+                CallbackCostFunction::raw_from_closure(
+                    move |parameters: &[&[f64]], residuals: &mut [f64], jacobians| {
+                        cost.evaluate(parameters, residuals, jacobians)
+                    },
+                    parameter_sizes,
+                    num_residuals,
+                    validation_label,
+                )
+            }),
+            error: None,
+        }
+    }
+
+    /// Whiten residuals and jacobians of the cost function, the whitening must match the number
+    /// of residuals, see [Whitening::matches].
+    pub(crate) fn whitened(self, whitening: Whitening) -> Self {
+        let num_residuals = self.num_residuals;
+        let create = self.create;
+        Self {
+            create: Box::new(move |parameter_sizes, validation_label| {
+                let func = create(parameter_sizes.clone(), validation_label);
+                whitening.wrap(func, parameter_sizes, num_residuals)
+            }),
+            ..self
+        }
+    }
+
+    /// Create the cost function for the given parameter block sizes, `validation_label` enables
+    /// validation, see [CallbackCostFunction::new].
+    pub(crate) fn create(
+        self,
+        parameter_sizes: Vec<usize>,
+        validation_label: Option<String>,
+    ) -> CallbackCostFunction<'cost> {
+        let func = (self.create)(parameter_sizes.clone(), validation_label);
+        let mut cost = CallbackCostFunction::from_raw(func, &parameter_sizes, self.num_residuals);
+        cost.error = self.error;
+        cost
+    }
+}
+
 /// A cost function for [NllsProblem](crate::nlls_problem::NllsProblem).
-pub(crate) struct CostFunction<'cost>(cxx::UniquePtr<ffi::CallbackCostFunction<'cost>>);
+pub(crate) struct CallbackCostFunction<'cost> {
+    inner: cxx::UniquePtr<ffi::CallbackCostFunction<'cost>>,
+    panic: PanicSlot,
+    /// Errors of [FallibleCostFunctionType] functions.
+    error: Option<ErrorSlot>,
+}
 
-impl<'cost> CostFunction<'cost> {
+impl<'cost> CallbackCostFunction<'cost> {
     /// Create a new cost function from a Rust function.
     ///
     /// # Arguments
-    /// - func - function to find residuals and Jacobian for the problem block. The function itself
-    ///   must return [false] if it cannot compute Jacobian, [true] otherwise, and accept following
-    ///   arguments:
-    ///   - parameters - slice of [f64] slices representing the current values of the parameters.
-    ///     Each parameter is represented as a slice, the slice sizes are specified by
-    ///     `parameter_sizes`.
-    ///   - residuals - mutable slice of [f64] for residuals outputs, the size is specified by
-    ///     `num_residuals`.
-    ///   - jacobians: [JacobianType](crate::types::JacobianType) - represents a mutable
-    ///     structure to output the Jacobian. Sometimes the solver doesn't need the Jacobian or
-    ///     some of its components, in this case the corresponding value is [None]. For the required
-    ///     components it has a 3-D shape: top index is for the parameter index, middle index is for
-    ///     the residual index, and the most inner dimension is for the given parameter component
-    ///     index. So the size of top-level [Some] is defined by `parameter_sizes.len()`,
-    ///     second-level [Some]'s slice length is `num_residuals`, and the bottom-level slice has
-    ///     length of `parameter_sizes[i]`, where `i` is the top-level index.
+    /// - func - function to find residuals and Jacobian for the problem block, see
+    ///   [CostFunction::evaluate].
     /// - parameter_sizes - sizes of the parameter vectors.
     /// - num_residuals - length of the residual vector, usually corresponds to the number of
     ///   data points.
+    /// - validation_label - if [Some], a warning is logged with glog when the Rust function leaves
+    ///   some residuals or requested jacobian components unwritten, the label identifies the cost
+    ///   function in the messages. The unwritten values are NaN, so Ceres rejects such
+    ///   evaluations. Writing outside of the declared sizes is impossible, because the Rust
+    ///   function gets slices of the exact sizes.
+    ///
+    /// A panic of the Rust function is reported as a failed evaluation, its message is available
+    /// with [CallbackCostFunction::take_panic].
     pub fn new(
-        func: impl Into<CostFunctionType<'cost>>,
+        func: impl Fn(&[&[f64]], &mut [f64], JacobianType<'_>) -> bool + 'cost,
         parameter_sizes: impl Into<Vec<usize>>,
         num_residuals: usize,
+        validation_label: Option<String>,
     ) -> Self {
         let parameter_sizes = parameter_sizes.into();
+        Self::from_raw(
+            Self::raw_from_closure(
+                func,
+                parameter_sizes.clone(),
+                num_residuals,
+                validation_label,
+            ),
+            &parameter_sizes,
+            num_residuals,
+        )
+    }
+
+    /// Adapt the Rust function working with slices to the Ceres arrays, see
+    /// [CallbackCostFunction::new] for the arguments.
+    fn raw_from_closure(
+        func: impl Fn(&[&[f64]], &mut [f64], JacobianType<'_>) -> bool + 'cost,
+        parameter_sizes: Vec<usize>,
+        num_residuals: usize,
+        validation_label: Option<String>,
+    ) -> RawCostFunctionType<'cost> {
+        Box::new(move |parameters_ptr, residuals_ptr, jacobians_ptr| {
+            let parameter_pointers =
+                unsafe { slice::from_raw_parts(parameters_ptr, parameter_sizes.len()) };
+            let parameters = parameter_pointers
+                .iter()
+                .zip(parameter_sizes.iter())
+                .map(|(&p, &size)| unsafe { slice::from_raw_parts(p, size) })
+                .collect::<Vec<_>>();
+            if validation_label.is_some() {
+                for_each_output(
+                    residuals_ptr,
+                    jacobians_ptr,
+                    &parameter_sizes,
+                    num_residuals,
+                    |_, values| values.fill(f64::from_bits(UNWRITTEN_BITS)),
+                );
+            }
+            let residuals = unsafe { slice::from_raw_parts_mut(residuals_ptr, num_residuals) };
+            let mut jacobians_owned =
+                OwnedJacobian::from_pointer(jacobians_ptr, &parameter_sizes, num_residuals);
+            let mut jacobian_references = jacobians_owned.references();
+            let success = func(
+                &parameters,
+                residuals,
+                jacobian_references.as_mut().map(|v| &mut v[..]),
+            );
+            if let (true, Some(label)) = (success, &validation_label) {
+                for_each_output(
+                    residuals_ptr,
+                    jacobians_ptr,
+                    &parameter_sizes,
+                    num_residuals,
+                    |output, values| {
+                        let unwritten = values
+                            .iter()
+                            .filter(|value| value.to_bits() == UNWRITTEN_BITS)
+                            .count();
+                        if unwritten > 0 {
+                            ffi::log_warning(&format!(
+                                    "Cost function of {label} left {unwritten} of {} {output} unwritten",
+                                    values.len(),
+                                ));
+                        }
+                    },
+                );
+            }
+            success
+        })
+    }
+
+    /// Create a new cost function from a Rust function working with the Ceres arrays directly.
+    ///
+    /// A panic of the Rust function is reported as a failed evaluation, its message is available
+    /// with [CallbackCostFunction::take_panic].
+    pub fn from_raw(
+        func: RawCostFunctionType<'cost>,
+        parameter_sizes: &[usize],
+        num_residuals: usize,
+    ) -> Self {
         let parameter_block_sizes: Vec<_> =
             parameter_sizes.iter().map(|&size| size as i32).collect();
-
-        let safe_func = func.into();
-        let rust_func: Box<dyn Fn(*const *const f64, *mut f64, *mut *mut f64) -> bool + 'cost> =
+        let panic = PanicSlot::default();
+        let panic_clone = panic.clone();
+        let rust_func: RawCostFunctionType<'cost> =
             Box::new(move |parameters_ptr, residuals_ptr, jacobians_ptr| {
-                let parameter_pointers =
-                    unsafe { slice::from_raw_parts(parameters_ptr, parameter_sizes.len()) };
-                let parameters = parameter_pointers
-                    .iter()
-                    .zip(parameter_sizes.iter())
-                    .map(|(&p, &size)| unsafe { slice::from_raw_parts(p, size) })
-                    .collect::<Vec<_>>();
-                let residuals = unsafe { slice::from_raw_parts_mut(residuals_ptr, num_residuals) };
-                let mut jacobians_owned =
-                    OwnedJacobian::from_pointer(jacobians_ptr, &parameter_sizes, num_residuals);
-                let mut jacobian_references = jacobians_owned.references();
-                safe_func(
-                    &parameters,
-                    residuals,
-                    jacobian_references.as_mut().map(|v| &mut v[..]),
+                panic_clone.call(
+                    || func(parameters_ptr, residuals_ptr, jacobians_ptr),
+                    || false,
                 )
             });
         let inner = ffi::new_callback_cost_function(
@@ -71,11 +1520,58 @@ impl<'cost> CostFunction<'cost> {
             num_residuals as i32,
             &parameter_block_sizes,
         );
-        Self(inner)
+        Self {
+            inner,
+            panic,
+            error: None,
+        }
     }
 
-    pub fn into_inner(self) -> cxx::UniquePtr<ffi::CallbackCostFunction<'cost>> {
-        self.0
+    /// The last error of the fallible Rust function since the previous call.
+    pub fn take_error(&self) -> Option<CostFunctionError> {
+        self.error.as_ref().and_then(ErrorSlot::take)
+    }
+
+    /// Message of the first panic of the Rust function since the previous call.
+    pub fn take_panic(&self) -> Option<String> {
+        self.panic.take()
+    }
+
+    /// Pointer to the underlying C++ object, it is valid while `self` is alive.
+    pub fn as_ptr(&self) -> *mut ffi::CallbackCostFunction<'cost> {
+        self.inner
+            .as_ref()
+            .expect("Underlying C++ unique_ptr<CallbackCostFunction> must hold non-null pointer")
+            as *const _ as *mut _
+    }
+}
+
+/// Bits of the value written to the outputs before the call of a validating cost function, a quiet
+/// NaN with a payload which is unlikely to be produced by arithmetic.
+const UNWRITTEN_BITS: u64 = 0x7ff8_dead_beef_0000;
+
+/// Call `f` for the residuals and for every requested jacobian block with their description.
+fn for_each_output(
+    residuals_ptr: *mut f64,
+    jacobians_ptr: *mut *mut f64,
+    parameter_sizes: &[usize],
+    num_residuals: usize,
+    mut f: impl FnMut(&str, &mut [f64]),
+) {
+    f("residuals", unsafe {
+        slice::from_raw_parts_mut(residuals_ptr, num_residuals)
+    });
+    if jacobians_ptr.is_null() {
+        return;
+    }
+    let per_parameter = unsafe { slice::from_raw_parts(jacobians_ptr, parameter_sizes.len()) };
+    for (index, (&pointer, &size)) in per_parameter.iter().zip(parameter_sizes).enumerate() {
+        if !pointer.is_null() {
+            f(
+                &format!("jacobian components of parameter block {index}"),
+                unsafe { slice::from_raw_parts_mut(pointer, size * num_residuals) },
+            );
+        }
     }
 }
 
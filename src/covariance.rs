@@ -0,0 +1,449 @@
+//! Covariance of the parameters of [NllsProblem](crate::nlls_problem::NllsProblem).
+//!
+//! [NllsProblem::estimate_covariance](crate::nlls_problem::NllsProblem::estimate_covariance)
+//! runs `ceres::Covariance`, which computes `(J^T J)^-1` from the jacobian `J` of the residuals at
+//! the current parameter values, so it is usually called after the problem is solved with
+//! [NllsProblem::solve_mut](crate::nlls_problem::NllsProblem::solve_mut). The residuals are
+//! expected to be normalized by their uncertainties, otherwise the covariance must be scaled.
+//!
+//! ```rust
+//! use ceres_solver::covariance::CovarianceOptions;
+//! use ceres_solver::{CostFunctionType, NllsProblem, SolverOptions};
+//!
+//! // Mean of measurements with the same uncertainty
+//! let measurements = [1.0, 2.0, 3.0, 4.0];
+//! let sigma = 0.5;
+//!
+//! let cost: CostFunctionType = Box::new(move |parameters, residuals, jacobians| {
+//!     let mean = parameters[0][0];
+//!     for (residual, &y) in residuals.iter_mut().zip(measurements.iter()) {
+//!         *residual = (mean - y) / sigma;
+//!     }
+//!     if let Some(jacobians) = jacobians {
+//!         if let Some(d_dmean) = &mut jacobians[0] {
+//!             for row in d_dmean.iter_mut() {
+//!                 row[0] = 1.0 / sigma;
+//!             }
+//!         }
+//!     }
+//!     true
+//! });
+//!
+//! let mut problem = NllsProblem::new()
+//!     .residual_block_builder()
+//!     .set_cost(cost, measurements.len())
+//!     .set_parameters([vec![0.0]])
+//!     .build_into_problem()
+//!     .unwrap()
+//!     .0;
+//! problem.solve_mut(&SolverOptions::default()).unwrap();
+//!
+//! let covariance = problem
+//!     .estimate_covariance(&[0], &CovarianceOptions::default())
+//!     .unwrap();
+//! // Variance of the mean is sigma^2 / n
+//! assert!((covariance.matrix.get(0, 0) - 0.0625).abs() < 1e-12);
+//! ```
+
+use crate::evaluation::DenseMatrix;
+
+pub use ceres_solver_sys::ffi::CovarianceAlgorithmType;
+
+/// Options for
+/// [NllsProblem::estimate_covariance](crate::nlls_problem::NllsProblem::estimate_covariance).
+#[derive(Clone, Copy, Debug)]
+pub struct CovarianceOptions {
+    /// Algorithm to invert `J^T J`, [CovarianceAlgorithmType::SPARSE_QR] by default.
+    /// [CovarianceAlgorithmType::DENSE_SVD] is slower and uses more memory, but it can handle
+    /// rank deficient jacobians with [CovarianceOptions::null_space_rank].
+    pub algorithm_type: CovarianceAlgorithmType,
+    /// Minimum ratio of the smallest to the largest eigenvalue of `J^T J`, `1e-14` by default.
+    /// The jacobian is considered rank deficient if the ratio is smaller, and the covariance
+    /// computation fails with
+    /// [NllsProblemError::RankDeficientJacobian](crate::error::NllsProblemError::RankDeficientJacobian)
+    /// unless [CovarianceOptions::null_space_rank] allows to drop the small eigenvalues.
+    pub min_reciprocal_condition_number: f64,
+    /// Number of the smallest eigenvalues of `J^T J` to drop when inverting it with
+    /// [CovarianceAlgorithmType::DENSE_SVD], 0 by default. Negative value drops all the
+    /// eigenvalues which are smaller than the largest one multiplied by
+    /// [CovarianceOptions::min_reciprocal_condition_number]. It gives the Moore-Penrose
+    /// pseudo-inverse of `J^T J` for a rank deficient jacobian.
+    pub null_space_rank: i32,
+    /// Number of threads to use, 1 by default. It is ignored for problems which are not created
+    /// with [NllsProblem::new_threadsafe](crate::nlls_problem::NllsProblem::new_threadsafe).
+    pub num_threads: i32,
+    /// Whether to apply the loss functions of the residual blocks to the jacobian, [true] by
+    /// default.
+    pub apply_loss_function: bool,
+}
+
+impl Default for CovarianceOptions {
+    fn default() -> Self {
+        Self {
+            algorithm_type: CovarianceAlgorithmType::SPARSE_QR,
+            min_reciprocal_condition_number: 1e-14,
+            null_space_rank: 0,
+            num_threads: 1,
+            apply_loss_function: true,
+        }
+    }
+}
+
+/// Result of
+/// [NllsProblem::estimate_covariance](crate::nlls_problem::NllsProblem::estimate_covariance).
+#[derive(Clone, Debug)]
+pub struct CovarianceResult {
+    /// Indices of the parameter blocks, in the order they were given.
+    pub parameter_blocks: Vec<usize>,
+    /// Sizes of the parameter blocks.
+    pub block_sizes: Vec<usize>,
+    /// Covariance matrix, rows and columns correspond to the components of the parameter blocks,
+    /// concatenated in the order of [CovarianceResult::parameter_blocks].
+    pub matrix: DenseMatrix,
+}
+
+impl CovarianceResult {
+    /// Cross-covariance of two parameter blocks given by their indices in the problem, the rows
+    /// correspond to the components of block `i` and the columns to the components of block
+    /// `j`. Returns [None] if any of the blocks is not in [CovarianceResult::parameter_blocks].
+    pub fn covariance_block(&self, i: usize, j: usize) -> Option<DenseMatrix> {
+        let (row_offset, num_rows) = self.block_range(i)?;
+        let (col_offset, num_cols) = self.block_range(j)?;
+        let values = (row_offset..row_offset + num_rows)
+            .flat_map(|row| &self.matrix.row(row)[col_offset..col_offset + num_cols])
+            .copied()
+            .collect();
+        Some(DenseMatrix {
+            num_rows,
+            num_cols,
+            values,
+        })
+    }
+
+    /// Covariance matrix of the given parameter blocks, rows and columns correspond to their
+    /// components concatenated in the given order. Returns [None] if any of the blocks is not in
+    /// [CovarianceResult::parameter_blocks].
+    ///
+    /// With `nalgebra` Cargo feature, the result can be converted into `nalgebra::DMatrix`.
+    pub fn full_covariance_matrix(&self, parameter_blocks: &[usize]) -> Option<DenseMatrix> {
+        let ranges = parameter_blocks
+            .iter()
+            .map(|&index| self.block_range(index))
+            .collect::<Option<Vec<_>>>()?;
+        let size = ranges.iter().map(|&(_, size)| size).sum();
+        let values = ranges
+            .iter()
+            .flat_map(|&(row_offset, num_rows)| row_offset..row_offset + num_rows)
+            .flat_map(|row| {
+                let row = self.matrix.row(row);
+                ranges.iter().flat_map(move |&(col_offset, num_cols)| {
+                    &row[col_offset..col_offset + num_cols]
+                })
+            })
+            .copied()
+            .collect();
+        Some(DenseMatrix {
+            num_rows: size,
+            num_cols: size,
+            values,
+        })
+    }
+
+    /// Square roots of the diagonal elements of the covariance matrix, one vector per parameter
+    /// block.
+    pub fn standard_errors(&self) -> Vec<Vec<f64>> {
+        let mut diagonal = (0..self.matrix.num_rows).map(|i| self.matrix.get(i, i).sqrt());
+        self.block_sizes
+            .iter()
+            .map(|&size| diagonal.by_ref().take(size).collect())
+            .collect()
+    }
+
+    /// Correlation matrix having the same layout as [CovarianceResult::matrix]. Its elements are
+    /// NaN for components having zero variance, e.g. components of constant parameter blocks.
+    pub fn correlation_matrix(&self) -> DenseMatrix {
+        let sigmas: Vec<_> = (0..self.matrix.num_rows)
+            .map(|i| self.matrix.get(i, i).sqrt())
+            .collect();
+        let values = self
+            .matrix
+            .rows()
+            .zip(sigmas.iter())
+            .flat_map(|(row, &sigma_row)| {
+                row.iter()
+                    .zip(sigmas.iter())
+                    .map(move |(&value, &sigma_col)| value / (sigma_row * sigma_col))
+            })
+            .collect();
+        DenseMatrix {
+            num_rows: self.matrix.num_rows,
+            num_cols: self.matrix.num_cols,
+            values,
+        }
+    }
+
+    /// Covariance multiplied by the given factor, e.g. by the reduced chi-square if the residuals
+    /// are not normalized by the absolute uncertainties.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            parameter_blocks: self.parameter_blocks.clone(),
+            block_sizes: self.block_sizes.clone(),
+            matrix: DenseMatrix {
+                num_rows: self.matrix.num_rows,
+                num_cols: self.matrix.num_cols,
+                values: self
+                    .matrix
+                    .values
+                    .iter()
+                    .map(|value| value * factor)
+                    .collect(),
+            },
+        }
+    }
+
+    /// Offset of the parameter block in [CovarianceResult::matrix] and its size.
+    fn block_range(&self, parameter_block: usize) -> Option<(usize, usize)> {
+        let position = self
+            .parameter_blocks
+            .iter()
+            .position(|&index| index == parameter_block)?;
+        let offset = self.block_sizes[..position].iter().sum();
+        Some((offset, self.block_sizes[position]))
+    }
+}
+
+/// Parameter uncertainties returned by
+/// [NllsProblem::parameter_uncertainties](crate::nlls_problem::NllsProblem::parameter_uncertainties).
+#[derive(Clone, Debug)]
+pub struct ParameterUncertainties {
+    /// Covariance scaled by [ParameterUncertainties::reduced_chi2].
+    pub covariance: CovarianceResult,
+    /// Standard errors of the parameters, one vector per parameter block, see
+    /// [CovarianceResult::standard_errors].
+    pub standard_errors: Vec<Vec<f64>>,
+    /// Correlation matrix of the parameters, see [CovarianceResult::correlation_matrix].
+    pub correlation: DenseMatrix,
+    /// Sum of the squared residuals without the loss functions applied.
+    pub chi2: f64,
+    /// Number of residuals minus the number of the varying parameters.
+    pub degrees_of_freedom: i64,
+    /// Chi-square divided by the number of degrees of freedom, infinite if the number of degrees
+    /// of freedom is not positive.
+    pub reduced_chi2: f64,
+}
+
+/// Direction in the space of the varying parameters along which the jacobian is degenerate, see
+/// [NllsProblemError::RankDeficientJacobian](crate::error::NllsProblemError::RankDeficientJacobian).
+///
+/// The direction is an eigenvector of `J^T J`, so the cost doesn't change to the second order when
+/// the parameters are moved along it, e.g. when two parameters enter the model as a sum only.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NullSpaceDirection {
+    /// Ratio of the eigenvalue of the direction to the largest eigenvalue of `J^T J`.
+    pub reciprocal_condition_number: f64,
+    /// Coefficients of the unit vector along the direction as
+    /// `(parameter block index, component index, coefficient)`, negligible coefficients are
+    /// omitted. Component indices of the parameter blocks having a manifold are in its tangent
+    /// space.
+    pub components: Vec<(usize, usize, f64)>,
+}
+
+impl std::fmt::Display for NullSpaceDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (block, component, coefficient)) in self.components.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{coefficient:+.3} * p{block}[{component}]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Coefficients smaller than this are omitted from [NullSpaceDirection::components].
+const NULL_SPACE_COEFFICIENT_THRESHOLD: f64 = 1e-6;
+
+/// Directions along which the jacobian is degenerate, `columns` gives the parameter block and
+/// component indices of every jacobian column.
+pub(crate) fn null_space_directions(
+    jacobian: &DenseMatrix,
+    columns: &[(usize, usize)],
+    min_reciprocal_condition_number: f64,
+) -> Vec<NullSpaceDirection> {
+    let n = jacobian.num_cols;
+    // J^T J
+    let mut normal = DenseMatrix::zeros(n, n);
+    for row in jacobian.rows() {
+        for i in 0..n {
+            for j in 0..n {
+                normal.values[i * n + j] += row[i] * row[j];
+            }
+        }
+    }
+    let (eigenvalues, eigenvectors) = symmetric_eigen(normal);
+    let max_eigenvalue = eigenvalues.iter().copied().fold(0.0, f64::max);
+    (0..n)
+        .filter_map(|k| {
+            let reciprocal_condition_number = if max_eigenvalue > 0.0 {
+                eigenvalues[k].max(0.0) / max_eigenvalue
+            } else {
+                0.0
+            };
+            if reciprocal_condition_number >= min_reciprocal_condition_number {
+                return None;
+            }
+            let components = columns
+                .iter()
+                .enumerate()
+                .map(|(i, &(block, component))| (block, component, eigenvectors.get(i, k)))
+                .filter(|&(_, _, coefficient)| coefficient.abs() > NULL_SPACE_COEFFICIENT_THRESHOLD)
+                .collect();
+            Some(NullSpaceDirection {
+                reciprocal_condition_number,
+                components,
+            })
+        })
+        .collect()
+}
+
+/// Eigenvalues and eigenvectors of a symmetric matrix with the cyclic Jacobi method, the
+/// eigenvectors are the columns of the returned matrix.
+fn symmetric_eigen(mut a: DenseMatrix) -> (Vec<f64>, DenseMatrix) {
+    const MAX_SWEEPS: usize = 100;
+
+    let n = a.num_rows;
+    let mut v = DenseMatrix::zeros(n, n);
+    for i in 0..n {
+        v.values[i * n + i] = 1.0;
+    }
+    // Rotate the columns p and q of the matrix
+    let rotate_columns = |m: &mut DenseMatrix, p: usize, q: usize, c: f64, s: f64| {
+        for k in 0..n {
+            let (mkp, mkq) = (m.values[k * n + p], m.values[k * n + q]);
+            m.values[k * n + p] = c * mkp - s * mkq;
+            m.values[k * n + q] = s * mkp + c * mkq;
+        }
+    };
+    let norm: f64 = a.values.iter().map(|x| x * x).sum();
+    for _ in 0..MAX_SWEEPS {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a.get(i, j).powi(2))
+            .sum();
+        if off_diagonal <= f64::EPSILON.powi(2) * norm {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                let apq = a.get(p, q);
+                if apq == 0.0 {
+                    continue;
+                }
+                let theta = (a.get(q, q) - a.get(p, p)) / (2.0 * apq);
+                let t = theta.signum() / (theta.abs() + f64::hypot(theta, 1.0));
+                let c = 1.0 / f64::hypot(t, 1.0);
+                let s = t * c;
+                rotate_columns(&mut a, p, q, c, s);
+                for k in 0..n {
+                    let (apk, aqk) = (a.values[p * n + k], a.values[q * n + k]);
+                    a.values[p * n + k] = c * apk - s * aqk;
+                    a.values[q * n + k] = s * apk + c * aqk;
+                }
+                rotate_columns(&mut v, p, q, c, s);
+            }
+        }
+    }
+    ((0..n).map(|i| a.get(i, i)).collect(), v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Covariance of blocks 3 (size 1) and 5 (size 2), element (i, j) is 10 i + j.
+    fn result() -> CovarianceResult {
+        CovarianceResult {
+            parameter_blocks: vec![3, 5],
+            block_sizes: vec![1, 2],
+            matrix: DenseMatrix {
+                num_rows: 3,
+                num_cols: 3,
+                values: vec![0.0, 1.0, 2.0, 10.0, 11.0, 12.0, 20.0, 21.0, 22.0],
+            },
+        }
+    }
+
+    #[test]
+    fn covariance_block() {
+        let result = result();
+        let block = result.covariance_block(5, 3).unwrap();
+        assert_eq!((block.num_rows, block.num_cols), (2, 1));
+        assert_eq!(block.values, [10.0, 20.0]);
+        assert_eq!(
+            result.covariance_block(5, 5).unwrap().values,
+            [11.0, 12.0, 21.0, 22.0]
+        );
+        assert!(result.covariance_block(3, 4).is_none());
+    }
+
+    #[test]
+    fn standard_errors_and_correlation() {
+        let result = CovarianceResult {
+            parameter_blocks: vec![0, 1],
+            block_sizes: vec![1, 1],
+            matrix: DenseMatrix {
+                num_rows: 2,
+                num_cols: 2,
+                values: vec![4.0, -3.0, -3.0, 9.0],
+            },
+        };
+        assert_eq!(result.standard_errors(), [vec![2.0], vec![3.0]]);
+        assert_eq!(result.correlation_matrix().values, [1.0, -0.5, -0.5, 1.0]);
+        let scaled = result.scaled(4.0);
+        assert_eq!(scaled.standard_errors(), [vec![4.0], vec![6.0]]);
+        assert_eq!(scaled.correlation_matrix(), result.correlation_matrix());
+    }
+
+    #[test]
+    fn null_space() {
+        // r = [a + b, 2 a + 2 b, c], a - b is not constrained
+        let jacobian = DenseMatrix {
+            num_rows: 3,
+            num_cols: 3,
+            values: vec![1.0, 1.0, 0.0, 2.0, 2.0, 0.0, 0.0, 0.0, 3.0],
+        };
+        let columns = [(0, 0), (0, 1), (1, 0)];
+        let directions = null_space_directions(&jacobian, &columns, 1e-14);
+        assert_eq!(directions.len(), 1);
+        let direction = &directions[0];
+        assert!(direction.reciprocal_condition_number < 1e-14);
+        assert_eq!(direction.components.len(), 2);
+        let (a, b) = (direction.components[0], direction.components[1]);
+        assert_eq!((a.0, a.1, b.0, b.1), (0, 0, 0, 1));
+        assert!((a.2 + b.2).abs() < 1e-12);
+        assert!((a.2.abs() - f64::sqrt(0.5)).abs() < 1e-12);
+
+        let full_rank = DenseMatrix {
+            num_rows: 2,
+            num_cols: 2,
+            values: vec![1.0, 2.0, 3.0, 4.0],
+        };
+        assert!(null_space_directions(&full_rank, &columns[..2], 1e-14).is_empty());
+    }
+
+    #[test]
+    fn full_covariance_matrix() {
+        let result = result();
+        assert_eq!(
+            result.full_covariance_matrix(&[3, 5]).unwrap(),
+            result.matrix
+        );
+        let reordered = result.full_covariance_matrix(&[5, 3]).unwrap();
+        assert_eq!((reordered.num_rows, reordered.num_cols), (3, 3));
+        assert_eq!(
+            reordered.values,
+            [11.0, 12.0, 10.0, 21.0, 22.0, 20.0, 1.0, 2.0, 0.0]
+        );
+        assert!(result.full_covariance_matrix(&[0]).is_none());
+    }
+}
@@ -5,22 +5,282 @@
 //! [CurveFunctionType] for given `x`, `y` and optionally inverse y error values. This approach
 //! also simplifies parameter usage, assuming that the function depends on a single parameter
 //! only.
+//!
+//! The jacobian of the model can be written by hand, found with finite differences from a model
+//! computing the value only, see [NumericCurveFunctionType], or found exactly by automatic
+//! differentiation of a model generic over [Scalar], see [AutoDiffCurveModel].
+//! [CurveFitProblem1DBuilder] switches between them with a single method call.
 
-use crate::cost::CostFunctionType;
+use crate::autodiff::Scalar;
+use crate::cost::{CostFunctionType, PendingCostFunction, RawCostFunctionType, Whitening};
 use crate::error::CurveFitProblemBuildError;
-use crate::loss::LossFunction;
-use crate::nlls_problem::{NllsProblem, NllsProblemSolution};
-use crate::parameter_block::ParameterBlock;
+use crate::evaluation::EvaluateOptions;
+use crate::jet::Jet;
+use crate::loss::{LossFunction, LossFunctionWrapper};
+use crate::manifold::Manifold;
+use crate::multistart::{unit_samples, MultiStartSolution, MultiStartStrategy};
+use crate::nlls_problem::NllsProblem;
+use crate::parameter_block::{ParameterBlock, ParameterBlockOrIndex};
+use crate::sigma_clipping::{ClippingMode, SigmaClipping, SigmaClippingSolution};
 use crate::solver::{SolverOptions, SolverSummary};
+use crate::template::{template_function, CurveFitTemplate, TemplateData};
 use crate::types::Either;
 
+use std::cell::RefCell;
+use std::iter;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::slice;
+use std::sync::Arc;
+
 pub type CurveFunctionType = Box<dyn Fn(f64, &[f64], &mut f64, Option<&mut [Option<f64>]>) -> bool>;
 
+/// Model function evaluated for all the data points at once, see
+/// [CurveFitProblem1D::new_vectorized].
+pub type VectorizedCurveFunctionType =
+    Box<dyn Fn(&[f64], &[f64], &mut [f64], Option<&mut [Option<&mut [f64]>]>) -> bool>;
+
+/// Model function computing the value only, its derivatives are found with finite differences,
+/// see [CurveFitProblem1D::new_numeric].
+pub type NumericCurveFunctionType = Box<dyn Fn(f64, &[f64], &mut f64) -> bool>;
+
+/// Default relative step size of finite differences for [NumericCurveFunctionType].
+pub(crate) const DEFAULT_RELATIVE_STEP_SIZE: f64 = 1e-6;
+
+/// Curve model generic over [Scalar], its jacobian is found by automatic differentiation, see
+/// [CurveFitProblem1D::new_autodiff].
+///
+/// Closures cannot be generic, so the model is a method of a type, usually a unit struct.
+pub trait AutoDiffCurveModel {
+    /// Value of the model at `x` for the given parameters.
+    fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T;
+}
+
+/// Number of parameters differentiated at once by [AutoDiffCurveModel] wrappers, the model is
+/// called `ceil(num_parameters / AUTODIFF_STRIDE)` times per jacobian evaluation.
+const AUTODIFF_STRIDE: usize = 4;
+
+/// Transform of a parameter keeping it inside its range, see
+/// [CurveFitProblem1DBuilder::transforms].
+///
+/// The solver works with an unconstrained internal value `u`, and the model gets the natural value
+/// `p(u)`. Unlike bounds, the transform never lets the solver step outside the range, and the
+/// internal value of a scale-like parameter changes at the natural rate of its order of magnitude.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParameterTransform {
+    /// `p = exp(u)` for strictly positive parameters, e.g. amplitudes and time scales.
+    Log,
+    /// `p = lower + (upper - lower) / (1 + exp(-u))` for parameters in `(lower, upper)`, e.g.
+    /// fractions in `(0, 1)`.
+    Logit { lower: f64, upper: f64 },
+}
+
+impl ParameterTransform {
+    fn is_valid(&self) -> bool {
+        match *self {
+            Self::Log => true,
+            Self::Logit { lower, upper } => lower.is_finite() && upper.is_finite() && lower < upper,
+        }
+    }
+
+    /// Internal value for the natural one, [None] if it is outside of the range.
+    fn to_internal(self, value: f64) -> Option<f64> {
+        match self {
+            Self::Log => (value > 0.0).then(|| value.ln()),
+            Self::Logit { lower, upper } => (lower < value && value < upper).then(|| {
+                let fraction = (value - lower) / (upper - lower);
+                (fraction / (1.0 - fraction)).ln()
+            }),
+        }
+    }
+
+    fn to_natural(self, internal: f64) -> f64 {
+        match self {
+            Self::Log => internal.exp(),
+            Self::Logit { lower, upper } => lower + (upper - lower) / (1.0 + (-internal).exp()),
+        }
+    }
+
+    /// Derivative of the natural value over the internal one.
+    fn derivative(self, internal: f64) -> f64 {
+        match self {
+            Self::Log => internal.exp(),
+            Self::Logit { lower, upper } => {
+                let fraction = 1.0 / (1.0 + (-internal).exp());
+                (upper - lower) * fraction * (1.0 - fraction)
+            }
+        }
+    }
+}
+
+/// Converts the internal parameter values to the natural ones, see [ParameterTransform].
+pub(crate) fn to_natural_parameters(
+    transforms: &[(usize, ParameterTransform)],
+    parameters: &mut [f64],
+) {
+    for &(i, transform) in transforms {
+        parameters[i] = transform.to_natural(parameters[i]);
+    }
+}
+
+/// Wraps the model of the natural parameters into a function of the internal ones, the jacobian
+/// is multiplied by the derivatives of the transforms.
+fn transformed_curve_function(
+    func: CurveFunctionType,
+    transforms: Vec<(usize, ParameterTransform)>,
+) -> CurveFunctionType {
+    Box::new(move |x, parameters, y, mut jacobians| {
+        let mut natural = parameters.to_vec();
+        to_natural_parameters(&transforms, &mut natural);
+        if !func(x, &natural, y, jacobians.as_deref_mut()) {
+            return false;
+        }
+        if let Some(jacobians) = jacobians {
+            for &(i, transform) in transforms.iter() {
+                if let Some(jacobian) = &mut jacobians[i] {
+                    *jacobian *= transform.derivative(parameters[i]);
+                }
+            }
+        }
+        true
+    })
+}
+
+/// Same as [transformed_curve_function] for [VectorizedCurveFunctionType].
+fn transformed_vectorized_curve_function(
+    func: VectorizedCurveFunctionType,
+    transforms: Vec<(usize, ParameterTransform)>,
+) -> VectorizedCurveFunctionType {
+    Box::new(move |x, parameters, y, mut jacobians| {
+        let mut natural = parameters.to_vec();
+        to_natural_parameters(&transforms, &mut natural);
+        if !func(x, &natural, y, jacobians.as_deref_mut()) {
+            return false;
+        }
+        if let Some(jacobians) = jacobians {
+            for &(i, transform) in transforms.iter() {
+                if let Some(column) = &mut jacobians[i] {
+                    let derivative = transform.derivative(parameters[i]);
+                    column.iter_mut().for_each(|d| *d *= derivative);
+                }
+            }
+        }
+        true
+    })
+}
+
+/// Data values of [CurveFitProblem1D], either borrowed or shared, see
+/// [CurveFitProblem1DBuilder].
+///
+/// A problem built from [CurveData::Shared] values only doesn't borrow anything, so it is
+/// `CurveFitProblem1D<'static>` and can be stored in a struct. The problem itself is not [Send],
+/// but the shared values are, so they can be moved to another thread to build the problem there.
+///
+/// [f32] values, e.g. sensor readings, are converted to [f64] on creation, as well as the items of
+/// any iterator of `Into<f64>` values collected into [CurveData]:
+///
+/// ```rust
+/// use ceres_solver::curve_fit::CurveData;
+///
+/// let x: &[f32] = &[0.5, 1.5];
+/// let data: CurveData = x.into();
+/// assert_eq!(&data[..], &[0.5, 1.5]);
+/// let data: CurveData = [1_u16, 2, 3].into_iter().collect();
+/// assert_eq!(&data[..], &[1.0, 2.0, 3.0]);
+/// ```
+#[derive(Clone, Debug)]
+pub enum CurveData<'a> {
+    /// Borrowed values, the problem cannot outlive them.
+    Borrowed(&'a [f64]),
+    /// Shared owned values.
+    Shared(Arc<[f64]>),
+}
+
+impl Deref for CurveData<'_> {
+    type Target = [f64];
+
+    fn deref(&self) -> &[f64] {
+        match self {
+            Self::Borrowed(values) => values,
+            Self::Shared(values) => values,
+        }
+    }
+}
+
+impl<'a> From<&'a [f64]> for CurveData<'a> {
+    fn from(values: &'a [f64]) -> Self {
+        Self::Borrowed(values)
+    }
+}
+
+impl<'a, const N: usize> From<&'a [f64; N]> for CurveData<'a> {
+    fn from(values: &'a [f64; N]) -> Self {
+        Self::Borrowed(values)
+    }
+}
+
+impl<'a> From<&'a Vec<f64>> for CurveData<'a> {
+    fn from(values: &'a Vec<f64>) -> Self {
+        Self::Borrowed(values)
+    }
+}
+
+impl From<Vec<f64>> for CurveData<'_> {
+    fn from(values: Vec<f64>) -> Self {
+        Self::Shared(values.into())
+    }
+}
+
+impl From<Arc<[f64]>> for CurveData<'_> {
+    fn from(values: Arc<[f64]>) -> Self {
+        Self::Shared(values)
+    }
+}
+
+impl From<&[f32]> for CurveData<'_> {
+    fn from(values: &[f32]) -> Self {
+        values.iter().copied().collect()
+    }
+}
+
+impl<const N: usize> From<&[f32; N]> for CurveData<'_> {
+    fn from(values: &[f32; N]) -> Self {
+        values.iter().copied().collect()
+    }
+}
+
+impl From<&Vec<f32>> for CurveData<'_> {
+    fn from(values: &Vec<f32>) -> Self {
+        values.iter().copied().collect()
+    }
+}
+
+impl From<Vec<f32>> for CurveData<'_> {
+    fn from(values: Vec<f32>) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+impl<T: Into<f64>> FromIterator<T> for CurveData<'_> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::Shared(iter.into_iter().map(Into::into).collect())
+    }
+}
+
 /// A wrapper for [NllsProblem] providing easier interface to solve an 1-D muliparameter curve fit
 /// problem. Use it in two steps: create a new instance with [CurveFitProblem1D::new] or
 /// [CurveFitProblem1D::builder] and then call a destructive method [CurveFitProblem1D::solve]
 /// to get a solution.
-pub struct CurveFitProblem1D<'cost>(NllsProblem<'cost>);
+pub struct CurveFitProblem1D<'cost> {
+    problem: NllsProblem<'cost>,
+    y: CurveData<'cost>,
+    inverse_error: Option<CurveData<'cost>>,
+    /// Whether the problem has latent x parameters, see [CurveFitProblem1DBuilder::x_inverse_error].
+    latent_x: bool,
+    whitening: Option<Whitening>,
+    /// Transformed parameters, the problem works with the internal values.
+    transforms: Vec<(usize, ParameterTransform)>,
+}
 
 impl<'cost> CurveFitProblem1D<'cost> {
     /// Creates a new instance of the `CurveFitProblem1D`. If you need more control over the problem
@@ -56,11 +316,184 @@ impl<'cost> CurveFitProblem1D<'cost> {
         let nlls_parameters: Vec<_> = parameters.iter().map(|&x| vec![x]).collect();
         let (problem, _block_id) = NllsProblem::new()
             .residual_block_builder()
-            .set_cost(Self::cost_function(x, y, None, func.into()), x.len())
+            .set_cost(
+                Self::cost_function(x.into(), y.into(), None, func.into()),
+                x.len(),
+            )
+            .set_parameters(nlls_parameters)
+            .build_into_problem()
+            .unwrap();
+        Self {
+            problem,
+            y: y.into(),
+            inverse_error: None,
+            latent_x: false,
+            whitening: None,
+            transforms: Vec::new(),
+        }
+    }
+
+    /// Creates a new instance of the `CurveFitProblem1D` with a model function called once for all
+    /// the data points, which allows vectorizing it and avoids a function call per point.
+    ///
+    /// # Arguments
+    /// - func - a function describing a curve. It must return [false] if it cannot calculate
+    ///   Jacobian, or [true] otherwise. It accepts the following parameters:
+    ///   - x - independent coordinates of all the data points.
+    ///   - parameters - a slice for the current value of the problem parameters.
+    ///   - y - a mutable slice to output the function values, it has the same length as `x`.
+    ///   - jacobians - an output Jacobian matrix, it (or any of its component) can be [None], which
+    ///     means that the solver doesn't need it. Otherwise the top-level slice has length of
+    ///     `parameters.len()`, and every inner slice is a column of derivatives over the parameter,
+    ///     which has the same length as `x`.
+    /// - x, y, parameters - the same as for [CurveFitProblem1D::new].
+    ///
+    /// The slices point directly to the solver buffers, so no data is copied.
+    ///
+    /// ```rust
+    /// use ceres_solver::curve_fit::{CurveFitProblem1D, VectorizedCurveFunctionType};
+    /// use ceres_solver::SolverOptions;
+    ///
+    /// // y = a * x + b
+    /// let func: VectorizedCurveFunctionType = Box::new(|x, parameters, y, jacobians| {
+    ///     let (a, b) = (parameters[0], parameters[1]);
+    ///     for (y, &x) in y.iter_mut().zip(x) {
+    ///         *y = a * x + b;
+    ///     }
+    ///     if let Some(jacobians) = jacobians {
+    ///         if let Some(d_da) = &mut jacobians[0] {
+    ///             d_da.copy_from_slice(x);
+    ///         }
+    ///         if let Some(d_db) = &mut jacobians[1] {
+    ///             d_db.fill(1.0);
+    ///         }
+    ///     }
+    ///     true
+    /// });
+    /// let x: Vec<_> = (0..100).map(|i| i as f64).collect();
+    /// let y: Vec<_> = x.iter().map(|&x| 3.0 * x - 2.0).collect();
+    /// let solution =
+    ///     CurveFitProblem1D::new_vectorized(func, &x, &y, &[1.0, 0.0]).solve(&SolverOptions::default());
+    /// assert!(f64::abs(solution.parameters[0] - 3.0) < 1e-8);
+    /// assert!(f64::abs(solution.parameters[1] + 2.0) < 1e-8);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `x` and `y` have different sizes.
+    pub fn new_vectorized(
+        func: impl Into<VectorizedCurveFunctionType>,
+        x: &'cost [f64],
+        y: &'cost [f64],
+        parameters: &[f64],
+    ) -> Self {
+        assert_eq!(x.len(), y.len());
+        let nlls_parameters: Vec<_> = parameters.iter().map(|&x| vec![x]).collect();
+        let (problem, _block_id) = NllsProblem::new()
+            .residual_block_builder()
+            .set_pending_cost(Self::vectorized_cost_function(
+                x.into(),
+                y.into(),
+                None,
+                func.into(),
+                parameters.len(),
+            ))
             .set_parameters(nlls_parameters)
             .build_into_problem()
             .unwrap();
-        Self(problem)
+        Self {
+            problem,
+            y: y.into(),
+            inverse_error: None,
+            latent_x: false,
+            whitening: None,
+            transforms: Vec::new(),
+        }
+    }
+
+    /// Creates a new instance of the `CurveFitProblem1D` with a model function which computes the
+    /// value only, so no derivatives need to be written. The jacobian is computed with central
+    /// finite differences, which costs two extra model calls per parameter, use
+    /// [CurveFitProblem1DBuilder::relative_step_size] to change the step.
+    ///
+    /// # Arguments
+    /// - func - a function describing a curve. It must return [false] if it cannot calculate the
+    ///   value, or [true] otherwise. It accepts `x`, a slice of the current parameter values and a
+    ///   mutable reference to output the function value.
+    /// - x, y, parameters - the same as for [CurveFitProblem1D::new].
+    ///
+    /// ```rust
+    /// use ceres_solver::curve_fit::{CurveFitProblem1D, NumericCurveFunctionType};
+    /// use ceres_solver::SolverOptions;
+    ///
+    /// // y = a * exp(-x / tau)
+    /// let func: NumericCurveFunctionType = Box::new(|x, parameters, y| {
+    ///     *y = parameters[0] * f64::exp(-x / parameters[1]);
+    ///     true
+    /// });
+    /// let x: Vec<_> = (0..100).map(|i| i as f64 * 0.1).collect();
+    /// let y: Vec<_> = x.iter().map(|&x| 5.0 * f64::exp(-x / 2.0)).collect();
+    /// let solution =
+    ///     CurveFitProblem1D::new_numeric(func, &x, &y, &[1.0, 1.0]).solve(&SolverOptions::default());
+    /// assert!(f64::abs(solution.parameters[0] - 5.0) < 1e-6);
+    /// assert!(f64::abs(solution.parameters[1] - 2.0) < 1e-6);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `x` and `y` have different sizes.
+    pub fn new_numeric(
+        func: impl Into<NumericCurveFunctionType>,
+        x: &'cost [f64],
+        y: &'cost [f64],
+        parameters: &[f64],
+    ) -> Self {
+        Self::new(
+            numeric_curve_function(func.into(), DEFAULT_RELATIVE_STEP_SIZE),
+            x,
+            y,
+            parameters,
+        )
+    }
+
+    /// Creates a new instance of the `CurveFitProblem1D` with a model generic over [Scalar], the
+    /// jacobian is found exactly with automatic differentiation.
+    ///
+    /// # Arguments
+    /// - model - a curve model, see [AutoDiffCurveModel].
+    /// - x, y, parameters - the same as for [CurveFitProblem1D::new].
+    ///
+    /// ```rust
+    /// use ceres_solver::autodiff::Scalar;
+    /// use ceres_solver::curve_fit::{AutoDiffCurveModel, CurveFitProblem1D};
+    /// use ceres_solver::SolverOptions;
+    /// // Math functions of Scalar
+    /// use num_traits::Float;
+    ///
+    /// // y = a * exp(-x / tau)
+    /// struct Decay;
+    ///
+    /// impl AutoDiffCurveModel for Decay {
+    ///     fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+    ///         parameters[0] * (-x / parameters[1]).exp()
+    ///     }
+    /// }
+    ///
+    /// let x: Vec<_> = (0..100).map(|i| i as f64 * 0.1).collect();
+    /// let y: Vec<_> = x.iter().map(|&x| 5.0 * f64::exp(-x / 2.0)).collect();
+    /// let solution =
+    ///     CurveFitProblem1D::new_autodiff(Decay, &x, &y, &[1.0, 1.0]).solve(&SolverOptions::default());
+    /// assert!(f64::abs(solution.parameters[0] - 5.0) < 1e-8);
+    /// assert!(f64::abs(solution.parameters[1] - 2.0) < 1e-8);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `x` and `y` have different sizes.
+    pub fn new_autodiff(
+        model: impl AutoDiffCurveModel + 'static,
+        x: &'cost [f64],
+        y: &'cost [f64],
+        parameters: &[f64],
+    ) -> Self {
+        Self::new(autodiff_curve_function(model), x, y, parameters)
     }
 
     /// Create a [CurveFitProblem1DBuilder] instance, see its docs for the details.
@@ -68,10 +501,10 @@ impl<'cost> CurveFitProblem1D<'cost> {
         CurveFitProblem1DBuilder::new()
     }
 
-    fn cost_function(
-        x: &'cost [f64],
-        y: &'cost [f64],
-        inv_err: Option<&'cost [f64]>,
+    pub(crate) fn cost_function(
+        x: CurveData<'cost>,
+        y: CurveData<'cost>,
+        inv_err: Option<CurveData<'cost>>,
         curve_func: CurveFunctionType,
     ) -> CostFunctionType<'cost> {
         let n_obs = x.len();
@@ -88,7 +521,7 @@ impl<'cost> CurveFitProblem1D<'cost> {
             for ((((i, &x), &y), &inv_err), residual) in (0..n_obs)
                 .zip(x.iter())
                 .zip(y.iter())
-                .zip(match inv_err {
+                .zip(match inv_err.as_deref() {
                     Some(inv_err) => Either::Left(inv_err.iter()),
                     None => Either::Right(std::iter::repeat(&1.0)),
                 })
@@ -108,19 +541,532 @@ impl<'cost> CurveFitProblem1D<'cost> {
         })
     }
 
+    fn vectorized_cost_function(
+        x: CurveData<'cost>,
+        y: CurveData<'cost>,
+        inv_err: Option<CurveData<'cost>>,
+        curve_func: VectorizedCurveFunctionType,
+        num_parameters: usize,
+    ) -> PendingCostFunction<'cost> {
+        let n_obs = x.len();
+        let func: RawCostFunctionType<'cost> =
+            Box::new(move |parameters_ptr, residuals_ptr, jacobians_ptr| {
+                // Every parameter block has a single component, and the jacobian of every block
+                // is a contiguous column of n_obs values.
+                let parameters: Vec<_> = (0..num_parameters)
+                    .map(|i| unsafe { **parameters_ptr.add(i) })
+                    .collect();
+                let residuals = unsafe { slice::from_raw_parts_mut(residuals_ptr, n_obs) };
+                let mut jacobians: Option<Vec<_>> = (!jacobians_ptr.is_null()).then(|| {
+                    (0..num_parameters)
+                        .map(|i| {
+                            let column = unsafe { *jacobians_ptr.add(i) };
+                            (!column.is_null())
+                                .then(|| unsafe { slice::from_raw_parts_mut(column, n_obs) })
+                        })
+                        .collect()
+                });
+                if !curve_func(&x, &parameters, residuals, jacobians.as_deref_mut()) {
+                    return false;
+                }
+                let weights = || match inv_err.as_deref() {
+                    Some(inv_err) => Either::Left(inv_err.iter().copied()),
+                    None => Either::Right(std::iter::repeat(1.0)),
+                };
+                for ((residual, &y), w) in residuals.iter_mut().zip(y.iter()).zip(weights()) {
+                    *residual = w * (y - *residual);
+                }
+                for column in jacobians.iter_mut().flatten().flatten() {
+                    for (d, w) in column.iter_mut().zip(weights()) {
+                        *d *= -w;
+                    }
+                }
+                true
+            });
+        PendingCostFunction::from_raw(func, vec![1; num_parameters], n_obs)
+    }
+
     /// Solves the problem and returns a solution for the parameters.
-    pub fn solve(self, options: &SolverOptions) -> CurveFitProblemSolution {
-        // We know that we have well-defined problem, so we can unwrap
-        let NllsProblemSolution {
-            parameters: nlls_parameters,
-            summary,
-        } = self.0.solve(options).unwrap();
-        // All parameters are 1D - compress to a single vector
-        let parameters = nlls_parameters.into_iter().map(|x| x[0]).collect();
-        CurveFitProblemSolution {
-            parameters,
-            summary,
+    ///
+    /// # Panics
+    /// If the model function panics, the panic is caught during the solve and raised again
+    /// afterwards.
+    pub fn solve(mut self, options: &SolverOptions) -> CurveFitProblemSolution {
+        let mut solution = solve_problem(
+            &mut self.problem,
+            &self.y,
+            self.inverse_error.as_deref(),
+            self.whitening.as_ref(),
+            options,
+            false,
+            self.latent_x,
+        );
+        to_natural_parameters(&self.transforms, &mut solution.parameters);
+        solution
+    }
+
+    /// Solves the problem like [CurveFitProblem1D::solve] and also fills
+    /// [CurveFitProblemSolution::y_fit] and [CurveFitProblemSolution::residuals] with the model
+    /// values and the residuals at the solution, so the fit can be plotted without calling the
+    /// model function again.
+    pub fn solve_with_residuals(mut self, options: &SolverOptions) -> CurveFitProblemSolution {
+        let mut solution = solve_problem(
+            &mut self.problem,
+            &self.y,
+            self.inverse_error.as_deref(),
+            self.whitening.as_ref(),
+            options,
+            true,
+            self.latent_x,
+        );
+        to_natural_parameters(&self.transforms, &mut solution.parameters);
+        solution
+    }
+
+    /// Solves the problem from many initial guesses and returns the best solution, see
+    /// [multistart](crate::multistart) module docs.
+    ///
+    /// The first start is the initial guess of the problem, followed by `n_starts` guesses
+    /// sampled with `strategy` within the parameter bounds. Only the non-constant parameters
+    /// having both lower and upper bounds are sampled, the others start from their initial
+    /// values. The starts are solved one after another, because the model function is not
+    /// required to be [Send], see `multistart::solve_multistart_parallel` with `rayon` Cargo
+    /// feature for the parallel version. The best solution is polished by one more solver run
+    /// from the best parameters, so [CurveFitProblemSolution::summary] describes that run.
+    ///
+    /// # Panics
+    /// If [MultiStartStrategy::Sobol] is used for more than
+    /// [SOBOL_MAX_DIMENSION](crate::multistart::SOBOL_MAX_DIMENSION) sampled parameters.
+    pub fn solve_multistart(
+        mut self,
+        options: &SolverOptions,
+        n_starts: usize,
+        strategy: MultiStartStrategy,
+    ) -> MultiStartSolution {
+        let initial_values = self.parameter_values();
+        let initial_parameters = self.multistart_initial_parameters(n_starts, strategy);
+        let mut final_costs: Vec<f64> = Vec::with_capacity(initial_parameters.len());
+        let mut best_start = 0;
+        let mut best_values = initial_values.clone();
+        for (start, parameters) in initial_parameters.iter().enumerate() {
+            let cost = self.solve_start(&initial_values, parameters, options);
+            if start == 0 || cost < final_costs[best_start] {
+                best_start = start;
+                best_values = self.parameter_values();
+            }
+            final_costs.push(cost);
+        }
+        self.finish_multistart(
+            options,
+            initial_parameters,
+            final_costs,
+            best_start,
+            &best_values,
+        )
+    }
+
+    /// Values of all the parameter blocks, including the latent x ones.
+    pub(crate) fn parameter_values(&self) -> Vec<Vec<f64>> {
+        self.problem
+            .parameter_blocks()
+            .iter()
+            .map(|block| block.values().to_vec())
+            .collect()
+    }
+
+    /// Initial guess of the problem followed by `n_starts` guesses sampled within the bounds,
+    /// the model parameters only.
+    pub(crate) fn multistart_initial_parameters(
+        &self,
+        n_starts: usize,
+        strategy: MultiStartStrategy,
+    ) -> Vec<Vec<f64>> {
+        let problem = &self.problem;
+        let num_model_parameters =
+            problem.parameter_blocks().len() - if self.latent_x { self.y.len() } else { 0 };
+        let initial_guess: Vec<f64> = problem.parameter_blocks()[..num_model_parameters]
+            .iter()
+            .map(|block| block.values()[0])
+            .collect();
+        // We know that all the parameter blocks exist and have a single component
+        let ranges: Vec<_> = (0..num_model_parameters)
+            .filter(|&i| !problem.is_parameter_block_constant(i).unwrap())
+            .filter_map(|i| {
+                match (
+                    problem.parameter_lower_bound(i, 0).unwrap(),
+                    problem.parameter_upper_bound(i, 0).unwrap(),
+                ) {
+                    (Some(lower), Some(upper)) => Some((i, lower, upper)),
+                    _ => None,
+                }
+            })
+            .collect();
+        let samples = unit_samples(strategy, ranges.len(), n_starts);
+        iter::once(initial_guess.clone())
+            .chain(samples.into_iter().map(|sample| {
+                let mut parameters = initial_guess.clone();
+                for (&(i, lower, upper), u) in ranges.iter().zip(sample) {
+                    parameters[i] = lower + (upper - lower) * u;
+                }
+                parameters
+            }))
+            .collect()
+    }
+
+    /// Solves the problem from the model `parameters`, the latent x values start from
+    /// `initial_values`. Returns the final cost, infinite if the solver fails.
+    pub(crate) fn solve_start(
+        &mut self,
+        initial_values: &[Vec<f64>],
+        parameters: &[f64],
+        options: &SolverOptions,
+    ) -> f64 {
+        for (i, values) in initial_values.iter().enumerate() {
+            let values = parameters.get(i).map_or(&values[..], slice::from_ref);
+            self.problem.set_parameter_block_values(i, values).unwrap();
+        }
+        let cost = self
+            .problem
+            .solve_mut(options)
+            .map_or(f64::NAN, |summary| summary.final_cost());
+        if cost.is_nan() {
+            f64::INFINITY
+        } else {
+            cost
+        }
+    }
+
+    /// Polishes the best start and converts the parameters to their natural values.
+    pub(crate) fn finish_multistart(
+        mut self,
+        options: &SolverOptions,
+        mut initial_parameters: Vec<Vec<f64>>,
+        final_costs: Vec<f64>,
+        best_start: usize,
+        best_values: &[Vec<f64>],
+    ) -> MultiStartSolution {
+        for (i, values) in best_values.iter().enumerate() {
+            self.problem.set_parameter_block_values(i, values).unwrap();
+        }
+        let mut best = solve_problem(
+            &mut self.problem,
+            &self.y,
+            self.inverse_error.as_deref(),
+            self.whitening.as_ref(),
+            options,
+            false,
+            self.latent_x,
+        );
+        to_natural_parameters(&self.transforms, &mut best.parameters);
+        for parameters in initial_parameters.iter_mut() {
+            to_natural_parameters(&self.transforms, parameters);
+        }
+        MultiStartSolution {
+            best,
+            best_start,
+            initial_parameters,
+            final_costs,
+        }
+    }
+}
+
+/// Wraps a model computing the value only into [CurveFunctionType] computing the derivatives with
+/// central finite differences. The step is the parameter value multiplied by the relative step
+/// size, or the relative step size itself for zero parameter values.
+pub(crate) fn numeric_curve_function(
+    func: NumericCurveFunctionType,
+    relative_step_size: f64,
+) -> CurveFunctionType {
+    Box::new(move |x, parameters, y, jacobians| {
+        if !func(x, parameters, y) {
+            return false;
+        }
+        let Some(jacobians) = jacobians else {
+            return true;
+        };
+        let mut shifted = parameters.to_vec();
+        for (i, jacobian) in jacobians.iter_mut().enumerate() {
+            let Some(jacobian) = jacobian else {
+                continue;
+            };
+            let value = parameters[i];
+            let step = if value == 0.0 {
+                relative_step_size
+            } else {
+                value.abs() * relative_step_size
+            };
+            let (mut y_plus, mut y_minus) = (0.0, 0.0);
+            shifted[i] = value + step;
+            if !func(x, &shifted, &mut y_plus) {
+                return false;
+            }
+            shifted[i] = value - step;
+            if !func(x, &shifted, &mut y_minus) {
+                return false;
+            }
+            shifted[i] = value;
+            *jacobian = (y_plus - y_minus) / (2.0 * step);
+        }
+        true
+    })
+}
+
+/// Wraps a model generic over [Scalar] into [CurveFunctionType], the requested derivatives are
+/// found with [Jet] in passes of [AUTODIFF_STRIDE] parameters.
+pub(crate) fn autodiff_curve_function(
+    model: impl AutoDiffCurveModel + 'static,
+) -> CurveFunctionType {
+    Box::new(move |x, parameters, y, jacobians| {
+        let variables: Vec<usize> = match &jacobians {
+            Some(jacobians) => jacobians
+                .iter()
+                .enumerate()
+                .filter_map(|(i, jacobian)| jacobian.is_some().then_some(i))
+                .collect(),
+            None => Vec::new(),
+        };
+        let (Some(jacobians), false) = (jacobians, variables.is_empty()) else {
+            *y = model.model(x, parameters);
+            return true;
+        };
+        let mut jet_parameters: Vec<Jet<AUTODIFF_STRIDE>> = parameters
+            .iter()
+            .map(|&value| Jet::constant(value))
+            .collect();
+        for chunk in variables.chunks(AUTODIFF_STRIDE) {
+            for (k, &i) in chunk.iter().enumerate() {
+                jet_parameters[i] = Jet::variable(parameters[i], k);
+            }
+            let jet_y = model.model(Jet::constant(x), &jet_parameters);
+            *y = jet_y.a;
+            for (k, &i) in chunk.iter().enumerate() {
+                if let Some(jacobian) = &mut jacobians[i] {
+                    *jacobian = jet_y.v[k];
+                }
+                jet_parameters[i] = Jet::constant(parameters[i]);
+            }
+        }
+        true
+    })
+}
+
+/// Problem with a residual block per data point for errors-in-x fitting, see
+/// [CurveFitProblem1DBuilder::x_inverse_error]. The first parameter blocks are the model
+/// parameters, followed by the latent x value of every data point.
+fn errors_in_x_problem<'cost>(
+    func: CurveFunctionType,
+    (x, y): (&[f64], &[f64]),
+    (x_inverse_error, y_inverse_error): (&[f64], Option<&[f64]>),
+    parameters: Vec<ParameterBlock>,
+    loss: Option<LossFunction>,
+    relative_step_size: f64,
+) -> NllsProblem<'cost> {
+    let func = Rc::new(func);
+    let num_parameters = parameters.len();
+    let loss = loss.map(|loss| LossFunctionWrapper::new(Some(loss)));
+    let mut new_parameters = Some(parameters);
+    let mut problem = NllsProblem::new();
+    for (i, (&x, &y)) in x.iter().zip(y).enumerate() {
+        // The model parameter blocks are added with the first residual block and reused later
+        let mut block_parameters: Vec<ParameterBlockOrIndex> = match new_parameters.take() {
+            Some(parameters) => parameters.into_iter().map(Into::into).collect(),
+            None => (0..num_parameters).map(Into::into).collect(),
+        };
+        // Latent x starts from the measured value
+        block_parameters.push(vec![x].into());
+        let mut residual_block = problem.residual_block_builder().set_cost(
+            errors_in_x_cost_function(
+                (x, y),
+                (
+                    x_inverse_error[i],
+                    y_inverse_error.map_or(1.0, |inverse_error| inverse_error[i]),
+                ),
+                Rc::clone(&func),
+                num_parameters,
+                relative_step_size,
+            ),
+            2,
+        );
+        if let Some(loss) = &loss {
+            residual_block = residual_block.set_loss_wrapper(loss.clone());
         }
+        problem = residual_block
+            .set_parameters(block_parameters)
+            .build_into_problem()
+            .unwrap()
+            .0;
+    }
+    problem
+}
+
+/// Cost function of a data point for errors-in-x fitting, the residuals are
+/// `(y - model(x_latent)) * y_inverse_error` and `(x - x_latent) * x_inverse_error`. The derivative
+/// of the model over `x_latent` is found with central finite differences.
+fn errors_in_x_cost_function(
+    (x, y): (f64, f64),
+    (x_weight, y_weight): (f64, f64),
+    func: Rc<CurveFunctionType>,
+    num_parameters: usize,
+    relative_step_size: f64,
+) -> CostFunctionType<'static> {
+    Box::new(move |parameters, residuals, jacobians| {
+        let model_parameters: Vec<_> = parameters[..num_parameters].iter().map(|p| p[0]).collect();
+        let latent_x = parameters[num_parameters][0];
+        let mut model_jacobians: Option<Vec<Option<f64>>> = jacobians.as_ref().map(|jacobians| {
+            jacobians[..num_parameters]
+                .iter()
+                .map(|jacobian| jacobian.as_ref().map(|_| 0.0))
+                .collect()
+        });
+        let mut value = 0.0;
+        if !func(
+            latent_x,
+            &model_parameters,
+            &mut value,
+            model_jacobians.as_deref_mut(),
+        ) {
+            return false;
+        }
+        residuals[0] = y_weight * (y - value);
+        residuals[1] = x_weight * (x - latent_x);
+        let (Some(jacobians), Some(model_jacobians)) = (jacobians, model_jacobians) else {
+            return true;
+        };
+        let (jacobians, latent_jacobian) = jacobians.split_at_mut(num_parameters);
+        for (d_out, d_in) in jacobians.iter_mut().zip(model_jacobians) {
+            if let (Some(d_out), Some(d_in)) = (d_out, d_in) {
+                d_out[0][0] = -y_weight * d_in;
+                d_out[1][0] = 0.0;
+            }
+        }
+        if let Some(d_out) = &mut latent_jacobian[0] {
+            let step = if latent_x == 0.0 {
+                relative_step_size
+            } else {
+                latent_x.abs() * relative_step_size
+            };
+            let (mut y_plus, mut y_minus) = (0.0, 0.0);
+            if !func(latent_x + step, &model_parameters, &mut y_plus, None)
+                || !func(latent_x - step, &model_parameters, &mut y_minus, None)
+            {
+                return false;
+            }
+            d_out[0][0] = -y_weight * (y_plus - y_minus) / (2.0 * step);
+            d_out[1][0] = -x_weight;
+        }
+        true
+    })
+}
+
+/// Solves a fit problem having a residual `(data - model) * inverse_error` per data point.
+pub(crate) fn solve_fit_problem(
+    mut problem: NllsProblem,
+    data: &[f64],
+    inverse_error: Option<&[f64]>,
+    options: &SolverOptions,
+    with_residuals: bool,
+) -> CurveFitProblemSolution {
+    solve_problem(
+        &mut problem,
+        data,
+        inverse_error,
+        None,
+        options,
+        with_residuals,
+        false,
+    )
+}
+
+/// Solves a fit problem like [solve_fit_problem], if `latent_x` is [true] the residuals of every
+/// data point are followed by the x residual, and the parameter blocks of the model parameters are
+/// followed by the latent x values, see [errors_in_x_problem]. The residuals are whitened with
+/// `whitening` instead of `inverse_error` if it is given. The problem is solved in place, so it
+/// may be solved again, see [CurveFitTemplate].
+pub(crate) fn solve_problem(
+    problem: &mut NllsProblem,
+    data: &[f64],
+    inverse_error: Option<&[f64]>,
+    whitening: Option<&Whitening>,
+    options: &SolverOptions,
+    with_residuals: bool,
+    latent_x: bool,
+) -> CurveFitProblemSolution {
+    let num_data = problem.num_residuals();
+    let num_varying = problem.num_varying_parameters();
+    // The solution has no error state, so a panic of the model function caught by the problem is
+    // raised again. Other errors are impossible, because the problem always has residual blocks.
+    let summary = problem
+        .solve_mut(options)
+        .unwrap_or_else(|error| panic!("Curve fit problem cannot be solved: {error}"));
+    let weighted_residuals = if with_residuals {
+        problem
+            .evaluate(&EvaluateOptions {
+                apply_loss_function: false,
+                ..Default::default()
+            })
+            .ok()
+            .map(|evaluation| {
+                if latent_x {
+                    evaluation.residuals.into_iter().step_by(2).collect()
+                } else {
+                    evaluation.residuals
+                }
+            })
+    } else {
+        None
+    };
+    let residuals: Option<Vec<_>> =
+        weighted_residuals.and_then(|residuals| match (whitening, inverse_error) {
+            (Some(whitening), _) => whitening.unwhiten(residuals),
+            (None, Some(inverse_error)) => Some(
+                residuals
+                    .iter()
+                    .zip(inverse_error)
+                    .map(|(residual, w)| residual / w)
+                    .collect(),
+            ),
+            (None, None) => Some(residuals),
+        });
+    let y_fit = residuals.as_ref().map(|residuals| {
+        data.iter()
+            .zip(residuals)
+            .map(|(value, residual)| value - residual)
+            .collect()
+    });
+    // All parameters are 1D - compress to a single vector
+    let mut parameters: Vec<_> = problem
+        .parameter_blocks()
+        .iter()
+        .map(|block| block.values()[0])
+        .collect();
+    let x_fit = latent_x.then(|| parameters.split_off(parameters.len() - data.len()));
+    let chi2 = 2.0 * summary.final_cost();
+    let degrees_of_freedom = num_data as i64 - num_varying as i64;
+    let reduced_chi2 = if degrees_of_freedom > 0 {
+        chi2 / degrees_of_freedom as f64
+    } else {
+        f64::INFINITY
+    };
+    // -2 ln(likelihood) up to a constant for Gaussian errors: chi2 itself if the errors are
+    // given, otherwise the scale of the errors is estimated from the residuals
+    let neg2_log_likelihood = if inverse_error.is_some() || whitening.is_some() || latent_x {
+        chi2
+    } else if chi2 > 0.0 {
+        num_data as f64 * f64::ln(chi2 / num_data as f64)
+    } else {
+        f64::NAN
+    };
+    CurveFitProblemSolution {
+        parameters,
+        summary,
+        chi2,
+        reduced_chi2,
+        degrees_of_freedom,
+        aic: neg2_log_likelihood + 2.0 * num_varying as f64,
+        bic: neg2_log_likelihood + f64::ln(num_data as f64) * num_varying as f64,
+        y_fit,
+        residuals,
+        x_fit,
     }
 }
 
@@ -130,6 +1076,34 @@ pub struct CurveFitProblemSolution {
     pub parameters: Vec<f64>,
     /// Solver summary.
     pub summary: SolverSummary,
+    /// Chi-square, the doubled final cost. It is the sum of the squared residuals weighted with
+    /// the inverse errors if the loss function is trivial.
+    pub chi2: f64,
+    /// Chi-square divided by the number of degrees of freedom, infinite if the number of degrees
+    /// of freedom is not positive.
+    pub reduced_chi2: f64,
+    /// Number of data points minus the number of the non-constant parameters.
+    pub degrees_of_freedom: i64,
+    /// Akaike information criterion `chi2 + 2 k`, where `k` is the number of the non-constant
+    /// parameters. If the problem has neither inverse errors nor whitening, the errors are unknown
+    /// and `n ln(chi2 / n)` replaces `chi2`, where `n` is the number of data points, the criterion
+    /// is NaN for an exact fit in this case. Only the differences between models fitted to the
+    /// same data are meaningful, the smaller value is better.
+    pub aic: f64,
+    /// Bayesian information criterion `chi2 + k ln(n)`, `chi2` is replaced the same way as for
+    /// [CurveFitProblemSolution::aic].
+    pub bic: f64,
+    /// Model values at the data points for the solution parameters. It is [Some] only if the
+    /// problem is solved with [CurveFitProblem1D::solve_with_residuals] and the model doesn't fail
+    /// at the solution.
+    pub y_fit: Option<Vec<f64>>,
+    /// Residuals `y - y_fit` at the data points, not multiplied by the inverse errors. It is
+    /// [Some] under the same conditions as [CurveFitProblemSolution::y_fit].
+    pub residuals: Option<Vec<f64>>,
+    /// Fitted latent x values of the data points, [Some] only for the problems with x errors, see
+    /// [CurveFitProblem1DBuilder::x_inverse_error]. [CurveFitProblemSolution::y_fit] is the model
+    /// at these values. The statistics above count the x residuals and the latent values too.
+    pub x_fit: Option<Vec<f64>>,
 }
 
 /// Builder for [CurveFitProblem1D].
@@ -275,12 +1249,23 @@ pub struct CurveFitProblemSolution {
 pub struct CurveFitProblem1DBuilder<'cost, 'param> {
     /// Model function
     pub func: Option<CurveFunctionType>,
+    /// Model function evaluated for all the data points at once, an alternative to `func`
+    pub vectorized_func: Option<VectorizedCurveFunctionType>,
+    /// Model function without derivatives, an alternative to `func`
+    pub numeric_func: Option<NumericCurveFunctionType>,
+    /// Relative step size of finite differences for `numeric_func`, `1e-6` if not given
+    pub relative_step_size: Option<f64>,
     /// Independent coordinates for data
-    pub x: Option<&'cost [f64]>,
+    pub x: Option<CurveData<'cost>>,
     /// Values for data
-    pub y: Option<&'cost [f64]>,
+    pub y: Option<CurveData<'cost>>,
     /// Optional inverse errors - square root of the weight
-    pub inverse_error: Option<&'cost [f64]>,
+    pub inverse_error: Option<CurveData<'cost>>,
+    /// Optional inverse errors of x, they switch the problem to errors-in-x fitting
+    pub x_inverse_error: Option<CurveData<'cost>>,
+    /// Optional whitening of the residuals for correlated errors, an alternative to
+    /// `inverse_error`
+    pub whitening: Option<Whitening>,
     /// Initial parameters' guess
     pub parameters: Option<&'param [f64]>,
     /// Optional lower bounds for parameters
@@ -289,6 +1274,10 @@ pub struct CurveFitProblem1DBuilder<'cost, 'param> {
     pub upper_bounds: Option<&'param [Option<f64>]>,
     /// Constant parameters, they will not be optimized.
     pub constant_parameters: Option<&'param [usize]>,
+    /// Periodic parameters as pairs of the parameter index and the period.
+    pub periodic_parameters: Option<&'param [(usize, f64)]>,
+    /// Transformed parameters as pairs of the parameter index and the transform.
+    pub transforms: Option<&'param [(usize, ParameterTransform)]>,
     /// Optional loss function
     pub loss: Option<LossFunction>,
 }
@@ -297,47 +1286,199 @@ impl<'cost, 'param> CurveFitProblem1DBuilder<'cost, 'param> {
     pub fn new() -> Self {
         Self {
             func: None,
+            vectorized_func: None,
+            numeric_func: None,
+            relative_step_size: None,
             x: None,
             y: None,
             inverse_error: None,
+            x_inverse_error: None,
+            whitening: None,
             parameters: None,
             lower_bounds: None,
             upper_bounds: None,
             constant_parameters: None,
+            periodic_parameters: None,
+            transforms: None,
             loss: None,
         }
     }
 
-    /// Add model function.
+    /// Add model function, it replaces the other model functions if any.
     pub fn func(mut self, func: impl Into<CurveFunctionType>) -> Self {
         self.func = Some(func.into());
+        self.vectorized_func = None;
+        self.numeric_func = None;
         self
     }
 
-    /// Add independent parameter values for the data points.
-    pub fn x(mut self, x: &'cost [f64]) -> Self {
-        self.x = Some(x);
+    /// Add model function evaluated for all the data points at once, see
+    /// [CurveFitProblem1D::new_vectorized]. It replaces the other model functions if any.
+    pub fn vectorized_func(mut self, func: impl Into<VectorizedCurveFunctionType>) -> Self {
+        self.vectorized_func = Some(func.into());
+        self.func = None;
+        self.numeric_func = None;
         self
     }
 
-    /// Add values for the data points.
-    pub fn y(mut self, y: &'cost [f64]) -> Self {
-        self.y = Some(y);
+    /// Add model function computing the value only, differentiated with finite differences, see
+    /// [CurveFitProblem1D::new_numeric]. It replaces the other model functions if any.
+    pub fn numeric_func(mut self, func: impl Into<NumericCurveFunctionType>) -> Self {
+        self.numeric_func = Some(func.into());
+        self.func = None;
+        self.vectorized_func = None;
         self
     }
 
-    /// Add optional inverse errors for the data points. They must to be positive: think about them
-    /// as the inverse y's uncertainties, or square root of the data point weight. The residual
-    /// would be `(y - model(x)) * inverse_error`. If not given, unity valueas are assumed.
-    pub fn inverse_error(mut self, inv_err: &'cost [f64]) -> Self {
-        self.inverse_error = Some(inv_err);
+    /// Add model function generic over [Scalar] differentiated automatically, see
+    /// [CurveFitProblem1D::new_autodiff]. It replaces the other model functions if any.
+    pub fn autodiff_func(self, model: impl AutoDiffCurveModel + 'static) -> Self {
+        self.func(autodiff_curve_function(model))
+    }
+
+    /// Set the step size of finite differences relative to the parameter value, it is used for
+    /// zero parameter values as is. It is used with [CurveFitProblem1DBuilder::numeric_func] and
+    /// for the derivative over x with [CurveFitProblem1DBuilder::x_inverse_error] only, the
+    /// default value is `1e-6`.
+    pub fn relative_step_size(mut self, relative_step_size: f64) -> Self {
+        self.relative_step_size = Some(relative_step_size);
         self
     }
 
-    /// Add initial parameter guess slice, it is borrowed until [CurveFitProblem1DBuilder::build()]
-    /// call only, there it will be copied to the [CurveFitProblem1D] instance.
-    pub fn parameters(mut self, parameters: &'param [f64]) -> Self {
-        self.parameters = Some(parameters);
+    /// Add independent parameter values for the data points, either borrowed or owned, see
+    /// [CurveData].
+    pub fn x(mut self, x: impl Into<CurveData<'cost>>) -> Self {
+        self.x = Some(x.into());
+        self
+    }
+
+    /// Add values for the data points, either borrowed or owned, see [CurveData].
+    pub fn y(mut self, y: impl Into<CurveData<'cost>>) -> Self {
+        self.y = Some(y.into());
+        self
+    }
+
+    /// Add optional inverse errors for the data points. They must to be positive: think about them
+    /// as the inverse y's uncertainties, or square root of the data point weight. The residual
+    /// would be `(y - model(x)) * inverse_error`. If not given, unity valueas are assumed.
+    pub fn inverse_error(mut self, inv_err: impl Into<CurveData<'cost>>) -> Self {
+        self.inverse_error = Some(inv_err.into());
+        self
+    }
+
+    /// Add independent parameter values for the data points from an iterator, e.g. an adapter
+    /// chain, they are collected into owned [CurveData]. The values may be of any type converting
+    /// to [f64], see [CurveFitProblem1DBuilder::x].
+    ///
+    /// ```rust
+    /// use ceres_solver::curve_fit::{AutoDiffCurveModel, CurveFitProblem1D};
+    /// use ceres_solver::autodiff::Scalar;
+    /// use ceres_solver::SolverOptions;
+    ///
+    /// struct Line;
+    ///
+    /// impl AutoDiffCurveModel for Line {
+    ///     fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+    ///         parameters[0] * x + parameters[1]
+    ///     }
+    /// }
+    ///
+    /// let timestamps: [u32; 4] = [0, 10, 20, 30];
+    /// let solution = CurveFitProblem1D::builder()
+    ///     .autodiff_func(Line)
+    ///     .x_iter(timestamps)
+    ///     .y_iter(timestamps.iter().map(|&t| 0.5 * f64::from(t) + 1.0))
+    ///     .inverse_error_iter(std::iter::repeat(2.0).take(timestamps.len()))
+    ///     .parameters(&[0.0, 0.0])
+    ///     .build()
+    ///     .unwrap()
+    ///     .solve(&SolverOptions::default());
+    /// assert!(f64::abs(solution.parameters[0] - 0.5) < 1e-8);
+    /// ```
+    pub fn x_iter(self, x: impl IntoIterator<Item = impl Into<f64>>) -> Self {
+        self.x(x.into_iter().collect::<CurveData>())
+    }
+
+    /// Add values for the data points from an iterator, see [CurveFitProblem1DBuilder::x_iter].
+    pub fn y_iter(self, y: impl IntoIterator<Item = impl Into<f64>>) -> Self {
+        self.y(y.into_iter().collect::<CurveData>())
+    }
+
+    /// Add optional inverse errors for the data points from an iterator, see
+    /// [CurveFitProblem1DBuilder::inverse_error] and [CurveFitProblem1DBuilder::x_iter].
+    pub fn inverse_error_iter(self, inv_err: impl IntoIterator<Item = impl Into<f64>>) -> Self {
+        self.inverse_error(inv_err.into_iter().collect::<CurveData>())
+    }
+
+    /// Add optional inverse errors of x for the data points, which switches the problem to
+    /// errors-in-x fitting, also known as orthogonal distance regression. Ordinary least squares
+    /// assume exact x values, and x errors bias the result, e.g. flatten the slope of a line.
+    ///
+    /// Every data point gets a latent "true" x value fitted together with the model parameters,
+    /// the residuals of the point are `(y - model(x_latent)) * inverse_error` and
+    /// `(x - x_latent) * x_inverse_error`. The derivative of the model over x is found with finite
+    /// differences, see [CurveFitProblem1DBuilder::relative_step_size]. The latent values are
+    /// returned in [CurveFitProblemSolution::x_fit]. The vectorized model function is not
+    /// supported in this mode.
+    pub fn x_inverse_error(mut self, x_inv_err: impl Into<CurveData<'cost>>) -> Self {
+        self.x_inverse_error = Some(x_inv_err.into());
+        self
+    }
+
+    /// Add optional whitening of the residuals for correlated errors of y, e.g. created from their
+    /// covariance matrix with [Whitening::from_covariance], which uses a fast path for banded
+    /// matrices. The residuals would be `W (y - model(x))`, where `W` is the whitening matrix. It
+    /// cannot be used together with [CurveFitProblem1DBuilder::inverse_error] or
+    /// [CurveFitProblem1DBuilder::x_inverse_error].
+    ///
+    /// ```rust
+    /// use ceres_solver::cost::Whitening;
+    /// use ceres_solver::curve_fit::{AutoDiffCurveModel, CurveFitProblem1D};
+    /// use ceres_solver::autodiff::Scalar;
+    /// use ceres_solver::evaluation::DenseMatrix;
+    /// use ceres_solver::SolverOptions;
+    ///
+    /// // y = a * x + b
+    /// struct Line;
+    ///
+    /// impl AutoDiffCurveModel for Line {
+    ///     fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+    ///         parameters[0] * x + parameters[1]
+    ///     }
+    /// }
+    ///
+    /// let x: Vec<_> = (0..50).map(|i| i as f64).collect();
+    /// let y: Vec<_> = x.iter().map(|&x| 2.0 * x - 1.0).collect();
+    /// // Noise correlated between the neighbouring points
+    /// let mut covariance = DenseMatrix::zeros(x.len(), x.len());
+    /// for i in 0..x.len() {
+    ///     covariance.values[i * x.len() + i] = 0.01;
+    ///     if i > 0 {
+    ///         covariance.values[i * x.len() + i - 1] = 0.005;
+    ///         covariance.values[(i - 1) * x.len() + i] = 0.005;
+    ///     }
+    /// }
+    /// let solution = CurveFitProblem1D::builder()
+    ///     .autodiff_func(Line)
+    ///     .x(&x)
+    ///     .y(&y)
+    ///     .whitening(Whitening::from_covariance(&covariance).unwrap())
+    ///     .parameters(&[1.0, 0.0])
+    ///     .build()
+    ///     .unwrap()
+    ///     .solve(&SolverOptions::default());
+    /// assert!(f64::abs(solution.parameters[0] - 2.0) < 1e-8);
+    /// assert!(f64::abs(solution.parameters[1] + 1.0) < 1e-8);
+    /// ```
+    pub fn whitening(mut self, whitening: Whitening) -> Self {
+        self.whitening = Some(whitening);
+        self
+    }
+
+    /// Add initial parameter guess slice, it is borrowed until [CurveFitProblem1DBuilder::build()]
+    /// call only, there it will be copied to the [CurveFitProblem1D] instance.
+    pub fn parameters(mut self, parameters: &'param [f64]) -> Self {
+        self.parameters = Some(parameters);
         self
     }
 
@@ -361,6 +1502,52 @@ impl<'cost, 'param> CurveFitProblem1DBuilder<'cost, 'param> {
         self
     }
 
+    /// Make parameters periodic, e.g. phases, given as pairs of the parameter index and the
+    /// period. The parameter values are kept in `[0, period)`, including the initial one, see
+    /// [Manifold::periodic].
+    pub fn periodic(mut self, parameters: &'param [(usize, f64)]) -> Self {
+        self.periodic_parameters = Some(parameters);
+        self
+    }
+
+    /// Transform parameters, given as pairs of the parameter index and the transform, to keep them
+    /// inside their ranges, see [ParameterTransform]. The initial values, the bounds and the
+    /// solution are in the natural space, the bounds outside of the range are ignored. The
+    /// transformed parameters cannot be periodic.
+    ///
+    /// ```rust
+    /// use ceres_solver::curve_fit::{AutoDiffCurveModel, CurveFitProblem1D, ParameterTransform};
+    /// use ceres_solver::autodiff::Scalar;
+    /// use ceres_solver::SolverOptions;
+    ///
+    /// // y = a exp(-x / t)
+    /// struct Decay;
+    ///
+    /// impl AutoDiffCurveModel for Decay {
+    ///     fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+    ///         parameters[0] * (-x / parameters[1]).exp()
+    ///     }
+    /// }
+    ///
+    /// let x: Vec<_> = (0..50).map(|i| 0.2 * i as f64).collect();
+    /// let y: Vec<_> = x.iter().map(|&x| 5.0 * f64::exp(-x / 2.0)).collect();
+    /// let solution = CurveFitProblem1D::builder()
+    ///     .autodiff_func(Decay)
+    ///     .x(&x)
+    ///     .y(&y)
+    ///     .parameters(&[1.0, 0.1])
+    ///     .transforms(&[(0, ParameterTransform::Log), (1, ParameterTransform::Log)])
+    ///     .build()
+    ///     .unwrap()
+    ///     .solve(&SolverOptions::default());
+    /// assert!(f64::abs(solution.parameters[0] - 5.0) < 1e-6);
+    /// assert!(f64::abs(solution.parameters[1] - 2.0) < 1e-6);
+    /// ```
+    pub fn transforms(mut self, transforms: &'param [(usize, ParameterTransform)]) -> Self {
+        self.transforms = Some(transforms);
+        self
+    }
+
     /// Add optional loss function, if not given the trivial loss is assumed.
     pub fn loss(mut self, loss: LossFunction) -> Self {
         self.loss = Some(loss);
@@ -370,52 +1557,366 @@ impl<'cost, 'param> CurveFitProblem1DBuilder<'cost, 'param> {
     /// Build the [CurveFitProblem1D] instance. Returns [Err] if one of the mandatory fields is
     /// missed or data slices have inconsistent lengths.
     pub fn build(self) -> Result<CurveFitProblem1D<'cost>, CurveFitProblemBuildError> {
-        let func = self.func.ok_or(CurveFitProblemBuildError::FuncMissed)?;
+        let relative_step_size = self
+            .relative_step_size
+            .unwrap_or(DEFAULT_RELATIVE_STEP_SIZE);
+        let func = match (self.func, self.vectorized_func, self.numeric_func) {
+            (Some(func), None, None) => Either::Left(func),
+            (None, Some(func), None) => Either::Right(func),
+            (None, None, Some(func)) => {
+                Either::Left(numeric_curve_function(func, relative_step_size))
+            }
+            (None, None, None) => return Err(CurveFitProblemBuildError::FuncMissed),
+            _ => return Err(CurveFitProblemBuildError::FuncConflict),
+        };
         let x = self.x.ok_or(CurveFitProblemBuildError::XMissed)?;
         let y = self.y.ok_or(CurveFitProblemBuildError::YMissed)?;
         let n_obs = x.len();
         if n_obs != y.len() {
             return Err(CurveFitProblemBuildError::DataSizesDontMatch);
         }
-        if let Some(inverse_error) = self.inverse_error {
+        for inverse_error in [&self.inverse_error, &self.x_inverse_error]
+            .into_iter()
+            .flatten()
+        {
             if inverse_error.len() != n_obs {
                 return Err(CurveFitProblemBuildError::DataSizesDontMatch);
             }
         }
-        let mut nlls_parameters: Vec<ParameterBlock> = self
+        if let Some(whitening) = &self.whitening {
+            if self.inverse_error.is_some() || self.x_inverse_error.is_some() {
+                return Err(CurveFitProblemBuildError::WhiteningConflict);
+            }
+            if !whitening.matches(n_obs) {
+                return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+            }
+        }
+        let mut parameters = self
             .parameters
             .ok_or(CurveFitProblemBuildError::ParametersMissed)?
-            .iter()
-            .map(|&p| vec![p].into())
-            .collect();
+            .to_vec();
+        // The transform of every parameter, the problem works with the internal values
+        let mut parameter_transforms = vec![None; parameters.len()];
+        let transforms = self.transforms.unwrap_or_default();
+        for &(i_param, transform) in transforms {
+            let periodic = self
+                .periodic_parameters
+                .unwrap_or_default()
+                .iter()
+                .any(|&(i, _)| i == i_param);
+            let value = parameters.get(i_param).copied();
+            match (parameter_transforms.get(i_param), value) {
+                (Some(None), Some(value)) if transform.is_valid() && !periodic => {
+                    parameters[i_param] = transform
+                        .to_internal(value)
+                        .ok_or(CurveFitProblemBuildError::InvalidParameterTransform)?;
+                    parameter_transforms[i_param] = Some(transform);
+                }
+                _ => return Err(CurveFitProblemBuildError::InvalidParameterTransform),
+            }
+        }
+        let to_internal_bound = |i: usize, bound: f64| match parameter_transforms[i] {
+            Some(transform) => transform.to_internal(bound),
+            None => Some(bound),
+        };
+        let mut nlls_parameters: Vec<ParameterBlock> =
+            parameters.iter().map(|&p| vec![p].into()).collect();
         if let Some(lower_bounds) = self.lower_bounds {
             if lower_bounds.len() != nlls_parameters.len() {
                 return Err(CurveFitProblemBuildError::LowerBoundarySizeMismatch);
             }
             for (i, &lb) in lower_bounds.iter().enumerate() {
-                if let Some(lb) = lb {
+                if let Some(lb) = lb.and_then(|lb| to_internal_bound(i, lb)) {
                     nlls_parameters[i].set_lower_bounds(vec![Some(lb)]);
                 }
             }
         }
-        // TODO: upper bounds
-        let mut residual_block = NllsProblem::new().residual_block_builder().set_cost(
-            CurveFitProblem1D::cost_function(x, y, self.inverse_error, func),
-            n_obs,
-        );
-        if let Some(loss) = self.loss {
-            residual_block = residual_block.set_loss(loss);
+        if let Some(upper_bounds) = self.upper_bounds {
+            if upper_bounds.len() != nlls_parameters.len() {
+                return Err(CurveFitProblemBuildError::UpperBoundarySizeMismatch);
+            }
+            for (i, &ub) in upper_bounds.iter().enumerate() {
+                if let Some(ub) = ub.and_then(|ub| to_internal_bound(i, ub)) {
+                    nlls_parameters[i].set_upper_bounds(vec![Some(ub)]);
+                }
+            }
         }
-        let (mut problem, _block_id) = residual_block
-            .set_parameters(nlls_parameters)
-            .build_into_problem()
-            .unwrap();
+        let transforms = transforms.to_vec();
+        let func = match func {
+            _ if transforms.is_empty() => func,
+            Either::Left(func) => {
+                Either::Left(transformed_curve_function(func, transforms.clone()))
+            }
+            Either::Right(func) => Either::Right(transformed_vectorized_curve_function(
+                func,
+                transforms.clone(),
+            )),
+        };
+        let mut problem = if let Some(x_inverse_error) = &self.x_inverse_error {
+            let Either::Left(func) = func else {
+                return Err(CurveFitProblemBuildError::XInverseErrorWithVectorizedFunc);
+            };
+            errors_in_x_problem(
+                func,
+                (&x, &y),
+                (x_inverse_error, self.inverse_error.as_deref()),
+                nlls_parameters,
+                self.loss,
+                relative_step_size,
+            )
+        } else {
+            let residual_block = NllsProblem::new().residual_block_builder();
+            let mut residual_block = match func {
+                Either::Left(func) => residual_block.set_cost(
+                    CurveFitProblem1D::cost_function(
+                        x,
+                        y.clone(),
+                        self.inverse_error.clone(),
+                        func,
+                    ),
+                    n_obs,
+                ),
+                Either::Right(func) => {
+                    residual_block.set_pending_cost(CurveFitProblem1D::vectorized_cost_function(
+                        x,
+                        y.clone(),
+                        self.inverse_error.clone(),
+                        func,
+                        nlls_parameters.len(),
+                    ))
+                }
+            };
+            if let Some(loss) = self.loss {
+                residual_block = residual_block.set_loss(loss);
+            }
+            if let Some(whitening) = self.whitening.clone() {
+                residual_block = residual_block.set_whitening(whitening);
+            }
+            residual_block
+                .set_parameters(nlls_parameters)
+                .build_into_problem()
+                .unwrap()
+                .0
+        };
         if let Some(indexes) = self.constant_parameters {
             for &i_param in indexes {
                 problem.set_parameter_block_constant(i_param)?;
             }
         }
-        Ok(CurveFitProblem1D(problem))
+        if let Some(periodic) = self.periodic_parameters {
+            for &(i_param, period) in periodic {
+                let manifold = Manifold::periodic(period)?;
+                problem.set_parameter_block_manifold(i_param, Some(manifold))?;
+                let value = problem.parameter_blocks()[i_param].values()[0];
+                problem.set_parameter_block_values(i_param, &[value.rem_euclid(period)])?;
+            }
+        }
+        Ok(CurveFitProblem1D {
+            problem,
+            y,
+            inverse_error: self.inverse_error,
+            latent_x: self.x_inverse_error.is_some(),
+            whitening: self.whitening,
+            transforms,
+        })
+    }
+}
+
+impl CurveFitProblem1DBuilder<'_, '_> {
+    /// Builds a [CurveFitTemplate] solving the problem for new data of the same size many times,
+    /// see [template](crate::template) module docs. `x` and `y` give the size of the data, and the
+    /// template uses `options` for every fit. The vectorized model function, x errors and
+    /// whitening are not supported. Returns [Err] for the same reasons as
+    /// [CurveFitProblem1DBuilder::build].
+    pub fn build_template(
+        self,
+        options: SolverOptions,
+    ) -> Result<CurveFitTemplate, CurveFitProblemBuildError> {
+        if self.x_inverse_error.is_some() || self.whitening.is_some() {
+            return Err(CurveFitProblemBuildError::TemplateUnsupported);
+        }
+        let relative_step_size = self
+            .relative_step_size
+            .unwrap_or(DEFAULT_RELATIVE_STEP_SIZE);
+        let func = match (self.func, self.vectorized_func, self.numeric_func) {
+            (Some(func), None, None) => func,
+            (None, Some(_), None) => return Err(CurveFitProblemBuildError::TemplateUnsupported),
+            (None, None, Some(func)) => numeric_curve_function(func, relative_step_size),
+            (None, None, None) => return Err(CurveFitProblemBuildError::FuncMissed),
+            _ => return Err(CurveFitProblemBuildError::FuncConflict),
+        };
+        let x = self.x.ok_or(CurveFitProblemBuildError::XMissed)?;
+        let y = self.y.ok_or(CurveFitProblemBuildError::YMissed)?;
+        let n_obs = x.len();
+        if y.len() != n_obs
+            || self
+                .inverse_error
+                .as_ref()
+                .map_or(false, |inverse_error| inverse_error.len() != n_obs)
+        {
+            return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+        }
+        let data = Rc::new(RefCell::new(TemplateData {
+            x: x.to_vec(),
+            y: y.to_vec(),
+            inverse_error: self
+                .inverse_error
+                .map_or_else(|| vec![1.0; n_obs], |inverse_error| inverse_error.to_vec()),
+        }));
+        // The model is evaluated by the data point index, and the data is taken from the template
+        let problem = CurveFitProblem1DBuilder {
+            func: Some(template_function(Rc::clone(&data), func)),
+            vectorized_func: None,
+            numeric_func: None,
+            relative_step_size: self.relative_step_size,
+            x: Some((0..n_obs).map(|i| i as f64).collect()),
+            y: Some(vec![0.0; n_obs].into()),
+            inverse_error: None,
+            x_inverse_error: None,
+            whitening: None,
+            parameters: self.parameters,
+            lower_bounds: self.lower_bounds,
+            upper_bounds: self.upper_bounds,
+            constant_parameters: self.constant_parameters,
+            periodic_parameters: self.periodic_parameters,
+            transforms: self.transforms,
+            loss: self.loss,
+        }
+        .build()?;
+        Ok(CurveFitTemplate::new(
+            problem.problem,
+            data,
+            problem.transforms,
+            options,
+        ))
+    }
+
+    /// Fits the data rejecting the outliers by iterative sigma clipping, see
+    /// [sigma_clipping](crate::sigma_clipping) module docs. Every fit is a problem built from
+    /// this builder with the outliers removed or down-weighted, and it starts from the parameters
+    /// of the previous fit. Returns [Err] if the problem cannot be built, or if the builder has a
+    /// loss function or whitening, which cannot be combined with the outlier rejection.
+    pub fn solve_clipped(
+        self,
+        options: &SolverOptions,
+        clipping: &SigmaClipping,
+    ) -> Result<SigmaClippingSolution, CurveFitProblemBuildError> {
+        if self.loss.is_some() || self.whitening.is_some() {
+            return Err(CurveFitProblemBuildError::OutlierRejectionConflict);
+        }
+        let relative_step_size = self
+            .relative_step_size
+            .unwrap_or(DEFAULT_RELATIVE_STEP_SIZE);
+        // The model is shared by all the fits
+        let func = match (self.func, self.vectorized_func, self.numeric_func) {
+            (Some(func), None, None) => Either::Left(Rc::new(func)),
+            (None, Some(func), None) => Either::Right(Rc::new(func)),
+            (None, None, Some(func)) => {
+                Either::Left(Rc::new(numeric_curve_function(func, relative_step_size)))
+            }
+            (None, None, None) => return Err(CurveFitProblemBuildError::FuncMissed),
+            _ => return Err(CurveFitProblemBuildError::FuncConflict),
+        };
+        let x = self.x.ok_or(CurveFitProblemBuildError::XMissed)?;
+        let y = self.y.ok_or(CurveFitProblemBuildError::YMissed)?;
+        let n_obs = x.len();
+        if y.len() != n_obs
+            || [&self.inverse_error, &self.x_inverse_error]
+                .into_iter()
+                .flatten()
+                .any(|inverse_error| inverse_error.len() != n_obs)
+        {
+            return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+        }
+        let mut parameters = self
+            .parameters
+            .ok_or(CurveFitProblemBuildError::ParametersMissed)?
+            .to_vec();
+        let y_inverse_error = |i: usize| {
+            self.inverse_error
+                .as_ref()
+                .map_or(1.0, |inverse_error| inverse_error[i])
+        };
+
+        let mut outliers = vec![false; n_obs];
+        let mut weights: Vec<f64> = vec![1.0; n_obs];
+        let mut model = vec![0.0; n_obs];
+        let mut iterations = 0;
+        loop {
+            let kept: Vec<_> = (0..n_obs)
+                .filter(|&i| clipping.mode != ClippingMode::Remove || !outliers[i])
+                .collect();
+            let select = |data: &[f64]| -> CurveData { kept.iter().map(|&i| data[i]).collect() };
+            let (problem_func, vectorized_func): (Option<CurveFunctionType>, Option<_>) =
+                match &func {
+                    Either::Left(func) => {
+                        let func = Rc::clone(func);
+                        let func: CurveFunctionType =
+                            Box::new(move |x, parameters, y, jacobians| {
+                                func(x, parameters, y, jacobians)
+                            });
+                        (Some(func), None)
+                    }
+                    Either::Right(func) => {
+                        let func = Rc::clone(func);
+                        let func: VectorizedCurveFunctionType =
+                            Box::new(move |x, parameters, y, jacobians| {
+                                func(x, parameters, y, jacobians)
+                            });
+                        (None, Some(func))
+                    }
+                };
+            let solution = CurveFitProblem1DBuilder {
+                func: problem_func,
+                vectorized_func,
+                numeric_func: None,
+                relative_step_size: self.relative_step_size,
+                x: Some(select(&x)),
+                y: Some(select(&y)),
+                inverse_error: Some(
+                    kept.iter()
+                        .map(|&i| y_inverse_error(i) * weights[i].sqrt())
+                        .collect(),
+                ),
+                x_inverse_error: self.x_inverse_error.as_deref().map(select),
+                whitening: None,
+                parameters: Some(&parameters),
+                lower_bounds: self.lower_bounds,
+                upper_bounds: self.upper_bounds,
+                constant_parameters: self.constant_parameters,
+                periodic_parameters: self.periodic_parameters,
+                transforms: self.transforms,
+                loss: None,
+            }
+            .build()?
+            .solve(options);
+            iterations += 1;
+            parameters.clone_from(&solution.parameters);
+
+            // Check all the points, including the removed ones
+            let evaluated = match &func {
+                Either::Left(func) => x
+                    .iter()
+                    .zip(model.iter_mut())
+                    .all(|(&x, value)| func(x, &parameters, value, None)),
+                Either::Right(func) => func(&x, &parameters, &mut model, None),
+            };
+            let converged = evaluated && {
+                let weighted_residuals: Vec<_> = (0..n_obs)
+                    .map(|i| (y[i] - model[i]) * y_inverse_error(i))
+                    .collect();
+                clipping.update(&weighted_residuals, &mut outliers, &mut weights)
+            };
+            if converged || !evaluated || iterations >= clipping.max_iterations {
+                return Ok(SigmaClippingSolution {
+                    solution,
+                    outliers,
+                    weights,
+                    iterations,
+                    converged,
+                });
+            }
+        }
     }
 }
 
@@ -429,10 +1930,13 @@ impl Default for CurveFitProblem1DBuilder<'_, '_> {
 mod tests {
     use super::*;
 
+    use crate::error::ManifoldError;
+    use crate::evaluation::DenseMatrix;
     use crate::LossFunctionType;
 
     use approx::assert_abs_diff_eq;
     use rand::{Rng, SeedableRng};
+    use std::f64::consts::TAU;
 
     fn curve_fit_problem_1d(loss: Option<LossFunction>) -> Vec<f64> {
         let (x, y): (Vec<_>, Vec<_>) = [
@@ -606,6 +2110,7 @@ mod tests {
         let CurveFitProblemSolution {
             parameters: solution,
             summary,
+            ..
         } = problem.solve(&SolverOptions::default());
 
         assert!(summary.is_solution_usable());
@@ -689,6 +2194,7 @@ mod tests {
         let CurveFitProblemSolution {
             parameters: solution_new,
             summary: summary_new,
+            ..
         } = CurveFitProblem1D::new(func, &x, &y, &initial_guess).solve(&options);
         assert!(summary_new.is_solution_usable());
 
@@ -696,6 +2202,7 @@ mod tests {
         let CurveFitProblemSolution {
             parameters: solution_build,
             summary: summary_build,
+            ..
         } = CurveFitProblem1D::builder()
             .func(func)
             .x(&x)
@@ -710,4 +2217,820 @@ mod tests {
         assert_abs_diff_eq!(&solution_new[..], &solution_build[..], epsilon = 1e-10);
         assert_abs_diff_eq!(&TRUE_PARAM[..], &solution_new[..], epsilon = 0.02);
     }
+
+    #[test]
+    fn compare_vectorized_with_pointwise() {
+        const N: usize = 1000;
+
+        const TRUE_PARAM: [f64; 3] = [1.5, std::f64::consts::PI, -1.0];
+
+        let x: Vec<_> = (0..N).map(|i| i as f64 / N as f64).collect();
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let noise_level: f64 = 0.1;
+        let y: Vec<_> = x
+            .iter()
+            .map(|&x| {
+                let mut y = 0.0;
+                model(x, &TRUE_PARAM, &mut y, None);
+                y + noise_level * rng.sample::<f64, _>(rand_distr::StandardNormal)
+            })
+            .collect();
+        let w = vec![noise_level.powi(-1); x.len()];
+
+        let initial_guess = [0.0, 1.0, 0.0];
+        let options = SolverOptions::default();
+
+        let func: CurveFunctionType = Box::new(model);
+        let CurveFitProblemSolution {
+            parameters: solution_pointwise,
+            summary: summary_pointwise,
+            ..
+        } = CurveFitProblem1D::builder()
+            .func(func)
+            .x(&x)
+            .y(&y)
+            .inverse_error(&w)
+            .parameters(&initial_guess)
+            .build()
+            .unwrap()
+            .solve(&options);
+        assert!(summary_pointwise.is_solution_usable());
+
+        let func: VectorizedCurveFunctionType = Box::new(|x, parameters, y, mut jacobians| {
+            for (i, (&x, y)) in x.iter().zip(y.iter_mut()).enumerate() {
+                let mut point_jacobians = [None; 3];
+                if let Some(jacobians) = &jacobians {
+                    for (point, column) in point_jacobians.iter_mut().zip(jacobians.iter()) {
+                        *point = column.as_ref().map(|_| 0.0);
+                    }
+                }
+                let need_jacobians = jacobians.is_some();
+                if !model(
+                    x,
+                    parameters,
+                    y,
+                    need_jacobians.then_some(&mut point_jacobians[..]),
+                ) {
+                    return false;
+                }
+                if let Some(jacobians) = &mut jacobians {
+                    for (column, point) in jacobians.iter_mut().zip(point_jacobians) {
+                        if let (Some(column), Some(point)) = (column, point) {
+                            column[i] = point;
+                        }
+                    }
+                }
+            }
+            true
+        });
+        let CurveFitProblemSolution {
+            parameters: solution_vectorized,
+            summary: summary_vectorized,
+            ..
+        } = CurveFitProblem1D::builder()
+            .vectorized_func(func)
+            .x(&x)
+            .y(&y)
+            .inverse_error(&w)
+            .parameters(&initial_guess)
+            .build()
+            .unwrap()
+            .solve(&options);
+        assert!(summary_vectorized.is_solution_usable());
+
+        assert_abs_diff_eq!(
+            &solution_pointwise[..],
+            &solution_vectorized[..],
+            epsilon = 1e-10
+        );
+    }
+
+    #[test]
+    fn upper_bounds_only() {
+        fn line(
+            x: f64,
+            parameters: &[f64],
+            y: &mut f64,
+            jacobians: Option<&mut [Option<f64>]>,
+        ) -> bool {
+            *y = parameters[0] * x + parameters[1];
+            if let Some(jacobians) = jacobians {
+                if let Some(d_da) = &mut jacobians[0] {
+                    *d_da = x;
+                }
+                if let Some(d_db) = &mut jacobians[1] {
+                    *d_db = 1.0;
+                }
+            }
+            true
+        }
+        let x = [0.0, 1.0, 2.0, 3.0];
+        let y: Vec<_> = x.iter().map(|&x| 2.0 * x + 1.0).collect();
+
+        let func: CurveFunctionType = Box::new(line);
+        let solution = CurveFitProblem1D::builder()
+            .func(func)
+            .x(&x)
+            .y(&y)
+            .parameters(&[0.0, 0.0])
+            .upper_bounds(&[Some(1.5), None])
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        assert!(solution.summary.is_solution_usable());
+        // The unconstrained slope is 2, so the bound is active
+        assert!(solution.parameters[0] <= 1.5);
+        assert_abs_diff_eq!(solution.parameters[0], 1.5, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn numeric_derivatives() {
+        const N: usize = 1000;
+
+        const TRUE_PARAM: [f64; 3] = [1.5, std::f64::consts::PI, -1.0];
+
+        let x: Vec<_> = (0..N).map(|i| i as f64 / N as f64).collect();
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let noise_level: f64 = 0.1;
+        let y: Vec<_> = x
+            .iter()
+            .map(|&x| {
+                let mut y = 0.0;
+                model(x, &TRUE_PARAM, &mut y, None);
+                y + noise_level * rng.sample::<f64, _>(rand_distr::StandardNormal)
+            })
+            .collect();
+
+        let initial_guess = [0.0, 1.0, 0.0];
+        let options = SolverOptions::default();
+
+        let func: NumericCurveFunctionType =
+            Box::new(|x, parameters, y| model(x, parameters, y, None));
+        let CurveFitProblemSolution {
+            parameters: solution_new,
+            summary: summary_new,
+            ..
+        } = CurveFitProblem1D::new_numeric(func, &x, &y, &initial_guess).solve(&options);
+        assert!(summary_new.is_solution_usable());
+
+        let func: NumericCurveFunctionType =
+            Box::new(|x, parameters, y| model(x, parameters, y, None));
+        let CurveFitProblemSolution {
+            parameters: solution_build,
+            summary: summary_build,
+            ..
+        } = CurveFitProblem1D::builder()
+            .numeric_func(func)
+            .relative_step_size(1e-7)
+            .x(&x)
+            .y(&y)
+            .parameters(&initial_guess)
+            .build()
+            .unwrap()
+            .solve(&options);
+        assert!(summary_build.is_solution_usable());
+
+        assert_abs_diff_eq!(&solution_new[..], &solution_build[..], epsilon = 1e-6);
+        assert_abs_diff_eq!(&TRUE_PARAM[..], &solution_new[..], epsilon = 0.02);
+
+        let func: CurveFunctionType = Box::new(model);
+        let numeric_func: NumericCurveFunctionType = Box::new(|_x, _parameters, _y| true);
+        let mut builder = CurveFitProblem1D::builder()
+            .x(&x)
+            .y(&y)
+            .parameters(&initial_guess)
+            .func(func);
+        builder.numeric_func = Some(numeric_func);
+        assert!(matches!(
+            builder.build(),
+            Err(CurveFitProblemBuildError::FuncConflict)
+        ));
+    }
+
+    #[test]
+    fn autodiff_model() {
+        /// y = a * sin (b * x) + c
+        struct Sine;
+
+        impl AutoDiffCurveModel for Sine {
+            fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+                parameters[0] * (parameters[1] * x).sin() + parameters[2]
+            }
+        }
+
+        let func = autodiff_curve_function(Sine);
+        let (a, b, x) = (1.5, 2.0, 0.3);
+        let mut y = 0.0;
+        let mut jacobians = [Some(0.0), None, Some(0.0)];
+        assert!(func(x, &[a, b, -1.0], &mut y, Some(&mut jacobians)));
+        assert_abs_diff_eq!(y, a * f64::sin(b * x) - 1.0, epsilon = 1e-15);
+        assert_abs_diff_eq!(jacobians[0].unwrap(), f64::sin(b * x), epsilon = 1e-15);
+        assert!(jacobians[1].is_none());
+        assert_eq!(jacobians[2], Some(1.0));
+        let mut jacobians = [None, Some(0.0), None];
+        assert!(func(x, &[a, b, -1.0], &mut y, Some(&mut jacobians)));
+        assert_abs_diff_eq!(
+            jacobians[1].unwrap(),
+            a * x * f64::cos(b * x),
+            epsilon = 1e-15
+        );
+
+        const N: usize = 1000;
+
+        const TRUE_PARAM: [f64; 3] = [1.5, std::f64::consts::PI, -1.0];
+
+        let x: Vec<_> = (0..N).map(|i| i as f64 / N as f64).collect();
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let noise_level: f64 = 0.1;
+        let y: Vec<_> = x
+            .iter()
+            .map(|&x| {
+                Sine.model(x, &TRUE_PARAM)
+                    + noise_level * rng.sample::<f64, _>(rand_distr::StandardNormal)
+            })
+            .collect();
+
+        let initial_guess = [0.0, 1.0, 0.0];
+        let options = SolverOptions::default();
+        let builder = || {
+            CurveFitProblem1D::builder()
+                .x(&x)
+                .y(&y)
+                .parameters(&initial_guess)
+        };
+
+        let CurveFitProblemSolution {
+            parameters: solution_autodiff,
+            summary: summary_autodiff,
+            ..
+        } = builder()
+            .autodiff_func(Sine)
+            .build()
+            .unwrap()
+            .solve(&options);
+        assert!(summary_autodiff.is_solution_usable());
+
+        let func: NumericCurveFunctionType = Box::new(|x, parameters, y| {
+            *y = Sine.model(x, parameters);
+            true
+        });
+        let CurveFitProblemSolution {
+            parameters: solution_numeric,
+            summary: summary_numeric,
+            ..
+        } = builder()
+            .autodiff_func(Sine)
+            .numeric_func(func)
+            .build()
+            .unwrap()
+            .solve(&options);
+        assert!(summary_numeric.is_solution_usable());
+
+        assert_abs_diff_eq!(
+            &solution_autodiff[..],
+            &solution_numeric[..],
+            epsilon = 1e-6
+        );
+        assert_abs_diff_eq!(&TRUE_PARAM[..], &solution_autodiff[..], epsilon = 0.02);
+    }
+
+    #[test]
+    fn owned_data() {
+        // y = a * x + b
+        struct Line;
+
+        impl AutoDiffCurveModel for Line {
+            fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+                parameters[0] * x + parameters[1]
+            }
+        }
+
+        fn problem(x: Arc<[f64]>, y: Vec<f64>) -> CurveFitProblem1D<'static> {
+            CurveFitProblem1D::builder()
+                .autodiff_func(Line)
+                .x(x)
+                .y(y)
+                .inverse_error(vec![2.0; 5])
+                .parameters(&[0.0, 0.0])
+                .build()
+                .unwrap()
+        }
+
+        let x: Arc<[f64]> = (0..5).map(|i| i as f64).collect();
+        let y = x.iter().map(|&x| 3.0 * x - 1.0).collect();
+        let parameters = std::thread::spawn(move || {
+            problem(x, y)
+                .solve_with_residuals(&SolverOptions::default())
+                .parameters
+        })
+        .join()
+        .unwrap();
+        assert_abs_diff_eq!(&parameters[..], &[3.0, -1.0][..], epsilon = 1e-8);
+    }
+
+    #[test]
+    fn f32_data() {
+        let x: Vec<f32> = (0..5).map(|i| i as f32).collect();
+        let y: Vec<f32> = x.iter().map(|&x| 0.5 * x + 2.0).collect();
+        let func: CurveFunctionType = Box::new(|x, parameters, y, jacobians| {
+            *y = parameters[0] * x + parameters[1];
+            if let Some(jacobians) = jacobians {
+                if let Some(d_da) = &mut jacobians[0] {
+                    *d_da = x;
+                }
+                if let Some(d_db) = &mut jacobians[1] {
+                    *d_db = 1.0;
+                }
+            }
+            true
+        });
+        let solution = CurveFitProblem1D::builder()
+            .func(func)
+            .x(&x)
+            .y(y)
+            .inverse_error(&[1.0_f32; 5])
+            .parameters(&[0.0, 0.0])
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        assert_abs_diff_eq!(&solution.parameters[..], &[0.5, 2.0][..], epsilon = 1e-8);
+    }
+
+    #[test]
+    fn iterator_data() {
+        // y = a * x + b
+        struct Line;
+
+        impl AutoDiffCurveModel for Line {
+            fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+                parameters[0] * x + parameters[1]
+            }
+        }
+
+        let problem = |x: &[f64], y: &[f64]| {
+            // Keep only the points with positive x
+            let points = || x.iter().zip(y).filter(|(&x, _)| x > 0.0);
+            CurveFitProblem1D::builder()
+                .autodiff_func(Line)
+                .x_iter(points().map(|(&x, _)| x))
+                .y_iter(points().map(|(_, &y)| y))
+                .inverse_error_iter(points().map(|(&x, _)| 1.0 / x))
+                .parameters(&[0.0, 0.0])
+                .build()
+                .unwrap()
+        };
+        let x: Vec<_> = (-5..5).map(|i| i as f64).collect();
+        let y: Vec<_> = x
+            .iter()
+            .map(|&x| if x > 0.0 { 2.0 * x + 1.0 } else { 0.0 })
+            .collect();
+        let solution = problem(&x, &y).solve(&SolverOptions::default());
+        assert_eq!(solution.degrees_of_freedom, 2);
+        assert_abs_diff_eq!(&solution.parameters[..], &[2.0, 1.0][..], epsilon = 1e-8);
+    }
+
+    #[test]
+    fn errors_in_x() {
+        // y = a * x + b
+        struct Line;
+
+        impl AutoDiffCurveModel for Line {
+            fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+                parameters[0] * x + parameters[1]
+            }
+        }
+
+        const N: usize = 1000;
+        const TRUE_PARAM: [f64; 2] = [2.0, 1.0];
+        let (x_sigma, y_sigma) = (1.0, 0.1);
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let (x, y): (Vec<_>, Vec<_>) = (0..N)
+            .map(|i| {
+                let x = 10.0 * i as f64 / N as f64;
+                (
+                    x + x_sigma * rng.sample::<f64, _>(rand_distr::StandardNormal),
+                    Line.model(x, &TRUE_PARAM)
+                        + y_sigma * rng.sample::<f64, _>(rand_distr::StandardNormal),
+                )
+            })
+            .unzip();
+
+        let builder = || {
+            CurveFitProblem1D::builder()
+                .autodiff_func(Line)
+                .x(&x)
+                .y(&y)
+                .inverse_error(vec![1.0 / y_sigma; N])
+                .parameters(&[1.0, 0.0])
+        };
+        let ordinary = builder().build().unwrap().solve(&SolverOptions::default());
+        assert!(ordinary.x_fit.is_none());
+        // Ordinary least squares flatten the slope
+        assert!(ordinary.parameters[0] < 1.9);
+
+        let solution = builder()
+            .x_inverse_error(vec![1.0 / x_sigma; N])
+            .build()
+            .unwrap()
+            .solve_with_residuals(&SolverOptions::default());
+        assert!(solution.summary.is_solution_usable());
+        assert_eq!(solution.parameters.len(), 2);
+        assert_abs_diff_eq!(solution.parameters[0], TRUE_PARAM[0], epsilon = 0.1);
+        let x_fit = solution.x_fit.unwrap();
+        let y_fit = solution.y_fit.unwrap();
+        assert_eq!(x_fit.len(), N);
+        for (&x, &y) in x_fit.iter().zip(y_fit.iter()) {
+            assert_abs_diff_eq!(y, Line.model(x, &solution.parameters), epsilon = 1e-8);
+        }
+        // Number of residuals minus the number of the model parameters and latent values
+        assert_eq!(solution.degrees_of_freedom, (N - 2) as i64);
+
+        let func: VectorizedCurveFunctionType = Box::new(|_x, _parameters, _y, _jacobians| true);
+        assert!(matches!(
+            builder()
+                .vectorized_func(func)
+                .x_inverse_error(vec![1.0; N])
+                .build(),
+            Err(CurveFitProblemBuildError::XInverseErrorWithVectorizedFunc)
+        ));
+    }
+
+    #[test]
+    fn correlated_errors() {
+        // y = a * x + b
+        struct Line;
+
+        impl AutoDiffCurveModel for Line {
+            fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+                parameters[0] * x + parameters[1]
+            }
+        }
+
+        const N: usize = 100;
+        let x: Vec<_> = (0..N).map(|i| i as f64 / N as f64).collect();
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let y: Vec<_> = x
+            .iter()
+            .map(|&x| {
+                Line.model(x, &[2.0, -1.0]) + 0.1 * rng.sample::<f64, _>(rand_distr::StandardNormal)
+            })
+            .collect();
+        let builder = || {
+            CurveFitProblem1D::builder()
+                .autodiff_func(Line)
+                .x(&x)
+                .y(&y)
+                .parameters(&[0.0, 0.0])
+        };
+
+        // Diagonal covariance is the same as inverse errors
+        let sigma: Vec<_> = (0..N).map(|i| 0.1 + 0.001 * i as f64).collect();
+        let mut covariance = DenseMatrix::zeros(N, N);
+        for (i, sigma) in sigma.iter().enumerate() {
+            covariance.values[i * N + i] = sigma.powi(2);
+        }
+        let whitening = Whitening::from_covariance(&covariance).unwrap();
+        assert!(matches!(
+            whitening,
+            Whitening::BandedCholesky { bandwidth: 0, .. }
+        ));
+        let whitened = builder()
+            .whitening(whitening)
+            .build()
+            .unwrap()
+            .solve_with_residuals(&SolverOptions::default());
+        let inverse_error: Vec<_> = sigma.iter().map(|sigma| sigma.recip()).collect();
+        let weighted = builder()
+            .inverse_error(inverse_error)
+            .build()
+            .unwrap()
+            .solve_with_residuals(&SolverOptions::default());
+        assert_abs_diff_eq!(
+            &whitened.parameters[..],
+            &weighted.parameters[..],
+            epsilon = 1e-10
+        );
+        assert_abs_diff_eq!(whitened.chi2, weighted.chi2, epsilon = 1e-10);
+        assert_abs_diff_eq!(
+            &whitened.residuals.unwrap()[..],
+            &weighted.residuals.unwrap()[..],
+            epsilon = 1e-10
+        );
+
+        // Exponentially decaying correlation, the covariance is dense
+        for (i, sigma_i) in sigma.iter().enumerate() {
+            for (j, sigma_j) in sigma.iter().enumerate() {
+                covariance.values[i * N + j] =
+                    sigma_i * sigma_j * 0.5_f64.powi((i as i32 - j as i32).abs());
+            }
+        }
+        let whitening = Whitening::from_covariance(&covariance).unwrap();
+        assert!(matches!(whitening, Whitening::Cholesky(_)));
+        let solution = builder()
+            .whitening(whitening)
+            .build()
+            .unwrap()
+            .solve_with_residuals(&SolverOptions::default());
+        assert!(solution.summary.is_solution_usable());
+        for ((&x, &y_fit), (&y, &residual)) in x
+            .iter()
+            .zip(solution.y_fit.as_ref().unwrap())
+            .zip(y.iter().zip(solution.residuals.as_ref().unwrap()))
+        {
+            assert_abs_diff_eq!(y_fit, Line.model(x, &solution.parameters), epsilon = 1e-8);
+            assert_abs_diff_eq!(residual, y - y_fit, epsilon = 1e-8);
+        }
+
+        assert!(matches!(
+            builder()
+                .inverse_error(vec![1.0; N])
+                .whitening(Whitening::Diagonal(vec![1.0; N]))
+                .build(),
+            Err(CurveFitProblemBuildError::WhiteningConflict)
+        ));
+        assert!(matches!(
+            builder()
+                .whitening(Whitening::Diagonal(vec![1.0; N - 1]))
+                .build(),
+            Err(CurveFitProblemBuildError::DataSizesDontMatch)
+        ));
+    }
+
+    #[test]
+    fn goodness_of_fit() {
+        const N: usize = 1000;
+
+        const TRUE_PARAM: [f64; 3] = [1.5, std::f64::consts::PI, -1.0];
+
+        let x: Vec<_> = (0..N).map(|i| i as f64 / N as f64).collect();
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        let noise_level: f64 = 0.1;
+        let y: Vec<_> = x
+            .iter()
+            .map(|&x| {
+                let mut y = 0.0;
+                model(x, &TRUE_PARAM, &mut y, None);
+                y + noise_level * rng.sample::<f64, _>(rand_distr::StandardNormal)
+            })
+            .collect();
+        let w = vec![noise_level.powi(-1); x.len()];
+
+        let solve = |constant: &[usize]| {
+            let func: CurveFunctionType = Box::new(model);
+            CurveFitProblem1D::builder()
+                .func(func)
+                .x(&x)
+                .y(&y)
+                .inverse_error(&w)
+                .parameters(&TRUE_PARAM)
+                .constant(constant)
+                .build()
+                .unwrap()
+                .solve(&SolverOptions::default())
+        };
+
+        let solution = solve(&[]);
+        assert_abs_diff_eq!(solution.chi2, 2.0 * solution.summary.final_cost());
+        assert_eq!(solution.degrees_of_freedom, N as i64 - 3);
+        assert_abs_diff_eq!(
+            solution.reduced_chi2,
+            solution.chi2 / (N - 3) as f64,
+            epsilon = 1e-12
+        );
+        // Weights are the true inverse errors
+        assert_abs_diff_eq!(solution.reduced_chi2, 1.0, epsilon = 0.15);
+        assert_abs_diff_eq!(
+            solution.aic - solution.bic,
+            3.0 * (2.0 - (N as f64).ln()),
+            epsilon = 1e-9
+        );
+
+        let solution_fixed = solve(&[0]);
+        assert_eq!(solution_fixed.degrees_of_freedom, N as i64 - 2);
+        assert!(solution_fixed.chi2 >= solution.chi2);
+    }
+
+    #[test]
+    fn information_criteria() {
+        fn constant(
+            _x: f64,
+            parameters: &[f64],
+            y: &mut f64,
+            jacobians: Option<&mut [Option<f64>]>,
+        ) -> bool {
+            *y = parameters[0];
+            if let Some(jacobians) = jacobians {
+                if let Some(d_da) = &mut jacobians[0] {
+                    *d_da = 1.0;
+                }
+            }
+            true
+        }
+        let x = [0.0, 1.0, 2.0, 3.0];
+        let solve = |y: &[f64], inverse_error: Option<&[f64]>, initial: f64| {
+            let func: CurveFunctionType = Box::new(constant);
+            let parameters = [initial];
+            let builder = CurveFitProblem1D::builder()
+                .func(func)
+                .x(&x)
+                .y(y)
+                .parameters(&parameters);
+            let builder = match inverse_error {
+                Some(inverse_error) => builder.inverse_error(inverse_error),
+                None => builder,
+            };
+            builder.build().unwrap().solve(&SolverOptions::default())
+        };
+        // The best fit is the mean 3, the squared residuals are 4, 1, 0 and 9
+        let y = [1.0, 2.0, 3.0, 6.0];
+
+        // Unknown errors: n ln(chi2 / n) with n = 4, chi2 = 14
+        let unweighted = solve(&y, None, 0.0);
+        assert_abs_diff_eq!(unweighted.chi2, 14.0, epsilon = 1e-8);
+        assert_abs_diff_eq!(unweighted.aic, 4.0 * f64::ln(3.5) + 2.0, epsilon = 1e-8);
+        assert_abs_diff_eq!(
+            unweighted.bic,
+            4.0 * f64::ln(3.5) + f64::ln(4.0),
+            epsilon = 1e-8
+        );
+
+        // Known errors: chi2 = 2^2 * 14
+        let weighted = solve(&y, Some(&[2.0; 4]), 0.0);
+        assert_abs_diff_eq!(weighted.chi2, 56.0, epsilon = 1e-8);
+        assert_abs_diff_eq!(weighted.aic, 58.0, epsilon = 1e-8);
+        assert_abs_diff_eq!(weighted.bic, 56.0 + f64::ln(4.0), epsilon = 1e-8);
+
+        // Exact fit with unknown errors, started at the solution to get zero chi2
+        let exact = solve(&[3.0; 4], None, 3.0);
+        assert_eq!(exact.chi2, 0.0);
+        assert!(exact.aic.is_nan());
+        assert!(exact.bic.is_nan());
+    }
+
+    #[test]
+    fn fitted_values_and_residuals() {
+        const TRUE_PARAM: [f64; 3] = [1.5, std::f64::consts::PI, -1.0];
+
+        let x: Vec<_> = (0..100).map(|i| i as f64 / 100.0).collect();
+        let y: Vec<_> = x
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                let mut y = 0.0;
+                model(x, &TRUE_PARAM, &mut y, None);
+                y + if i % 2 == 0 { 0.1 } else { -0.1 }
+            })
+            .collect();
+        let w = vec![2.0; x.len()];
+
+        let func: CurveFunctionType = Box::new(model);
+        let solution = CurveFitProblem1D::builder()
+            .func(func)
+            .x(&x)
+            .y(&y)
+            .inverse_error(&w)
+            .parameters(&TRUE_PARAM)
+            .build()
+            .unwrap()
+            .solve_with_residuals(&SolverOptions::default());
+        let y_fit = solution.y_fit.unwrap();
+        let residuals = solution.residuals.unwrap();
+        assert_eq!(y_fit.len(), x.len());
+        for (((&x, &y), y_fit), residual) in x.iter().zip(&y).zip(y_fit).zip(residuals) {
+            let mut expected = 0.0;
+            model(x, &solution.parameters, &mut expected, None);
+            assert_abs_diff_eq!(y_fit, expected, epsilon = 1e-12);
+            assert_abs_diff_eq!(residual, y - expected, epsilon = 1e-12);
+        }
+
+        let func: CurveFunctionType = Box::new(model);
+        let solution =
+            CurveFitProblem1D::new(func, &x, &y, &TRUE_PARAM).solve(&SolverOptions::default());
+        assert!(solution.y_fit.is_none());
+        assert!(solution.residuals.is_none());
+    }
+
+    #[test]
+    fn periodic_phase() {
+        // y = a sin(x + phi)
+        fn model(
+            x: f64,
+            parameters: &[f64],
+            y: &mut f64,
+            jacobians: Option<&mut [Option<f64>]>,
+        ) -> bool {
+            let (a, phi) = (parameters[0], parameters[1]);
+            *y = a * f64::sin(x + phi);
+            if let Some(jacobians) = jacobians {
+                if let Some(d_da) = &mut jacobians[0] {
+                    *d_da = f64::sin(x + phi);
+                }
+                if let Some(d_dphi) = &mut jacobians[1] {
+                    *d_dphi = a * f64::cos(x + phi);
+                }
+            }
+            true
+        }
+
+        let true_phi = 0.3;
+        let x: Vec<_> = (0..50).map(|i| 0.2 * i as f64).collect();
+        let y: Vec<_> = x.iter().map(|&x| 2.0 * f64::sin(x + true_phi)).collect();
+
+        // The initial phase is out of [0, 2 pi), and the solver goes through zero
+        let func: CurveFunctionType = Box::new(model);
+        let solution = CurveFitProblem1D::builder()
+            .func(func)
+            .x(&x)
+            .y(&y)
+            .parameters(&[1.0, -0.2])
+            .periodic(&[(1, TAU)])
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        assert_abs_diff_eq!(solution.parameters[0], 2.0, epsilon = 1e-8);
+        assert_abs_diff_eq!(solution.parameters[1], true_phi, epsilon = 1e-8);
+
+        let func: CurveFunctionType = Box::new(model);
+        let result = CurveFitProblem1D::builder()
+            .func(func)
+            .x(&x)
+            .y(&y)
+            .parameters(&[1.0, 0.0])
+            .periodic(&[(1, -1.0)])
+            .build();
+        assert!(matches!(
+            result,
+            Err(CurveFitProblemBuildError::ManifoldError(
+                ManifoldError::InvalidPeriod { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn parameter_transforms() {
+        // y = a (f exp(-x) + (1 - f) exp(-x / 4))
+        struct Mixture;
+
+        impl AutoDiffCurveModel for Mixture {
+            fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+                let (a, f) = (parameters[0], parameters[1]);
+                let one = T::from_f64(1.0);
+                a * (f * (-x).exp() + (one - f) * (-x / 4.0).exp())
+            }
+        }
+
+        let x: Vec<_> = (0..50).map(|i| 0.2 * i as f64).collect();
+        let y: Vec<_> = x.iter().map(|&x| Mixture.model(x, &[3.0, 0.3])).collect();
+        let transforms = [
+            (0, ParameterTransform::Log),
+            (
+                1,
+                ParameterTransform::Logit {
+                    lower: 0.0,
+                    upper: 1.0,
+                },
+            ),
+        ];
+        let problem = |parameters: &[f64], transforms: &[(usize, ParameterTransform)]| {
+            CurveFitProblem1D::builder()
+                .autodiff_func(Mixture)
+                .x(&x)
+                .y(&y)
+                .parameters(parameters)
+                // Bounds outside of the ranges are ignored
+                .lower_bounds(&[Some(-1.0), None])
+                .upper_bounds(&[None, Some(2.0)])
+                .transforms(transforms)
+                .build()
+        };
+
+        let solution = problem(&[1.0, 0.5], &transforms)
+            .unwrap()
+            .solve_with_residuals(&SolverOptions::default());
+        assert_abs_diff_eq!(&solution.parameters[..], &[3.0, 0.3][..], epsilon = 1e-8);
+        assert_abs_diff_eq!(&solution.y_fit.unwrap()[..], &y[..], epsilon = 1e-8);
+
+        let invalid_range = [(
+            1,
+            ParameterTransform::Logit {
+                lower: 1.0,
+                upper: 0.0,
+            },
+        )];
+        let duplicate = [transforms[0], transforms[0]];
+        for (parameters, transforms) in [
+            // Initial value outside of the range
+            (&[-1.0, 0.5], &transforms[..]),
+            (&[1.0, 1.5], &transforms[..]),
+            (&[1.0, 0.5], &invalid_range[..]),
+            (&[1.0, 0.5], &duplicate[..]),
+            (&[1.0, 0.5], &[(2, ParameterTransform::Log)][..]),
+        ] {
+            assert!(matches!(
+                problem(parameters, transforms),
+                Err(CurveFitProblemBuildError::InvalidParameterTransform)
+            ));
+        }
+    }
 }
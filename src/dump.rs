@@ -0,0 +1,645 @@
+//! Dump of [NllsProblem] structure and values, to attach reproducers to bug reports.
+//!
+//! [NllsProblem::dump] records parameter blocks with their values, bounds, constant flags and
+//! tangent space sizes, and residual blocks with their residuals and jacobians at the current
+//! parameter values. The dump can be written to a self-contained text file with
+//! [ProblemDump::write_to] and read back with [ProblemDump::read_from]. [ProblemDump::into_problem]
+//! creates a replay problem which doesn't need the original cost functions: every residual block
+//! is replaced with its linearization at the dumped point, `r(x) = r_0 + J (x - x_0)`. Loss
+//! functions and manifolds are not dumped, jacobians are in the tangent spaces of the parameter
+//! blocks, so the replay problem varies the tangent space offsets of the blocks with manifolds.
+//!
+//! ```rust
+//! use ceres_solver::dump::ProblemDump;
+//! use ceres_solver::{CostFunctionType, NllsProblem, SolverOptions};
+//!
+//! let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+//!     residuals[0] = 2.0 * parameters[0][0] - 1.0;
+//!     if let Some(jacobians) = jacobians {
+//!         if let Some(d_dx) = &mut jacobians[0] {
+//!             d_dx[0][0] = 2.0;
+//!         }
+//!     }
+//!     true
+//! });
+//! let (problem, _block_id) = NllsProblem::new()
+//!     .residual_block_builder()
+//!     .set_cost(cost, 1)
+//!     .set_parameters([vec![0.0]])
+//!     .build_into_problem()
+//!     .unwrap();
+//!
+//! let mut file = Vec::new();
+//! problem.dump().unwrap().write_to(&mut file).unwrap();
+//! let dump = ProblemDump::read_from(file.as_slice()).unwrap();
+//! let solution = dump
+//!     .into_problem()
+//!     .unwrap()
+//!     .solve(&SolverOptions::default())
+//!     .unwrap();
+//! assert!((solution.parameters[0][0] - 0.5).abs() < 1e-8);
+//! ```
+
+use crate::cost::CostFunctionType;
+use crate::error::{NllsProblemError, ProblemDumpError, ResidualBlockBuildingError};
+use crate::evaluation::DenseMatrix;
+use crate::nlls_problem::NllsProblem;
+use crate::parameter_block::ParameterBlock;
+
+use std::io::{BufRead, Write};
+
+const HEADER: &str = "ceres-solver-rs problem dump 2";
+
+/// Dump of [NllsProblem], see [module-level documentation](crate::dump).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProblemDump {
+    /// Parameter blocks in the order they were added to the problem.
+    pub parameter_blocks: Vec<ParameterBlockDump>,
+    /// Residual blocks in the order they were added to the problem.
+    pub residual_blocks: Vec<ResidualBlockDump>,
+}
+
+/// Dumped parameter block.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParameterBlockDump {
+    pub values: Vec<f64>,
+    /// Lower bounds of the components, [None] means no bound.
+    pub lower_bounds: Vec<Option<f64>>,
+    /// Upper bounds of the components, [None] means no bound.
+    pub upper_bounds: Vec<Option<f64>>,
+    pub constant: bool,
+    /// Size of the tangent space, it is less than the block size if the block has a manifold.
+    pub tangent_size: usize,
+}
+
+/// Dumped residual block.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResidualBlockDump {
+    pub name: Option<String>,
+    /// Indices of the parameter blocks of the residual block.
+    pub parameter_blocks: Vec<usize>,
+    /// Residuals at the dumped parameter values, without loss function applied.
+    pub residuals: Vec<f64>,
+    /// Jacobians with respect to the tangent spaces of the parameter blocks at the dumped parameter
+    /// values, [None] for the constant blocks.
+    pub jacobians: Vec<Option<DenseMatrix>>,
+}
+
+impl NllsProblem<'_> {
+    /// Dump the problem structure and the current values, see [crate::dump].
+    ///
+    /// Returns the same errors as [NllsProblem::evaluate_residual_block].
+    pub fn dump(&self) -> Result<ProblemDump, NllsProblemError> {
+        let parameter_blocks = self
+            .parameter_blocks()
+            .iter()
+            .enumerate()
+            .map(|(index, block)| {
+                Ok(ParameterBlockDump {
+                    values: block.values().to_vec(),
+                    lower_bounds: block
+                        .lower_bounds()
+                        .map_or_else(|| vec![None; block.len()], <[_]>::to_vec),
+                    upper_bounds: block
+                        .upper_bounds()
+                        .map_or_else(|| vec![None; block.len()], <[_]>::to_vec),
+                    constant: self.is_parameter_block_constant(index)?,
+                    tangent_size: self.parameter_block_tangent_size(index)?,
+                })
+            })
+            .collect::<Result<Vec<_>, NllsProblemError>>()?;
+        let residual_blocks = self
+            .residual_block_ids()
+            .map(|id| {
+                let evaluation = self.evaluate_residual_block(id, false)?;
+                Ok(ResidualBlockDump {
+                    name: self.residual_block_name(id).map(str::to_owned),
+                    parameter_blocks: self.residual_block_parameters(id)?,
+                    residuals: evaluation.residuals,
+                    jacobians: evaluation.jacobians,
+                })
+            })
+            .collect::<Result<Vec<_>, NllsProblemError>>()?;
+        Ok(ProblemDump {
+            parameter_blocks,
+            residual_blocks,
+        })
+    }
+}
+
+impl ProblemDump {
+    /// Write the dump in a line-based text format, floats are written with full precision.
+    pub fn write_to(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "{HEADER}")?;
+        writeln!(writer, "parameter_blocks {}", self.parameter_blocks.len())?;
+        for block in self.parameter_blocks.iter() {
+            let state = if block.constant {
+                "constant"
+            } else {
+                "variable"
+            };
+            writeln!(
+                writer,
+                "block {} {} {state}",
+                block.values.len(),
+                block.tangent_size
+            )?;
+            writeln!(writer, "values{}", format_values(&block.values))?;
+            writeln!(writer, "lower{}", format_bounds(&block.lower_bounds))?;
+            writeln!(writer, "upper{}", format_bounds(&block.upper_bounds))?;
+        }
+        writeln!(writer, "residual_blocks {}", self.residual_blocks.len())?;
+        for block in self.residual_blocks.iter() {
+            writeln!(writer, "residual_block {}", block.residuals.len())?;
+            if let Some(name) = &block.name {
+                writeln!(writer, "name {}", name.replace('\n', " "))?;
+            }
+            let parameters: String = block
+                .parameter_blocks
+                .iter()
+                .map(|index| format!(" {index}"))
+                .collect();
+            writeln!(writer, "parameters{parameters}")?;
+            writeln!(writer, "residuals{}", format_values(&block.residuals))?;
+            for jacobian in block.jacobians.iter() {
+                match jacobian {
+                    Some(jacobian) => writeln!(
+                        writer,
+                        "jacobian {} {}{}",
+                        jacobian.num_rows,
+                        jacobian.num_cols,
+                        format_values(&jacobian.values)
+                    )?,
+                    None => writeln!(writer, "jacobian none")?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the dump written with [ProblemDump::write_to].
+    pub fn read_from(reader: impl BufRead) -> Result<Self, ProblemDumpError> {
+        let mut lines = Lines::new(reader);
+        let (line_number, header) = lines.next_line()?;
+        if header.trim() != HEADER {
+            return Err(ProblemDumpError::Parse {
+                line: line_number,
+                message: format!("expected header '{HEADER}'"),
+            });
+        }
+        let num_parameter_blocks = lines.expect_one("parameter_blocks")?;
+        let mut parameter_blocks = Vec::with_capacity(num_parameter_blocks);
+        for _ in 0..num_parameter_blocks {
+            let (line_number, tokens) = lines.expect("block")?;
+            let size: usize = parse_token(line_number, tokens.first())?;
+            let tangent_size: usize = parse_token(line_number, tokens.get(1))?;
+            if tangent_size > size {
+                return Err(ProblemDumpError::Parse {
+                    line: line_number,
+                    message: "tangent size is larger than block size".to_owned(),
+                });
+            }
+            let constant = match tokens.get(2).map(String::as_str) {
+                Some("constant") => true,
+                Some("variable") => false,
+                _ => {
+                    return Err(ProblemDumpError::Parse {
+                        line: line_number,
+                        message: "expected 'constant' or 'variable'".to_owned(),
+                    })
+                }
+            };
+            let values = lines.expect_values("values", size)?;
+            let lower_bounds = lines.expect_bounds("lower", size)?;
+            let upper_bounds = lines.expect_bounds("upper", size)?;
+            parameter_blocks.push(ParameterBlockDump {
+                values,
+                lower_bounds,
+                upper_bounds,
+                constant,
+                tangent_size,
+            });
+        }
+        let num_residual_blocks = lines.expect_one("residual_blocks")?;
+        let mut residual_blocks = Vec::with_capacity(num_residual_blocks);
+        for _ in 0..num_residual_blocks {
+            let num_residuals = lines.expect_one("residual_block")?;
+            let (mut line_number, mut line) = lines.next_line()?;
+            let name = match line.strip_prefix("name ") {
+                Some(name) => {
+                    let name = name.to_owned();
+                    (line_number, line) = lines.next_line()?;
+                    Some(name)
+                }
+                None => None,
+            };
+            let parameters = split_keyword(line_number, &line, "parameters")?
+                .iter()
+                .map(|token| parse_token(line_number, Some(token)))
+                .collect::<Result<Vec<usize>, _>>()?;
+            if let Some(&index) = parameters.iter().find(|&&i| i >= parameter_blocks.len()) {
+                return Err(ProblemDumpError::Parse {
+                    line: line_number,
+                    message: format!("unknown parameter block {index}"),
+                });
+            }
+            let residuals = lines.expect_values("residuals", num_residuals)?;
+            let jacobians = parameters
+                .iter()
+                .map(|&index| {
+                    let (line_number, tokens) = lines.expect("jacobian")?;
+                    let block = &parameter_blocks[index];
+                    let is_none = tokens.len() == 1 && tokens[0] == "none";
+                    match (block.constant, is_none) {
+                        (true, true) => return Ok(None),
+                        (false, false) => {}
+                        _ => {
+                            return Err(ProblemDumpError::Parse {
+                                line: line_number,
+                                message: "jacobian must be 'none' for constant blocks only"
+                                    .to_owned(),
+                            })
+                        }
+                    }
+                    let num_rows: usize = parse_token(line_number, tokens.first())?;
+                    let num_cols: usize = parse_token(line_number, tokens.get(1))?;
+                    let values = parse_values(line_number, &tokens[2.min(tokens.len())..])?;
+                    if num_rows != num_residuals
+                        || num_cols != block.tangent_size
+                        || values.len() != num_rows * num_cols
+                    {
+                        return Err(ProblemDumpError::Parse {
+                            line: line_number,
+                            message: "jacobian has wrong shape".to_owned(),
+                        });
+                    }
+                    Ok(Some(DenseMatrix {
+                        num_rows,
+                        num_cols,
+                        values,
+                    }))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            residual_blocks.push(ResidualBlockDump {
+                name,
+                parameter_blocks: parameters,
+                residuals,
+                jacobians,
+            });
+        }
+        Ok(Self {
+            parameter_blocks,
+            residual_blocks,
+        })
+    }
+
+    /// Create a replay problem, residual blocks are replaced with their linearizations at the
+    /// dumped parameter values, see [crate::dump].
+    ///
+    /// Parameter blocks are added in the order of their first use by the residual blocks, which is
+    /// the same order as in the dumped problem, blocks not used by any residual block are skipped.
+    /// The blocks with manifolds, i.e. with [ParameterBlockDump::tangent_size] less than their
+    /// size, are replaced with their tangent space offsets from the dumped values, which start at
+    /// zero and have no bounds.
+    ///
+    /// # Panics
+    /// If the sizes of the values, bounds and jacobians are inconsistent, which is not possible
+    /// for the dumps created with [NllsProblem::dump] or [ProblemDump::read_from].
+    pub fn into_problem(self) -> Result<NllsProblem<'static>, ResidualBlockBuildingError> {
+        let Self {
+            parameter_blocks,
+            residual_blocks,
+        } = self;
+        // Values the blocks are linearized at
+        let initial_values: Vec<_> = parameter_blocks
+            .iter()
+            .map(|dump| {
+                if dump.tangent_size == dump.values.len() {
+                    dump.values.clone()
+                } else {
+                    vec![0.0; dump.tangent_size]
+                }
+            })
+            .collect();
+        let mut new_blocks: Vec<_> = parameter_blocks
+            .iter()
+            .zip(&initial_values)
+            .map(|(dump, values)| {
+                let mut block = ParameterBlock::new(values.clone());
+                if dump.tangent_size == dump.values.len() {
+                    block.set_lower_bounds(dump.lower_bounds.clone());
+                    block.set_upper_bounds(dump.upper_bounds.clone());
+                }
+                Some(block)
+            })
+            .collect();
+        let mut problem_indices = vec![None; parameter_blocks.len()];
+        let mut num_added_blocks = 0;
+        let mut problem = NllsProblem::new();
+        for block in residual_blocks {
+            let block_initial_values = block
+                .parameter_blocks
+                .iter()
+                .map(|&index| initial_values[index].clone())
+                .collect();
+            let num_residuals = block.residuals.len();
+            let cost = linearized_cost(block.residuals, block.jacobians, block_initial_values);
+            let mut builder = problem
+                .residual_block_builder()
+                .set_cost(cost, num_residuals);
+            if let Some(name) = block.name {
+                builder = builder.set_name(name);
+            }
+            for &index in block.parameter_blocks.iter() {
+                builder = match problem_indices[index] {
+                    Some(problem_index) => builder.add_parameter(problem_index),
+                    None => {
+                        problem_indices[index] = Some(num_added_blocks);
+                        num_added_blocks += 1;
+                        builder.add_parameter(
+                            new_blocks[index]
+                                .take()
+                                .expect("Parameter block must be added only once"),
+                        )
+                    }
+                };
+            }
+            problem = builder.build_into_problem()?.0;
+        }
+        for (dump, problem_index) in parameter_blocks.iter().zip(problem_indices) {
+            if let (true, Some(problem_index)) = (dump.constant, problem_index) {
+                problem.set_parameter_block_constant(problem_index)?;
+            }
+        }
+        Ok(problem)
+    }
+}
+
+/// Cost function of the linearized residual block `r(x) = r_0 + J (x - x_0)`, missed jacobians of
+/// the constant blocks are zeros.
+fn linearized_cost(
+    residuals: Vec<f64>,
+    jacobians: Vec<Option<DenseMatrix>>,
+    initial_values: Vec<Vec<f64>>,
+) -> CostFunctionType<'static> {
+    Box::new(move |parameters, out_residuals, out_jacobians| {
+        out_residuals.copy_from_slice(&residuals);
+        for ((jacobian, x), x0) in jacobians.iter().zip(parameters).zip(&initial_values) {
+            let Some(jacobian) = jacobian else {
+                continue;
+            };
+            for (row, residual) in jacobian.rows().zip(out_residuals.iter_mut()) {
+                *residual += row
+                    .iter()
+                    .zip(x.iter().zip(x0))
+                    .map(|(d, (x, x0))| d * (x - x0))
+                    .sum::<f64>();
+            }
+        }
+        if let Some(out_jacobians) = out_jacobians {
+            for (out, jacobian) in out_jacobians.iter_mut().zip(&jacobians) {
+                match (out, jacobian) {
+                    (Some(out), Some(jacobian)) => {
+                        for (out_row, row) in out.iter_mut().zip(jacobian.rows()) {
+                            out_row.copy_from_slice(row);
+                        }
+                    }
+                    (Some(out), None) => {
+                        for out_row in out.iter_mut() {
+                            out_row.fill(0.0);
+                        }
+                    }
+                    (None, _) => {}
+                }
+            }
+        }
+        true
+    })
+}
+
+fn format_values(values: &[f64]) -> String {
+    // Debug formatting is the shortest representation which is parsed back to the same value.
+    values.iter().map(|value| format!(" {value:?}")).collect()
+}
+
+fn format_bounds(bounds: &[Option<f64>]) -> String {
+    bounds
+        .iter()
+        .map(|bound| match bound {
+            Some(bound) => format!(" {bound:?}"),
+            None => " none".to_owned(),
+        })
+        .collect()
+}
+
+fn parse_token<T: std::str::FromStr>(
+    line: usize,
+    token: Option<&String>,
+) -> Result<T, ProblemDumpError> {
+    token
+        .and_then(|token| token.parse().ok())
+        .ok_or_else(|| ProblemDumpError::Parse {
+            line,
+            message: format!("cannot parse '{}'", token.map_or("", String::as_str)),
+        })
+}
+
+fn parse_values(line: usize, tokens: &[String]) -> Result<Vec<f64>, ProblemDumpError> {
+    tokens
+        .iter()
+        .map(|token| parse_token(line, Some(token)))
+        .collect()
+}
+
+/// Tokens of the line after the keyword.
+fn split_keyword(line: usize, text: &str, keyword: &str) -> Result<Vec<String>, ProblemDumpError> {
+    let mut tokens = text.split_whitespace();
+    if tokens.next() != Some(keyword) {
+        return Err(ProblemDumpError::Parse {
+            line,
+            message: format!("expected '{keyword}'"),
+        });
+    }
+    Ok(tokens.map(str::to_owned).collect())
+}
+
+/// Line reader keeping track of the line numbers.
+struct Lines<R> {
+    reader: R,
+    line_number: usize,
+}
+
+impl<R: BufRead> Lines<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line_number: 0,
+        }
+    }
+
+    fn next_line(&mut self) -> Result<(usize, String), ProblemDumpError> {
+        let mut line = String::new();
+        self.line_number += 1;
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(ProblemDumpError::Parse {
+                line: self.line_number,
+                message: "unexpected end of file".to_owned(),
+            });
+        }
+        Ok((
+            self.line_number,
+            line.trim_end_matches(['\n', '\r']).to_owned(),
+        ))
+    }
+
+    fn expect(&mut self, keyword: &str) -> Result<(usize, Vec<String>), ProblemDumpError> {
+        let (line_number, line) = self.next_line()?;
+        Ok((line_number, split_keyword(line_number, &line, keyword)?))
+    }
+
+    fn expect_one(&mut self, keyword: &str) -> Result<usize, ProblemDumpError> {
+        let (line_number, tokens) = self.expect(keyword)?;
+        parse_token(line_number, tokens.first())
+    }
+
+    fn expect_values(&mut self, keyword: &str, size: usize) -> Result<Vec<f64>, ProblemDumpError> {
+        let (line_number, tokens) = self.expect(keyword)?;
+        let values = parse_values(line_number, &tokens)?;
+        check_size(line_number, values.len(), size)?;
+        Ok(values)
+    }
+
+    fn expect_bounds(
+        &mut self,
+        keyword: &str,
+        size: usize,
+    ) -> Result<Vec<Option<f64>>, ProblemDumpError> {
+        let (line_number, tokens) = self.expect(keyword)?;
+        let bounds = tokens
+            .iter()
+            .map(|token| match token.as_str() {
+                "none" => Ok(None),
+                _ => parse_token(line_number, Some(token)).map(Some),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        check_size(line_number, bounds.len(), size)?;
+        Ok(bounds)
+    }
+}
+
+fn check_size(line: usize, actual: usize, expected: usize) -> Result<(), ProblemDumpError> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(ProblemDumpError::Parse {
+            line,
+            message: format!("expected {expected} values, got {actual}"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn dump_round_trip() {
+        // r = [x^2 - 2, x * y]
+        let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+            let x = parameters[0][0];
+            let y = parameters[1][0];
+            residuals[0] = x * x - 2.0;
+            residuals[1] = x * y;
+            if let Some(jacobians) = jacobians {
+                if let Some(d_dx) = &mut jacobians[0] {
+                    d_dx[0][0] = 2.0 * x;
+                    d_dx[1][0] = y;
+                }
+                if let Some(d_dy) = &mut jacobians[1] {
+                    d_dy[0][0] = 0.0;
+                    d_dy[1][0] = x;
+                }
+            }
+            true
+        });
+        let mut y = ParameterBlock::new(vec![0.1]);
+        y.set_lower_bounds([Some(f64::NEG_INFINITY)]);
+        let (mut problem, _block_id) = NllsProblem::new()
+            .residual_block_builder()
+            .set_cost(cost, 2)
+            .set_parameters([ParameterBlock::new(vec![1.0]), y])
+            .set_name("two words")
+            .build_into_problem()
+            .unwrap();
+        problem.set_parameter_block_constant(1).unwrap();
+
+        let dump = problem.dump().unwrap();
+        assert_eq!(dump.residual_blocks[0].residuals, [-1.0, 0.1]);
+        assert_eq!(dump.residual_blocks[0].parameter_blocks, [0, 1]);
+        assert_eq!(dump.residual_blocks[0].jacobians[1], None);
+        let mut file = Vec::new();
+        dump.write_to(&mut file).unwrap();
+        let read = ProblemDump::read_from(file.as_slice()).unwrap();
+        assert_eq!(read, dump);
+
+        let replay = read.into_problem().unwrap();
+        assert!(replay.is_parameter_block_constant(1).unwrap());
+        let replay_dump = replay.dump().unwrap();
+        assert_eq!(replay_dump, dump);
+
+        // Linearized residuals at (1, 0.1) with constant y are [2x - 3, 0.1 x].
+        let solution = replay.solve(&Default::default()).unwrap();
+        assert_abs_diff_eq!(solution.parameters[0][0], 12.0 / 8.02, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn dump_with_manifold() {
+        // r = x - [1, 2, 3]
+        let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+            for (i, residual) in residuals.iter_mut().enumerate() {
+                *residual = parameters[0][i] - (i + 1) as f64;
+            }
+            if let Some(jacobians) = jacobians {
+                if let Some(d_dx) = &mut jacobians[0] {
+                    for (i, row) in d_dx.iter_mut().enumerate() {
+                        for (j, d) in row.iter_mut().enumerate() {
+                            *d = if i == j { 1.0 } else { 0.0 };
+                        }
+                    }
+                }
+            }
+            true
+        });
+        let (mut problem, _block_id) = NllsProblem::new()
+            .residual_block_builder()
+            .set_cost(cost, 3)
+            .set_parameters([vec![0.0; 3]])
+            .build_into_problem()
+            .unwrap();
+        problem.set_parameter_components_constant(0, &[1]).unwrap();
+
+        let dump = problem.dump().unwrap();
+        assert_eq!(dump.parameter_blocks[0].tangent_size, 2);
+        let jacobian = dump.residual_blocks[0].jacobians[0].as_ref().unwrap();
+        assert_eq!(jacobian.values, [1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+        let mut file = Vec::new();
+        dump.write_to(&mut file).unwrap();
+        let read = ProblemDump::read_from(file.as_slice()).unwrap();
+        assert_eq!(read, dump);
+
+        // Replay varies the offsets of the varying components
+        let replay = read.into_problem().unwrap();
+        assert_eq!(replay.parameter_blocks()[0].values(), [0.0, 0.0]);
+        let solution = replay.solve(&Default::default()).unwrap();
+        assert_abs_diff_eq!(&solution.parameters[0][..], &[1.0, 3.0][..], epsilon = 1e-8);
+    }
+
+    #[test]
+    fn malformed_dump_is_reported() {
+        let text = format!("{HEADER}\nparameter_blocks 1\nblock 2 2 variable\nvalues 1.0\n");
+        let error = ProblemDump::read_from(text.as_bytes()).unwrap_err();
+        assert!(matches!(error, ProblemDumpError::Parse { line: 4, .. }));
+    }
+}
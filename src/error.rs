@@ -1,5 +1,9 @@
 //! Error enums.
 
+use crate::cost::CostFunctionError;
+use crate::covariance::NullSpaceDirection;
+use crate::solver::MinimizerType;
+
 use std::fmt::Debug;
 
 #[derive(Debug, thiserror::Error)]
@@ -12,6 +16,12 @@ pub enum Error {
     CurveFitProblemBuildError(#[from] CurveFitProblemBuildError),
     #[error(transparent)]
     NllsProblemError(#[from] NllsProblemError),
+    #[error(transparent)]
+    CostFunctionEvaluationError(#[from] CostFunctionEvaluationError),
+    #[error(transparent)]
+    GradientCheckError(#[from] GradientCheckError),
+    #[error(transparent)]
+    ManifoldError(#[from] ManifoldError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -20,6 +30,17 @@ pub enum ResidualBlockBuildingError {
     MissingCost,
     #[error("No parameters set for residual block")]
     MissingParameters,
+    #[error("Cost or loss function of the residual block is not thread-safe")]
+    NotThreadSafe,
+    #[error("Parameter {index} of the residual block is the same block as another parameter")]
+    AliasedParameterBlock { index: usize },
+    #[error("Cost function expects parameter block sizes {expected:?}, got {actual:?}")]
+    WrongParameterSizes {
+        expected: Vec<usize>,
+        actual: Vec<usize>,
+    },
+    #[error("Whitening doesn't match the number of residuals {num_residuals}")]
+    WrongWhiteningSize { num_residuals: usize },
     #[error(transparent)]
     ParameterBlockStorageError(#[from] ParameterBlockStorageError),
 }
@@ -28,14 +49,79 @@ pub enum ResidualBlockBuildingError {
 pub enum ParameterBlockStorageError {
     #[error("Index of ParameterBlock out of bounds: {index} >= {len}")]
     IndexOutOfBounds { index: usize, len: usize },
+    #[error("Index of parameter component out of bounds: {index} >= {len}")]
+    ComponentIndexOutOfBounds { index: usize, len: usize },
+    #[error("Wrong number of parameter values: expected {expected}, got {actual}")]
+    WrongSize { expected: usize, actual: usize },
+    #[error("Manifold is not thread-safe")]
+    ManifoldNotThreadSafe,
 }
 
+/// Error for [crate::solver::SolverOptionsBuilder].
+///
+/// The builder checks the obviously inconsistent options itself and reports the offending one,
+/// [SolverOptionsBuildingError::Invalid] holds the message of Ceres' own validation for the rest.
 #[derive(Debug, thiserror::Error)]
 pub enum SolverOptionsBuildingError {
+    #[error("{option} must be non-negative, got {value}")]
+    Negative { option: &'static str, value: f64 },
+    #[error("{option} must be positive, got {value}")]
+    NonPositive { option: &'static str, value: f64 },
+    #[error("{lower} ({lower_value}) must not be greater than {upper} ({upper_value})")]
+    WrongOrder {
+        lower: &'static str,
+        lower_value: f64,
+        upper: &'static str,
+        upper_value: f64,
+    },
+    #[error("{option} is set, but it is not used by {minimizer_type:?} minimizer")]
+    UnusedByMinimizer {
+        option: &'static str,
+        minimizer_type: MinimizerType,
+    },
     #[error("SolverOptions is invalid: {0}")]
     Invalid(String),
 }
 
+/// Error for [crate::nlls_problem::NllsProblem::validate].
+#[derive(Debug, thiserror::Error)]
+pub enum NllsProblemValidationError {
+    #[error("No residual blocks added to the problem")]
+    NoResidualBlocks,
+    #[error("Residual block #{index} has no residuals")]
+    NoResiduals { index: usize },
+    #[error("Parameter block #{index} is not used by any residual block")]
+    UnusedParameterBlock { index: usize },
+    #[error(
+        "Parameter block #{block} component #{component}: lower bound {lower} > upper {upper}"
+    )]
+    InvertedBounds {
+        block: usize,
+        component: usize,
+        lower: f64,
+        upper: f64,
+    },
+    #[error(
+        "Parameter block #{block} component #{component} has value {value} outside of its bounds"
+    )]
+    ValueOutOfBounds {
+        block: usize,
+        component: usize,
+        value: f64,
+    },
+    #[error("All parameter blocks are constant")]
+    AllParameterBlocksConstant,
+}
+
+/// Error for [crate::dump::ProblemDump::read_from].
+#[derive(Debug, thiserror::Error)]
+pub enum ProblemDumpError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Problem dump line {line}: {message}")]
+    Parse { line: usize, message: String },
+}
+
 /// Error for [crate::curve_fit::CurveFitProblem1DBuilder].
 #[derive(Debug, thiserror::Error)]
 pub enum CurveFitProblemBuildError {
@@ -43,18 +129,58 @@ pub enum CurveFitProblemBuildError {
     DataSizesDontMatch,
     #[error("Cost function is missed")]
     FuncMissed,
+    #[error("More than one model function is set")]
+    FuncConflict,
+    #[error("Inverse errors of x cannot be used with the vectorized model function")]
+    XInverseErrorWithVectorizedFunc,
+    #[error("Whitening cannot be used together with inverse errors of x or y")]
+    WhiteningConflict,
+    #[error("Outlier rejection cannot be used together with a loss function or whitening")]
+    OutlierRejectionConflict,
+    #[error("Parameter transform has a wrong index or an invalid range, its parameter is periodic, or the initial value is outside of the range")]
+    InvalidParameterTransform,
+    #[error(
+        "Fit templates support neither vectorized model functions, nor x errors, nor whitening"
+    )]
+    TemplateUnsupported,
+    #[error("Data source is missed")]
+    SourceMissed,
+    #[error("Data source is empty")]
+    EmptySource,
+    #[error("Chunk size must be positive")]
+    ZeroChunkSize,
     #[error("Independent parameter x is missed")]
     XMissed,
     #[error("Dependent parameter y is missed")]
     YMissed,
+    #[error("Dependent parameter z is missed")]
+    ZMissed,
     #[error("Initial parameters' guess are missed")]
     ParametersMissed,
+    #[error("No datasets are added")]
+    DatasetsMissed,
+    #[error("Spline knots are missed")]
+    KnotsMissed,
+    #[error("Interior knots must be strictly increasing and lie inside the data range")]
+    InvalidKnots,
+    #[error("Data x values must span a non-empty range")]
+    DegenerateDataRange,
+    #[error("Smoothing weight must be non-negative")]
+    NegativeSmoothing,
+    #[error("Parameter {index} has {actual} per-dataset initial values, but there are {expected} datasets")]
+    PerDatasetSizeMismatch {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
     #[error("Lower boundary size doesn't match the number of parameters")]
     LowerBoundarySizeMismatch,
     #[error("Upper boundary size doesn't match the number of parameters")]
     UpperBoundarySizeMismatch,
     #[error("Constant parameter index is out of bounds: {0}")]
     ParameterBlockStorageError(#[from] ParameterBlockStorageError),
+    #[error(transparent)]
+    ManifoldError(#[from] ManifoldError),
 }
 
 /// Error for [crate::nlls_problem::NllsProblem].
@@ -62,4 +188,76 @@ pub enum CurveFitProblemBuildError {
 pub enum NllsProblemError {
     #[error("No residual blocks added to the problem")]
     NoResidualBlocks,
+    #[error("Cost function evaluation failed")]
+    EvaluationFailed,
+    #[error("Cost function evaluation failed in residual block '{0}'")]
+    NamedEvaluationFailed(String),
+    #[error("Cost function evaluation failed: {0}")]
+    EvaluationError(#[source] CostFunctionError),
+    #[error("Residual block doesn't belong to the problem")]
+    UnknownResidualBlock,
+    #[error("Residual block was built without replaceable loss function")]
+    LossNotReplaceable,
+    #[error("Problem with an evaluation callback cannot be cloned")]
+    EvaluationCallbackNotCloneable,
+    #[error("Loss function is not thread-safe")]
+    LossNotThreadSafe,
+    #[error("Rust function called by Ceres panicked: {0}")]
+    CallbackPanicked(String),
+    #[error("Parameter block #{0} is given more than once")]
+    DuplicateParameterBlock(usize),
+    #[error("Covariance computation failed, the jacobian may be rank deficient")]
+    CovarianceFailed,
+    #[error(
+        "Covariance computation failed, the jacobian is rank deficient along {}",
+        join_directions(.0)
+    )]
+    RankDeficientJacobian(Vec<NullSpaceDirection>),
+    #[error(transparent)]
+    ParameterBlockStorageError(#[from] ParameterBlockStorageError),
+}
+
+/// Error for [crate::cost::CostFunction::evaluate_at].
+#[derive(Debug, thiserror::Error)]
+pub enum CostFunctionEvaluationError {
+    #[error("Cost function expects parameter block sizes {expected:?}, got {actual:?}")]
+    WrongParameterSizes {
+        expected: Vec<usize>,
+        actual: Vec<usize>,
+    },
+    #[error("Cost function evaluation failed")]
+    EvaluationFailed,
+}
+
+/// Error for [crate::gradient_checker::check_gradients].
+#[derive(Debug, thiserror::Error)]
+pub enum GradientCheckError {
+    #[error("Cost function expects parameter block sizes {expected:?}, got {actual:?}")]
+    WrongParameterSizes {
+        expected: Vec<usize>,
+        actual: Vec<usize>,
+    },
+    #[error("Cost function evaluation failed")]
+    EvaluationFailed,
+    #[error("Cost function panicked: {0}")]
+    CallbackPanicked(String),
+}
+
+/// Error for [crate::manifold::Manifold] constructors.
+#[derive(Debug, thiserror::Error)]
+pub enum ManifoldError {
+    #[error("Manifold size must be at least {min}, got {size}")]
+    TooSmall { size: usize, min: usize },
+    #[error("Product manifold needs at least two manifolds, got {count}")]
+    TooFewManifolds { count: usize },
+    #[error("Period must be positive and finite, got {period}")]
+    InvalidPeriod { period: f64 },
+}
+
+fn join_directions(directions: &[NullSpaceDirection]) -> String {
+    directions
+        .iter()
+        .map(|direction| format!("[{direction}]"))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
@@ -0,0 +1,183 @@
+//! Evaluation of [NllsProblem](crate::nlls_problem::NllsProblem) at the current parameter values.
+//!
+//! Use [NllsProblem::evaluate](crate::nlls_problem::NllsProblem::evaluate) to get the cost, the
+//! residuals, the gradient and the jacobian without running the solver.
+
+use crate::residual_block::ResidualBlockId;
+
+/// Options for [NllsProblem::evaluate](crate::nlls_problem::NllsProblem::evaluate).
+#[derive(Clone)]
+pub struct EvaluateOptions {
+    /// Indices of the parameter blocks to compute the gradient for, all the blocks in the order they
+    /// were added to the problem if [None].
+    pub parameter_blocks: Option<Vec<usize>>,
+    /// Residual blocks to evaluate, all the blocks in the order they were added to the problem if
+    /// [None].
+    pub residual_blocks: Option<Vec<ResidualBlockId>>,
+    /// Whether to apply the loss functions of the residual blocks, [true] by default.
+    pub apply_loss_function: bool,
+    /// Number of threads to use, 1 by default. It is ignored for problems which are not created
+    /// with [NllsProblem::new_threadsafe](crate::nlls_problem::NllsProblem::new_threadsafe).
+    pub num_threads: i32,
+    /// Whether to compute the jacobian, [false] by default.
+    pub compute_jacobian: bool,
+}
+
+impl Default for EvaluateOptions {
+    fn default() -> Self {
+        Self {
+            parameter_blocks: None,
+            residual_blocks: None,
+            apply_loss_function: true,
+            num_threads: 1,
+            compute_jacobian: false,
+        }
+    }
+}
+
+/// Result of [NllsProblem::evaluate](crate::nlls_problem::NllsProblem::evaluate).
+#[derive(Clone, Debug)]
+pub struct Evaluation {
+    /// Cost of the evaluated residual blocks.
+    pub cost: f64,
+    /// Residuals of the evaluated residual blocks, concatenated in the order of the blocks.
+    pub residuals: Vec<f64>,
+    /// Gradient of the cost, one vector per evaluated parameter block. The vectors are in the
+    /// tangent spaces of the blocks, so they are shorter than the blocks with manifolds, e.g. the
+    /// ones with constant components.
+    pub gradient: Vec<Vec<f64>>,
+    /// Jacobian of the residuals, [Some] if [EvaluateOptions::compute_jacobian] is set.
+    ///
+    /// Rows correspond to [Evaluation::residuals], columns correspond to the tangent space
+    /// components of the evaluated parameter blocks, concatenated in the order of the blocks.
+    pub jacobian: Option<CrsMatrix>,
+}
+
+impl Evaluation {
+    /// Dense jacobian, [None] if it wasn't computed, see [EvaluateOptions::compute_jacobian].
+    pub fn jacobian_dense(&self) -> Option<DenseMatrix> {
+        self.jacobian.as_ref().map(CrsMatrix::to_dense)
+    }
+}
+
+/// Result of
+/// [NllsProblem::evaluate_residual_block](crate::nlls_problem::NllsProblem::evaluate_residual_block).
+#[derive(Clone, Debug)]
+pub struct ResidualBlockEvaluation {
+    /// Cost of the residual block.
+    pub cost: f64,
+    /// Residuals of the block.
+    pub residuals: Vec<f64>,
+    /// Jacobians of the residuals with respect to the parameter blocks of the residual block, each
+    /// has a row per residual and a column per tangent space component of the parameter block.
+    /// They are [None] for the constant parameter blocks.
+    pub jacobians: Vec<Option<DenseMatrix>>,
+}
+
+/// Cost of a residual block, see
+/// [NllsProblem::residual_block_costs](crate::nlls_problem::NllsProblem::residual_block_costs).
+#[derive(Clone)]
+pub struct ResidualBlockCost {
+    /// Id of the residual block.
+    pub id: ResidualBlockId,
+    /// Name of the residual block, if it was given.
+    pub name: Option<String>,
+    /// Cost of the residual block.
+    pub cost: f64,
+}
+
+/// Sparse matrix in compressed row storage (CSR) format, Rust counterpart of `ceres::CRSMatrix`.
+///
+/// Stored elements of row `i` have column indices
+/// `col_indices[row_offsets[i]..row_offsets[i + 1]]` and values
+/// `values[row_offsets[i]..row_offsets[i + 1]]`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CrsMatrix {
+    pub num_rows: usize,
+    pub num_cols: usize,
+    /// Offsets of the rows in [CrsMatrix::col_indices] and [CrsMatrix::values], has
+    /// `num_rows + 1` elements.
+    pub row_offsets: Vec<usize>,
+    pub col_indices: Vec<usize>,
+    pub values: Vec<f64>,
+}
+
+impl CrsMatrix {
+    /// Number of stored elements.
+    #[inline]
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Column indices and values of the stored elements of the row.
+    pub fn row(&self, row: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let range = self.row_offsets[row]..self.row_offsets[row + 1];
+        self.col_indices[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.values[range].iter().copied())
+    }
+
+    /// Convert to a dense matrix, elements which are not stored are zeros.
+    pub fn to_dense(&self) -> DenseMatrix {
+        let mut dense = DenseMatrix::zeros(self.num_rows, self.num_cols);
+        for row in 0..self.num_rows {
+            for (col, value) in self.row(row) {
+                dense.values[row * self.num_cols + col] = value;
+            }
+        }
+        dense
+    }
+}
+
+/// Dense matrix with elements stored in row-major order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DenseMatrix {
+    pub num_rows: usize,
+    pub num_cols: usize,
+    /// Elements in row-major order, element `(i, j)` has index `i * num_cols + j`.
+    pub values: Vec<f64>,
+}
+
+impl DenseMatrix {
+    pub fn zeros(num_rows: usize, num_cols: usize) -> Self {
+        Self {
+            num_rows,
+            num_cols,
+            values: vec![0.0; num_rows * num_cols],
+        }
+    }
+
+    /// Element `(row, col)`.
+    #[inline]
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        assert!(col < self.num_cols, "column index is out of bounds");
+        self.values[row * self.num_cols + col]
+    }
+
+    /// Elements of the row.
+    #[inline]
+    pub fn row(&self, row: usize) -> &[f64] {
+        &self.values[row * self.num_cols..(row + 1) * self.num_cols]
+    }
+
+    /// Iterator over the rows.
+    pub fn rows(&self) -> impl Iterator<Item = &[f64]> + '_ {
+        // chunks_exact() panics on zero chunk size
+        (0..self.num_rows).map(|row| self.row(row))
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<DenseMatrix> for nalgebra::DMatrix<f64> {
+    fn from(matrix: DenseMatrix) -> Self {
+        Self::from_row_slice(matrix.num_rows, matrix.num_cols, &matrix.values)
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<&CrsMatrix> for nalgebra::DMatrix<f64> {
+    fn from(matrix: &CrsMatrix) -> Self {
+        matrix.to_dense().into()
+    }
+}
@@ -0,0 +1,86 @@
+//! Evaluation callback for [NllsProblem](crate::nlls_problem::NllsProblem).
+//!
+//! Box your callback into [EvaluationCallbackType] and create the problem with
+//! [NllsProblem::with_evaluation_callback](crate::nlls_problem::NllsProblem::with_evaluation_callback).
+//! The callback is called before every evaluation of the residual blocks, so it is a good place to
+//! run an expensive computation shared by many cost functions once per parameter update.
+
+use crate::panic_slot::PanicSlot;
+
+use ceres_solver_sys::cxx::UniquePtr;
+use ceres_solver_sys::ffi;
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::slice;
+
+/// Evaluation callback function type.
+///
+/// The function accepts following arguments:
+/// - parameters - slice of [f64] slices representing the values of all parameter blocks of the
+///   problem at the point where the residuals are going to be evaluated, in the same order as they
+///   were added to the problem.
+/// - evaluate_jacobians - [true] if the Jacobians are going to be requested by the following
+///   evaluations.
+/// - new_evaluation_point - [true] if the parameter values have changed since the previous call.
+pub type EvaluationCallbackType<'a> = Box<dyn Fn(&[&[f64]], bool, bool) + 'a>;
+
+/// Pointers and sizes of the parameter blocks known to the callback.
+pub(crate) type ParameterBlockRegistry = Rc<RefCell<Vec<(*const f64, usize)>>>;
+
+pub(crate) struct EvaluationCallback<'cost> {
+    inner: UniquePtr<ffi::CallbackEvaluationCallback<'cost>>,
+    parameter_blocks: ParameterBlockRegistry,
+    panic: PanicSlot,
+}
+
+impl<'cost> EvaluationCallback<'cost> {
+    /// A panic of the function is caught, its message is available with
+    /// [EvaluationCallback::take_panic].
+    pub(crate) fn new(func: impl Into<EvaluationCallbackType<'cost>>) -> Self {
+        let safe_func = func.into();
+        let panic = PanicSlot::default();
+        let panic_clone = panic.clone();
+        let parameter_blocks = ParameterBlockRegistry::default();
+        let blocks = parameter_blocks.clone();
+        let rust_func: Box<dyn Fn(bool, bool) + 'cost> =
+            Box::new(move |evaluate_jacobians, new_evaluation_point| {
+                let blocks = blocks.borrow();
+                let parameters = blocks
+                    .iter()
+                    .map(|&(pointer, size)| unsafe { slice::from_raw_parts(pointer, size) })
+                    .collect::<Vec<_>>();
+                panic_clone.call(
+                    || safe_func(&parameters, evaluate_jacobians, new_evaluation_point),
+                    || (),
+                );
+            });
+        let inner = ffi::new_callback_evaluation_callback(Box::new(rust_func.into()));
+        Self {
+            inner,
+            parameter_blocks,
+            panic,
+        }
+    }
+
+    /// Message of the first panic of the function since the previous call.
+    pub(crate) fn take_panic(&self) -> Option<String> {
+        self.panic.take()
+    }
+
+    /// Update parameter blocks to be passed to the callback.
+    pub(crate) fn set_parameter_blocks(
+        &self,
+        blocks: impl IntoIterator<Item = (*const f64, usize)>,
+    ) {
+        let mut registry = self.parameter_blocks.borrow_mut();
+        registry.clear();
+        registry.extend(blocks);
+    }
+
+    pub(crate) fn inner_mut(&mut self) -> Pin<&mut ffi::CallbackEvaluationCallback<'cost>> {
+        self.inner.as_mut().expect(
+            "Underlying C++ unique_ptr<CallbackEvaluationCallback> must hold non-null pointer",
+        )
+    }
+}
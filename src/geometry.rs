@@ -0,0 +1,286 @@
+//! Rigid body poses for pose-graph and odometry problems, available with `geometry` Cargo feature.
+//!
+//! A rotation, an element of SO(3), is a unit quaternion `[w, x, y, z]`, see
+//! [rotation](crate::rotation) module. A pose, an element of SE(3), is a 7-vector of the rotation
+//! quaternion followed by the translation `[x, y, z]`, so it is a single parameter block with the
+//! [se3] manifold. Pose `p` maps points from its local frame to the world frame,
+//! `x_world = R(p) x_local + t(p)`.
+//!
+//! The functions are generic over [Scalar], so they can be used in automatically differentiated
+//! cost functions, [BetweenPoses] is the one for relative pose measurements.
+//!
+//! ```rust
+//! use ceres_solver::geometry::{compose, pose_from_parts, se3, BetweenPoses};
+//! use ceres_solver::residual_block::ResidualBlockSpec;
+//! use ceres_solver::rotation::angle_axis_to_quaternion;
+//! use ceres_solver::{NllsProblem, ParameterBlockOrIndex, SolverOptions};
+//! use std::f64::consts::FRAC_PI_2;
+//!
+//! // Odometry: move one meter forward and turn left by 90 degrees, four times
+//! let step = pose_from_parts(&angle_axis_to_quaternion(&[0.0, 0.0, FRAC_PI_2]), &[1.0, 0.0, 0.0]);
+//! // Initial guesses are accumulated from a biased step
+//! let biased = pose_from_parts(&angle_axis_to_quaternion(&[0.0, 0.05, 1.5]), &[1.1, 0.1, 0.0]);
+//! let mut guesses = vec![[1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]];
+//! for i in 1..4 {
+//!     guesses.push(compose(&guesses[i - 1], &biased));
+//! }
+//! let between = || BetweenPoses::new(step).into_cost_function();
+//!
+//! let (mut problem, _block_id) = NllsProblem::new()
+//!     .residual_block_builder()
+//!     .set_cost_fn(between())
+//!     .set_parameters([guesses[0].to_vec(), guesses[1].to_vec()])
+//!     .build_into_problem()
+//!     .unwrap();
+//! for i in 2..4 {
+//!     let parameters: [ParameterBlockOrIndex; 2] = [(i - 1).into(), guesses[i].to_vec().into()];
+//!     problem
+//!         .add_residual_blocks([ResidualBlockSpec::from_cost_fn(between(), parameters)])
+//!         .unwrap();
+//! }
+//! // Loop closure: the fourth step returns to the first pose
+//! problem
+//!     .add_residual_blocks([ResidualBlockSpec::from_cost_fn(between(), [3_usize, 0])])
+//!     .unwrap();
+//! for i in 0..4 {
+//!     problem.set_parameter_block_manifold(i, Some(se3())).unwrap();
+//! }
+//! // The first pose fixes the gauge freedom
+//! problem.set_parameter_block_constant(0).unwrap();
+//!
+//! let solution = problem.solve(&SolverOptions::default()).unwrap();
+//! let third = compose(&step, &step);
+//! for (actual, expected) in solution.parameters[2].iter().zip(&third) {
+//!     assert!((actual - expected).abs() < 1e-6);
+//! }
+//! ```
+
+use crate::autodiff::{AutoDiffCostFunction, AutoDiffCostFunctor, Scalar};
+use crate::manifold::Manifold;
+use crate::rotation::{quaternion_product, unit_quaternion_rotate_point};
+
+/// Manifold of SO(3) rotations stored as unit quaternions `[w, x, y, z]`, it is
+/// [Manifold::quaternion].
+pub fn so3() -> Manifold {
+    Manifold::quaternion()
+}
+
+/// Manifold of SE(3) poses stored as the rotation quaternion `[w, x, y, z]` followed by the
+/// translation, it is the product of [Manifold::quaternion] and three-dimensional
+/// [Manifold::euclidean].
+pub fn se3() -> Manifold {
+    Manifold::product([Manifold::quaternion(), Manifold::euclidean(3)])
+        .expect("Two manifolds are given")
+}
+
+/// Rotation quaternion of the pose.
+pub fn rotation<T: Scalar>(pose: &[T; 7]) -> [T; 4] {
+    [pose[0], pose[1], pose[2], pose[3]]
+}
+
+/// Translation of the pose.
+pub fn translation<T: Scalar>(pose: &[T; 7]) -> [T; 3] {
+    [pose[4], pose[5], pose[6]]
+}
+
+/// Pose of the rotation quaternion and the translation.
+pub fn pose_from_parts<T: Scalar>(rotation: &[T; 4], translation: &[T; 3]) -> [T; 7] {
+    [
+        rotation[0],
+        rotation[1],
+        rotation[2],
+        rotation[3],
+        translation[0],
+        translation[1],
+        translation[2],
+    ]
+}
+
+/// Inverse of the unit quaternion, its conjugate.
+pub fn quaternion_inverse<T: Scalar>(quaternion: &[T; 4]) -> [T; 4] {
+    [
+        quaternion[0],
+        -quaternion[1],
+        -quaternion[2],
+        -quaternion[3],
+    ]
+}
+
+/// Map the point from the local frame of the pose to the world frame, `R x + t`. The rotation
+/// quaternion must be normalized.
+pub fn transform_point<T: Scalar>(pose: &[T; 7], point: &[T; 3]) -> [T; 3] {
+    let rotated = unit_quaternion_rotate_point(&rotation(pose), point);
+    [0, 1, 2].map(|i| rotated[i] + pose[4 + i])
+}
+
+/// Composition of the poses `a b`: the pose `b` given relative to the pose `a` becomes the pose in
+/// the world frame. The rotation quaternions must be normalized.
+pub fn compose<T: Scalar>(a: &[T; 7], b: &[T; 7]) -> [T; 7] {
+    pose_from_parts(
+        &quaternion_product(&rotation(a), &rotation(b)),
+        &transform_point(a, &translation(b)),
+    )
+}
+
+/// Inverse of the pose, it maps points from the world frame to the local frame of the pose. The
+/// rotation quaternion must be normalized.
+pub fn inverse<T: Scalar>(pose: &[T; 7]) -> [T; 7] {
+    let rotation = quaternion_inverse(&rotation(pose));
+    let translation = unit_quaternion_rotate_point(&rotation, &translation(pose)).map(|x| -x);
+    pose_from_parts(&rotation, &translation)
+}
+
+/// Pose `b` relative to the pose `a`, `a^-1 b`, so `compose(a, between(a, b)) = b`. The rotation
+/// quaternions must be normalized.
+pub fn between<T: Scalar>(a: &[T; 7], b: &[T; 7]) -> [T; 7] {
+    compose(&inverse(a), b)
+}
+
+/// Residual of the measured relative pose of two poses, the Rust counterpart of the error term of
+/// Ceres' `pose_graph_3d` example.
+///
+/// The residual block has two parameter blocks of size 7, the poses `a` and `b`, see
+/// [module documentation](crate::geometry) for the layout, and six residuals: the translation
+/// error followed by the rotation error, which is twice the vector part of the error quaternion.
+/// Both are multiplied by the square root of the information matrix, which is the identity by
+/// default.
+#[derive(Clone, Debug)]
+pub struct BetweenPoses {
+    measured: [f64; 7],
+    sqrt_information: [[f64; 6]; 6],
+}
+
+impl BetweenPoses {
+    /// Measurement of the pose `b` relative to the pose `a`, see [between].
+    pub fn new(measured: [f64; 7]) -> Self {
+        let mut sqrt_information = [[0.0; 6]; 6];
+        for (i, row) in sqrt_information.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Self {
+            measured,
+            sqrt_information,
+        }
+    }
+
+    /// Set the square root of the information matrix, i.e. of the inverse covariance matrix of the
+    /// measurement. Rows and columns correspond to the residuals: translation first, rotation
+    /// second.
+    pub fn with_sqrt_information(mut self, sqrt_information: [[f64; 6]; 6]) -> Self {
+        self.sqrt_information = sqrt_information;
+        self
+    }
+
+    /// Wrap into a cost function differentiated automatically.
+    pub fn into_cost_function(self) -> AutoDiffCostFunction<Self, 14> {
+        AutoDiffCostFunction::new(self)
+    }
+}
+
+impl AutoDiffCostFunctor for BetweenPoses {
+    fn residuals<T: Scalar>(&self, parameters: &[&[T]], residuals: &mut [T]) -> bool {
+        let (Ok(a), Ok(b)) = (
+            <[T; 7]>::try_from(parameters[0]),
+            <[T; 7]>::try_from(parameters[1]),
+        ) else {
+            return false;
+        };
+        let estimated = between(&a, &b);
+        let measured = self.measured.map(T::from_f64);
+        let delta_rotation = quaternion_product(
+            &rotation(&measured),
+            &quaternion_inverse(&rotation(&estimated)),
+        );
+        let error = [
+            estimated[4] - measured[4],
+            estimated[5] - measured[5],
+            estimated[6] - measured[6],
+            delta_rotation[1] * 2.0,
+            delta_rotation[2] * 2.0,
+            delta_rotation[3] * 2.0,
+        ];
+        for (residual, row) in residuals.iter_mut().zip(&self.sqrt_information) {
+            *residual = error
+                .iter()
+                .zip(row)
+                .fold(T::zero(), |sum, (&e, &s)| sum + e * s);
+        }
+        true
+    }
+
+    fn num_residuals(&self) -> usize {
+        6
+    }
+
+    fn parameter_sizes(&self) -> Vec<usize> {
+        vec![7, 7]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rotation::angle_axis_to_quaternion;
+
+    use approx::assert_abs_diff_eq;
+
+    fn pose(angle_axis: [f64; 3], translation: [f64; 3]) -> [f64; 7] {
+        pose_from_parts(&angle_axis_to_quaternion(&angle_axis), &translation)
+    }
+
+    #[test]
+    fn pose_algebra() {
+        let a = pose([0.1, -0.2, 0.3], [1.0, 2.0, 3.0]);
+        let b = pose([-0.4, 0.5, 0.2], [-1.0, 0.5, 2.0]);
+        let identity = [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+        assert_abs_diff_eq!(compose(&a, &inverse(&a))[..], identity[..], epsilon = 1e-12);
+        assert_abs_diff_eq!(compose(&inverse(&a), &a)[..], identity[..], epsilon = 1e-12);
+        assert_abs_diff_eq!(compose(&a, &between(&a, &b))[..], b[..], epsilon = 1e-12);
+
+        // Composition of the poses is composition of the point transformations
+        let point = [0.3, -0.7, 1.1];
+        assert_abs_diff_eq!(
+            transform_point(&compose(&a, &b), &point)[..],
+            transform_point(&a, &transform_point(&b, &point))[..],
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            transform_point(&inverse(&a), &transform_point(&a, &point))[..],
+            point[..],
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn between_poses_residual() {
+        let a = pose([0.1, -0.2, 0.3], [1.0, 2.0, 3.0]);
+        let b = pose([-0.4, 0.5, 0.2], [-1.0, 0.5, 2.0]);
+        let functor = BetweenPoses::new(between(&a, &b));
+        let mut residuals = [1.0; 6];
+        assert!(functor.residuals(&[&a[..], &b[..]], &mut residuals));
+        assert_abs_diff_eq!(residuals[..], [0.0; 6][..], epsilon = 1e-12);
+
+        // Small rotation error around z-axis and translation error along x-axis
+        let b_moved = compose(&b, &pose([0.0, 0.0, 1e-3], [0.0; 3]));
+        let b_moved = compose(&pose([0.0; 3], [0.5, 0.0, 0.0]), &b_moved);
+        let functor = functor.with_sqrt_information({
+            let mut sqrt_information = [[0.0; 6]; 6];
+            for (i, row) in sqrt_information.iter_mut().enumerate() {
+                row[i] = 2.0;
+            }
+            sqrt_information
+        });
+        assert!(functor.residuals(&[&a[..], &b_moved[..]], &mut residuals));
+        let translation_error =
+            unit_quaternion_rotate_point(&quaternion_inverse(&rotation(&a)), &[0.5, 0.0, 0.0]);
+        assert_abs_diff_eq!(
+            residuals[..3],
+            translation_error.map(|x| 2.0 * x)[..],
+            epsilon = 1e-12
+        );
+        // Rotation by -1e-3 around z-axis of the frame of b, seen from the frame of a
+        let rotation_error =
+            unit_quaternion_rotate_point(&rotation(&between(&a, &b)), &[0.0, 0.0, -2e-3]);
+        assert_abs_diff_eq!(residuals[3..], rotation_error[..], epsilon = 1e-9);
+    }
+}
@@ -0,0 +1,222 @@
+//! Check analytic jacobians of a [CostFunction] against finite differences.
+//!
+//! [check_gradients] runs `ceres::GradientChecker` for a single evaluation point, which is handy
+//! for unit-testing jacobians without solving a problem with the gradient check enabled.
+//!
+//! ```rust
+//! use ceres_solver::gradient_checker::check_gradients;
+//! use ceres_solver::types::JacobianType;
+//! use ceres_solver::CostFunction;
+//!
+//! // r = x^2 y
+//! struct Product;
+//!
+//! impl CostFunction for Product {
+//!     fn evaluate(
+//!         &self,
+//!         parameters: &[&[f64]],
+//!         residuals: &mut [f64],
+//!         jacobians: JacobianType<'_>,
+//!     ) -> bool {
+//!         let (x, y) = (parameters[0][0], parameters[1][0]);
+//!         residuals[0] = x * x * y;
+//!         if let Some(jacobians) = jacobians {
+//!             if let Some(d_dx) = &mut jacobians[0] {
+//!                 d_dx[0][0] = 2.0 * x * y;
+//!             }
+//!             if let Some(d_dy) = &mut jacobians[1] {
+//!                 // Wrong derivative, must be x^2
+//!                 d_dy[0][0] = x;
+//!             }
+//!         }
+//!         true
+//!     }
+//!
+//!     fn num_residuals(&self) -> usize {
+//!         1
+//!     }
+//!
+//!     fn parameter_sizes(&self) -> Vec<usize> {
+//!         vec![1, 1]
+//!     }
+//! }
+//!
+//! let report = check_gradients(&Product, &[&[3.0], &[2.0]], 1e-6).unwrap();
+//! assert!(!report.passed);
+//! assert!(report.block_max_relative_errors[0] < 1e-6);
+//! assert!(report.block_max_relative_errors[1] > 0.5);
+//! ```
+
+use crate::cost::{CallbackCostFunction, CostFunction};
+use crate::error::GradientCheckError;
+use crate::evaluation::DenseMatrix;
+
+use ceres_solver_sys::ffi;
+
+/// Result of [check_gradients].
+#[derive(Clone, Debug)]
+pub struct GradientCheckReport {
+    /// Whether all jacobian components agree with finite differences within the relative
+    /// precision.
+    pub passed: bool,
+    /// Maximum relative error over all jacobian components.
+    pub max_relative_error: f64,
+    /// Maximum relative error of the jacobian components, one value per parameter block.
+    pub block_max_relative_errors: Vec<f64>,
+    /// Residuals at the evaluation point.
+    pub residuals: Vec<f64>,
+    /// Jacobians computed by the cost function, one per parameter block.
+    pub jacobians: Vec<DenseMatrix>,
+    /// Jacobians computed with central finite differences, one per parameter block.
+    pub numeric_jacobians: Vec<DenseMatrix>,
+    /// Human-readable description of the mismatching components, empty if the check passed.
+    pub error_log: String,
+}
+
+/// Compare jacobians of the cost function at the given parameter values with finite differences.
+///
+/// The relative error of a jacobian component is `|a - n| / max(|a|, |n|)`, where `a` and `n` are
+/// the analytic and numeric values, or `|a - n|` if any of them is zero. The check passes if all
+/// the errors are less than `relative_precision`.
+///
+/// # Errors
+/// - [GradientCheckError::WrongParameterSizes] if `parameters` doesn't match
+///   [CostFunction::parameter_sizes].
+/// - [GradientCheckError::EvaluationFailed] if the cost function returns [false].
+/// - [GradientCheckError::CallbackPanicked] if the cost function panics.
+pub fn check_gradients(
+    cost: &impl CostFunction,
+    parameters: &[&[f64]],
+    relative_precision: f64,
+) -> Result<GradientCheckReport, GradientCheckError> {
+    let parameter_sizes = cost.parameter_sizes();
+    let actual: Vec<_> = parameters.iter().map(|block| block.len()).collect();
+    if actual != parameter_sizes {
+        return Err(GradientCheckError::WrongParameterSizes {
+            expected: parameter_sizes,
+            actual,
+        });
+    }
+    let num_residuals = cost.num_residuals();
+    let callback = CallbackCostFunction::new(
+        |parameters, residuals, jacobians| cost.evaluate(parameters, residuals, jacobians),
+        parameter_sizes,
+        num_residuals,
+        None,
+    );
+    let parameter_pointers: Vec<_> = parameters.iter().map(|block| block.as_ptr()).collect();
+    let results = unsafe {
+        ffi::check_gradients(
+            &*callback.as_ptr(),
+            parameter_pointers.as_ptr(),
+            relative_precision,
+        )
+    };
+    if let Some(message) = callback.take_panic() {
+        return Err(GradientCheckError::CallbackPanicked(message));
+    }
+    let results = results
+        .as_ref()
+        .ok_or(GradientCheckError::EvaluationFailed)?;
+
+    let residuals = results.residuals().as_slice().to_vec();
+    let jacobian = |values: &[f64]| DenseMatrix {
+        num_rows: residuals.len(),
+        num_cols: values.len().checked_div(residuals.len()).unwrap_or(0),
+        values: values.to_vec(),
+    };
+    let jacobians: Vec<_> = (0..results.num_jacobians())
+        .map(|index| jacobian(results.jacobian(index).as_slice()))
+        .collect();
+    let numeric_jacobians: Vec<_> = (0..results.num_jacobians())
+        .map(|index| jacobian(results.numeric_jacobian(index).as_slice()))
+        .collect();
+    let block_max_relative_errors = jacobians
+        .iter()
+        .zip(numeric_jacobians.iter())
+        .map(|(analytic, numeric)| {
+            analytic
+                .values
+                .iter()
+                .zip(numeric.values.iter())
+                .map(|(&a, &n)| relative_error(a, n))
+                .fold(0.0, f64::max)
+        })
+        .collect();
+    Ok(GradientCheckReport {
+        passed: results.passed(),
+        max_relative_error: results.maximum_relative_error(),
+        block_max_relative_errors,
+        residuals,
+        jacobians,
+        numeric_jacobians,
+        error_log: results.error_log().to_string_lossy().into_owned(),
+    })
+}
+
+/// Relative error as defined by `ceres::GradientChecker`.
+fn relative_error(analytic: f64, numeric: f64) -> f64 {
+    let absolute_error = (analytic - numeric).abs();
+    if analytic == 0.0 || numeric == 0.0 {
+        absolute_error
+    } else {
+        absolute_error / f64::max(analytic.abs(), numeric.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::{CostFunctionType, DynamicCostFunction};
+
+    use approx::assert_abs_diff_eq;
+
+    fn exp_cost(wrong_derivative: bool) -> DynamicCostFunction<'static> {
+        let func: CostFunctionType = Box::new(move |parameters, residuals, jacobians| {
+            let x = parameters[0][0];
+            residuals[0] = x.exp();
+            residuals[1] = x * x;
+            if let Some(jacobians) = jacobians {
+                if let Some(d_dx) = &mut jacobians[0] {
+                    d_dx[0][0] = x.exp();
+                    d_dx[1][0] = if wrong_derivative { x } else { 2.0 * x };
+                }
+            }
+            true
+        });
+        let mut cost = DynamicCostFunction::new(func);
+        cost.add_parameter_block(1);
+        cost.set_num_residuals(2);
+        cost
+    }
+
+    #[test]
+    fn correct_jacobian() {
+        let report = check_gradients(&exp_cost(false), &[&[0.5]], 1e-6).unwrap();
+        assert!(report.passed, "{}", report.error_log);
+        assert_eq!(report.block_max_relative_errors.len(), 1);
+        assert!(report.max_relative_error < 1e-6);
+        assert_abs_diff_eq!(report.residuals[0], 0.5_f64.exp());
+        assert_eq!(report.jacobians[0].num_rows, 2);
+        assert_eq!(report.jacobians[0].num_cols, 1);
+        assert_abs_diff_eq!(report.numeric_jacobians[0].values[1], 1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn wrong_jacobian() {
+        let report = check_gradients(&exp_cost(true), &[&[0.5]], 1e-6).unwrap();
+        assert!(!report.passed);
+        assert!(!report.error_log.is_empty());
+        assert_abs_diff_eq!(report.block_max_relative_errors[0], 0.5, epsilon = 1e-6);
+        assert_abs_diff_eq!(report.max_relative_error, 0.5, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn wrong_parameter_sizes() {
+        let error = check_gradients(&exp_cost(false), &[&[0.5, 1.0]], 1e-6).unwrap_err();
+        assert!(matches!(
+            error,
+            GradientCheckError::WrongParameterSizes { .. }
+        ));
+    }
+}
@@ -0,0 +1,592 @@
+//! Dual numbers for automatic differentiation, see [autodiff](crate::autodiff).
+//!
+//! [Jet] implements [num_traits::Float], so generic numeric code can be called from
+//! [AutoDiffCostFunctor](crate::autodiff::AutoDiffCostFunctor) residuals without modification.
+//! Import [num_traits::Float] to use its math functions with a concrete [Jet].
+
+use crate::autodiff::Scalar;
+
+use num_traits::{Float, Num, NumCast, One, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::iter::{Product, Sum};
+use std::num::FpCategory;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, Sub, SubAssign};
+
+/// Dual number holding a value and its derivatives with respect to `N` variables, the Rust
+/// counterpart of `ceres::Jet`.
+///
+/// Comparison operators compare the values only, ignoring the derivatives.
+#[derive(Clone, Copy, Debug)]
+pub struct Jet<const N: usize> {
+    /// Value.
+    pub a: f64,
+    /// Derivatives of the value.
+    pub v: [f64; N],
+}
+
+impl<const N: usize> Jet<N> {
+    /// Constant, all derivatives are zero.
+    pub fn constant(a: f64) -> Self {
+        Self { a, v: [0.0; N] }
+    }
+
+    /// Variable with the given index, its derivative with respect to itself is one.
+    pub fn variable(a: f64, index: usize) -> Self {
+        let mut jet = Self::constant(a);
+        jet.v[index] = 1.0;
+        jet
+    }
+
+    /// Apply a function with the given value and derivative at `self.a`, the chain rule.
+    fn chain(self, value: f64, derivative: f64) -> Self {
+        Self {
+            a: value,
+            v: self.v.map(|v| v * derivative),
+        }
+    }
+}
+
+impl<const N: usize> PartialEq for Jet<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.a == other.a
+    }
+}
+
+impl<const N: usize> PartialOrd for Jet<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.a.partial_cmp(&other.a)
+    }
+}
+
+impl<const N: usize> Add for Jet<N> {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self {
+        self += rhs;
+        self
+    }
+}
+
+impl<const N: usize> Sub for Jet<N> {
+    type Output = Self;
+
+    fn sub(mut self, rhs: Self) -> Self {
+        self -= rhs;
+        self
+    }
+}
+
+impl<const N: usize> Mul for Jet<N> {
+    type Output = Self;
+
+    fn mul(mut self, rhs: Self) -> Self {
+        self *= rhs;
+        self
+    }
+}
+
+impl<const N: usize> Div for Jet<N> {
+    type Output = Self;
+
+    fn div(mut self, rhs: Self) -> Self {
+        self /= rhs;
+        self
+    }
+}
+
+impl<const N: usize> Neg for Jet<N> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            a: -self.a,
+            v: self.v.map(|v| -v),
+        }
+    }
+}
+
+impl<const N: usize> AddAssign for Jet<N> {
+    fn add_assign(&mut self, rhs: Self) {
+        self.a += rhs.a;
+        for (v, rhs_v) in self.v.iter_mut().zip(rhs.v) {
+            *v += rhs_v;
+        }
+    }
+}
+
+impl<const N: usize> SubAssign for Jet<N> {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.a -= rhs.a;
+        for (v, rhs_v) in self.v.iter_mut().zip(rhs.v) {
+            *v -= rhs_v;
+        }
+    }
+}
+
+impl<const N: usize> MulAssign for Jet<N> {
+    fn mul_assign(&mut self, rhs: Self) {
+        for (v, rhs_v) in self.v.iter_mut().zip(rhs.v) {
+            *v = *v * rhs.a + self.a * rhs_v;
+        }
+        self.a *= rhs.a;
+    }
+}
+
+impl<const N: usize> DivAssign for Jet<N> {
+    fn div_assign(&mut self, rhs: Self) {
+        let a = self.a / rhs.a;
+        for (v, rhs_v) in self.v.iter_mut().zip(rhs.v) {
+            *v = (*v - a * rhs_v) / rhs.a;
+        }
+        self.a = a;
+    }
+}
+
+impl<const N: usize> Add<f64> for Jet<N> {
+    type Output = Self;
+
+    fn add(mut self, rhs: f64) -> Self {
+        self.a += rhs;
+        self
+    }
+}
+
+impl<const N: usize> Sub<f64> for Jet<N> {
+    type Output = Self;
+
+    fn sub(mut self, rhs: f64) -> Self {
+        self.a -= rhs;
+        self
+    }
+}
+
+impl<const N: usize> Mul<f64> for Jet<N> {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        self.chain(self.a * rhs, rhs)
+    }
+}
+
+impl<const N: usize> Div<f64> for Jet<N> {
+    type Output = Self;
+
+    fn div(self, rhs: f64) -> Self {
+        self.chain(self.a / rhs, 1.0 / rhs)
+    }
+}
+
+impl<const N: usize> Rem for Jet<N> {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self {
+        // x % y = x - trunc(x / y) * y
+        let quotient = (self.a / rhs.a).trunc();
+        let mut jet = Self::constant(self.a % rhs.a);
+        for ((v, self_v), rhs_v) in jet.v.iter_mut().zip(self.v).zip(rhs.v) {
+            *v = self_v - quotient * rhs_v;
+        }
+        jet
+    }
+}
+
+impl<const N: usize> Sum for Jet<N> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::constant(0.0), Add::add)
+    }
+}
+
+impl<const N: usize> Product for Jet<N> {
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::constant(1.0), Mul::mul)
+    }
+}
+
+impl<const N: usize> Zero for Jet<N> {
+    fn zero() -> Self {
+        Self::constant(0.0)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.a == 0.0
+    }
+}
+
+impl<const N: usize> One for Jet<N> {
+    fn one() -> Self {
+        Self::constant(1.0)
+    }
+}
+
+impl<const N: usize> Num for Jet<N> {
+    type FromStrRadixErr = <f64 as Num>::FromStrRadixErr;
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        f64::from_str_radix(s, radix).map(Self::constant)
+    }
+}
+
+impl<const N: usize> ToPrimitive for Jet<N> {
+    fn to_i64(&self) -> Option<i64> {
+        self.a.to_i64()
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        self.a.to_u64()
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        Some(self.a)
+    }
+}
+
+impl<const N: usize> NumCast for Jet<N> {
+    fn from<T: ToPrimitive>(n: T) -> Option<Self> {
+        n.to_f64().map(Self::constant)
+    }
+}
+
+impl<const N: usize> Float for Jet<N> {
+    fn nan() -> Self {
+        Self::constant(f64::NAN)
+    }
+
+    fn infinity() -> Self {
+        Self::constant(f64::INFINITY)
+    }
+
+    fn neg_infinity() -> Self {
+        Self::constant(f64::NEG_INFINITY)
+    }
+
+    fn neg_zero() -> Self {
+        Self::constant(-0.0)
+    }
+
+    fn min_value() -> Self {
+        Self::constant(f64::MIN)
+    }
+
+    fn min_positive_value() -> Self {
+        Self::constant(f64::MIN_POSITIVE)
+    }
+
+    fn max_value() -> Self {
+        Self::constant(f64::MAX)
+    }
+
+    fn is_nan(self) -> bool {
+        self.a.is_nan()
+    }
+
+    fn is_infinite(self) -> bool {
+        self.a.is_infinite()
+    }
+
+    fn is_finite(self) -> bool {
+        self.a.is_finite()
+    }
+
+    fn is_normal(self) -> bool {
+        self.a.is_normal()
+    }
+
+    fn classify(self) -> FpCategory {
+        self.a.classify()
+    }
+
+    // Rounding functions are piecewise constant, so their derivatives are zero.
+
+    fn floor(self) -> Self {
+        Self::constant(self.a.floor())
+    }
+
+    fn ceil(self) -> Self {
+        Self::constant(self.a.ceil())
+    }
+
+    fn round(self) -> Self {
+        Self::constant(self.a.round())
+    }
+
+    fn trunc(self) -> Self {
+        Self::constant(self.a.trunc())
+    }
+
+    fn fract(self) -> Self {
+        Self {
+            a: self.a.fract(),
+            v: self.v,
+        }
+    }
+
+    fn abs(self) -> Self {
+        if self.a < 0.0 {
+            -self
+        } else {
+            self
+        }
+    }
+
+    fn signum(self) -> Self {
+        Self::constant(self.a.signum())
+    }
+
+    fn is_sign_positive(self) -> bool {
+        self.a.is_sign_positive()
+    }
+
+    fn is_sign_negative(self) -> bool {
+        self.a.is_sign_negative()
+    }
+
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        self * a + b
+    }
+
+    fn recip(self) -> Self {
+        self.chain(self.a.recip(), -1.0 / (self.a * self.a))
+    }
+
+    fn powi(self, n: i32) -> Self {
+        // Avoid NaN from 0 * inf at zero
+        let derivative = if n == 0 {
+            0.0
+        } else {
+            n as f64 * self.a.powi(n - 1)
+        };
+        self.chain(self.a.powi(n), derivative)
+    }
+
+    fn powf(self, n: Self) -> Self {
+        let value = self.a.powf(n.a);
+        let d_dself = n.a * self.a.powf(n.a - 1.0);
+        if n.v.iter().all(|&v| v == 0.0) {
+            // Avoid NaN from ln(0) for a constant exponent
+            return self.chain(value, d_dself);
+        }
+        let d_dn = value * self.a.ln();
+        let mut jet = Self::constant(value);
+        for ((v, self_v), n_v) in jet.v.iter_mut().zip(self.v).zip(n.v) {
+            *v = d_dself * self_v + d_dn * n_v;
+        }
+        jet
+    }
+
+    fn sqrt(self) -> Self {
+        let sqrt = self.a.sqrt();
+        self.chain(sqrt, 0.5 / sqrt)
+    }
+
+    fn exp(self) -> Self {
+        let exp = self.a.exp();
+        self.chain(exp, exp)
+    }
+
+    fn exp2(self) -> Self {
+        let exp2 = self.a.exp2();
+        self.chain(exp2, exp2 * std::f64::consts::LN_2)
+    }
+
+    fn ln(self) -> Self {
+        self.chain(self.a.ln(), 1.0 / self.a)
+    }
+
+    fn log(self, base: Self) -> Self {
+        self.ln() / base.ln()
+    }
+
+    fn log2(self) -> Self {
+        self.chain(self.a.log2(), 1.0 / (self.a * std::f64::consts::LN_2))
+    }
+
+    fn log10(self) -> Self {
+        self.chain(self.a.log10(), 1.0 / (self.a * std::f64::consts::LN_10))
+    }
+
+    fn max(self, other: Self) -> Self {
+        if other.a > self.a {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn min(self, other: Self) -> Self {
+        if other.a < self.a {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn abs_sub(self, other: Self) -> Self {
+        if self.a > other.a {
+            self - other
+        } else {
+            Self::zero()
+        }
+    }
+
+    fn cbrt(self) -> Self {
+        let cbrt = self.a.cbrt();
+        self.chain(cbrt, 1.0 / (3.0 * cbrt * cbrt))
+    }
+
+    fn hypot(self, other: Self) -> Self {
+        let hypot = self.a.hypot(other.a);
+        let mut jet = Self::constant(hypot);
+        for ((v, self_v), other_v) in jet.v.iter_mut().zip(self.v).zip(other.v) {
+            *v = (self.a * self_v + other.a * other_v) / hypot;
+        }
+        jet
+    }
+
+    fn sin(self) -> Self {
+        self.chain(self.a.sin(), self.a.cos())
+    }
+
+    fn cos(self) -> Self {
+        self.chain(self.a.cos(), -self.a.sin())
+    }
+
+    fn tan(self) -> Self {
+        let tan = self.a.tan();
+        self.chain(tan, 1.0 + tan * tan)
+    }
+
+    fn asin(self) -> Self {
+        self.chain(self.a.asin(), 1.0 / (1.0 - self.a * self.a).sqrt())
+    }
+
+    fn acos(self) -> Self {
+        self.chain(self.a.acos(), -1.0 / (1.0 - self.a * self.a).sqrt())
+    }
+
+    fn atan(self) -> Self {
+        self.chain(self.a.atan(), 1.0 / (1.0 + self.a * self.a))
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        let norm2 = self.a * self.a + other.a * other.a;
+        let mut jet = Self::constant(self.a.atan2(other.a));
+        for ((v, self_v), other_v) in jet.v.iter_mut().zip(self.v).zip(other.v) {
+            *v = (other.a * self_v - self.a * other_v) / norm2;
+        }
+        jet
+    }
+
+    fn sin_cos(self) -> (Self, Self) {
+        (self.sin(), self.cos())
+    }
+
+    fn exp_m1(self) -> Self {
+        self.chain(self.a.exp_m1(), self.a.exp())
+    }
+
+    fn ln_1p(self) -> Self {
+        self.chain(self.a.ln_1p(), 1.0 / (1.0 + self.a))
+    }
+
+    fn sinh(self) -> Self {
+        self.chain(self.a.sinh(), self.a.cosh())
+    }
+
+    fn cosh(self) -> Self {
+        self.chain(self.a.cosh(), self.a.sinh())
+    }
+
+    fn tanh(self) -> Self {
+        let tanh = self.a.tanh();
+        self.chain(tanh, 1.0 - tanh * tanh)
+    }
+
+    fn asinh(self) -> Self {
+        self.chain(self.a.asinh(), 1.0 / (self.a * self.a + 1.0).sqrt())
+    }
+
+    fn acosh(self) -> Self {
+        self.chain(self.a.acosh(), 1.0 / (self.a * self.a - 1.0).sqrt())
+    }
+
+    fn atanh(self) -> Self {
+        self.chain(self.a.atanh(), 1.0 / (1.0 - self.a * self.a))
+    }
+
+    fn integer_decode(self) -> (u64, i16, i8) {
+        Float::integer_decode(self.a)
+    }
+}
+
+impl<const N: usize> Scalar for Jet<N> {
+    fn from_f64(value: f64) -> Self {
+        Self::constant(value)
+    }
+
+    fn value(self) -> f64 {
+        self.a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    /// Compare derivative of `f` at `x` with the central finite difference.
+    fn check_derivative(f: impl Fn(Jet<1>) -> Jet<1>, x: f64) {
+        const STEP: f64 = 1e-6;
+        let jet = f(Jet::variable(x, 0));
+        let numeric = (f(Jet::constant(x + STEP)).a - f(Jet::constant(x - STEP)).a) / (2.0 * STEP);
+        assert_abs_diff_eq!(jet.v[0], numeric, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn derivatives() {
+        let x = 0.3;
+        check_derivative(|x| x * x * 3.0 - x / 2.0 + 1.0, x);
+        check_derivative(|x| Jet::from_f64(1.0) / x, x);
+        check_derivative(|x| (-x).abs(), x);
+        check_derivative(Float::sqrt, x);
+        check_derivative(Float::exp, x);
+        check_derivative(Float::ln, x);
+        check_derivative(|x| x.powi(3), x);
+        check_derivative(|x| x.powi(0), x);
+        check_derivative(|x| x.powi(0), 0.0);
+        check_derivative(|x| x.powf(Jet::constant(2.5)), x);
+        check_derivative(|x| x.powf(x), x);
+        check_derivative(|x| x % Jet::constant(0.2), x);
+        check_derivative(Float::exp2, x);
+        check_derivative(Float::log2, x);
+        check_derivative(Float::log10, x);
+        check_derivative(Float::recip, x);
+        check_derivative(Float::cbrt, x);
+        check_derivative(|x| x.hypot(x * 2.0 + 1.0), x);
+        check_derivative(Float::exp_m1, x);
+        check_derivative(Float::ln_1p, x);
+        check_derivative(Float::asinh, x);
+        check_derivative(|x| (x + 1.0).acosh(), x);
+        check_derivative(Float::atanh, x);
+        check_derivative(Float::sin, x);
+        check_derivative(Float::cos, x);
+        check_derivative(Float::tan, x);
+        check_derivative(Float::asin, x);
+        check_derivative(Float::acos, x);
+        check_derivative(Float::atan, x);
+        check_derivative(|x| x.atan2(x * x + 1.0), x);
+        check_derivative(Float::sinh, x);
+        check_derivative(Float::cosh, x);
+        check_derivative(Float::tanh, x);
+    }
+
+    #[test]
+    fn partial_derivatives() {
+        let x = Jet::<2>::variable(2.0, 0);
+        let y = Jet::<2>::variable(3.0, 1);
+        let f = x * y + x / y;
+        assert_abs_diff_eq!(f.a, 6.0 + 2.0 / 3.0);
+        assert_abs_diff_eq!(f.v[0], 3.0 + 1.0 / 3.0);
+        assert_abs_diff_eq!(f.v[1], 2.0 - 2.0 / 9.0);
+    }
+}
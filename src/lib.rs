@@ -2,8 +2,12 @@
 //! ## Safe Rust bindings for [Ceres Solver](http://ceres-solver.org)
 //!
 //! Solve large and small non-linear optimization problems in Rust.
-//! See [NllsProblem] for general non-linear least squares problem and
-//! [CurveFitProblem1D] for a multiparametric 1-D curve fitting.
+//! See [NllsProblem] for general non-linear least squares problem,
+//! [CurveFitProblem1D] for a multiparametric 1-D curve fitting, [MultiCurveFitProblem] for fitting
+//! several datasets with shared parameters, [SplineFitProblem] for non-parametric B-spline fitting
+//! and [SurfaceFitProblem] for 2-D surface fitting. With
+//! the `complex` Cargo feature, `complex_fit::ComplexCurveFitProblem` fits complex-valued data,
+//! and with the `rayon` feature `batch::fit_many` fits many independent curves in parallel.
 //!
 //! # Examples
 //!
@@ -125,19 +129,59 @@
 //!
 //! See more examples in [curve_fit::CurveFitProblem1DBuilder]'s documentation.
 
-pub use cost::CostFunctionType;
+// Paths generated by ceres-solver-derive macros refer to the crate by name
+#[cfg(feature = "derive")]
+extern crate self as ceres_solver;
+
+pub use context::Context;
+pub use cost::{
+    CostFunction, CostFunctionType, FallibleCostFunctionType, ThreadSafeCostFunctionType,
+};
 pub use curve_fit::{CurveFitProblem1D, CurveFunctionType};
-pub use loss::{LossFunction, LossFunctionType};
+pub use evaluation_callback::EvaluationCallbackType;
+pub use logging::init_logging;
+pub use loss::{LossFunction, LossFunctionType, LossFunctionWrapper, ThreadSafeLossFunctionType};
+pub use multi_curve_fit::{MultiCurveFitProblem, MultiCurveParameter};
 pub use nlls_problem::NllsProblem;
-pub use parameter_block::{ParameterBlock, ParameterBlockOrIndex};
+pub use parameter_block::{BorrowedParameterBlock, ParameterBlock, ParameterBlockOrIndex};
 pub use solver::SolverOptions;
+pub use spline_fit::{BSpline, SplineFitProblem};
+pub use surface_fit::{SurfaceFitProblem, SurfaceFunctionType};
 
+pub mod autodiff;
+#[cfg(feature = "rayon")]
+pub mod batch;
+#[cfg(feature = "complex")]
+pub mod complex_fit;
+pub mod context;
 pub mod cost;
+pub mod covariance;
 pub mod curve_fit;
+pub mod dump;
 pub mod error;
+pub mod evaluation;
+pub mod evaluation_callback;
+#[cfg(feature = "geometry")]
+pub mod geometry;
+pub mod gradient_checker;
+pub mod jet;
+pub mod logging;
 pub mod loss;
+pub mod manifold;
+pub mod multi_curve_fit;
+pub mod multistart;
 pub mod nlls_problem;
+mod panic_slot;
 pub mod parameter_block;
+pub mod piecewise;
+#[cfg(feature = "indicatif")]
+pub mod progress_bar;
 pub mod residual_block;
+pub mod rotation;
+pub mod sigma_clipping;
 pub mod solver;
+pub mod spline_fit;
+pub mod streaming;
+pub mod surface_fit;
+pub mod template;
 pub mod types;
@@ -0,0 +1,132 @@
+//! Control of Ceres Solver logging.
+//!
+//! Ceres logs with [glog](https://github.com/google/glog), which writes to stderr by default.
+//! Use [init_logging] to set the minimum severity and to silence stderr output. With the `log`
+//! feature enabled, `redirect_to_log()` routes these messages into the `log` crate.
+
+use ceres_solver_sys::ffi;
+#[cfg(feature = "log")]
+use ceres_solver_sys::LogSinkFunction;
+
+/// Severity of glog messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogSeverity {
+    Info = 0,
+    Warning = 1,
+    Error = 2,
+    Fatal = 3,
+}
+
+/// Initialize Ceres (glog) logging.
+///
+/// Arguments:
+/// - program_name - name glog uses in the log records.
+/// - min_level - messages with lower severity are not logged.
+/// - log_to_stderr - if [true] messages are written to stderr, otherwise they are not written
+///   anywhere but to the sink installed with `redirect_to_log()`.
+///
+/// glog is initialized only by the first call of this function or `redirect_to_log()`, subsequent
+/// calls change the minimum severity and the destination only. Call it before the first solve to
+/// avoid "Logging before InitGoogleLogging()" warning.
+pub fn init_logging(program_name: &str, min_level: LogSeverity, log_to_stderr: bool) {
+    ffi::init_logging(program_name, min_level as i32, log_to_stderr);
+}
+
+/// Target of the log records emitted for Ceres messages.
+#[cfg(feature = "log")]
+pub const LOG_TARGET: &str = "ceres";
+
+/// Route all Ceres log messages into the [log] crate.
+///
+/// glog stops writing to stderr and log files after this call. Messages are emitted with
+/// [LOG_TARGET] target: glog INFO severity becomes [log::Level::Info], WARNING becomes
+/// [log::Level::Warn], ERROR and FATAL become [log::Level::Error]. Per-iteration progress is logged
+/// with [log::Level::Info] when the solver is configured with
+/// [LoggingType::PER_MINIMIZER_ITERATION](crate::solver::LoggingType::PER_MINIMIZER_ITERATION).
+///
+/// Calling the function again replaces the previously installed sink, so no message is duplicated.
+#[cfg(feature = "log")]
+pub fn redirect_to_log() {
+    let sink: LogSinkFunction = Box::new(|severity, base_filename, line, message| {
+        let level = match severity {
+            0 => log::Level::Info,
+            1 => log::Level::Warn,
+            _ => log::Level::Error,
+        };
+        if level > log::max_level() {
+            return;
+        }
+        let file = String::from_utf8_lossy(base_filename);
+        let message = String::from_utf8_lossy(message);
+        log::logger().log(
+            &log::Record::builder()
+                .level(level)
+                .target(LOG_TARGET)
+                .file(Some(&file))
+                .line(u32::try_from(line).ok())
+                .args(format_args!("{message}"))
+                .build(),
+        );
+    });
+    ffi::set_log_sink(Box::new(sink.into()));
+}
+
+#[cfg(all(test, feature = "log"))]
+mod tests {
+    use super::*;
+    use crate::cost::CostFunctionType;
+    use crate::nlls_problem::NllsProblem;
+    use crate::solver::{LoggingType, SolverOptions};
+
+    use std::sync::Mutex;
+
+    static MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct TestLogger;
+
+    impl log::Log for TestLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == LOG_TARGET
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                MESSAGES.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn solver_progress_goes_to_log() {
+        log::set_logger(&TestLogger).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+        redirect_to_log();
+
+        let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+            residuals[0] = parameters[0][0] - 2.0;
+            if let Some(jacobians) = jacobians {
+                if let Some(d_dx) = &mut jacobians[0] {
+                    d_dx[0][0] = 1.0;
+                }
+            }
+            true
+        });
+        let options = SolverOptions::builder()
+            .logging_type(LoggingType::PER_MINIMIZER_ITERATION)
+            .build()
+            .unwrap();
+        NllsProblem::new()
+            .residual_block_builder()
+            .set_cost(cost, 1)
+            .set_parameters([vec![0.0]])
+            .build_into_problem()
+            .unwrap()
+            .0
+            .solve(&options)
+            .unwrap();
+
+        assert!(!MESSAGES.lock().unwrap().is_empty());
+    }
+}
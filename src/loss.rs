@@ -6,16 +6,30 @@
 //! functions boxed into [LossFunctionType] and Ceres stock functions having one or two
 //! scale parameters.
 
+use crate::error::NllsProblemError;
+use crate::panic_slot::PanicSlot;
+
 use ceres_solver_sys::cxx::UniquePtr;
 use ceres_solver_sys::ffi;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 pub type LossFunctionType = Box<dyn Fn(f64, &mut [f64; 3])>;
 
+/// Custom loss function which can be called from several threads at once, see
+/// [LossFunction::custom_threadsafe].
+pub type ThreadSafeLossFunctionType = Box<dyn Fn(f64, &mut [f64; 3]) + Send + Sync>;
+
 /// Loss function for [NllsProblem](crate::nlls_problem::NllsProblem) and
 /// [CurveFitProblem1D](crate::curve_fit::CurveFitProblem1D), it is a transformation of the squared
 /// residuals which is generally used to make the solver less sensitive to outliers. This enum has
 /// two flavours: user specified function and Ceres stock function.
-pub struct LossFunction(UniquePtr<ffi::LossFunction>);
+pub struct LossFunction {
+    inner: UniquePtr<ffi::LossFunction>,
+    threadsafe: bool,
+    /// [None] for stock functions.
+    panic: Option<PanicSlot>,
+}
 
 impl LossFunction {
     /// Create a [LossFunction] to handle a custom loss function.
@@ -26,46 +40,490 @@ impl LossFunction {
     ///   details at
     ///   <http://ceres-solver.org/nnls_modeling.html#_CPPv4N5ceres12LossFunctionE>.
     pub fn custom(func: impl Into<LossFunctionType>) -> Self {
-        let safe_func = func.into();
+        Self::callback(func.into(), false)
+    }
+
+    /// Create a [LossFunction] to handle a custom loss function which can be called from several
+    /// threads at once, so it can be used with
+    /// [NllsProblem::new_threadsafe](crate::nlls_problem::NllsProblem::new_threadsafe). See
+    /// [LossFunction::custom] for the arguments.
+    pub fn custom_threadsafe(func: impl Into<ThreadSafeLossFunctionType>) -> Self {
+        Self::callback(func.into(), true)
+    }
+
+    /// A panic of the function makes the loss NaN, so the evaluation fails, its message is
+    /// available with [LossFunction::take_panic].
+    fn callback(safe_func: LossFunctionType, threadsafe: bool) -> Self {
+        let panic = PanicSlot::default();
+        let panic_clone = panic.clone();
         let rust_func: Box<dyn Fn(f64, *mut f64)> = Box::new(move |sq_norm, out_ptr| {
             let out = unsafe { &mut *(out_ptr as *mut [f64; 3]) };
-            safe_func(sq_norm, out);
+            panic_clone.call(
+                || safe_func(sq_norm, out),
+                || unsafe { *(out_ptr as *mut [f64; 3]) = [f64::NAN; 3] },
+            );
         });
-        let inner = ffi::new_callback_loss_function(Box::new(rust_func.into()));
-        Self(inner)
+        Self {
+            inner: ffi::new_callback_loss_function(Box::new(rust_func.into())),
+            threadsafe,
+            panic: Some(panic),
+        }
+    }
+
+    /// Stock Ceres loss functions are thread-safe.
+    fn stock(inner: UniquePtr<ffi::LossFunction>) -> Self {
+        Self {
+            inner,
+            threadsafe: true,
+            panic: None,
+        }
+    }
+
+    /// Whether the loss function can be called from several threads at once, it is [true] for
+    /// stock functions and functions created with [LossFunction::custom_threadsafe].
+    pub fn is_threadsafe(&self) -> bool {
+        self.threadsafe
     }
 
     /// Huber loss function, see details at <http://ceres-solver.org/nnls_modeling.html#_CPPv4N5ceres9HuberLossE>.
     pub fn huber(a: f64) -> Self {
-        Self(ffi::new_huber_loss(a))
+        Self::stock(ffi::new_huber_loss(a))
     }
 
     /// Soft L1 loss function, see details at <http://ceres-solver.org/nnls_modeling.html#_CPPv4N5ceres12SoftLOneLossE>.
     pub fn soft_l1(a: f64) -> Self {
-        Self(ffi::new_soft_l_one_loss(a))
+        Self::stock(ffi::new_soft_l_one_loss(a))
     }
 
     /// log(1+s) loss function, see details at <http://ceres-solver.org/nnls_modeling.html#_CPPv4N5ceres10CauchyLossE>.
     pub fn cauchy(a: f64) -> Self {
-        Self(ffi::new_cauchy_loss(a))
+        Self::stock(ffi::new_cauchy_loss(a))
     }
 
     /// Arctangent loss function, see details at <http://ceres-solver.org/nnls_modeling.html#_CPPv4N5ceres10ArctanLossE>.
     pub fn arctan(a: f64) -> Self {
-        Self(ffi::new_arctan_loss(a))
+        Self::stock(ffi::new_arctan_loss(a))
     }
 
     /// Tolerant loss function, see details at <http://ceres-solver.org/nnls_modeling.html#_CPPv4N5ceres12TolerantLossE>.
     pub fn tolerant(a: f64, b: f64) -> Self {
-        Self(ffi::new_tolerant_loss(a, b))
+        Self::stock(ffi::new_tolerant_loss(a, b))
     }
 
     /// Tukey loss function
     pub fn tukey(a: f64) -> Self {
-        Self(ffi::new_tukey_loss(a))
+        Self::stock(ffi::new_tukey_loss(a))
+    }
+
+    /// Geman-McClure loss function `rho(s) = s a^2 / (a^2 + s)`, it approaches `a^2` for large
+    /// residuals, so outliers have vanishing influence. Implemented in Rust.
+    pub fn geman_mcclure(a: f64) -> Self {
+        let func: ThreadSafeLossFunctionType =
+            Box::new(move |sq_norm, out| *out = geman_mcclure(a, sq_norm));
+        Self::custom_threadsafe(func)
+    }
+
+    /// Welsch (Leclerc) loss function `rho(s) = a^2 (1 - exp(-s / a^2))`. Implemented in Rust.
+    pub fn welsch(a: f64) -> Self {
+        let func: ThreadSafeLossFunctionType =
+            Box::new(move |sq_norm, out| *out = welsch(a, sq_norm));
+        Self::custom_threadsafe(func)
+    }
+
+    /// Fair loss function `rho(s) = 2 a^2 (sqrt(s) / a - ln(1 + sqrt(s) / a))`, it is convex and
+    /// grows linearly for large residuals. Implemented in Rust.
+    pub fn fair(a: f64) -> Self {
+        let func: ThreadSafeLossFunctionType =
+            Box::new(move |sq_norm, out| *out = fair(a, sq_norm));
+        Self::custom_threadsafe(func)
+    }
+
+    /// General adaptive robust loss function by J. T. Barron, "A General and Adaptive Robust Loss
+    /// Function", CVPR 2019, <https://arxiv.org/abs/1701.03077>.
+    ///
+    /// `alpha` is the shape parameter and `c > 0` is the scale of the quadratic bowl. The loss is
+    /// multiplied by `2 c^2` to follow the Ceres convention `rho(s) ~ s` for small `s`:
+    /// `rho(s) = 2 c^2 |alpha - 2| / alpha * ((s / c^2 / |alpha - 2| + 1)^(alpha / 2) - 1)`.
+    /// It is the trivial loss for `alpha = 2`, [soft L1](LossFunction::soft_l1) with `a = c` for
+    /// `alpha = 1`, [Cauchy](LossFunction::cauchy) with `a = sqrt(2) c` for `alpha = 0`,
+    /// Geman-McClure for `alpha = -2`, and Welsch for `alpha = -inf` ([f64::NEG_INFINITY]).
+    ///
+    /// The loss is implemented in Rust, Ceres loss functions cannot have parameters to optimize,
+    /// so `alpha` is fixed. Use [LossFunctionWrapper] to change it between solves, e.g. to start
+    /// from a convex loss and decrease `alpha` step by step.
+    pub fn barron(alpha: f64, c: f64) -> Self {
+        let func: ThreadSafeLossFunctionType =
+            Box::new(move |sq_norm, out| *out = barron(alpha, c, sq_norm));
+        Self::custom_threadsafe(func)
+    }
+
+    /// Scaled loss function `a * rho(s)`, where `rho` is the wrapped loss function, see details at
+    /// <http://ceres-solver.org/nnls_modeling.html#_CPPv4N5ceres10ScaledLossE>. It allows weighting
+    /// residual blocks relative to each other while keeping a robust kernel.
+    pub fn scaled(inner: LossFunction, a: f64) -> Self {
+        Self {
+            threadsafe: inner.threadsafe,
+            panic: inner.panic.clone(),
+            inner: ffi::new_scaled_loss(inner.into_inner(), a),
+        }
+    }
+
+    /// Evaluate the loss function at the squared residual norm, returns the value, its first and
+    /// second derivatives. It is the same function the solver calls, so it can be used to plot the
+    /// loss shape or to classify outliers, e.g. by the first derivative being small.
+    ///
+    /// # Panics
+    /// Panics if the custom function panics.
+    pub fn evaluate(&self, sq_norm: f64) -> [f64; 3] {
+        let mut out = [0.0; 3];
+        unsafe {
+            self.inner
+                .as_ref()
+                .expect("Underlying C++ unique_ptr<LossFunction> must hold non-null pointer")
+                .Evaluate(sq_norm, out.as_mut_ptr());
+        }
+        if let Some(message) = self.take_panic() {
+            panic!("{message}");
+        }
+        out
+    }
+
+    /// Message of the first panic of the custom function since the previous call.
+    pub(crate) fn take_panic(&self) -> Option<String> {
+        self.panic.as_ref().and_then(PanicSlot::take)
     }
 
     pub fn into_inner(self) -> UniquePtr<ffi::LossFunction> {
-        self.0
+        self.inner
+    }
+
+    /// Pointer to the underlying C++ object, it is valid while `self` is alive.
+    pub(crate) fn as_ptr(&self) -> *mut ffi::LossFunction {
+        self.inner
+            .as_ref()
+            .expect("Underlying C++ unique_ptr<LossFunction> must hold non-null pointer")
+            as *const _ as *mut _
+    }
+}
+
+/// Loss function which can be replaced after it is added to the problem, binding of
+/// `ceres::LossFunctionWrapper`.
+///
+/// It is a shared handle: clones refer to the same wrapped loss function, so a single wrapper can
+/// be given to many residual blocks with
+/// [ResidualBlockBuilder::set_loss_wrapper](crate::nlls_problem::ResidualBlockBuilder::set_loss_wrapper)
+/// and then [reset](LossFunctionWrapper::reset) between solves, e.g. to decrease the robustness
+/// parameter step by step for graduated non-convexity.
+///
+/// ```rust
+/// use ceres_solver::residual_block::ResidualBlockSpec;
+/// use ceres_solver::{
+///     CostFunctionType, LossFunction, LossFunctionWrapper, NllsProblem, ParameterBlockOrIndex,
+///     SolverOptions,
+/// };
+///
+/// // Estimate the mean of the data having an outlier
+/// fn cost(y: f64) -> CostFunctionType<'static> {
+///     Box::new(move |parameters, residuals, jacobians| {
+///         residuals[0] = parameters[0][0] - y;
+///         if let Some(d_dx) = jacobians.and_then(|jacobians| jacobians[0].as_mut()) {
+///             d_dx[0][0] = 1.0;
+///         }
+///         true
+///     })
+/// }
+///
+/// let wrapper = LossFunctionWrapper::new(Some(LossFunction::cauchy(10.0)));
+/// let mut problem = NllsProblem::new();
+/// problem
+///     .add_residual_blocks([1.0, 1.1, 0.9, 10.0].into_iter().enumerate().map(|(i, y)| {
+///         let parameter: ParameterBlockOrIndex =
+///             if i == 0 { vec![0.0].into() } else { 0.into() };
+///         ResidualBlockSpec::new(cost(y), 1, [parameter]).set_loss_wrapper(wrapper.clone())
+///     }))
+///     .unwrap();
+/// for scale in [10.0, 1.0, 0.1] {
+///     wrapper.reset(Some(LossFunction::cauchy(scale))).unwrap();
+///     problem.solve_mut(&SolverOptions::default()).unwrap();
+/// }
+/// assert!(f64::abs(problem.parameter_blocks()[0].values()[0] - 1.0) < 0.1);
+/// ```
+#[derive(Clone)]
+pub struct LossFunctionWrapper(Rc<RefCell<WrappedLoss>>);
+
+struct WrappedLoss {
+    inner: UniquePtr<ffi::LossFunctionWrapper>,
+    /// Panic slot of the wrapped custom loss function.
+    panic: Option<PanicSlot>,
+    /// Whether the wrapped loss function is thread-safe.
+    threadsafe: bool,
+    /// Whether the wrapper is used by a problem created with
+    /// [NllsProblem::new_threadsafe](crate::nlls_problem::NllsProblem::new_threadsafe).
+    threadsafe_required: bool,
+}
+
+impl LossFunctionWrapper {
+    /// Wrap the loss function, [None] means no loss function.
+    pub fn new(loss: Option<LossFunction>) -> Self {
+        Self(Rc::new(RefCell::new(WrappedLoss {
+            panic: loss.as_ref().and_then(|loss| loss.panic.clone()),
+            threadsafe: loss.as_ref().map_or(true, LossFunction::is_threadsafe),
+            threadsafe_required: false,
+            inner: ffi::new_loss_function_wrapper(
+                loss.map_or_else(UniquePtr::null, LossFunction::into_inner),
+            ),
+        })))
+    }
+
+    /// Replace the wrapped loss function for all the residual blocks using the wrapper, [None]
+    /// means no loss function. Returns [NllsProblemError::LossNotThreadSafe] if the wrapper is
+    /// used by a problem created with
+    /// [NllsProblem::new_threadsafe](crate::nlls_problem::NllsProblem::new_threadsafe) and the
+    /// loss function is not thread-safe.
+    pub fn reset(&self, loss: Option<LossFunction>) -> Result<(), NllsProblemError> {
+        let mut wrapped = self.0.borrow_mut();
+        let threadsafe = loss.as_ref().map_or(true, LossFunction::is_threadsafe);
+        if wrapped.threadsafe_required && !threadsafe {
+            return Err(NllsProblemError::LossNotThreadSafe);
+        }
+        wrapped.panic = loss.as_ref().and_then(|loss| loss.panic.clone());
+        wrapped.threadsafe = threadsafe;
+        ffi::reset_loss_function_wrapper(
+            wrapped.inner.pin_mut(),
+            loss.map_or_else(UniquePtr::null, LossFunction::into_inner),
+        );
+        Ok(())
+    }
+
+    /// Whether the wrapped loss function can be called from several threads at once.
+    pub fn is_threadsafe(&self) -> bool {
+        self.0.borrow().threadsafe
+    }
+
+    /// Forbid replacing the loss function with a non-thread-safe one.
+    pub(crate) fn require_threadsafe(&self) {
+        self.0.borrow_mut().threadsafe_required = true;
+    }
+
+    /// Message of the first panic of the wrapped custom function since the previous call.
+    pub(crate) fn take_panic(&self) -> Option<String> {
+        self.0.borrow().panic.as_ref().and_then(PanicSlot::take)
+    }
+
+    /// Pointer to the underlying C++ object, it is valid while `self` is alive.
+    pub(crate) fn as_ptr(&self) -> *mut ffi::LossFunction {
+        let wrapped = self.0.borrow();
+        let wrapper = wrapped
+            .inner
+            .as_ref()
+            .expect("Underlying C++ unique_ptr<LossFunctionWrapper> must hold non-null pointer");
+        unsafe { ffi::loss_function_wrapper_as_loss_function(wrapper as *const _ as *mut _) }
+    }
+}
+
+/// Geman-McClure loss and its derivatives over `sq_norm`.
+fn geman_mcclure(a: f64, sq_norm: f64) -> [f64; 3] {
+    let a_sq = a * a;
+    let sum = a_sq + sq_norm;
+    let d_rho = (a_sq / sum).powi(2);
+    [sq_norm * a_sq / sum, d_rho, -2.0 * d_rho / sum]
+}
+
+/// Welsch loss and its derivatives over `sq_norm`.
+fn welsch(a: f64, sq_norm: f64) -> [f64; 3] {
+    let a_sq = a * a;
+    let exp = f64::exp(-sq_norm / a_sq);
+    [-a_sq * f64::exp_m1(-sq_norm / a_sq), exp, -exp / a_sq]
+}
+
+/// Fair loss and its derivatives over `sq_norm`, the second derivative is infinite at zero.
+fn fair(a: f64, sq_norm: f64) -> [f64; 3] {
+    let x = f64::sqrt(sq_norm) / a;
+    let d_rho = 1.0 / (1.0 + x);
+    [
+        2.0 * a * a * (x - f64::ln_1p(x)),
+        d_rho,
+        -0.5 * d_rho * d_rho / (a * a * x),
+    ]
+}
+
+/// Barron loss scaled by `2 c^2` and its derivatives over `sq_norm`.
+fn barron(alpha: f64, c: f64, sq_norm: f64) -> [f64; 3] {
+    let c_sq = c * c;
+    let z = sq_norm / c_sq;
+    if alpha == 2.0 {
+        [sq_norm, 1.0, 0.0]
+    } else if alpha == 0.0 {
+        let u = 0.5 * z + 1.0;
+        [
+            2.0 * c_sq * f64::ln_1p(0.5 * z),
+            1.0 / u,
+            -0.5 / (c_sq * u * u),
+        ]
+    } else if alpha == f64::NEG_INFINITY {
+        let exp = f64::exp(-0.5 * z);
+        [-2.0 * c_sq * f64::exp_m1(-0.5 * z), exp, -0.5 * exp / c_sq]
+    } else {
+        let b = f64::abs(alpha - 2.0);
+        let ln_u = f64::ln_1p(z / b);
+        // u^(alpha/2 - 1) and u^(alpha/2 - 2)
+        let d_rho = f64::exp((0.5 * alpha - 1.0) * ln_u);
+        let d2_rho = f64::signum(alpha - 2.0) * f64::exp((0.5 * alpha - 2.0) * ln_u) / (2.0 * c_sq);
+        [
+            2.0 * c_sq * b / alpha * f64::exp_m1(0.5 * alpha * ln_u),
+            d_rho,
+            d2_rho,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::{assert_abs_diff_eq, assert_relative_eq};
+
+    #[test]
+    fn evaluate_stock() {
+        // Quadratic part of the Huber loss
+        let [rho, d_rho, d2_rho] = LossFunction::huber(2.0).evaluate(1.0);
+        assert_abs_diff_eq!(rho, 1.0);
+        assert_abs_diff_eq!(d_rho, 1.0);
+        assert_abs_diff_eq!(d2_rho, 0.0);
+        // Linear part of the Huber loss: 2 a sqrt(s) - a^2
+        let [rho, d_rho, d2_rho] = LossFunction::huber(2.0).evaluate(16.0);
+        assert_abs_diff_eq!(rho, 12.0);
+        assert_abs_diff_eq!(d_rho, 0.5);
+        assert_abs_diff_eq!(d2_rho, -0.5 / 32.0);
+
+        let scaled = LossFunction::scaled(LossFunction::cauchy(1.0), 3.0);
+        let [rho, d_rho, d2_rho] = scaled.evaluate(1.0);
+        assert_abs_diff_eq!(rho, 3.0 * f64::ln(2.0));
+        assert_abs_diff_eq!(d_rho, 1.5);
+        assert_abs_diff_eq!(d2_rho, -0.75);
+    }
+
+    #[test]
+    fn evaluate_custom() {
+        let loss: LossFunctionType = Box::new(|sq_norm, out| {
+            *out = [
+                sq_norm.sqrt(),
+                0.5 / sq_norm.sqrt(),
+                -0.25 * sq_norm.powf(-1.5),
+            ];
+        });
+        let loss = LossFunction::custom(loss);
+        assert_abs_diff_eq!(&loss.evaluate(4.0)[..], &[2.0, 0.25, -0.25 / 8.0][..]);
+    }
+
+    #[test]
+    #[should_panic(expected = "custom loss")]
+    fn evaluate_panicking_custom() {
+        let loss: LossFunctionType = Box::new(|_sq_norm, _out| panic!("custom loss"));
+        LossFunction::custom(loss).evaluate(1.0);
+    }
+
+    #[test]
+    fn barron_special_cases() {
+        let c = 1.5;
+        for sq_norm in [0.0, 0.1, 1.0, 10.0, 1e3] {
+            let pairs = [
+                (LossFunction::barron(1.0, c), LossFunction::soft_l1(c)),
+                (
+                    LossFunction::barron(0.0, c),
+                    LossFunction::cauchy(f64::sqrt(2.0) * c),
+                ),
+            ];
+            for (barron, stock) in pairs {
+                let actual = barron.evaluate(sq_norm);
+                let desired = stock.evaluate(sq_norm);
+                assert_abs_diff_eq!(&actual[..], &desired[..], epsilon = 1e-12);
+            }
+            assert_eq!(
+                LossFunction::barron(2.0, c).evaluate(sq_norm),
+                [sq_norm, 1.0, 0.0]
+            );
+            // Close to the limits, the second derivative at zero is discontinuous at alpha = 2
+            for alpha in [2.0, 0.0, f64::NEG_INFINITY] {
+                let near = if alpha.is_finite() {
+                    alpha + 1e-9
+                } else {
+                    -1e9
+                };
+                let actual = barron(near, c, sq_norm);
+                let desired = barron(alpha, c, sq_norm);
+                assert_relative_eq!(&actual[..2], &desired[..2], max_relative = 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn barron_derivatives() {
+        const EPS: f64 = 1e-6;
+        for alpha in [
+            -10.0,
+            -2.0,
+            -0.5,
+            0.0,
+            0.5,
+            1.0,
+            1.5,
+            3.0,
+            f64::NEG_INFINITY,
+        ] {
+            for sq_norm in [0.1, 1.0, 10.0] {
+                let [_, d_rho, d2_rho] = barron(alpha, 0.7, sq_norm);
+                let [rho_plus, d_rho_plus, _] = barron(alpha, 0.7, sq_norm + EPS);
+                let [rho_minus, d_rho_minus, _] = barron(alpha, 0.7, sq_norm - EPS);
+                assert_abs_diff_eq!(d_rho, (rho_plus - rho_minus) / (2.0 * EPS), epsilon = 1e-6);
+                assert_abs_diff_eq!(
+                    d2_rho,
+                    (d_rho_plus - d_rho_minus) / (2.0 * EPS),
+                    epsilon = 1e-6
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn classic_kernels() {
+        const EPS: f64 = 1e-6;
+        let a = 1.3;
+        for sq_norm in [0.0, 0.1, 1.0, 10.0, 1e3] {
+            // Special cases of the Barron loss
+            assert_relative_eq!(
+                &geman_mcclure(a, sq_norm)[..],
+                &barron(-2.0, 0.5 * a, sq_norm)[..],
+                max_relative = 1e-12
+            );
+            assert_relative_eq!(
+                &welsch(a, sq_norm)[..],
+                &barron(f64::NEG_INFINITY, a / f64::sqrt(2.0), sq_norm)[..],
+                max_relative = 1e-12
+            );
+            // Trivial loss for small residuals
+            for kernel in [geman_mcclure, welsch, fair] {
+                let [rho, d_rho, _] = kernel(a, 1e-16 * sq_norm);
+                assert_relative_eq!(rho, 1e-16 * sq_norm, max_relative = 1e-5);
+                assert_relative_eq!(d_rho, 1.0, max_relative = 1e-5);
+            }
+        }
+        for sq_norm in [0.1, 1.0, 10.0] {
+            for kernel in [geman_mcclure, welsch, fair] {
+                let [_, d_rho, d2_rho] = kernel(a, sq_norm);
+                let [rho_plus, d_rho_plus, _] = kernel(a, sq_norm + EPS);
+                let [rho_minus, d_rho_minus, _] = kernel(a, sq_norm - EPS);
+                assert_abs_diff_eq!(d_rho, (rho_plus - rho_minus) / (2.0 * EPS), epsilon = 1e-6);
+                assert_abs_diff_eq!(
+                    d2_rho,
+                    (d_rho_plus - d_rho_minus) / (2.0 * EPS),
+                    epsilon = 1e-6
+                );
+            }
+        }
+        assert_eq!(
+            LossFunction::fair(a).evaluate(0.0),
+            [0.0, 1.0, f64::NEG_INFINITY]
+        );
     }
 }
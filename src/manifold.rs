@@ -0,0 +1,687 @@
+//! Manifolds of the parameter blocks of [NllsProblem](crate::nlls_problem::NllsProblem).
+//!
+//! Manifold describes how a parameter block is updated by the solver, e.g. keeping some of its
+//! components constant. Set it with
+//! [NllsProblem::set_parameter_block_manifold](crate::nlls_problem::NllsProblem::set_parameter_block_manifold).
+//!
+//! Manifolds not provided by Ceres can be implemented in Rust with [CustomManifold] trait, see
+//! [Manifold::custom].
+//!
+//! Note the storage order of the quaternion manifolds: [Manifold::quaternion] expects
+//! `[w, x, y, z]` as Ceres rotation functions do, while [Manifold::eigen_quaternion] expects
+//! `[x, y, z, w]` as `Eigen::Quaternion` and `nalgebra::Quaternion` store them.
+//!
+//! ```rust
+//! use ceres_solver::manifold::Manifold;
+//! use ceres_solver::{CostFunctionType, NllsProblem, SolverOptions};
+//!
+//! // r = x - (1, 2, 3)
+//! let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+//!     for (i, residual) in residuals.iter_mut().enumerate() {
+//!         *residual = parameters[0][i] - (i + 1) as f64;
+//!     }
+//!     if let Some(jacobians) = jacobians {
+//!         if let Some(d_dx) = &mut jacobians[0] {
+//!             for (i, row) in d_dx.iter_mut().enumerate() {
+//!                 row.fill(0.0);
+//!                 row[i] = 1.0;
+//!             }
+//!         }
+//!     }
+//!     true
+//! });
+//! let (mut problem, _block_id) = NllsProblem::new()
+//!     .residual_block_builder()
+//!     .set_cost(cost, 3)
+//!     .set_parameters([vec![0.0; 3]])
+//!     .build_into_problem()
+//!     .unwrap();
+//! // Keep the middle component constant
+//! problem
+//!     .set_parameter_block_manifold(0, Some(Manifold::subset(3, &[1]).unwrap()))
+//!     .unwrap();
+//! let solution = problem.solve(&SolverOptions::default()).unwrap();
+//! assert_eq!(solution.parameters[0][1], 0.0);
+//! assert!(f64::abs(solution.parameters[0][0] - 1.0) < 1e-8);
+//! assert!(f64::abs(solution.parameters[0][2] - 3.0) < 1e-8);
+//! ```
+
+use crate::error::{ManifoldError, ParameterBlockStorageError};
+use crate::panic_slot::PanicSlot;
+
+use ceres_solver_sys::cxx::UniquePtr;
+use ceres_solver_sys::{ffi, RawManifold};
+use std::slice;
+
+/// Manifold implemented in Rust, see [Manifold::custom].
+///
+/// `x` and `y` are points of the ambient space of [CustomManifold::ambient_size] dimensions,
+/// `delta` and `y_minus_x` are vectors of the tangent space of [CustomManifold::tangent_size]
+/// dimensions. Jacobians are row-major. All the functions return [false] if the operation fails,
+/// see details at <http://ceres-solver.org/nnls_modeling.html#manifold>.
+///
+/// Periodic angle, its values are kept in `[0, 2 pi)`:
+///
+/// ```rust
+/// use ceres_solver::manifold::{CustomManifold, Manifold};
+/// use std::f64::consts::TAU;
+///
+/// struct Angle;
+///
+/// impl CustomManifold for Angle {
+///     fn ambient_size(&self) -> usize {
+///         1
+///     }
+///
+///     fn tangent_size(&self) -> usize {
+///         1
+///     }
+///
+///     fn plus(&self, x: &[f64], delta: &[f64], x_plus_delta: &mut [f64]) -> bool {
+///         x_plus_delta[0] = (x[0] + delta[0]).rem_euclid(TAU);
+///         true
+///     }
+///
+///     fn plus_jacobian(&self, _x: &[f64], jacobian: &mut [f64]) -> bool {
+///         jacobian[0] = 1.0;
+///         true
+///     }
+///
+///     fn minus(&self, y: &[f64], x: &[f64], y_minus_x: &mut [f64]) -> bool {
+///         // Shortest arc from x to y
+///         y_minus_x[0] = (y[0] - x[0] + TAU / 2.0).rem_euclid(TAU) - TAU / 2.0;
+///         true
+///     }
+///
+///     fn minus_jacobian(&self, _x: &[f64], jacobian: &mut [f64]) -> bool {
+///         jacobian[0] = 1.0;
+///         true
+///     }
+/// }
+///
+/// let angle = Manifold::custom_threadsafe(Angle);
+/// assert_eq!(angle.ambient_size(), 1);
+/// assert!(angle.is_threadsafe());
+/// ```
+pub trait CustomManifold {
+    /// Dimension of the ambient space, it is the size of the parameter block.
+    fn ambient_size(&self) -> usize;
+
+    /// Dimension of the tangent space.
+    fn tangent_size(&self) -> usize;
+
+    /// Move the point `x` along the tangent vector `delta`, `x_plus_delta = x ⊞ delta`.
+    fn plus(&self, x: &[f64], delta: &[f64], x_plus_delta: &mut [f64]) -> bool;
+
+    /// Jacobian of [CustomManifold::plus] by `delta` at `delta = 0`, it has
+    /// [CustomManifold::ambient_size] rows and [CustomManifold::tangent_size] columns.
+    fn plus_jacobian(&self, x: &[f64], jacobian: &mut [f64]) -> bool;
+
+    /// Tangent vector moving `x` to `y`, `y_minus_x = y ⊟ x`.
+    fn minus(&self, y: &[f64], x: &[f64], y_minus_x: &mut [f64]) -> bool;
+
+    /// Jacobian of [CustomManifold::minus] by `y` at `y = x`, it has
+    /// [CustomManifold::tangent_size] rows and [CustomManifold::ambient_size] columns.
+    fn minus_jacobian(&self, x: &[f64], jacobian: &mut [f64]) -> bool;
+}
+
+/// Manifold of a parameter block, binding of `ceres::Manifold`.
+pub struct Manifold {
+    inner: UniquePtr<ffi::Manifold>,
+    threadsafe: bool,
+    /// Panic slots of the custom manifolds, empty for stock ones.
+    panics: Vec<PanicSlot>,
+}
+
+impl Manifold {
+    /// Manifold implemented in Rust, see [CustomManifold] for an example. A panic of the manifold
+    /// is caught and seen by the solver as a failed operation, the solve returns
+    /// [NllsProblemError::CallbackPanicked](crate::error::NllsProblemError::CallbackPanicked)
+    /// afterwards.
+    pub fn custom(manifold: impl CustomManifold + 'static) -> Self {
+        Self::callback(manifold, false)
+    }
+
+    /// Manifold implemented in Rust which can be called from several threads at once, so it can be
+    /// used with
+    /// [NllsProblem::new_threadsafe](crate::nlls_problem::NllsProblem::new_threadsafe). See
+    /// [Manifold::custom].
+    pub fn custom_threadsafe(manifold: impl CustomManifold + Send + Sync + 'static) -> Self {
+        Self::callback(manifold, true)
+    }
+
+    fn callback(manifold: impl CustomManifold + 'static, threadsafe: bool) -> Self {
+        let ambient_size = manifold.ambient_size();
+        let tangent_size = manifold.tangent_size();
+        let panic = PanicSlot::default();
+        let raw: Box<dyn RawManifold> = Box::new(CallbackManifold {
+            manifold,
+            ambient_size,
+            tangent_size,
+            panic: panic.clone(),
+        });
+        Self {
+            inner: ffi::new_callback_manifold(
+                Box::new(raw.into()),
+                ambient_size as i32,
+                tangent_size as i32,
+            ),
+            threadsafe,
+            panics: vec![panic],
+        }
+    }
+
+    /// Stock Ceres manifolds are thread-safe.
+    fn stock(inner: UniquePtr<ffi::Manifold>) -> Self {
+        Self {
+            inner,
+            threadsafe: true,
+            panics: vec![],
+        }
+    }
+
+    /// Manifold of a parameter block of the given size which keeps the given components constant,
+    /// `ceres::SubsetManifold`. Duplicated components are ignored.
+    ///
+    /// Returns [ParameterBlockStorageError::ComponentIndexOutOfBounds] if any component is not
+    /// less than `size`.
+    pub fn subset(
+        size: usize,
+        constant_components: &[usize],
+    ) -> Result<Self, ParameterBlockStorageError> {
+        if let Some(&index) = constant_components.iter().find(|&&index| index >= size) {
+            return Err(ParameterBlockStorageError::ComponentIndexOutOfBounds { index, len: size });
+        }
+        let mut constant_components: Vec<_> =
+            constant_components.iter().map(|&i| i as i32).collect();
+        constant_components.sort_unstable();
+        constant_components.dedup();
+        Ok(Self::stock(ffi::new_subset_manifold(
+            size as i32,
+            &constant_components,
+        )))
+    }
+
+    /// Manifold of unit quaternions stored as `[w, x, y, z]`, i.e. the scalar part first,
+    /// `ceres::QuaternionManifold`. It is the order used by Ceres rotation functions, like
+    /// `ceres::QuaternionRotatePoint`. The parameter block must have size 4, the solver varies
+    /// three degrees of freedom and keeps the quaternion normalized.
+    pub fn quaternion() -> Self {
+        Self::stock(ffi::new_quaternion_manifold())
+    }
+
+    /// Manifold of unit quaternions stored as `[x, y, z, w]`, i.e. the scalar part last, which is
+    /// the memory layout of `Eigen::Quaternion` and `nalgebra::Quaternion`,
+    /// `ceres::EigenQuaternionManifold`. Otherwise it is the same as [Manifold::quaternion].
+    pub fn eigen_quaternion() -> Self {
+        Self::stock(ffi::new_eigen_quaternion_manifold())
+    }
+
+    /// Manifold of unit-norm vectors of the given size, `ceres::SphereManifold`. It is used for
+    /// homogeneous vectors, e.g. points in projective space, the solver varies `size - 1` degrees
+    /// of freedom and keeps the vector normalized.
+    ///
+    /// Returns [ManifoldError::TooSmall] if `size` is less than 2.
+    pub fn sphere(size: usize) -> Result<Self, ManifoldError> {
+        if size < 2 {
+            return Err(ManifoldError::TooSmall { size, min: 2 });
+        }
+        Ok(Self::stock(ffi::new_sphere_manifold(size as i32)))
+    }
+
+    /// Manifold of lines in the space of the given dimension, `ceres::LineManifold`. The parameter
+    /// block has size `2 * size` and stores a point on the line followed by the unit direction
+    /// vector, the solver varies `2 * (size - 1)` degrees of freedom.
+    ///
+    /// Returns [ManifoldError::TooSmall] if `size` is less than 2.
+    pub fn line(size: usize) -> Result<Self, ManifoldError> {
+        if size < 2 {
+            return Err(ManifoldError::TooSmall { size, min: 2 });
+        }
+        Ok(Self::stock(ffi::new_line_manifold(size as i32)))
+    }
+
+    /// Euclidean space of the given size, `ceres::EuclideanManifold`. It is the same as no manifold
+    /// at all, and it is useful as a part of a [product](Manifold::product).
+    pub fn euclidean(size: usize) -> Self {
+        Self::stock(ffi::new_euclidean_manifold(size as i32))
+    }
+
+    /// Periodic one-dimensional parameter, e.g. a phase, its values are kept in `[0, period)`.
+    /// The solver steps are not bounded, so the parameter cannot get stuck at an artificial bound.
+    /// Implemented in Rust.
+    ///
+    /// Returns [ManifoldError::InvalidPeriod] if `period` is not positive and finite.
+    pub fn periodic(period: f64) -> Result<Self, ManifoldError> {
+        if !(period > 0.0 && period.is_finite()) {
+            return Err(ManifoldError::InvalidPeriod { period });
+        }
+        Ok(Self::custom_threadsafe(Periodic { period }))
+    }
+
+    /// Cartesian product of the manifolds, `ceres::ProductManifold`. The parameter block is a
+    /// concatenation of the parameter blocks of the manifolds, so a pose stored as a quaternion
+    /// followed by a translation can be a single block of size 7:
+    ///
+    /// ```rust
+    /// use ceres_solver::manifold::Manifold;
+    ///
+    /// let pose = Manifold::product([Manifold::quaternion(), Manifold::euclidean(3)]).unwrap();
+    /// assert_eq!(pose.ambient_size(), 7);
+    /// assert_eq!(pose.tangent_size(), 6);
+    /// ```
+    ///
+    /// Returns [ManifoldError::TooFewManifolds] if less than two manifolds are given.
+    pub fn product(manifolds: impl IntoIterator<Item = Manifold>) -> Result<Self, ManifoldError> {
+        let manifolds: Vec<_> = manifolds.into_iter().collect();
+        if manifolds.len() < 2 {
+            return Err(ManifoldError::TooFewManifolds {
+                count: manifolds.len(),
+            });
+        }
+        let threadsafe = manifolds.iter().all(Manifold::is_threadsafe);
+        let panics = manifolds
+            .iter()
+            .flat_map(|manifold| manifold.panics.iter().cloned())
+            .collect();
+        let product = manifolds
+            .into_iter()
+            .map(|manifold| manifold.inner)
+            .reduce(ffi::new_product_manifold)
+            .expect("we checked that there are at least two manifolds");
+        Ok(Self {
+            inner: product,
+            threadsafe,
+            panics,
+        })
+    }
+
+    /// Whether the manifold can be called from several threads at once, it is [true] for stock
+    /// manifolds and ones created with [Manifold::custom_threadsafe].
+    pub fn is_threadsafe(&self) -> bool {
+        self.threadsafe
+    }
+
+    /// Dimension of the ambient space, it is the size of the parameter block.
+    pub fn ambient_size(&self) -> usize {
+        self.inner().AmbientSize() as usize
+    }
+
+    /// Dimension of the tangent space, it is the number of the degrees of freedom the solver
+    /// varies.
+    pub fn tangent_size(&self) -> usize {
+        self.inner().TangentSize() as usize
+    }
+
+    /// Message of the first panic of the custom manifolds since the previous call.
+    pub(crate) fn take_panic(&self) -> Option<String> {
+        self.panics
+            .iter()
+            .filter_map(PanicSlot::take)
+            // Take all of them, so they are not reported by the next call
+            .fold(None, |first, panic| first.or(Some(panic)))
+    }
+
+    fn inner(&self) -> &ffi::Manifold {
+        self.inner
+            .as_ref()
+            .expect("Underlying C++ unique_ptr<Manifold> must hold non-null pointer")
+    }
+
+    /// Pointer to the underlying C++ object, it is valid while `self` is alive.
+    pub(crate) fn as_ptr(&self) -> *mut ffi::Manifold {
+        self.inner() as *const _ as *mut _
+    }
+}
+
+/// See [Manifold::periodic].
+struct Periodic {
+    period: f64,
+}
+
+impl CustomManifold for Periodic {
+    fn ambient_size(&self) -> usize {
+        1
+    }
+
+    fn tangent_size(&self) -> usize {
+        1
+    }
+
+    fn plus(&self, x: &[f64], delta: &[f64], x_plus_delta: &mut [f64]) -> bool {
+        x_plus_delta[0] = (x[0] + delta[0]).rem_euclid(self.period);
+        true
+    }
+
+    fn plus_jacobian(&self, _x: &[f64], jacobian: &mut [f64]) -> bool {
+        jacobian[0] = 1.0;
+        true
+    }
+
+    fn minus(&self, y: &[f64], x: &[f64], y_minus_x: &mut [f64]) -> bool {
+        // The shortest way from x to y
+        let half_period = 0.5 * self.period;
+        y_minus_x[0] = (y[0] - x[0] + half_period).rem_euclid(self.period) - half_period;
+        true
+    }
+
+    fn minus_jacobian(&self, _x: &[f64], jacobian: &mut [f64]) -> bool {
+        jacobian[0] = 1.0;
+        true
+    }
+}
+
+/// Adapter of [CustomManifold] to raw pointers passed by Ceres.
+struct CallbackManifold<M> {
+    manifold: M,
+    ambient_size: usize,
+    tangent_size: usize,
+    panic: PanicSlot,
+}
+
+impl<M: CustomManifold> RawManifold for CallbackManifold<M> {
+    fn plus(&self, x: *const f64, delta: *const f64, x_plus_delta: *mut f64) -> bool {
+        let x = unsafe { slice::from_raw_parts(x, self.ambient_size) };
+        let delta = unsafe { slice::from_raw_parts(delta, self.tangent_size) };
+        let x_plus_delta = unsafe { slice::from_raw_parts_mut(x_plus_delta, self.ambient_size) };
+        self.panic
+            .call(|| self.manifold.plus(x, delta, x_plus_delta), || false)
+    }
+
+    fn plus_jacobian(&self, x: *const f64, jacobian: *mut f64) -> bool {
+        let x = unsafe { slice::from_raw_parts(x, self.ambient_size) };
+        let jacobian =
+            unsafe { slice::from_raw_parts_mut(jacobian, self.ambient_size * self.tangent_size) };
+        self.panic
+            .call(|| self.manifold.plus_jacobian(x, jacobian), || false)
+    }
+
+    fn minus(&self, y: *const f64, x: *const f64, y_minus_x: *mut f64) -> bool {
+        let y = unsafe { slice::from_raw_parts(y, self.ambient_size) };
+        let x = unsafe { slice::from_raw_parts(x, self.ambient_size) };
+        let y_minus_x = unsafe { slice::from_raw_parts_mut(y_minus_x, self.tangent_size) };
+        self.panic
+            .call(|| self.manifold.minus(y, x, y_minus_x), || false)
+    }
+
+    fn minus_jacobian(&self, x: *const f64, jacobian: *mut f64) -> bool {
+        let x = unsafe { slice::from_raw_parts(x, self.ambient_size) };
+        let jacobian =
+            unsafe { slice::from_raw_parts_mut(jacobian, self.ambient_size * self.tangent_size) };
+        self.panic
+            .call(|| self.manifold.minus_jacobian(x, jacobian), || false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost::CostFunctionType;
+    use crate::error::NllsProblemError;
+    use crate::nlls_problem::NllsProblem;
+    use crate::solver::SolverOptions;
+
+    use approx::assert_abs_diff_eq;
+    use std::f64::consts::{PI, TAU};
+
+    /// Find the quaternion closest to the target, starting from the identity one.
+    fn fit_quaternion(manifold: Manifold, identity: [f64; 4], target: [f64; 4]) -> Vec<f64> {
+        assert_eq!(manifold.ambient_size(), 4);
+        assert_eq!(manifold.tangent_size(), 3);
+        let cost: CostFunctionType = Box::new(move |parameters, residuals, jacobians| {
+            for ((residual, &q), &target) in residuals.iter_mut().zip(parameters[0]).zip(&target) {
+                *residual = q - target;
+            }
+            if let Some(jacobians) = jacobians {
+                if let Some(d_dq) = &mut jacobians[0] {
+                    for (i, row) in d_dq.iter_mut().enumerate() {
+                        row.fill(0.0);
+                        row[i] = 1.0;
+                    }
+                }
+            }
+            true
+        });
+        let (mut problem, _block_id) = NllsProblem::new()
+            .residual_block_builder()
+            .set_cost(cost, 4)
+            .set_parameters([identity.to_vec()])
+            .build_into_problem()
+            .unwrap();
+        problem
+            .set_parameter_block_manifold(0, Some(manifold))
+            .unwrap();
+        let solution = problem.solve(&SolverOptions::default()).unwrap();
+        solution.parameters.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn quaternion_manifolds() {
+        // Rotation by 90 degrees around z-axis
+        let (c, s) = (f64::sqrt(0.5), f64::sqrt(0.5));
+        let q = fit_quaternion(
+            Manifold::quaternion(),
+            [1.0, 0.0, 0.0, 0.0],
+            [c, 0.0, 0.0, s],
+        );
+        assert_abs_diff_eq!(&q[..], &[c, 0.0, 0.0, s][..], epsilon = 1e-8);
+        let q = fit_quaternion(
+            Manifold::eigen_quaternion(),
+            [0.0, 0.0, 0.0, 1.0],
+            [0.0, 0.0, s, c],
+        );
+        assert_abs_diff_eq!(&q[..], &[0.0, 0.0, s, c][..], epsilon = 1e-8);
+
+        // Targets out of the unit sphere are projected onto it
+        let q = fit_quaternion(
+            Manifold::quaternion(),
+            [1.0, 0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0, 2.0],
+        );
+        assert_abs_diff_eq!(q.iter().map(|x| x * x).sum::<f64>(), 1.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(&q[..], &[c, 0.0, 0.0, s][..], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn sphere_and_line_manifolds() {
+        let sphere = Manifold::sphere(3).unwrap();
+        assert_eq!(sphere.ambient_size(), 3);
+        assert_eq!(sphere.tangent_size(), 2);
+        let line = Manifold::line(3).unwrap();
+        assert_eq!(line.ambient_size(), 6);
+        assert_eq!(line.tangent_size(), 4);
+        assert!(matches!(
+            Manifold::sphere(1),
+            Err(ManifoldError::TooSmall { size: 1, min: 2 })
+        ));
+        assert!(Manifold::line(0).is_err());
+    }
+
+    #[test]
+    fn homogeneous_vector() {
+        // Find the unit vector closest to (3, 0, 4)
+        let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+            for ((residual, &x), target) in
+                residuals.iter_mut().zip(parameters[0]).zip([3.0, 0.0, 4.0])
+            {
+                *residual = x - target;
+            }
+            if let Some(jacobians) = jacobians {
+                if let Some(d_dx) = &mut jacobians[0] {
+                    for (i, row) in d_dx.iter_mut().enumerate() {
+                        row.fill(0.0);
+                        row[i] = 1.0;
+                    }
+                }
+            }
+            true
+        });
+        let (mut problem, _block_id) = NllsProblem::new()
+            .residual_block_builder()
+            .set_cost(cost, 3)
+            .set_parameters([vec![1.0, 0.0, 0.0]])
+            .build_into_problem()
+            .unwrap();
+        problem
+            .set_parameter_block_manifold(0, Some(Manifold::sphere(3).unwrap()))
+            .unwrap();
+        let solution = problem.solve(&SolverOptions::default()).unwrap();
+        assert_abs_diff_eq!(
+            &solution.parameters[0][..],
+            &[0.6, 0.0, 0.8][..],
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn product_manifold() {
+        assert!(matches!(
+            Manifold::product([Manifold::quaternion()]),
+            Err(ManifoldError::TooFewManifolds { count: 1 })
+        ));
+        let product = Manifold::product([
+            Manifold::subset(2, &[1]).unwrap(),
+            Manifold::quaternion(),
+            Manifold::euclidean(3),
+        ])
+        .unwrap();
+        assert_eq!(product.ambient_size(), 9);
+        assert_eq!(product.tangent_size(), 7);
+
+        // Pose block of a rotation quaternion followed by a translation, fitted to the target
+        // with the scaled rotation part.
+        let target = [2.0, 0.0, 0.0, 2.0, 1.0, 2.0, 3.0];
+        let cost: CostFunctionType = Box::new(move |parameters, residuals, jacobians| {
+            for ((residual, &x), &target) in residuals.iter_mut().zip(parameters[0]).zip(&target) {
+                *residual = x - target;
+            }
+            if let Some(jacobians) = jacobians {
+                if let Some(d_dx) = &mut jacobians[0] {
+                    for (i, row) in d_dx.iter_mut().enumerate() {
+                        row.fill(0.0);
+                        row[i] = 1.0;
+                    }
+                }
+            }
+            true
+        });
+        let (mut problem, _block_id) = NllsProblem::new()
+            .residual_block_builder()
+            .set_cost(cost, 7)
+            .set_parameters([vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]])
+            .build_into_problem()
+            .unwrap();
+        let pose = Manifold::product([Manifold::quaternion(), Manifold::euclidean(3)]).unwrap();
+        problem.set_parameter_block_manifold(0, Some(pose)).unwrap();
+        let solution = problem.solve(&SolverOptions::default()).unwrap();
+        let c = f64::sqrt(0.5);
+        assert_abs_diff_eq!(
+            &solution.parameters[0][..],
+            &[c, 0.0, 0.0, c, 1.0, 2.0, 3.0][..],
+            epsilon = 1e-6
+        );
+    }
+
+    /// Periodic angle in `[0, 2 pi)`, optionally panicking in [CustomManifold::plus].
+    struct Angle {
+        panic: bool,
+    }
+
+    impl CustomManifold for Angle {
+        fn ambient_size(&self) -> usize {
+            1
+        }
+
+        fn tangent_size(&self) -> usize {
+            1
+        }
+
+        fn plus(&self, x: &[f64], delta: &[f64], x_plus_delta: &mut [f64]) -> bool {
+            assert!(!self.panic, "plus panicked");
+            x_plus_delta[0] = (x[0] + delta[0]).rem_euclid(TAU);
+            true
+        }
+
+        fn plus_jacobian(&self, _x: &[f64], jacobian: &mut [f64]) -> bool {
+            jacobian[0] = 1.0;
+            true
+        }
+
+        fn minus(&self, y: &[f64], x: &[f64], y_minus_x: &mut [f64]) -> bool {
+            y_minus_x[0] = (y[0] - x[0] + PI).rem_euclid(TAU) - PI;
+            true
+        }
+
+        fn minus_jacobian(&self, _x: &[f64], jacobian: &mut [f64]) -> bool {
+            jacobian[0] = 1.0;
+            true
+        }
+    }
+
+    /// Problem of fitting a point on the unit circle by its angle.
+    fn angle_problem(
+        problem: NllsProblem<'static>,
+        angle: f64,
+        target: f64,
+    ) -> NllsProblem<'static> {
+        let cost: CostFunctionType = Box::new(move |parameters, residuals, jacobians| {
+            let x = parameters[0][0];
+            residuals[0] = x.cos() - target.cos();
+            residuals[1] = x.sin() - target.sin();
+            if let Some(jacobians) = jacobians {
+                if let Some(d_dx) = &mut jacobians[0] {
+                    d_dx[0][0] = -x.sin();
+                    d_dx[1][0] = x.cos();
+                }
+            }
+            true
+        });
+        problem
+            .residual_block_builder()
+            .set_cost(cost, 2)
+            .set_parameters([vec![angle]])
+            .build_into_problem()
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn custom_manifold() {
+        let mut problem = angle_problem(NllsProblem::new(), 6.0, 0.3);
+        let angle = Manifold::custom(Angle { panic: false });
+        assert!(!angle.is_threadsafe());
+        problem
+            .set_parameter_block_manifold(0, Some(angle))
+            .unwrap();
+        let solution = problem.solve(&SolverOptions::default()).unwrap();
+        // The angle goes over 2 pi and is wrapped
+        assert_abs_diff_eq!(solution.parameters[0][0], 0.3, epsilon = 1e-6);
+
+        let mut problem = angle_problem(NllsProblem::new_threadsafe(), 6.0, 0.3);
+        assert!(matches!(
+            problem.set_parameter_block_manifold(0, Some(Manifold::custom(Angle { panic: false }))),
+            Err(ParameterBlockStorageError::ManifoldNotThreadSafe)
+        ));
+        problem
+            .set_parameter_block_manifold(
+                0,
+                Some(Manifold::custom_threadsafe(Angle { panic: false })),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn custom_manifold_panic() {
+        let mut problem = angle_problem(NllsProblem::new(), 6.0, 0.3);
+        let angle = Manifold::custom(Angle { panic: true });
+        problem
+            .set_parameter_block_manifold(0, Some(angle))
+            .unwrap();
+        match problem.solve(&SolverOptions::default()) {
+            Err(NllsProblemError::CallbackPanicked(message)) => {
+                assert_eq!(message, "plus panicked")
+            }
+            _ => panic!("solve must fail"),
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! Wrapping [NllsProblem] to fit a curve to several datasets at once.
+//!
+//! [MultiCurveFitProblem] fits the same model `y = f(x; parameters)` to several datasets, some
+//! of the model parameters are shared by all the datasets, and the others have a separate value
+//! per dataset. The mapping is declared with [MultiCurveParameter], so no parameter block indices
+//! are needed, compare with the example in [nlls_problem](crate::nlls_problem) module docs.
+//!
+//! Let's fit a family of curves `y_ij = a + b_i * exp(c_i * x_ij)` with the common offset `a`:
+//!
+//! ```rust
+//! use ceres_solver::curve_fit::CurveFunctionType;
+//! use ceres_solver::multi_curve_fit::{MultiCurveFitProblem, MultiCurveParameter};
+//! use ceres_solver::SolverOptions;
+//!
+//! fn model(x: f64, parameters: &[f64], y: &mut f64, jacobians: Option<&mut [Option<f64>]>) -> bool {
+//!     let &[a, b, c]: &[f64; 3] = parameters.try_into().unwrap();
+//!     let e = f64::exp(c * x);
+//!     *y = a + b * e;
+//!     if let Some(jacobians) = jacobians {
+//!         for (jacobian, derivative) in jacobians.iter_mut().zip([1.0, e, b * x * e]) {
+//!             if let Some(jacobian) = jacobian {
+//!                 *jacobian = derivative;
+//!             }
+//!         }
+//!     }
+//!     true
+//! }
+//!
+//! let a_true = -2.0;
+//! let b_true = [2.0, 2.0, -1.0];
+//! let c_true = [3.0, -1.0, 3.0];
+//!
+//! let x: Vec<_> = (0..100).map(|i| i as f64 / 100.0).collect();
+//! let y: Vec<Vec<_>> = b_true
+//!     .iter()
+//!     .zip(c_true.iter())
+//!     .map(|(&b, &c)| {
+//!         x.iter()
+//!             .map(|&x| {
+//!                 let mut y = 0.0;
+//!                 model(x, &[a_true, b, c], &mut y, None);
+//!                 y
+//!             })
+//!             .collect()
+//!     })
+//!     .collect();
+//!
+//! let func: CurveFunctionType = Box::new(model);
+//! let mut builder = MultiCurveFitProblem::builder().func(func).parameters([
+//!     // a is shared by all the datasets
+//!     MultiCurveParameter::Shared(0.0),
+//!     // b and c have initial values for every dataset
+//!     MultiCurveParameter::PerDataset(vec![1.0; 3]),
+//!     MultiCurveParameter::PerDataset(vec![1.0; 3]),
+//! ]);
+//! for y in y.iter() {
+//!     builder = builder.dataset(&x, y);
+//! }
+//! let solution = builder.build().unwrap().solve(&SolverOptions::default());
+//!
+//! // Parameters of the model for every dataset
+//! for (parameters, (&b, &c)) in solution.parameters.iter().zip(b_true.iter().zip(c_true.iter())) {
+//!     assert!(f64::abs(parameters[0] - a_true) < 1e-6);
+//!     assert!(f64::abs(parameters[1] - b) < 1e-6);
+//!     assert!(f64::abs(parameters[2] - c) < 1e-6);
+//! }
+//! ```
+
+use crate::curve_fit::{
+    autodiff_curve_function, numeric_curve_function, solve_fit_problem, AutoDiffCurveModel,
+    CurveFitProblem1D, CurveFunctionType, NumericCurveFunctionType, DEFAULT_RELATIVE_STEP_SIZE,
+};
+use crate::error::CurveFitProblemBuildError;
+use crate::loss::{LossFunction, LossFunctionWrapper};
+use crate::nlls_problem::NllsProblem;
+use crate::parameter_block::ParameterBlockOrIndex;
+use crate::solver::{SolverOptions, SolverSummary};
+
+use std::rc::Rc;
+
+/// Declares how a model parameter of [MultiCurveFitProblem] is shared between the datasets, and
+/// gives its initial value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MultiCurveParameter {
+    /// The parameter has the same value for all the datasets, with the given initial value.
+    Shared(f64),
+    /// The parameter has a separate value for every dataset, with the given initial values in the
+    /// order of the datasets.
+    PerDataset(Vec<f64>),
+}
+
+/// A wrapper for [NllsProblem] to fit the same model to several datasets with some of the
+/// parameters shared, see [multi_curve_fit](crate::multi_curve_fit) module docs for an example.
+/// Create it with [MultiCurveFitProblem::builder] and then call a destructive method
+/// [MultiCurveFitProblem::solve].
+pub struct MultiCurveFitProblem<'cost> {
+    problem: NllsProblem<'cost>,
+    /// Parameter block indices of the model parameters for every dataset.
+    parameter_blocks: Vec<Vec<usize>>,
+}
+
+impl<'cost> MultiCurveFitProblem<'cost> {
+    /// Create a [MultiCurveFitProblemBuilder] instance, see its docs for the details.
+    pub fn builder() -> MultiCurveFitProblemBuilder<'cost> {
+        MultiCurveFitProblemBuilder::new()
+    }
+
+    /// Solves the problem and returns a solution for the parameters.
+    pub fn solve(self, options: &SolverOptions) -> MultiCurveFitProblemSolution {
+        let solution = solve_fit_problem(self.problem, &[], None, options, false);
+        let parameters = self
+            .parameter_blocks
+            .iter()
+            .map(|blocks| blocks.iter().map(|&i| solution.parameters[i]).collect())
+            .collect();
+        MultiCurveFitProblemSolution {
+            parameters,
+            summary: solution.summary,
+            chi2: solution.chi2,
+            reduced_chi2: solution.reduced_chi2,
+            degrees_of_freedom: solution.degrees_of_freedom,
+        }
+    }
+}
+
+/// A solution for [MultiCurveFitProblem].
+pub struct MultiCurveFitProblemSolution {
+    /// Model parameters for every dataset, in the order of the datasets. Shared parameters have
+    /// the same value for all the datasets.
+    pub parameters: Vec<Vec<f64>>,
+    /// Solver summary.
+    pub summary: SolverSummary,
+    /// Chi-square of all the datasets, see
+    /// [CurveFitProblemSolution::chi2](crate::curve_fit::CurveFitProblemSolution::chi2).
+    pub chi2: f64,
+    /// Chi-square divided by the number of degrees of freedom, infinite if the number of degrees
+    /// of freedom is not positive.
+    pub reduced_chi2: f64,
+    /// Total number of data points minus the number of the fitted parameters, a shared parameter
+    /// is counted once.
+    pub degrees_of_freedom: i64,
+}
+
+/// Dataset of [MultiCurveFitProblem].
+pub struct CurveDataset<'cost> {
+    /// Independent coordinates of the data points
+    pub x: &'cost [f64],
+    /// Values of the data points
+    pub y: &'cost [f64],
+    /// Optional inverse errors of y - square root of the weight
+    pub inverse_error: Option<&'cost [f64]>,
+}
+
+/// Builder for [MultiCurveFitProblem].
+pub struct MultiCurveFitProblemBuilder<'cost> {
+    /// Model function, the same for all the datasets
+    pub func: Option<CurveFunctionType>,
+    /// Datasets to fit
+    pub datasets: Vec<CurveDataset<'cost>>,
+    /// Model parameters with their initial values
+    pub parameters: Option<Vec<MultiCurveParameter>>,
+    /// Optional loss function, the same for all the datasets
+    pub loss: Option<LossFunction>,
+}
+
+impl<'cost> MultiCurveFitProblemBuilder<'cost> {
+    pub fn new() -> Self {
+        Self {
+            func: None,
+            datasets: Vec::new(),
+            parameters: None,
+            loss: None,
+        }
+    }
+
+    /// Add model function, see [CurveFitProblem1D::new] for its arguments. It replaces the model
+    /// function set before.
+    pub fn func(mut self, func: impl Into<CurveFunctionType>) -> Self {
+        self.func = Some(func.into());
+        self
+    }
+
+    /// Add model function computing the value only, differentiated with finite differences, see
+    /// [CurveFitProblem1D::new_numeric]. It replaces the model function set before.
+    pub fn numeric_func(self, func: impl Into<NumericCurveFunctionType>) -> Self {
+        self.func(numeric_curve_function(
+            func.into(),
+            DEFAULT_RELATIVE_STEP_SIZE,
+        ))
+    }
+
+    /// Add model function generic over [Scalar](crate::autodiff::Scalar) differentiated automatically, see
+    /// [CurveFitProblem1D::new_autodiff]. It replaces the model function set before.
+    pub fn autodiff_func(self, model: impl AutoDiffCurveModel + 'static) -> Self {
+        self.func(autodiff_curve_function(model))
+    }
+
+    /// Add a dataset, `x` and `y` must have the same length.
+    pub fn dataset(mut self, x: &'cost [f64], y: &'cost [f64]) -> Self {
+        self.datasets.push(CurveDataset {
+            x,
+            y,
+            inverse_error: None,
+        });
+        self
+    }
+
+    /// Add a dataset with inverse errors of `y`, all the slices must have the same length. The
+    /// residual would be `(y - model(x)) * inverse_error`.
+    pub fn dataset_with_errors(
+        mut self,
+        x: &'cost [f64],
+        y: &'cost [f64],
+        inverse_error: &'cost [f64],
+    ) -> Self {
+        self.datasets.push(CurveDataset {
+            x,
+            y,
+            inverse_error: Some(inverse_error),
+        });
+        self
+    }
+
+    /// Declare the model parameters in the order the model function accepts them, see
+    /// [MultiCurveParameter].
+    pub fn parameters(mut self, parameters: impl IntoIterator<Item = MultiCurveParameter>) -> Self {
+        self.parameters = Some(parameters.into_iter().collect());
+        self
+    }
+
+    /// Add optional loss function, if not given the trivial loss is assumed.
+    pub fn loss(mut self, loss: LossFunction) -> Self {
+        self.loss = Some(loss);
+        self
+    }
+
+    /// Build the [MultiCurveFitProblem] instance. Returns [Err] if one of the mandatory fields is
+    /// missed, data slices have inconsistent lengths, or a per-dataset parameter has a wrong
+    /// number of initial values.
+    pub fn build(self) -> Result<MultiCurveFitProblem<'cost>, CurveFitProblemBuildError> {
+        let func = Rc::new(self.func.ok_or(CurveFitProblemBuildError::FuncMissed)?);
+        if self.datasets.is_empty() {
+            return Err(CurveFitProblemBuildError::DatasetsMissed);
+        }
+        for dataset in self.datasets.iter() {
+            if dataset.x.len() != dataset.y.len()
+                || dataset.inverse_error.map_or(false, |inverse_error| {
+                    inverse_error.len() != dataset.y.len()
+                })
+            {
+                return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+            }
+        }
+        let parameters = self
+            .parameters
+            .ok_or(CurveFitProblemBuildError::ParametersMissed)?;
+        let num_datasets = self.datasets.len();
+        for (index, parameter) in parameters.iter().enumerate() {
+            if let MultiCurveParameter::PerDataset(values) = parameter {
+                if values.len() != num_datasets {
+                    return Err(CurveFitProblemBuildError::PerDatasetSizeMismatch {
+                        index,
+                        expected: num_datasets,
+                        actual: values.len(),
+                    });
+                }
+            }
+        }
+
+        let loss = self.loss.map(|loss| LossFunctionWrapper::new(Some(loss)));
+        let mut problem = NllsProblem::new();
+        let mut parameter_blocks: Vec<Vec<usize>> = Vec::with_capacity(num_datasets);
+        // Parameter blocks are indexed in the order they are added to the problem
+        let mut num_blocks = 0;
+        for (i_dataset, dataset) in self.datasets.into_iter().enumerate() {
+            let mut blocks = Vec::with_capacity(parameters.len());
+            let mut block_parameters: Vec<ParameterBlockOrIndex> =
+                Vec::with_capacity(parameters.len());
+            for (i_parameter, parameter) in parameters.iter().enumerate() {
+                let initial = match parameter {
+                    MultiCurveParameter::Shared(_) if i_dataset > 0 => {
+                        let index = parameter_blocks[0][i_parameter];
+                        blocks.push(index);
+                        block_parameters.push(index.into());
+                        continue;
+                    }
+                    MultiCurveParameter::Shared(value) => *value,
+                    MultiCurveParameter::PerDataset(values) => values[i_dataset],
+                };
+                blocks.push(num_blocks);
+                block_parameters.push(vec![initial].into());
+                num_blocks += 1;
+            }
+            let func = Rc::clone(&func);
+            let func: CurveFunctionType =
+                Box::new(move |x, parameters, y, jacobians| func(x, parameters, y, jacobians));
+            let mut residual_block = problem.residual_block_builder().set_cost(
+                CurveFitProblem1D::cost_function(
+                    dataset.x.into(),
+                    dataset.y.into(),
+                    dataset.inverse_error.map(Into::into),
+                    func,
+                ),
+                dataset.x.len(),
+            );
+            if let Some(loss) = &loss {
+                residual_block = residual_block.set_loss_wrapper(loss.clone());
+            }
+            problem = residual_block
+                .set_parameters(block_parameters)
+                .build_into_problem()
+                .unwrap()
+                .0;
+            parameter_blocks.push(blocks);
+        }
+        Ok(MultiCurveFitProblem {
+            problem,
+            parameter_blocks,
+        })
+    }
+}
+
+impl Default for MultiCurveFitProblemBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::autodiff::Scalar;
+
+    use approx::assert_abs_diff_eq;
+
+    // y = a + b x
+    struct Line;
+
+    impl AutoDiffCurveModel for Line {
+        fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+            parameters[0] + parameters[1] * x
+        }
+    }
+
+    #[test]
+    fn shared_offset() {
+        let x0 = [0.0, 1.0, 2.0, 3.0];
+        let y0: Vec<_> = x0.iter().map(|&x| 1.0 + 2.0 * x).collect();
+        let x1 = [-1.0, 0.5, 4.0];
+        let y1: Vec<_> = x1.iter().map(|&x| 1.0 - 3.0 * x).collect();
+        let w1 = [1.0, 2.0, 0.5];
+
+        let solution = MultiCurveFitProblem::builder()
+            .autodiff_func(Line)
+            .dataset(&x0, &y0)
+            .dataset_with_errors(&x1, &y1, &w1)
+            .parameters([
+                MultiCurveParameter::Shared(0.0),
+                MultiCurveParameter::PerDataset(vec![0.0, 0.0]),
+            ])
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        assert!(solution.summary.is_solution_usable());
+        assert_eq!(solution.parameters.len(), 2);
+        assert_abs_diff_eq!(&solution.parameters[0][..], &[1.0, 2.0][..], epsilon = 1e-8);
+        assert_abs_diff_eq!(
+            &solution.parameters[1][..],
+            &[1.0, -3.0][..],
+            epsilon = 1e-8
+        );
+        // 7 data points, 3 parameters
+        assert_eq!(solution.degrees_of_freedom, 4);
+        assert_abs_diff_eq!(solution.chi2, 0.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn build_errors() {
+        let x = [0.0, 1.0];
+        let y = [1.0, 2.0];
+        let builder = || {
+            MultiCurveFitProblem::builder()
+                .autodiff_func(Line)
+                .dataset(&x, &y)
+                .dataset(&x, &y)
+        };
+        assert!(matches!(
+            MultiCurveFitProblem::builder()
+                .autodiff_func(Line)
+                .parameters([MultiCurveParameter::Shared(0.0)])
+                .build(),
+            Err(CurveFitProblemBuildError::DatasetsMissed)
+        ));
+        assert!(matches!(
+            builder()
+                .parameters([
+                    MultiCurveParameter::Shared(0.0),
+                    MultiCurveParameter::PerDataset(vec![0.0]),
+                ])
+                .build(),
+            Err(CurveFitProblemBuildError::PerDatasetSizeMismatch {
+                index: 1,
+                expected: 2,
+                actual: 1
+            })
+        ));
+        assert!(matches!(
+            builder().dataset(&x, &y[..1]).build(),
+            Err(CurveFitProblemBuildError::DataSizesDontMatch)
+        ));
+    }
+}
@@ -0,0 +1,416 @@
+//! Multi-start global fitting, see
+//! [CurveFitProblem1D::solve_multistart](crate::curve_fit::CurveFitProblem1D::solve_multistart).
+//!
+//! Local solvers converge to the minimum nearest to the initial guess, which is not always the
+//! global one, e.g. for periodic models or models with degenerate parameters. Multi-start fitting
+//! runs the solver from many initial guesses sampled within the parameter bounds and keeps the
+//! best solution. The distribution of the final costs shows how rugged the cost landscape is.
+//!
+//! With `rayon` Cargo feature `solve_multistart_parallel` solves the starts in parallel, building
+//! a problem per worker thread.
+
+#[cfg(feature = "rayon")]
+use crate::curve_fit::CurveFitProblem1D;
+use crate::curve_fit::CurveFitProblemSolution;
+#[cfg(feature = "rayon")]
+use crate::error::CurveFitProblemBuildError;
+#[cfg(feature = "rayon")]
+use crate::solver::SolverOptions;
+
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+/// Maximum number of the sampled parameters supported by [MultiStartStrategy::Sobol].
+pub const SOBOL_MAX_DIMENSION: usize = 21;
+
+/// Sampling of the initial guesses for
+/// [CurveFitProblem1D::solve_multistart](crate::curve_fit::CurveFitProblem1D::solve_multistart).
+#[derive(Clone, Copy, Debug)]
+pub enum MultiStartStrategy {
+    /// Latin hypercube sampling: the range of every parameter is split into equal strata, and
+    /// every stratum is used by exactly one start. The strata order and the positions within them
+    /// are random, given by the seed.
+    LatinHypercube { seed: u64 },
+    /// Halton low-discrepancy sequence, deterministic and uniformly covering the bounds box.
+    Halton,
+    /// Sobol low-discrepancy sequence with Joe and Kuo direction numbers, deterministic and
+    /// covering the bounds box more uniformly than [MultiStartStrategy::Halton] for many
+    /// parameters. It supports up to [SOBOL_MAX_DIMENSION] sampled parameters.
+    Sobol,
+}
+
+/// A solution for
+/// [CurveFitProblem1D::solve_multistart](crate::curve_fit::CurveFitProblem1D::solve_multistart).
+pub struct MultiStartSolution {
+    /// Solution of the start with the lowest final cost.
+    pub best: CurveFitProblemSolution,
+    /// Index of the best start in [MultiStartSolution::initial_parameters] and
+    /// [MultiStartSolution::final_costs].
+    pub best_start: usize,
+    /// Initial parameters of all the starts, the first one is the initial guess of the problem.
+    pub initial_parameters: Vec<Vec<f64>>,
+    /// Final costs of all the starts, infinite for the failed ones.
+    pub final_costs: Vec<f64>,
+}
+
+/// SplitMix64 pseudo-random generator, good enough for sampling and free of dependencies.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform index in `0..n`.
+    fn next_index(&mut self, n: usize) -> usize {
+        ((self.next_f64() * n as f64) as usize).min(n - 1)
+    }
+}
+
+/// Radical inverse of `index` in the given base, the Halton sequence component.
+fn radical_inverse(mut index: usize, base: usize) -> f64 {
+    let mut result = 0.0;
+    let mut fraction = 1.0 / base as f64;
+    while index > 0 {
+        result += (index % base) as f64 * fraction;
+        index /= base;
+        fraction /= base as f64;
+    }
+    result
+}
+
+/// Degrees `s`, coefficients `a` of the primitive polynomials and initial direction numbers
+/// `m_1..m_s` of the Sobol sequence dimensions after the first one, from Joe and Kuo (2008)
+/// `new-joe-kuo-6.21201` table.
+const SOBOL_PARAMETERS: [(usize, u32, &[u32]); SOBOL_MAX_DIMENSION - 1] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+    (6, 19, &[1, 1, 1, 15, 7, 5]),
+    (6, 22, &[1, 3, 1, 15, 13, 25]),
+    (6, 25, &[1, 1, 5, 5, 19, 61]),
+    (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+    (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+];
+
+/// Direction numbers of the Sobol sequence dimension as 32-bit fractions.
+fn sobol_directions(dimension: usize) -> [u32; 32] {
+    let mut directions = [0; 32];
+    if dimension == 0 {
+        // van der Corput sequence
+        for (k, direction) in directions.iter_mut().enumerate() {
+            *direction = 1 << (31 - k);
+        }
+        return directions;
+    }
+    let (s, a, m) = SOBOL_PARAMETERS[dimension - 1];
+    for k in 0..directions.len() {
+        directions[k] = if k < s {
+            m[k] << (31 - k)
+        } else {
+            let mut direction = directions[k - s] ^ (directions[k - s] >> s);
+            for j in 1..s {
+                if (a >> (s - 1 - j)) & 1 == 1 {
+                    direction ^= directions[k - j];
+                }
+            }
+            direction
+        };
+    }
+    directions
+}
+
+/// Component of the Sobol sequence point, `index` is in Gray code order.
+fn sobol_component(index: usize, directions: &[u32; 32]) -> f64 {
+    let gray = index ^ (index >> 1);
+    let value = directions
+        .iter()
+        .enumerate()
+        .filter(|&(k, _)| (gray >> k) & 1 == 1)
+        .fold(0, |value, (_, direction)| value ^ direction);
+    value as f64 / (1u64 << 32) as f64
+}
+
+fn first_primes(n: usize) -> Vec<usize> {
+    let mut primes = Vec::with_capacity(n);
+    let mut candidate = 2;
+    while primes.len() < n {
+        if primes.iter().all(|&p| candidate % p != 0) {
+            primes.push(candidate);
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+/// Samples `num_samples` points of the unit hypercube of the given dimension, returns a vector
+/// per point.
+///
+/// # Panics
+/// If the dimension is larger than [SOBOL_MAX_DIMENSION] for [MultiStartStrategy::Sobol].
+pub(crate) fn unit_samples(
+    strategy: MultiStartStrategy,
+    dimension: usize,
+    num_samples: usize,
+) -> Vec<Vec<f64>> {
+    match strategy {
+        MultiStartStrategy::LatinHypercube { seed } => {
+            let mut rng = SplitMix64(seed);
+            let mut samples = vec![vec![0.0; dimension]; num_samples];
+            for i_dim in 0..dimension {
+                // Fisher-Yates shuffle of the strata
+                let mut strata: Vec<_> = (0..num_samples).collect();
+                for i in (1..num_samples).rev() {
+                    strata.swap(i, rng.next_index(i + 1));
+                }
+                for (sample, stratum) in samples.iter_mut().zip(strata) {
+                    sample[i_dim] = (stratum as f64 + rng.next_f64()) / num_samples as f64;
+                }
+            }
+            samples
+        }
+        MultiStartStrategy::Halton => {
+            let primes = first_primes(dimension);
+            // Zero index gives the corner of the box, skip it
+            (1..=num_samples)
+                .map(|index| {
+                    primes
+                        .iter()
+                        .map(|&base| radical_inverse(index, base))
+                        .collect()
+                })
+                .collect()
+        }
+        MultiStartStrategy::Sobol => {
+            assert!(
+                dimension <= SOBOL_MAX_DIMENSION,
+                "Sobol sequence supports up to {SOBOL_MAX_DIMENSION} dimensions, {dimension} requested"
+            );
+            let directions: Vec<_> = (0..dimension).map(sobol_directions).collect();
+            // Zero index gives the corner of the box, skip it
+            (1..=num_samples)
+                .map(|index| {
+                    directions
+                        .iter()
+                        .map(|directions| sobol_component(index, directions))
+                        .collect()
+                })
+                .collect()
+        }
+    }
+}
+
+/// Solves the problem from many initial guesses in parallel on the current [rayon] thread pool,
+/// the parallel version of
+/// [CurveFitProblem1D::solve_multistart](crate::curve_fit::CurveFitProblem1D::solve_multistart).
+///
+/// [CurveFitProblem1D] and [SolverOptions] are not [Send], so, like
+/// [fit_many](crate::batch::fit_many), it builds them on the worker threads: `model_factory` must
+/// build the same problem on every call, and `options` builds the solver options. The starts and
+/// the polishing of the best one are the same as for the sequential version, so the solution is
+/// the same too.
+///
+/// Returns the error of `model_factory` if any of its calls fails.
+///
+/// # Panics
+/// If [MultiStartStrategy::Sobol] is used for more than [SOBOL_MAX_DIMENSION] sampled parameters.
+#[cfg(feature = "rayon")]
+pub fn solve_multistart_parallel<'a, F, O>(
+    model_factory: F,
+    options: O,
+    n_starts: usize,
+    strategy: MultiStartStrategy,
+) -> Result<MultiStartSolution, CurveFitProblemBuildError>
+where
+    F: Fn() -> Result<CurveFitProblem1D<'a>, CurveFitProblemBuildError> + Send + Sync,
+    O: Fn() -> SolverOptions + Send + Sync,
+{
+    let problem = model_factory()?;
+    let initial_values = problem.parameter_values();
+    let initial_parameters = problem.multistart_initial_parameters(n_starts, strategy);
+    let results = initial_parameters
+        .par_iter()
+        .map_init(
+            || (None, options()),
+            |(worker_problem, options), parameters| {
+                // Built lazily, so a failed build is reported by the start which needs it
+                let worker_problem: &mut CurveFitProblem1D = match worker_problem {
+                    Some(problem) => problem,
+                    None => worker_problem.insert(model_factory()?),
+                };
+                let cost = worker_problem.solve_start(&initial_values, parameters, options);
+                Ok((cost, worker_problem.parameter_values()))
+            },
+        )
+        .collect::<Result<Vec<_>, CurveFitProblemBuildError>>()?;
+    // The first of the best starts, like the sequential version
+    let best_start = (0..results.len()).fold(0, |best, start| {
+        if results[start].0 < results[best].0 {
+            start
+        } else {
+            best
+        }
+    });
+    let final_costs = results.iter().map(|&(cost, _)| cost).collect();
+    Ok(problem.finish_multistart(
+        &options(),
+        initial_parameters,
+        final_costs,
+        best_start,
+        &results[best_start].1,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::autodiff::Scalar;
+    use crate::curve_fit::{AutoDiffCurveModel, CurveFitProblem1D};
+    use crate::solver::SolverOptions;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn samples() {
+        let samples = unit_samples(MultiStartStrategy::LatinHypercube { seed: 42 }, 3, 10);
+        assert_eq!(samples.len(), 10);
+        for i_dim in 0..3 {
+            let mut strata: Vec<_> = samples
+                .iter()
+                .map(|sample| (sample[i_dim] * 10.0) as usize)
+                .collect();
+            strata.sort();
+            assert_eq!(strata, (0..10).collect::<Vec<_>>());
+        }
+
+        let samples = unit_samples(MultiStartStrategy::Halton, 2, 3);
+        let expected = [[0.5, 1.0 / 3.0], [0.25, 2.0 / 3.0], [0.75, 1.0 / 9.0]];
+        for (sample, expected) in samples.iter().zip(expected) {
+            assert_abs_diff_eq!(&sample[..], &expected[..], epsilon = 1e-15);
+        }
+
+        let samples = unit_samples(MultiStartStrategy::Sobol, 3, 4);
+        let expected = [
+            [0.5, 0.5, 0.5],
+            [0.75, 0.25, 0.25],
+            [0.25, 0.75, 0.75],
+            [0.375, 0.375, 0.625],
+        ];
+        for (sample, expected) in samples.iter().zip(expected) {
+            assert_abs_diff_eq!(&sample[..], &expected[..], epsilon = 1e-15);
+        }
+    }
+
+    #[test]
+    fn sobol_strata() {
+        // Together with the skipped zero point, the first 2^m points of every dimension hit every
+        // stratum of width 2^-m exactly once
+        const N: usize = 256;
+        let samples = unit_samples(MultiStartStrategy::Sobol, SOBOL_MAX_DIMENSION, N - 1);
+        for i_dim in 0..SOBOL_MAX_DIMENSION {
+            let mut strata: Vec<_> = std::iter::once(0)
+                .chain(
+                    samples
+                        .iter()
+                        .map(|sample| (sample[i_dim] * N as f64) as usize),
+                )
+                .collect();
+            strata.sort();
+            assert_eq!(strata, (0..N).collect::<Vec<_>>());
+        }
+    }
+
+    struct Sine;
+
+    impl AutoDiffCurveModel for Sine {
+        fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+            parameters[0] * (parameters[1] * x).sin()
+        }
+    }
+
+    #[test]
+    fn multistart() {
+        let x: Vec<_> = (0..100).map(|i| 0.1 * i as f64).collect();
+        let y: Vec<_> = x.iter().map(|&x| 1.5 * f64::sin(3.0 * x)).collect();
+
+        for strategy in [
+            MultiStartStrategy::LatinHypercube { seed: 0 },
+            MultiStartStrategy::Halton,
+            MultiStartStrategy::Sobol,
+        ] {
+            // The initial guess converges to a local minimum far from the true frequency
+            let solution = CurveFitProblem1D::builder()
+                .autodiff_func(Sine)
+                .x(&x)
+                .y(&y)
+                .parameters(&[1.0, 0.5])
+                .lower_bounds(&[Some(0.5), Some(0.1)])
+                .upper_bounds(&[Some(2.0), Some(5.0)])
+                .build()
+                .unwrap()
+                .solve_multistart(&SolverOptions::default(), 30, strategy);
+            assert_eq!(solution.initial_parameters.len(), 31);
+            assert_eq!(solution.final_costs.len(), 31);
+            assert_eq!(solution.initial_parameters[0], [1.0, 0.5]);
+            for parameters in solution.initial_parameters.iter() {
+                assert!((0.5..=2.0).contains(&parameters[0]));
+                assert!((0.1..=5.0).contains(&parameters[1]));
+            }
+            assert!(solution.final_costs[0] > 1.0);
+            assert!(f64::abs(solution.best.parameters[0] - 1.5) < 1e-6);
+            assert!(f64::abs(solution.best.parameters[1] - 3.0) < 1e-6);
+            assert!(solution.final_costs[solution.best_start] < 1e-10);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_multistart() {
+        let x: Vec<_> = (0..100).map(|i| 0.1 * i as f64).collect();
+        let y: Vec<_> = x.iter().map(|&x| 1.5 * f64::sin(3.0 * x)).collect();
+        let model_factory = || {
+            CurveFitProblem1D::builder()
+                .autodiff_func(Sine)
+                .x(&x)
+                .y(&y)
+                .parameters(&[1.0, 0.5])
+                .lower_bounds(&[Some(0.5), Some(0.1)])
+                .upper_bounds(&[Some(2.0), Some(5.0)])
+                .build()
+        };
+        let strategy = MultiStartStrategy::Sobol;
+
+        let parallel =
+            solve_multistart_parallel(model_factory, SolverOptions::default, 30, strategy).unwrap();
+        let sequential =
+            model_factory()
+                .unwrap()
+                .solve_multistart(&SolverOptions::default(), 30, strategy);
+        assert_eq!(parallel.initial_parameters, sequential.initial_parameters);
+        assert_eq!(parallel.final_costs, sequential.final_costs);
+        assert_eq!(parallel.best_start, sequential.best_start);
+        assert_eq!(parallel.best.parameters, sequential.best.parameters);
+    }
+}
@@ -0,0 +1,42 @@
+//! Catching panics of Rust functions called by Ceres, unwinding into C++ is undefined behaviour.
+
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+/// Storage for the message of the first panic of Rust functions called by Ceres, clones share the
+/// storage. It is thread-safe, because Ceres may call the functions from several threads.
+#[derive(Clone, Default)]
+pub(crate) struct PanicSlot(Arc<Mutex<Option<String>>>);
+
+impl PanicSlot {
+    /// Call `f`, if it panics record the message and return the result of `on_panic`.
+    pub(crate) fn call<R>(&self, f: impl FnOnce() -> R, on_panic: impl FnOnce() -> R) -> R {
+        match catch_unwind(AssertUnwindSafe(f)) {
+            Ok(value) => value,
+            Err(payload) => {
+                let mut message = self.0.lock().unwrap_or_else(|error| error.into_inner());
+                message.get_or_insert_with(|| panic_message(payload.as_ref()));
+                on_panic()
+            }
+        }
+    }
+
+    /// Message of the recorded panic, the slot is cleared.
+    pub(crate) fn take(&self) -> Option<String> {
+        self.0
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .take()
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
@@ -2,12 +2,16 @@
 
 use crate::error::ParameterBlockStorageError;
 
+use std::marker::PhantomData;
+use std::ops::Deref;
 use std::pin::Pin;
 
 /// Parameter vector representation to use with [NllsProblem](crate::nlls_problem::NllsProblem).
 pub struct ParameterBlock {
-    values: Pin<Vec<f64>>,
+    // None if the values are borrowed from the user, see [BorrowedParameterBlock].
+    values: Option<Pin<Vec<f64>>>,
     pointer: *mut f64,
+    len: usize,
     lower_bounds: Option<Vec<Option<f64>>>,
     upper_bounds: Option<Vec<Option<f64>>>,
 }
@@ -20,7 +24,8 @@ impl ParameterBlock {
         assert!(!values.is_empty());
         let pointer = values.as_mut_ptr();
         Self {
-            values,
+            len: values.len(),
+            values: Some(values),
             pointer,
             lower_bounds: None,
             upper_bounds: None,
@@ -59,7 +64,7 @@ impl ParameterBlock {
 
     /// Number of parameters.
     pub fn len(&self) -> usize {
-        self.values.len()
+        self.len
     }
 
     /// Lower bounds of the parameters, if any. [None] means no lower bound.
@@ -72,9 +77,58 @@ impl ParameterBlock {
         self.upper_bounds.as_deref()
     }
 
+    /// Lower bound of the component, [None] if it is unbounded.
+    pub fn lower_bound(&self, index: usize) -> Result<Option<f64>, ParameterBlockStorageError> {
+        self.check_component_index(index)?;
+        Ok(self.lower_bounds.as_ref().and_then(|bounds| bounds[index]))
+    }
+
+    /// Upper bound of the component, [None] if it is unbounded.
+    pub fn upper_bound(&self, index: usize) -> Result<Option<f64>, ParameterBlockStorageError> {
+        self.check_component_index(index)?;
+        Ok(self.upper_bounds.as_ref().and_then(|bounds| bounds[index]))
+    }
+
+    pub(crate) fn check_component_index(
+        &self,
+        index: usize,
+    ) -> Result<(), ParameterBlockStorageError> {
+        if index < self.len() {
+            Ok(())
+        } else {
+            Err(ParameterBlockStorageError::ComponentIndexOutOfBounds {
+                index,
+                len: self.len(),
+            })
+        }
+    }
+
     /// Components of the parameter.
     pub fn values(&self) -> &[f64] {
-        &self.values
+        // Safety: the pointer is valid for the lifetime of the owned vector or the borrowed slice.
+        unsafe { std::slice::from_raw_parts(self.pointer, self.len) }
+    }
+
+    /// Copy of the block which owns its values.
+    pub(crate) fn to_owned_block(&self) -> Self {
+        let mut block = Self::new(self.values());
+        block.lower_bounds = self.lower_bounds.clone();
+        block.upper_bounds = self.upper_bounds.clone();
+        block
+    }
+
+    /// Overwrite the components of the parameter, the number of values must be equal to
+    /// [ParameterBlock::len].
+    pub fn set_values(&mut self, values: &[f64]) -> Result<(), ParameterBlockStorageError> {
+        if values.len() != self.len() {
+            return Err(ParameterBlockStorageError::WrongSize {
+                expected: self.len(),
+                actual: values.len(),
+            });
+        }
+        // Copy in place, so the pointer given to Ceres stays valid.
+        unsafe { std::slice::from_raw_parts_mut(self.pointer, self.len) }.copy_from_slice(values);
+        Ok(())
     }
 
     pub(crate) fn pointer_mut(&self) -> *mut f64 {
@@ -83,7 +137,76 @@ impl ParameterBlock {
 
     /// Convert to vector of parameters, each parameter is vector of floats.
     pub fn to_values(self) -> Vec<f64> {
-        Pin::into_inner(self.values)
+        match self.values {
+            Some(values) => Pin::into_inner(values),
+            None => self.values().to_vec(),
+        }
+    }
+}
+
+/// Parameter block which borrows its values from the user, so the solver writes the solution
+/// directly into them and no copy via [ParameterBlock::to_values] is needed.
+///
+/// It can be used as [ParameterBlock] through [Deref], and passed to
+/// [ResidualBlockBuilder](crate::nlls_problem::ResidualBlockBuilder) as a new parameter block.
+pub struct BorrowedParameterBlock<'a> {
+    block: ParameterBlock,
+    _values: PhantomData<&'a mut [f64]>,
+}
+
+impl<'a> BorrowedParameterBlock<'a> {
+    /// Create a new parameter block borrowing the values.
+    pub fn new(values: &'a mut [f64]) -> Self {
+        assert!(!values.is_empty());
+        Self {
+            block: ParameterBlock {
+                values: None,
+                pointer: values.as_mut_ptr(),
+                len: values.len(),
+                lower_bounds: None,
+                upper_bounds: None,
+            },
+            _values: PhantomData,
+        }
+    }
+
+    /// Add lower bounds to the parameter vector. [None] means no lower bound.
+    pub fn set_lower_bounds(&mut self, lower_bounds: impl Into<Vec<Option<f64>>>) -> &mut Self {
+        self.block.set_lower_bounds(lower_bounds);
+        self
+    }
+
+    /// Add upper bounds to the parameter vector. [None] means no upper bound.
+    pub fn set_upper_bounds(&mut self, upper_bounds: impl Into<Vec<Option<f64>>>) -> &mut Self {
+        self.block.set_upper_bounds(upper_bounds);
+        self
+    }
+
+    /// Add lower bounds to the parameter vector.
+    pub fn set_all_lower_bounds(&mut self, lower_bounds: impl Into<Vec<f64>>) -> &mut Self {
+        self.block.set_all_lower_bounds(lower_bounds);
+        self
+    }
+
+    /// Add upper bounds to the parameter vector.
+    pub fn set_all_upper_bounds(&mut self, upper_bounds: impl Into<Vec<f64>>) -> &mut Self {
+        self.block.set_all_upper_bounds(upper_bounds);
+        self
+    }
+}
+
+// No DerefMut: swapping the inner block out would let it outlive the borrowed values.
+impl Deref for BorrowedParameterBlock<'_> {
+    type Target = ParameterBlock;
+
+    fn deref(&self) -> &Self::Target {
+        &self.block
+    }
+}
+
+impl<'a> From<&'a mut [f64]> for BorrowedParameterBlock<'a> {
+    fn from(values: &'a mut [f64]) -> Self {
+        Self::new(values)
     }
 }
 
@@ -93,24 +216,37 @@ impl From<Vec<f64>> for ParameterBlock {
     }
 }
 
-pub enum ParameterBlockOrIndex {
+pub enum ParameterBlockOrIndex<'a> {
     Block(ParameterBlock),
+    Borrowed(BorrowedParameterBlock<'a>),
     Index(usize),
 }
 
-impl From<ParameterBlock> for ParameterBlockOrIndex {
+impl From<ParameterBlock> for ParameterBlockOrIndex<'_> {
     fn from(block: ParameterBlock) -> Self {
         Self::Block(block)
     }
 }
 
-impl From<usize> for ParameterBlockOrIndex {
+impl<'a> From<BorrowedParameterBlock<'a>> for ParameterBlockOrIndex<'a> {
+    fn from(block: BorrowedParameterBlock<'a>) -> Self {
+        Self::Borrowed(block)
+    }
+}
+
+impl<'a> From<&'a mut [f64]> for ParameterBlockOrIndex<'a> {
+    fn from(values: &'a mut [f64]) -> Self {
+        Self::Borrowed(BorrowedParameterBlock::new(values))
+    }
+}
+
+impl From<usize> for ParameterBlockOrIndex<'_> {
     fn from(index: usize) -> Self {
         Self::Index(index)
     }
 }
 
-impl From<Vec<f64>> for ParameterBlockOrIndex {
+impl From<Vec<f64>> for ParameterBlockOrIndex<'_> {
     fn from(values: Vec<f64>) -> Self {
         Self::Block(ParameterBlock::new(values))
     }
@@ -127,13 +263,19 @@ impl ParameterBlockStorage {
         }
     }
 
-    pub fn extend<P>(
+    /// Add new parameter blocks and return indices of all the given blocks. The storage is left
+    /// unchanged on error.
+    ///
+    /// Borrowed blocks must outlive the storage, [NllsProblem](crate::nlls_problem::NllsProblem)
+    /// guarantees it with its lifetime.
+    pub(crate) fn extend<'a, P>(
         &mut self,
         parameter_blocks: impl IntoIterator<Item = P>,
     ) -> Result<Vec<usize>, ParameterBlockStorageError>
     where
-        P: Into<ParameterBlockOrIndex>,
+        P: Into<ParameterBlockOrIndex<'a>>,
     {
+        let initial_len = self.storage.len();
         let mut indices = Vec::new();
         for parameter_block in parameter_blocks {
             let parameter_block = parameter_block.into();
@@ -143,8 +285,13 @@ impl ParameterBlockStorage {
                     indices.push(len);
                     self.storage.push(block);
                 }
+                ParameterBlockOrIndex::Borrowed(BorrowedParameterBlock { block, .. }) => {
+                    indices.push(len);
+                    self.storage.push(block);
+                }
                 ParameterBlockOrIndex::Index(index) => {
                     if index >= self.storage.len() {
+                        self.storage.truncate(initial_len);
                         return Err(ParameterBlockStorageError::IndexOutOfBounds { index, len });
                     }
                     indices.push(index);
@@ -169,6 +316,17 @@ impl ParameterBlockStorage {
             })
     }
 
+    #[inline]
+    pub fn get_block_mut(
+        &mut self,
+        index: usize,
+    ) -> Result<&mut ParameterBlock, ParameterBlockStorageError> {
+        let len = self.storage.len();
+        self.storage
+            .get_mut(index)
+            .ok_or(ParameterBlockStorageError::IndexOutOfBounds { index, len })
+    }
+
     pub fn to_values(self) -> Vec<Vec<f64>> {
         self.storage.into_iter().map(|p| p.to_values()).collect()
     }
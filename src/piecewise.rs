@@ -0,0 +1,384 @@
+//! Continuous piecewise models with unknown breakpoints for curve fitting.
+//!
+//! Fitting breakpoints is hard with a naive piecewise definition: every segment brings its own
+//! parameters which must be constrained to join at the breakpoints, and the segments must be
+//! kept in order. The models here are written in the hinge basis instead,
+//! `y = a + b x + sum_j db_j h(x - t_j)`, where `h(u) = max(u, 0)`, so they are continuous for
+//! any parameters, and the breakpoints `t_j` may go in any order. Optionally, the hinge is
+//! replaced by the softplus function `h(u) = s ln(1 + exp(u / s))` of the width `s`, which makes
+//! the model smooth over the breakpoints and so the problem differentiable everywhere.
+//!
+//! The models implement [AutoDiffCurveModel], so they are used with
+//! [CurveFitProblem1D::new_autodiff](crate::curve_fit::CurveFitProblem1D::new_autodiff) or
+//! [CurveFitProblem1DBuilder::autodiff_func](crate::curve_fit::CurveFitProblem1DBuilder::autodiff_func).
+//!
+//! Let's fit a line with a single break:
+//!
+//! ```rust
+//! use ceres_solver::curve_fit::CurveFitProblem1D;
+//! use ceres_solver::piecewise::SegmentedLinear;
+//! use ceres_solver::SolverOptions;
+//!
+//! let x: Vec<_> = (0..100).map(|i| 0.1 * i as f64).collect();
+//! // Slope changes from 2 to -1 at x = 3
+//! let y: Vec<_> = x
+//!     .iter()
+//!     .map(|&x| if x < 3.0 { 1.0 + 2.0 * x } else { 10.0 - x })
+//!     .collect();
+//!
+//! let model = SegmentedLinear::new(1);
+//! // Intercept, slope, slope change, breakpoint
+//! let solution = CurveFitProblem1D::new_autodiff(model, &x, &y, &[0.0, 1.0, -1.0, 5.0])
+//!     .solve(&SolverOptions::default());
+//!
+//! assert!(f64::abs(model.breakpoints(&solution.parameters)[0] - 3.0) < 1e-6);
+//! let slopes = model.slopes(&solution.parameters);
+//! assert!(f64::abs(slopes[0] - 2.0) < 1e-6);
+//! assert!(f64::abs(slopes[1] + 1.0) < 1e-6);
+//! ```
+
+use crate::autodiff::Scalar;
+use crate::curve_fit::AutoDiffCurveModel;
+
+/// Hinge `max(u, 0)`, or softplus `s ln(1 + exp(u / s))` for positive smoothness `s`.
+fn hinge<T: Scalar>(u: T, smoothness: f64) -> T {
+    let zero = T::from_f64(0.0);
+    if smoothness > 0.0 {
+        // Stable form of softplus
+        u.max(zero) + (-u.abs() / smoothness).exp().ln_1p() * smoothness
+    } else {
+        u.max(zero)
+    }
+}
+
+/// Breakpoints and their slope changes sorted by the breakpoints.
+fn sorted_breaks(changes: &[f64], breakpoints: &[f64]) -> Vec<(f64, f64)> {
+    let mut breaks: Vec<_> = breakpoints
+        .iter()
+        .copied()
+        .zip(changes.iter().copied())
+        .collect();
+    breaks.sort_by(|a, b| a.0.total_cmp(&b.0));
+    breaks
+}
+
+/// Slopes of the segments, the first one followed by the cumulative sums of the changes.
+fn cumulative_slopes(slope: f64, breaks: &[(f64, f64)]) -> Vec<f64> {
+    let mut slopes = vec![slope];
+    slopes.extend(breaks.iter().scan(slope, |slope, &(_, change)| {
+        *slope += change;
+        Some(*slope)
+    }));
+    slopes
+}
+
+/// Ordinary least squares line `(intercept, slope)`, [None] for fewer than two distinct `x`.
+fn linear_regression(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|&(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|&(_, y)| y).sum::<f64>() / n;
+    let (sxx, sxy) = points.iter().fold((0.0, 0.0), |(sxx, sxy), &(x, y)| {
+        (
+            sxx + (x - mean_x).powi(2),
+            sxy + (x - mean_x) * (y - mean_y),
+        )
+    });
+    if points.len() < 2 || sxx <= 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    Some((mean_y - slope * mean_x, slope))
+}
+
+/// Initial guess of [SegmentedLinear] parameters: the finite points are split by `x` into
+/// `num_breakpoints + 1` segments of equal counts, a line is fitted to every segment, and the
+/// breakpoints are put between the segments. A segment with too few points gets the line fitted to
+/// all the points.
+fn segmented_guess(x: &[f64], y: &[f64], num_breakpoints: usize) -> Vec<f64> {
+    let mut points: Vec<_> = x
+        .iter()
+        .copied()
+        .zip(y.iter().copied())
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let n = points.len();
+    let num_segments = num_breakpoints + 1;
+    let overall = linear_regression(&points).unwrap_or_else(|| {
+        let mean_y = points.iter().map(|&(_, y)| y).sum::<f64>() / n.max(1) as f64;
+        (mean_y, 0.0)
+    });
+    let bounds: Vec<_> = (0..=num_segments).map(|j| j * n / num_segments).collect();
+    let lines: Vec<_> = bounds
+        .windows(2)
+        .map(|w| linear_regression(&points[w[0]..w[1]]).unwrap_or(overall))
+        .collect();
+    let breakpoints = bounds[1..num_segments].iter().map(|&i| match i {
+        0 => points.first().map_or(0.0, |&(x, _)| x),
+        i if i >= n => points.last().map_or(0.0, |&(x, _)| x),
+        i => 0.5 * (points[i - 1].0 + points[i].0),
+    });
+    let mut parameters = vec![lines[0].0, lines[0].1];
+    parameters.extend(lines.windows(2).map(|w| w[1].1 - w[0].1));
+    parameters.extend(breakpoints);
+    parameters
+}
+
+/// Continuous piecewise linear model with unknown breakpoints.
+///
+/// The parameters are `[a, b, db_1, ..., db_k, t_1, ..., t_k]` for `k` breakpoints, the model is
+/// `y = a + b x + sum_j db_j h(x - t_j)`, see [piecewise](crate::piecewise) module docs for `h`.
+/// So `a` and `b` are the intercept and the slope of the leftmost segment, `db_j` is the change
+/// of the slope at the breakpoint `t_j`.
+#[derive(Clone, Copy, Debug)]
+pub struct SegmentedLinear {
+    num_breakpoints: usize,
+    smoothness: f64,
+}
+
+impl SegmentedLinear {
+    /// Creates a new model with the given number of breakpoints and sharp breaks.
+    pub fn new(num_breakpoints: usize) -> Self {
+        Self {
+            num_breakpoints,
+            smoothness: 0.0,
+        }
+    }
+
+    /// Smooths the breaks with the softplus function of the given width in `x` units, zero means
+    /// sharp breaks.
+    pub fn smoothness(mut self, smoothness: f64) -> Self {
+        self.smoothness = smoothness;
+        self
+    }
+
+    /// Number of the model parameters, `2 + 2 * num_breakpoints`.
+    pub fn num_parameters(&self) -> usize {
+        2 + 2 * self.num_breakpoints
+    }
+
+    /// Sorted breakpoints for the given parameters.
+    pub fn breakpoints(&self, parameters: &[f64]) -> Vec<f64> {
+        let (changes, breakpoints) = parameters[2..].split_at(self.num_breakpoints);
+        sorted_breaks(changes, breakpoints)
+            .into_iter()
+            .map(|(breakpoint, _)| breakpoint)
+            .collect()
+    }
+
+    /// Slopes of all `num_breakpoints + 1` segments from left to right for the given parameters.
+    pub fn slopes(&self, parameters: &[f64]) -> Vec<f64> {
+        let (changes, breakpoints) = parameters[2..].split_at(self.num_breakpoints);
+        cumulative_slopes(parameters[1], &sorted_breaks(changes, breakpoints))
+    }
+
+    /// Estimates the initial parameters from the data. The points are split by `x` into
+    /// `num_breakpoints + 1` segments with equal numbers of points, a line is fitted to every
+    /// segment by ordinary least squares, and the breakpoints are put between the segments.
+    /// Non-finite points are ignored.
+    ///
+    /// ```rust
+    /// use ceres_solver::piecewise::SegmentedLinear;
+    ///
+    /// let x = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+    /// let y = [0.0, 1.0, 2.0, 2.0, 1.0, 0.0];
+    /// let parameters = SegmentedLinear::new(1).estimate_initial(&x, &y);
+    /// assert_eq!(parameters, [0.0, 1.0, -2.0, 2.5]);
+    /// ```
+    pub fn estimate_initial(&self, x: &[f64], y: &[f64]) -> Vec<f64> {
+        segmented_guess(x, y, self.num_breakpoints)
+    }
+}
+
+impl AutoDiffCurveModel for SegmentedLinear {
+    fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+        let (changes, breakpoints) = parameters[2..].split_at(self.num_breakpoints);
+        changes.iter().zip(breakpoints).fold(
+            parameters[0] + parameters[1] * x,
+            |y, (&change, &breakpoint)| y + change * hinge(x - breakpoint, self.smoothness),
+        )
+    }
+}
+
+/// Continuous broken power law with unknown breaks, a [SegmentedLinear] model in the log-log
+/// space.
+///
+/// The parameters are `[A, g, dg_1, ..., dg_k, x_1, ..., x_k]` for `k` breaks, the model is
+/// `ln y = ln A + g ln x + sum_j dg_j h(ln x - ln x_j)`, see [piecewise](crate::piecewise) module
+/// docs for `h`. So `g` is the index of the leftmost power-law segment, `A` is its value at
+/// `x = 1`, and `dg_j` is the change of the index at the break `x_j`. `x`, `A` and `x_j` must be
+/// positive, so consider positive lower bounds for `A` and `x_j`.
+#[derive(Clone, Copy, Debug)]
+pub struct BrokenPowerLaw {
+    num_breaks: usize,
+    smoothness: f64,
+}
+
+impl BrokenPowerLaw {
+    /// Creates a new model with the given number of breaks and sharp breaks.
+    pub fn new(num_breaks: usize) -> Self {
+        Self {
+            num_breaks,
+            smoothness: 0.0,
+        }
+    }
+
+    /// Smooths the breaks with the softplus function of the given width in `ln x` units, zero
+    /// means sharp breaks.
+    pub fn smoothness(mut self, smoothness: f64) -> Self {
+        self.smoothness = smoothness;
+        self
+    }
+
+    /// Number of the model parameters, `2 + 2 * num_breaks`.
+    pub fn num_parameters(&self) -> usize {
+        2 + 2 * self.num_breaks
+    }
+
+    /// Sorted breaks for the given parameters.
+    pub fn breaks(&self, parameters: &[f64]) -> Vec<f64> {
+        let (changes, breaks) = parameters[2..].split_at(self.num_breaks);
+        sorted_breaks(changes, breaks)
+            .into_iter()
+            .map(|(x_break, _)| x_break)
+            .collect()
+    }
+
+    /// Indices of all `num_breaks + 1` power-law segments from left to right for the given
+    /// parameters.
+    pub fn indices(&self, parameters: &[f64]) -> Vec<f64> {
+        let (changes, breaks) = parameters[2..].split_at(self.num_breaks);
+        cumulative_slopes(parameters[1], &sorted_breaks(changes, breaks))
+    }
+
+    /// Estimates the initial parameters from the data like [SegmentedLinear::estimate_initial]
+    /// in the log-log space. The points with non-positive `x` or `y` are ignored.
+    pub fn estimate_initial(&self, x: &[f64], y: &[f64]) -> Vec<f64> {
+        let (ln_x, ln_y): (Vec<_>, Vec<_>) = x
+            .iter()
+            .zip(y)
+            .filter(|(&x, &y)| x > 0.0 && y > 0.0)
+            .map(|(x, y)| (x.ln(), y.ln()))
+            .unzip();
+        let mut parameters = segmented_guess(&ln_x, &ln_y, self.num_breaks);
+        parameters[0] = parameters[0].exp();
+        for x_break in parameters[2 + self.num_breaks..].iter_mut() {
+            *x_break = x_break.exp();
+        }
+        parameters
+    }
+}
+
+impl AutoDiffCurveModel for BrokenPowerLaw {
+    fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+        let ln_x = x.ln();
+        let (changes, breaks) = parameters[2..].split_at(self.num_breaks);
+        changes
+            .iter()
+            .zip(breaks)
+            .fold(
+                parameters[0].ln() + parameters[1] * ln_x,
+                |ln_y, (&change, &x_break)| {
+                    ln_y + change * hinge(ln_x - x_break.ln(), self.smoothness)
+                },
+            )
+            .exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::curve_fit::CurveFitProblem1D;
+    use crate::solver::SolverOptions;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn segmented_linear() {
+        let model = SegmentedLinear::new(2).smoothness(0.1);
+        // Breakpoints in reverse order
+        let parameters = [1.0, 2.0, 1.0, -3.0, 6.0, 3.0];
+        assert_eq!(model.num_parameters(), parameters.len());
+        assert_eq!(model.breakpoints(&parameters), [3.0, 6.0]);
+        assert_eq!(model.slopes(&parameters), [2.0, -1.0, 0.0]);
+        // Softplus approaches the hinge far from the breakpoints
+        assert_abs_diff_eq!(model.model(0.0, &parameters), 1.0, epsilon = 1e-12);
+        assert_abs_diff_eq!(model.model(10.0, &parameters), 4.0, epsilon = 1e-12);
+
+        let x: Vec<_> = (0..100).map(|i| 0.1 * i as f64).collect();
+        let y: Vec<_> = x.iter().map(|&x| model.model(x, &parameters)).collect();
+        let solution =
+            CurveFitProblem1D::new_autodiff(model, &x, &y, &[0.0, 1.0, 0.5, -2.0, 7.0, 2.0])
+                .solve(&SolverOptions::default());
+        assert_abs_diff_eq!(
+            &model.breakpoints(&solution.parameters)[..],
+            &[3.0, 6.0][..],
+            epsilon = 1e-6
+        );
+        assert_abs_diff_eq!(
+            &model.slopes(&solution.parameters)[..],
+            &[2.0, -1.0, 0.0][..],
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn estimate_initial() {
+        // Sharp break at the middle of the data is found exactly
+        let model = SegmentedLinear::new(1);
+        let x: Vec<_> = (0..100).map(|i| 0.1 * i as f64).collect();
+        let y: Vec<_> = x
+            .iter()
+            .map(|&x| model.model(x, &[1.0, 2.0, -3.0, 4.95]))
+            .collect();
+        assert_abs_diff_eq!(
+            &model.estimate_initial(&x, &y)[..],
+            &[1.0, 2.0, -3.0, 4.95][..],
+            epsilon = 1e-10
+        );
+
+        // Unknown breaks are estimated roughly, good enough to converge
+        let model = BrokenPowerLaw::new(1);
+        let parameters = [3.0, 0.5, -2.0, 30.0];
+        let x: Vec<_> = (0..60).map(|i| f64::powf(10.0, 0.05 * i as f64)).collect();
+        let y: Vec<_> = x.iter().map(|&x| model.model(x, &parameters)).collect();
+        let initial = model.estimate_initial(&x, &y);
+        assert_eq!(initial.len(), model.num_parameters());
+        assert_abs_diff_eq!(initial[1], 0.5, epsilon = 1e-10);
+        let solution = CurveFitProblem1D::builder()
+            .autodiff_func(model)
+            .x(&x)
+            .y(&y)
+            .parameters(&initial)
+            .lower_bounds(&[Some(0.0), None, None, Some(1.0)])
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        assert_abs_diff_eq!(&solution.parameters[..], &parameters[..], epsilon = 1e-6);
+    }
+
+    #[test]
+    fn broken_power_law() {
+        let model = BrokenPowerLaw::new(1).smoothness(0.05);
+        let parameters = [2.0, -1.0, -1.5, 10.0];
+        let x: Vec<_> = (0..50).map(|i| f64::powf(10.0, 0.05 * i as f64)).collect();
+        let y: Vec<_> = x.iter().map(|&x| model.model(x, &parameters)).collect();
+        let solution = CurveFitProblem1D::builder()
+            .autodiff_func(model)
+            .x(&x)
+            .y(&y)
+            .parameters(&[1.0, -0.5, -1.0, 5.0])
+            .lower_bounds(&[Some(0.0), None, None, Some(1.0)])
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        assert_abs_diff_eq!(&solution.parameters[..], &parameters[..], epsilon = 1e-6);
+        assert_abs_diff_eq!(
+            &model.indices(&solution.parameters)[..],
+            &[-1.0, -2.5][..],
+            epsilon = 1e-6
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! [indicatif] progress bar adapter for the solver iterations.
+//!
+//! Pass a [ProgressBar] to [SolverOptionsBuilder::progress_bar] and it will show the iteration
+//! number, current cost and gradient norm while the problem is being solved.
+
+use crate::solver::{CallbackReturnType, IterationCallbackType, SolverOptionsBuilder};
+
+use indicatif::ProgressBar;
+
+/// Create an iteration callback updating `bar` after every minimizer iteration.
+///
+/// Position of the bar is set to the iteration number, the message shows the current cost and
+/// gradient norm. The bar is reset when iteration 0 arrives, so it restarts for every solve.
+/// The callback never stops the solver.
+pub fn iteration_callback(bar: ProgressBar) -> IterationCallbackType {
+    Box::new(move |summary| {
+        if summary.iteration == 0 {
+            bar.reset();
+        }
+        bar.set_position(summary.iteration as u64);
+        bar.set_message(format!(
+            "cost: {:.6e}, gradient norm: {:.3e}",
+            summary.cost, summary.gradient_norm
+        ));
+        CallbackReturnType::SOLVER_CONTINUE
+    })
+}
+
+impl SolverOptionsBuilder {
+    /// Show solver progress with an [indicatif] progress bar, see [iteration_callback].
+    ///
+    /// The bar is not finished by the solver, call [ProgressBar::finish] when the problem is
+    /// solved. The bar is shared by all the clones of the options, so solving several problems
+    /// with them at the same time makes them update the same bar.
+    pub fn progress_bar(self, bar: ProgressBar) -> Self {
+        self.iteration_callback(iteration_callback(bar))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::autodiff::Scalar;
+    use crate::curve_fit::{AutoDiffCurveModel, CurveFitProblem1D};
+
+    struct Line;
+
+    impl AutoDiffCurveModel for Line {
+        fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+            parameters[0] * x + parameters[1]
+        }
+    }
+
+    #[test]
+    fn bar_restarts_for_every_solve() {
+        let x = [0.0, 1.0, 2.0, 3.0];
+        let y = [1.0, 3.0, 5.0, 7.0];
+        let bar = ProgressBar::hidden();
+        let options = SolverOptionsBuilder::new()
+            .progress_bar(bar.clone())
+            .build()
+            .unwrap();
+
+        for _ in 0..2 {
+            CurveFitProblem1D::builder()
+                .autodiff_func(Line)
+                .x(&x)
+                .y(&y)
+                .parameters(&[0.0, 0.0])
+                .build()
+                .unwrap()
+                .solve(&options);
+            assert!(!bar.is_finished());
+            bar.finish();
+        }
+    }
+}
@@ -1,13 +1,207 @@
 //! Residual-block related structures.
 
+use crate::cost::{
+    CallbackCostFunction, CostFunction, CostFunctionType, FallibleCostFunctionType,
+    PendingCostFunction, SizedCost, ThreadSafeCostFunctionType, Whitening,
+};
+use crate::loss::{LossFunction, LossFunctionWrapper};
+use crate::parameter_block::ParameterBlockOrIndex;
+
 use ceres_solver_sys::cxx::SharedPtr;
 use ceres_solver_sys::ffi;
 use std::pin::Pin;
+use std::rc::Rc;
 
 pub type ResidualBlockId = SharedPtr<ffi::ResidualBlockId>;
 
-#[allow(dead_code)] // we use this struct to pin the parameter pointers array in memory
-pub(crate) struct ResidualBlock {
+// We use this struct to pin the parameter pointers array in memory and to keep the functions alive,
+// the problem doesn't own them, so they can be shared with its clones.
+pub(crate) struct ResidualBlock<'cost> {
     pub(crate) id: ResidualBlockId,
     pub(crate) parameter_pointers: Pin<Vec<*mut f64>>,
+    pub(crate) cost: Rc<CallbackCostFunction<'cost>>,
+    pub(crate) loss: Option<ResidualBlockLoss>,
+    pub(crate) name: Option<String>,
+    pub(crate) num_residuals: usize,
+}
+
+/// Loss function of a residual block, shared with the problem clones.
+#[derive(Clone)]
+pub(crate) enum ResidualBlockLoss {
+    Fixed(Rc<LossFunction>),
+    Replaceable(LossFunctionWrapper),
+}
+
+impl ResidualBlockLoss {
+    pub(crate) fn as_ptr(&self) -> *mut ffi::LossFunction {
+        match self {
+            Self::Fixed(loss) => loss.as_ptr(),
+            Self::Replaceable(wrapper) => wrapper.as_ptr(),
+        }
+    }
+
+    pub(crate) fn take_panic(&self) -> Option<String> {
+        match self {
+            Self::Fixed(loss) => loss.take_panic(),
+            Self::Replaceable(wrapper) => wrapper.take_panic(),
+        }
+    }
+}
+
+/// Description of a residual block for
+/// [NllsProblem::add_residual_blocks](crate::nlls_problem::NllsProblem::add_residual_blocks), an
+/// alternative to [ResidualBlockBuilder](crate::nlls_problem::ResidualBlockBuilder) for adding
+/// many residual blocks at once.
+pub struct ResidualBlockSpec<'cost> {
+    pub(crate) cost: PendingCostFunction<'cost>,
+    pub(crate) threadsafe_cost: bool,
+    pub(crate) parameters: Vec<ParameterBlockOrIndex<'cost>>,
+    pub(crate) loss: Option<LossFunction>,
+    pub(crate) replaceable_loss: bool,
+    pub(crate) loss_wrapper: Option<LossFunctionWrapper>,
+    pub(crate) whitening: Option<Whitening>,
+    pub(crate) name: Option<String>,
+}
+
+impl<'cost> ResidualBlockSpec<'cost> {
+    /// Residual block with the given cost function, number of residuals and parameters, see
+    /// [ResidualBlockBuilder::set_cost](crate::nlls_problem::ResidualBlockBuilder::set_cost) and
+    /// [ResidualBlockBuilder::set_parameters](crate::nlls_problem::ResidualBlockBuilder::set_parameters).
+    pub fn new<P>(
+        func: impl Into<CostFunctionType<'cost>>,
+        num_residuals: usize,
+        parameters: impl IntoIterator<Item = P>,
+    ) -> Self
+    where
+        P: Into<ParameterBlockOrIndex<'cost>>,
+    {
+        Self::with_pending_cost(
+            PendingCostFunction::from_closure(func.into(), num_residuals),
+            parameters,
+        )
+    }
+
+    /// Residual block with a cost function returning an error on failure, see
+    /// [ResidualBlockBuilder::set_fallible_cost](crate::nlls_problem::ResidualBlockBuilder::set_fallible_cost).
+    pub fn new_fallible<P>(
+        func: impl Into<FallibleCostFunctionType<'cost>>,
+        num_residuals: usize,
+        parameters: impl IntoIterator<Item = P>,
+    ) -> Self
+    where
+        P: Into<ParameterBlockOrIndex<'cost>>,
+    {
+        Self::with_pending_cost(
+            PendingCostFunction::from_fallible_closure(func.into(), num_residuals),
+            parameters,
+        )
+    }
+
+    /// Residual block with the cost function implemented by a type, see
+    /// [ResidualBlockBuilder::set_cost_fn](crate::nlls_problem::ResidualBlockBuilder::set_cost_fn).
+    pub fn from_cost_fn<P>(
+        cost: impl CostFunction + 'cost,
+        parameters: impl IntoIterator<Item = P>,
+    ) -> Self
+    where
+        P: Into<ParameterBlockOrIndex<'cost>>,
+    {
+        Self::with_pending_cost(PendingCostFunction::from_cost_fn(cost), parameters)
+    }
+
+    /// Residual block with the thread-safe cost function implemented by a type, see
+    /// [ResidualBlockBuilder::set_threadsafe_cost_fn](crate::nlls_problem::ResidualBlockBuilder::set_threadsafe_cost_fn).
+    pub fn from_threadsafe_cost_fn<P>(
+        cost: impl CostFunction + Send + Sync + 'cost,
+        parameters: impl IntoIterator<Item = P>,
+    ) -> Self
+    where
+        P: Into<ParameterBlockOrIndex<'cost>>,
+    {
+        Self {
+            threadsafe_cost: true,
+            ..Self::from_cost_fn(cost, parameters)
+        }
+    }
+
+    /// Residual block with a cost function of compile-time sizes, see
+    /// [ResidualBlockBuilder::set_sized_cost](crate::nlls_problem::ResidualBlockBuilder::set_sized_cost).
+    pub fn from_sized_cost<P>(
+        cost: impl SizedCost + 'cost,
+        parameters: impl IntoIterator<Item = P>,
+    ) -> Self
+    where
+        P: Into<ParameterBlockOrIndex<'cost>>,
+    {
+        Self::with_pending_cost(PendingCostFunction::from_sized_cost(cost), parameters)
+    }
+
+    fn with_pending_cost<P>(
+        cost: PendingCostFunction<'cost>,
+        parameters: impl IntoIterator<Item = P>,
+    ) -> Self
+    where
+        P: Into<ParameterBlockOrIndex<'cost>>,
+    {
+        Self {
+            cost,
+            threadsafe_cost: false,
+            parameters: parameters.into_iter().map(|p| p.into()).collect(),
+            loss: None,
+            replaceable_loss: false,
+            loss_wrapper: None,
+            whitening: None,
+            name: None,
+        }
+    }
+
+    /// Residual block with a thread-safe cost function, required by problems created with
+    /// [NllsProblem::new_threadsafe](crate::nlls_problem::NllsProblem::new_threadsafe).
+    pub fn new_threadsafe<P>(
+        func: impl Into<ThreadSafeCostFunctionType<'cost>>,
+        num_residuals: usize,
+        parameters: impl IntoIterator<Item = P>,
+    ) -> Self
+    where
+        P: Into<ParameterBlockOrIndex<'cost>>,
+    {
+        let func: ThreadSafeCostFunctionType<'cost> = func.into();
+        Self {
+            threadsafe_cost: true,
+            ..Self::new(func as CostFunctionType<'cost>, num_residuals, parameters)
+        }
+    }
+
+    /// Set loss function for the residual block.
+    pub fn set_loss(mut self, loss: LossFunction) -> Self {
+        self.loss = Some(loss);
+        self
+    }
+
+    /// Make the loss function replaceable, see
+    /// [ResidualBlockBuilder::replaceable_loss](crate::nlls_problem::ResidualBlockBuilder::replaceable_loss).
+    pub fn replaceable_loss(mut self) -> Self {
+        self.replaceable_loss = true;
+        self
+    }
+
+    /// Use the shared loss function wrapper, see
+    /// [ResidualBlockBuilder::set_loss_wrapper](crate::nlls_problem::ResidualBlockBuilder::set_loss_wrapper).
+    pub fn set_loss_wrapper(mut self, wrapper: LossFunctionWrapper) -> Self {
+        self.loss_wrapper = Some(wrapper);
+        self
+    }
+
+    /// Whiten residuals and jacobians of the cost function, see
+    /// [ResidualBlockBuilder::set_whitening](crate::nlls_problem::ResidualBlockBuilder::set_whitening).
+    pub fn set_whitening(mut self, whitening: Whitening) -> Self {
+        self.whitening = Some(whitening);
+        self
+    }
+
+    /// Set a name for the residual block.
+    pub fn set_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
 }
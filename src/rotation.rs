@@ -0,0 +1,369 @@
+//! Rotations in 3D, the Rust counterpart of `ceres/rotation.h`.
+//!
+//! The functions are generic over [Scalar], so they can be used in
+//! [AutoDiffCostFunctor](crate::autodiff::AutoDiffCostFunctor) and
+//! [AutoDiffManifoldFunctor](crate::autodiff::AutoDiffManifoldFunctor), and the derivatives are
+//! exact for small rotations too.
+//!
+//! Conventions follow Ceres:
+//! - angle-axis is a 3-vector, its direction is the rotation axis and its norm is the angle in
+//!   radians,
+//! - quaternion is stored as `[w, x, y, z]`, like for
+//!   [Manifold::quaternion](crate::manifold::Manifold::quaternion),
+//! - rotation matrix is indexed as `r[row][column]` and rotates column vectors, `p' = R p`.
+//!
+//! ```rust
+//! use ceres_solver::rotation::{angle_axis_rotate_point, angle_axis_to_quaternion, quaternion_rotate_point};
+//! use std::f64::consts::FRAC_PI_2;
+//!
+//! // Rotation by 90 degrees around z-axis
+//! let angle_axis = [0.0, 0.0, FRAC_PI_2];
+//! let point = angle_axis_rotate_point(&angle_axis, &[1.0, 0.0, 0.0]);
+//! assert!((point[1] - 1.0).abs() < 1e-12);
+//! let quaternion = angle_axis_to_quaternion(&angle_axis);
+//! let point = quaternion_rotate_point(&quaternion, &[1.0, 0.0, 0.0]);
+//! assert!((point[1] - 1.0).abs() < 1e-12);
+//! ```
+
+use crate::autodiff::Scalar;
+
+/// Quaternion `[w, x, y, z]` of the rotation given by the angle-axis vector.
+pub fn angle_axis_to_quaternion<T: Scalar>(angle_axis: &[T; 3]) -> [T; 4] {
+    let theta_squared = dot_product(angle_axis, angle_axis);
+    // For points not at the origin, the full conversion is numerically stable.
+    if theta_squared > T::zero() {
+        let theta = theta_squared.sqrt();
+        let half_theta = theta * 0.5;
+        let k = half_theta.sin() / theta;
+        [
+            half_theta.cos(),
+            angle_axis[0] * k,
+            angle_axis[1] * k,
+            angle_axis[2] * k,
+        ]
+    } else {
+        // At the origin sqrt() has infinite derivative, so use the Taylor expansion
+        // sin(x / 2) / x ~ 1 / 2, it keeps the derivatives exact.
+        [
+            T::one(),
+            angle_axis[0] * 0.5,
+            angle_axis[1] * 0.5,
+            angle_axis[2] * 0.5,
+        ]
+    }
+}
+
+/// Angle-axis vector of the rotation given by the quaternion `[w, x, y, z]`, the angle is in
+/// `[-pi, pi]`. The quaternion doesn't have to be normalized.
+pub fn quaternion_to_angle_axis<T: Scalar>(quaternion: &[T; 4]) -> [T; 3] {
+    let [q0, q1, q2, q3] = *quaternion;
+    let sin_squared_theta = q1 * q1 + q2 * q2 + q3 * q3;
+    let k = if sin_squared_theta > T::zero() {
+        let sin_theta = sin_squared_theta.sqrt();
+        let cos_theta = q0;
+        // If cos_theta is negative, theta is greater than pi/2, so the angle of the rotation
+        // 2 theta is greater than pi. Use the equivalent rotation by 2 theta - 2 pi, which is
+        // the rotation by the angle less than pi in the opposite direction.
+        let two_theta = if cos_theta < T::zero() {
+            (-sin_theta).atan2(-cos_theta) * 2.0
+        } else {
+            sin_theta.atan2(cos_theta) * 2.0
+        };
+        two_theta / sin_theta
+    } else {
+        // Taylor expansion of atan2(sin, cos) / sin at zero, it keeps the derivatives exact.
+        T::from_f64(2.0)
+    };
+    [q1 * k, q2 * k, q3 * k]
+}
+
+/// Rotation matrix of the quaternion `[w, x, y, z]` scaled by its squared norm, so it is a proper
+/// rotation matrix for unit quaternions only. It avoids the division of
+/// [quaternion_to_rotation].
+pub fn quaternion_to_scaled_rotation<T: Scalar>(quaternion: &[T; 4]) -> [[T; 3]; 3] {
+    let [a, b, c, d] = *quaternion;
+    let (aa, ab, ac, ad) = (a * a, a * b, a * c, a * d);
+    let (bb, bc, bd) = (b * b, b * c, b * d);
+    let (cc, cd) = (c * c, c * d);
+    let dd = d * d;
+    [
+        [aa + bb - cc - dd, (bc - ad) * 2.0, (ac + bd) * 2.0],
+        [(ad + bc) * 2.0, aa - bb + cc - dd, (cd - ab) * 2.0],
+        [(bd - ac) * 2.0, (ab + cd) * 2.0, aa - bb - cc + dd],
+    ]
+}
+
+/// Rotation matrix of the quaternion `[w, x, y, z]`, the quaternion doesn't have to be
+/// normalized.
+pub fn quaternion_to_rotation<T: Scalar>(quaternion: &[T; 4]) -> [[T; 3]; 3] {
+    let normalizer = T::one() / dot_product(quaternion, quaternion);
+    quaternion_to_scaled_rotation(quaternion).map(|row| row.map(|value| value * normalizer))
+}
+
+/// Unit quaternion `[w, x, y, z]` of the rotation matrix, the scalar part is non-negative.
+pub fn rotation_matrix_to_quaternion<T: Scalar>(r: &[[T; 3]; 3]) -> [T; 4] {
+    let trace = r[0][0] + r[1][1] + r[2][2];
+    if trace >= T::zero() {
+        let t = (trace + 1.0).sqrt();
+        let half_inverse = t.recip() * 0.5;
+        [
+            t * 0.5,
+            (r[2][1] - r[1][2]) * half_inverse,
+            (r[0][2] - r[2][0]) * half_inverse,
+            (r[1][0] - r[0][1]) * half_inverse,
+        ]
+    } else {
+        // Use the largest diagonal element to keep the square root argument positive
+        let mut i = 0;
+        if r[1][1] > r[0][0] {
+            i = 1;
+        }
+        if r[2][2] > r[i][i] {
+            i = 2;
+        }
+        let j = (i + 1) % 3;
+        let k = (j + 1) % 3;
+        let t = (r[i][i] - r[j][j] - r[k][k] + 1.0).sqrt();
+        let half_inverse = t.recip() * 0.5;
+        let mut quaternion = [T::zero(); 4];
+        quaternion[0] = (r[k][j] - r[j][k]) * half_inverse;
+        quaternion[i + 1] = t * 0.5;
+        quaternion[j + 1] = (r[j][i] + r[i][j]) * half_inverse;
+        quaternion[k + 1] = (r[k][i] + r[i][k]) * half_inverse;
+        quaternion
+    }
+}
+
+/// Rotation matrix of the angle-axis vector.
+pub fn angle_axis_to_rotation_matrix<T: Scalar>(angle_axis: &[T; 3]) -> [[T; 3]; 3] {
+    let theta_squared = dot_product(angle_axis, angle_axis);
+    if theta_squared > T::from_f64(f64::EPSILON) {
+        // Rodrigues' formula, it is numerically stable far from zero angle only
+        let theta = theta_squared.sqrt();
+        let [wx, wy, wz] = angle_axis.map(|w| w / theta);
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let one_minus_cos = T::one() - cos_theta;
+        [
+            [
+                cos_theta + wx * wx * one_minus_cos,
+                wx * wy * one_minus_cos - wz * sin_theta,
+                wy * sin_theta + wx * wz * one_minus_cos,
+            ],
+            [
+                wz * sin_theta + wx * wy * one_minus_cos,
+                cos_theta + wy * wy * one_minus_cos,
+                -wx * sin_theta + wy * wz * one_minus_cos,
+            ],
+            [
+                -wy * sin_theta + wx * wz * one_minus_cos,
+                wx * sin_theta + wy * wz * one_minus_cos,
+                cos_theta + wz * wz * one_minus_cos,
+            ],
+        ]
+    } else {
+        // First order Taylor expansion at zero angle, R = I + [angle_axis]_x
+        let [x, y, z] = *angle_axis;
+        let one = T::one();
+        [[one, -z, y], [z, one, -x], [-y, x, one]]
+    }
+}
+
+/// Angle-axis vector of the rotation matrix, the angle is in `[-pi, pi]`.
+pub fn rotation_matrix_to_angle_axis<T: Scalar>(r: &[[T; 3]; 3]) -> [T; 3] {
+    quaternion_to_angle_axis(&rotation_matrix_to_quaternion(r))
+}
+
+/// Rotate the point by the angle-axis vector.
+pub fn angle_axis_rotate_point<T: Scalar>(angle_axis: &[T; 3], point: &[T; 3]) -> [T; 3] {
+    let theta_squared = dot_product(angle_axis, angle_axis);
+    if theta_squared > T::from_f64(f64::EPSILON) {
+        // Rodrigues' formula
+        let theta = theta_squared.sqrt();
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let w = angle_axis.map(|w| w / theta);
+        let w_cross_point = cross_product(&w, point);
+        let tmp = dot_product(&w, point) * (T::one() - cos_theta);
+        [0, 1, 2].map(|i| point[i] * cos_theta + w_cross_point[i] * sin_theta + w[i] * tmp)
+    } else {
+        // First order Taylor expansion at zero angle, the derivatives are exact at zero
+        let w_cross_point = cross_product(angle_axis, point);
+        [0, 1, 2].map(|i| point[i] + w_cross_point[i])
+    }
+}
+
+/// Rotate the point by the unit quaternion `[w, x, y, z]`, the result is undefined for
+/// non-normalized quaternions, see [quaternion_rotate_point].
+pub fn unit_quaternion_rotate_point<T: Scalar>(quaternion: &[T; 4], point: &[T; 3]) -> [T; 3] {
+    let w = quaternion[0];
+    let v = [quaternion[1], quaternion[2], quaternion[3]];
+    // p' = p + 2 w (v x p) + 2 v x (v x p)
+    let uv = cross_product(&v, point).map(|x| x * 2.0);
+    let v_cross_uv = cross_product(&v, &uv);
+    [0, 1, 2].map(|i| point[i] + w * uv[i] + v_cross_uv[i])
+}
+
+/// Rotate the point by the quaternion `[w, x, y, z]`, the quaternion doesn't have to be
+/// normalized.
+pub fn quaternion_rotate_point<T: Scalar>(quaternion: &[T; 4], point: &[T; 3]) -> [T; 3] {
+    let scale = dot_product(quaternion, quaternion).sqrt().recip();
+    let unit = quaternion.map(|q| q * scale);
+    unit_quaternion_rotate_point(&unit, point)
+}
+
+/// Product of the quaternions `z w`, it is the rotation by `w` followed by the rotation by `z`.
+pub fn quaternion_product<T: Scalar>(z: &[T; 4], w: &[T; 4]) -> [T; 4] {
+    [
+        z[0] * w[0] - z[1] * w[1] - z[2] * w[2] - z[3] * w[3],
+        z[0] * w[1] + z[1] * w[0] + z[2] * w[3] - z[3] * w[2],
+        z[0] * w[2] - z[1] * w[3] + z[2] * w[0] + z[3] * w[1],
+        z[0] * w[3] + z[1] * w[2] - z[2] * w[1] + z[3] * w[0],
+    ]
+}
+
+/// Cross product `x × y`.
+pub fn cross_product<T: Scalar>(x: &[T; 3], y: &[T; 3]) -> [T; 3] {
+    [
+        x[1] * y[2] - x[2] * y[1],
+        x[2] * y[0] - x[0] * y[2],
+        x[0] * y[1] - x[1] * y[0],
+    ]
+}
+
+/// Dot product of the vectors.
+pub fn dot_product<T: Scalar, const N: usize>(x: &[T; N], y: &[T; N]) -> T {
+    x.iter().zip(y).fold(T::zero(), |sum, (&x, &y)| sum + x * y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jet::Jet;
+
+    use approx::assert_abs_diff_eq;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    fn assert_matrix_eq(a: [[f64; 3]; 3], b: [[f64; 3]; 3]) {
+        for (a, b) in a.iter().zip(&b) {
+            assert_abs_diff_eq!(a[..], b[..], epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        // Rotation by 90 degrees around z-axis
+        let angle_axis = [0.0, 0.0, FRAC_PI_2];
+        let c = f64::sqrt(0.5);
+        let quaternion = angle_axis_to_quaternion(&angle_axis);
+        assert_abs_diff_eq!(quaternion[..], [c, 0.0, 0.0, c], epsilon = 1e-12);
+        assert_abs_diff_eq!(
+            quaternion_to_angle_axis(&quaternion)[..],
+            angle_axis[..],
+            epsilon = 1e-12
+        );
+        // Non-normalized quaternion of the rotation by more than pi
+        assert_abs_diff_eq!(
+            quaternion_to_angle_axis(&[-2.0, 0.0, 0.0, 2.0])[..],
+            [0.0, 0.0, -FRAC_PI_2],
+            epsilon = 1e-12
+        );
+
+        let matrix = [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+        assert_matrix_eq(angle_axis_to_rotation_matrix(&angle_axis), matrix);
+        assert_matrix_eq(
+            quaternion_to_rotation(&[2.0 * c, 0.0, 0.0, 2.0 * c]),
+            matrix,
+        );
+        assert_abs_diff_eq!(
+            rotation_matrix_to_quaternion(&matrix)[..],
+            quaternion[..],
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            rotation_matrix_to_angle_axis(&matrix)[..],
+            angle_axis[..],
+            epsilon = 1e-12
+        );
+
+        // Negative trace branch, rotation by pi around x-axis
+        let matrix = [[1.0, 0.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, -1.0]];
+        assert_abs_diff_eq!(
+            rotation_matrix_to_quaternion(&matrix)[..],
+            [0.0, 1.0, 0.0, 0.0],
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            rotation_matrix_to_angle_axis(&matrix)[..],
+            [PI, 0.0, 0.0],
+            epsilon = 1e-12
+        );
+
+        // Generic rotation survives the round trips
+        let angle_axis = [0.3, -0.5, 0.7];
+        let matrix = angle_axis_to_rotation_matrix(&angle_axis);
+        assert_matrix_eq(
+            quaternion_to_rotation(&angle_axis_to_quaternion(&angle_axis)),
+            matrix,
+        );
+        assert_abs_diff_eq!(
+            rotation_matrix_to_angle_axis(&matrix)[..],
+            angle_axis[..],
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn rotate_point() {
+        let angle_axis = [0.3, -0.5, 0.7];
+        let quaternion = angle_axis_to_quaternion(&angle_axis);
+        let matrix = angle_axis_to_rotation_matrix(&angle_axis);
+        let point = [1.0, 2.0, 3.0];
+        let expected = matrix.map(|row| dot_product(&row, &point));
+        assert_abs_diff_eq!(
+            angle_axis_rotate_point(&angle_axis, &point)[..],
+            expected[..],
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            unit_quaternion_rotate_point(&quaternion, &point)[..],
+            expected[..],
+            epsilon = 1e-12
+        );
+        assert_abs_diff_eq!(
+            quaternion_rotate_point(&quaternion.map(|q| 3.0 * q), &point)[..],
+            expected[..],
+            epsilon = 1e-12
+        );
+
+        // Rotation by the product is the composition of the rotations
+        let other = angle_axis_to_quaternion(&[-0.1, 0.2, 0.4]);
+        let product = quaternion_product(&other, &quaternion);
+        assert_abs_diff_eq!(
+            unit_quaternion_rotate_point(&product, &point)[..],
+            unit_quaternion_rotate_point(&other, &expected)[..],
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn derivatives_at_zero_angle() {
+        // d(R p)/d(angle_axis) = -[p]_x at zero angle
+        let angle_axis: [Jet<3>; 3] = [0, 1, 2].map(|i| Jet::variable(0.0, i));
+        let point = [1.0, 2.0, 3.0].map(Jet::constant);
+        let expected = [[0.0, 3.0, -2.0], [-3.0, 0.0, 1.0], [2.0, -1.0, 0.0]];
+
+        let rotated = angle_axis_rotate_point(&angle_axis, &point);
+        for (jet, row) in rotated.iter().zip(&expected) {
+            assert_abs_diff_eq!(jet.v[..], row[..]);
+        }
+        let quaternion = angle_axis_to_quaternion(&angle_axis);
+        let rotated = unit_quaternion_rotate_point(&quaternion, &point);
+        for (jet, row) in rotated.iter().zip(&expected) {
+            assert_abs_diff_eq!(jet.v[..], row[..]);
+        }
+        let matrix = angle_axis_to_rotation_matrix(&angle_axis);
+        for (i, row) in expected.iter().enumerate() {
+            let rotated = dot_product(&matrix[i], &point);
+            assert_abs_diff_eq!(rotated.v[..], row[..]);
+        }
+    }
+}
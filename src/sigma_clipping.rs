@@ -0,0 +1,253 @@
+//! Outlier rejection by iterative sigma clipping, see
+//! [CurveFitProblem1DBuilder::solve_clipped](crate::curve_fit::CurveFitProblem1DBuilder::solve_clipped).
+//!
+//! The data is fitted, the weighted residuals `(y - model(x)) * inverse_error` of all the points
+//! are standardized by their scale, and the points with the standardized residuals larger than
+//! the threshold are flagged as outliers. The outliers are either removed from the next fit, or
+//! down-weighted with the Huber weights `threshold / |z|`, which is iteratively reweighted least
+//! squares for the Huber loss. The removed points are still checked after every fit, so they
+//! return if the model moves towards them. The loop stops when the outlier mask and the weights
+//! don't change anymore.
+//!
+//! ```rust
+//! use ceres_solver::curve_fit::{CurveFitProblem1D, CurveFunctionType};
+//! use ceres_solver::sigma_clipping::SigmaClipping;
+//! use ceres_solver::SolverOptions;
+//!
+//! // y = a * x + b
+//! fn line(x: f64, parameters: &[f64], y: &mut f64, jacobians: Option<&mut [Option<f64>]>) -> bool {
+//!     *y = parameters[0] * x + parameters[1];
+//!     if let Some(jacobians) = jacobians {
+//!         if let Some(d_da) = &mut jacobians[0] {
+//!             *d_da = x;
+//!         }
+//!         if let Some(d_db) = &mut jacobians[1] {
+//!             *d_db = 1.0;
+//!         }
+//!     }
+//!     true
+//! }
+//!
+//! let x: Vec<_> = (0..50).map(|i| i as f64).collect();
+//! let mut y: Vec<_> = x
+//!     .iter()
+//!     .map(|&x| 2.0 * x + 1.0 + 0.1 * f64::sin(10.0 * x))
+//!     .collect();
+//! // Cosmic ray hits
+//! y[10] += 30.0;
+//! y[40] -= 50.0;
+//!
+//! let func: CurveFunctionType = Box::new(line);
+//! let clipped = CurveFitProblem1D::builder()
+//!     .func(func)
+//!     .x(&x)
+//!     .y(&y)
+//!     .parameters(&[1.0, 0.0])
+//!     .solve_clipped(&SolverOptions::default(), &SigmaClipping::default())
+//!     .unwrap();
+//!
+//! assert!(clipped.converged);
+//! assert!(clipped.outliers[10] && clipped.outliers[40]);
+//! assert!(f64::abs(clipped.solution.parameters[0] - 2.0) < 0.01);
+//! ```
+
+use crate::curve_fit::CurveFitProblemSolution;
+
+/// How [SigmaClipping] treats the outliers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClippingMode {
+    /// Remove the outliers from the next fit.
+    Remove,
+    /// Multiply the squared residuals of the outliers by the Huber weights `threshold / |z|`.
+    Downweight,
+}
+
+/// Options of the sigma clipping, see [sigma_clipping](crate::sigma_clipping) module docs.
+#[derive(Clone, Debug)]
+pub struct SigmaClipping {
+    /// Threshold of the absolute standardized residual, 3 by default.
+    pub threshold: f64,
+    /// Maximum number of fits, 10 by default.
+    pub max_iterations: usize,
+    /// What to do with the outliers, [ClippingMode::Remove] by default.
+    pub mode: ClippingMode,
+    /// Scale of the weighted residuals, e.g. unity if the inverse errors are known to be right.
+    /// If [None], the default, it is estimated from the residuals of all the points as
+    /// `1.4826 * median(|r|)`, which is robust to the outliers.
+    pub scale: Option<f64>,
+}
+
+impl Default for SigmaClipping {
+    fn default() -> Self {
+        Self {
+            threshold: 3.0,
+            max_iterations: 10,
+            mode: ClippingMode::Remove,
+            scale: None,
+        }
+    }
+}
+
+/// A solution for
+/// [CurveFitProblem1DBuilder::solve_clipped](crate::curve_fit::CurveFitProblem1DBuilder::solve_clipped).
+pub struct SigmaClippingSolution {
+    /// Solution of the last fit, its statistics don't count the removed points.
+    pub solution: CurveFitProblemSolution,
+    /// Outlier mask of all the data points for the solution.
+    pub outliers: Vec<bool>,
+    /// Weights of the squared residuals of all the data points for the next fit, they are unity
+    /// for [ClippingMode::Remove].
+    pub weights: Vec<f64>,
+    /// Number of fits done.
+    pub iterations: usize,
+    /// Whether the outlier mask and the weights were stable after the last fit, [false] if the
+    /// loop stopped after [SigmaClipping::max_iterations] fits.
+    pub converged: bool,
+}
+
+/// Change of the weights below which they are considered stable.
+const WEIGHT_TOLERANCE: f64 = 1e-6;
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let n = values.len();
+    if n == 0 {
+        f64::NAN
+    } else if n % 2 == 1 {
+        values[n / 2]
+    } else {
+        0.5 * (values[n / 2 - 1] + values[n / 2])
+    }
+}
+
+impl SigmaClipping {
+    /// Updates the outlier mask and the weights from the weighted residuals of all the data
+    /// points, returns [true] if they didn't change.
+    pub(crate) fn update(
+        &self,
+        weighted_residuals: &[f64],
+        outliers: &mut [bool],
+        weights: &mut [f64],
+    ) -> bool {
+        let scale = self.scale.unwrap_or_else(|| {
+            1.4826 * median(weighted_residuals.iter().map(|r| r.abs()).collect())
+        });
+        let mut stable = true;
+        for ((residual, outlier), weight) in weighted_residuals.iter().zip(outliers).zip(weights) {
+            let z = (residual / scale).abs();
+            let new_outlier = z > self.threshold;
+            let new_weight = match self.mode {
+                ClippingMode::Downweight if new_outlier => self.threshold / z,
+                _ => 1.0,
+            };
+            stable &= new_outlier == *outlier && (new_weight - *weight).abs() < WEIGHT_TOLERANCE;
+            *outlier = new_outlier;
+            *weight = new_weight;
+        }
+        stable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::curve_fit::{CurveFitProblem1D, CurveFunctionType};
+    use crate::error::CurveFitProblemBuildError;
+    use crate::loss::LossFunction;
+    use crate::solver::SolverOptions;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn update() {
+        let clipping = SigmaClipping {
+            scale: Some(1.0),
+            mode: ClippingMode::Downweight,
+            ..Default::default()
+        };
+        let mut outliers = vec![false; 3];
+        let mut weights = vec![1.0; 3];
+        assert!(!clipping.update(&[0.5, -6.0, 2.0], &mut outliers, &mut weights));
+        assert_eq!(outliers, [false, true, false]);
+        assert_eq!(weights, [1.0, 0.5, 1.0]);
+        assert!(clipping.update(&[0.5, -6.0, 2.0], &mut outliers, &mut weights));
+
+        assert_eq!(median(vec![3.0, 1.0, 2.0, 10.0]), 2.5);
+    }
+
+    fn line(
+        x: f64,
+        parameters: &[f64],
+        y: &mut f64,
+        jacobians: Option<&mut [Option<f64>]>,
+    ) -> bool {
+        *y = parameters[0] * x + parameters[1];
+        if let Some(jacobians) = jacobians {
+            for (jacobian, derivative) in jacobians.iter_mut().zip([x, 1.0]) {
+                if let Some(jacobian) = jacobian {
+                    *jacobian = derivative;
+                }
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn remove_and_downweight() {
+        let x: Vec<_> = (0..100).map(|i| 0.1 * i as f64).collect();
+        let mut y: Vec<_> = x
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| -x + 5.0 + 0.01 * f64::sin(1e3 * i as f64))
+            .collect();
+        let outlier_indexes = [3, 50, 51, 97];
+        for &i in outlier_indexes.iter() {
+            y[i] += 1.0;
+        }
+
+        for mode in [ClippingMode::Remove, ClippingMode::Downweight] {
+            let func: CurveFunctionType = Box::new(line);
+            let clipped = CurveFitProblem1D::builder()
+                .func(func)
+                .x(&x)
+                .y(&y)
+                .parameters(&[0.0, 0.0])
+                .solve_clipped(
+                    &SolverOptions::default(),
+                    &SigmaClipping {
+                        mode,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+            assert!(clipped.converged);
+            let outliers: Vec<_> = (0..x.len()).filter(|&i| clipped.outliers[i]).collect();
+            assert_eq!(outliers, outlier_indexes);
+            assert_abs_diff_eq!(clipped.solution.parameters[0], -1.0, epsilon = 1e-2);
+            assert_abs_diff_eq!(clipped.solution.parameters[1], 5.0, epsilon = 1e-2);
+            if mode == ClippingMode::Remove {
+                assert_eq!(clipped.solution.degrees_of_freedom, 94);
+                assert!(clipped.weights.iter().all(|&w| w == 1.0));
+            } else {
+                assert_eq!(clipped.solution.degrees_of_freedom, 98);
+                assert!(clipped.weights[50] < 0.1);
+            }
+        }
+
+        let func: CurveFunctionType = Box::new(line);
+        let error = CurveFitProblem1D::builder()
+            .func(func)
+            .x(&x)
+            .y(&y)
+            .parameters(&[0.0, 0.0])
+            .loss(LossFunction::cauchy(1.0))
+            .solve_clipped(&SolverOptions::default(), &SigmaClipping::default())
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            CurveFitProblemBuildError::OutlierRejectionConflict
+        ));
+    }
+}
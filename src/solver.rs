@@ -6,15 +6,24 @@ use crate::residual_block::ResidualBlockId;
 use ceres_solver_sys::cxx::{let_cxx_string, UniquePtr};
 use ceres_solver_sys::ffi;
 pub use ceres_solver_sys::ffi::{
-    DenseLinearAlgebraLibraryType, DoglegType, DumpFormatType, LineSearchDirectionType,
-    LineSearchInterpolationType, LineSearchType, LinearSolverType, LoggingType, MinimizerType,
+    CallbackReturnType, DenseLinearAlgebraLibraryType, DoglegType, DumpFormatType,
+    IterationSummary, LineSearchDirectionType, LineSearchInterpolationType, LineSearchType,
+    LinearSolverOrderingType, LinearSolverType, LoggingType, MinimizerType,
     NonlinearConjugateGradientType, PreconditionerType, SparseLinearAlgebraLibraryType,
-    TrustRegionStrategyType, VisibilityClusteringType,
+    TerminationType, TrustRegionStrategyType, VisibilityClusteringType,
 };
 use std::borrow::Cow;
 use std::ffi::OsStr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Iteration callback function type.
+///
+/// It is called after every minimizer iteration with the [IterationSummary] of the iteration and
+/// returns [CallbackReturnType] which tells the solver whether to continue or to stop.
+pub type IterationCallbackType = Box<dyn FnMut(&IterationSummary) -> CallbackReturnType>;
 
 pub struct SolverOptions(pub(crate) UniquePtr<ffi::SolverOptions>);
 
@@ -22,6 +31,528 @@ impl SolverOptions {
     pub fn builder() -> SolverOptionsBuilder {
         SolverOptionsBuilder::new()
     }
+
+    /// Convert the options back to the builder, so they can be adjusted and built again.
+    pub fn into_builder(self) -> SolverOptionsBuilder {
+        SolverOptionsBuilder(self.0)
+    }
+
+    pub(crate) fn inner(&self) -> &ffi::SolverOptions {
+        self.0
+            .as_ref()
+            .expect("Underlying C++ unique_ptr<SolverOptions> must not hold nullptr")
+    }
+
+    pub(crate) fn inner_mut(&mut self) -> Pin<&mut ffi::SolverOptions> {
+        self.0
+            .as_mut()
+            .expect("Underlying C++ unique_ptr<SolverOptions> must not hold nullptr")
+    }
+
+    /// Copy of the options with an additional iteration callback.
+    pub(crate) fn with_iteration_callback(&self, callback: IterationCallbackType) -> Self {
+        let mut options = self.clone();
+        options
+            .inner_mut()
+            .add_iteration_callback(Box::new(callback.into()));
+        options
+    }
+
+    /// Copy of the options with another number of threads.
+    pub(crate) fn with_num_threads(&self, num_threads: i32) -> Self {
+        let mut options = self.clone();
+        options.inner_mut().set_num_threads(num_threads);
+        options
+    }
+
+    #[inline]
+    pub fn minimizer_type(&self) -> MinimizerType {
+        self.inner().minimizer_type()
+    }
+
+    #[inline]
+    pub fn line_search_direction_type(&self) -> LineSearchDirectionType {
+        self.inner().line_search_direction_type()
+    }
+
+    #[inline]
+    pub fn line_search_type(&self) -> LineSearchType {
+        self.inner().line_search_type()
+    }
+
+    #[inline]
+    pub fn nonlinear_conjugate_gradient_type(&self) -> NonlinearConjugateGradientType {
+        self.inner().nonlinear_conjugate_gradient_type()
+    }
+
+    #[inline]
+    pub fn max_lbfgs_rank(&self) -> i32 {
+        self.inner().max_lbfgs_rank()
+    }
+
+    #[inline]
+    pub fn use_approximate_eigenvalue_bfgs_scaling(&self) -> bool {
+        self.inner().use_approximate_eigenvalue_bfgs_scaling()
+    }
+
+    #[inline]
+    pub fn line_search_interpolation_type(&self) -> LineSearchInterpolationType {
+        self.inner().line_search_interpolation_type()
+    }
+
+    #[inline]
+    pub fn min_line_search_step_size(&self) -> f64 {
+        self.inner().min_line_search_step_size()
+    }
+
+    #[inline]
+    pub fn line_search_sufficient_function_decrease(&self) -> f64 {
+        self.inner().line_search_sufficient_function_decrease()
+    }
+
+    #[inline]
+    pub fn max_line_search_step_contraction(&self) -> f64 {
+        self.inner().max_line_search_step_contraction()
+    }
+
+    #[inline]
+    pub fn min_line_search_step_contraction(&self) -> f64 {
+        self.inner().min_line_search_step_contraction()
+    }
+
+    #[inline]
+    pub fn max_num_line_search_direction_restarts(&self) -> i32 {
+        self.inner().max_num_line_search_direction_restarts()
+    }
+
+    #[inline]
+    pub fn line_search_sufficient_curvature_decrease(&self) -> f64 {
+        self.inner().line_search_sufficient_curvature_decrease()
+    }
+
+    #[inline]
+    pub fn max_line_search_step_expansion(&self) -> f64 {
+        self.inner().max_line_search_step_expansion()
+    }
+
+    #[inline]
+    pub fn trust_region_strategy_type(&self) -> TrustRegionStrategyType {
+        self.inner().trust_region_strategy_type()
+    }
+
+    #[inline]
+    pub fn dogleg_type(&self) -> DoglegType {
+        self.inner().dogleg_type()
+    }
+
+    #[inline]
+    pub fn use_nonmonotonic_steps(&self) -> bool {
+        self.inner().use_nonmonotonic_steps()
+    }
+
+    #[inline]
+    pub fn max_consecutive_nonmonotonic_steps(&self) -> i32 {
+        self.inner().max_consecutive_nonmonotonic_steps()
+    }
+
+    #[inline]
+    pub fn max_num_iterations(&self) -> i32 {
+        self.inner().max_num_iterations()
+    }
+
+    #[inline]
+    pub fn max_solver_time_in_seconds(&self) -> f64 {
+        self.inner().max_solver_time_in_seconds()
+    }
+
+    #[inline]
+    pub fn num_threads(&self) -> i32 {
+        self.inner().num_threads()
+    }
+
+    #[inline]
+    pub fn initial_trust_region_radius(&self) -> f64 {
+        self.inner().initial_trust_region_radius()
+    }
+
+    #[inline]
+    pub fn max_trust_region_radius(&self) -> f64 {
+        self.inner().max_trust_region_radius()
+    }
+
+    #[inline]
+    pub fn min_trust_region_radius(&self) -> f64 {
+        self.inner().min_trust_region_radius()
+    }
+
+    #[inline]
+    pub fn min_relative_decrease(&self) -> f64 {
+        self.inner().min_relative_decrease()
+    }
+
+    #[inline]
+    pub fn min_lm_diagonal(&self) -> f64 {
+        self.inner().min_lm_diagonal()
+    }
+
+    #[inline]
+    pub fn max_lm_diagonal(&self) -> f64 {
+        self.inner().max_lm_diagonal()
+    }
+
+    #[inline]
+    pub fn max_num_consecutive_invalid_steps(&self) -> i32 {
+        self.inner().max_num_consecutive_invalid_steps()
+    }
+
+    #[inline]
+    pub fn function_tolerance(&self) -> f64 {
+        self.inner().function_tolerance()
+    }
+
+    #[inline]
+    pub fn gradient_tolerance(&self) -> f64 {
+        self.inner().gradient_tolerance()
+    }
+
+    #[inline]
+    pub fn parameter_tolerance(&self) -> f64 {
+        self.inner().parameter_tolerance()
+    }
+
+    #[inline]
+    pub fn linear_solver_type(&self) -> LinearSolverType {
+        self.inner().linear_solver_type()
+    }
+
+    #[inline]
+    pub fn preconditioner_type(&self) -> PreconditionerType {
+        self.inner().preconditioner_type()
+    }
+
+    #[inline]
+    pub fn visibility_clustering_type(&self) -> VisibilityClusteringType {
+        self.inner().visibility_clustering_type()
+    }
+
+    pub fn residual_blocks_for_subset_preconditioner(&self) -> Vec<ResidualBlockId> {
+        let inner = self.inner();
+        (0..inner.num_residual_blocks_for_subset_preconditioner())
+            .map(|index| inner.residual_block_for_subset_preconditioner(index))
+            .collect()
+    }
+
+    #[inline]
+    pub fn dense_linear_algebra_library_type(&self) -> DenseLinearAlgebraLibraryType {
+        self.inner().dense_linear_algebra_library_type()
+    }
+
+    #[inline]
+    pub fn sparse_linear_algebra_library_type(&self) -> SparseLinearAlgebraLibraryType {
+        self.inner().sparse_linear_algebra_library_type()
+    }
+
+    #[inline]
+    pub fn linear_solver_ordering_type(&self) -> LinearSolverOrderingType {
+        self.inner().linear_solver_ordering_type()
+    }
+
+    #[inline]
+    pub fn use_mixed_precision_solves(&self) -> bool {
+        self.inner().use_mixed_precision_solves()
+    }
+
+    #[inline]
+    pub fn max_num_refinement_iterations(&self) -> i32 {
+        self.inner().max_num_refinement_iterations()
+    }
+
+    #[inline]
+    pub fn min_linear_solver_iterations(&self) -> i32 {
+        self.inner().min_linear_solver_iterations()
+    }
+
+    #[inline]
+    pub fn max_linear_solver_iterations(&self) -> i32 {
+        self.inner().max_linear_solver_iterations()
+    }
+
+    #[inline]
+    pub fn eta(&self) -> f64 {
+        self.inner().eta()
+    }
+
+    #[inline]
+    pub fn jacobi_scaling(&self) -> bool {
+        self.inner().jacobi_scaling()
+    }
+
+    #[inline]
+    pub fn logging_type(&self) -> LoggingType {
+        self.inner().logging_type()
+    }
+
+    #[inline]
+    pub fn minimizer_progress_to_stdout(&self) -> bool {
+        self.inner().minimizer_progress_to_stdout()
+    }
+
+    #[inline]
+    pub fn trust_region_minimizer_iterations_to_dump(&self) -> &[i32] {
+        self.inner()
+            .trust_region_minimizer_iterations_to_dump()
+            .as_slice()
+    }
+
+    /// Ceres stores the directory as a narrow string: its bytes are the path on Unix and WASI, and
+    /// UTF-8 elsewhere, see [SolverOptionsBuilder::trust_region_problem_dump_directory].
+    pub fn trust_region_problem_dump_directory(&self) -> PathBuf {
+        let cxx_string = self.inner().trust_region_problem_dump_directory();
+        let directory: PathBuf;
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            directory = OsStr::from_bytes(cxx_string.as_bytes()).into();
+        }
+        #[cfg(target_family = "wasm")]
+        {
+            use std::os::wasi::ffi::OsStrExt;
+            directory = OsStr::from_bytes(cxx_string.as_bytes()).into();
+        }
+        #[cfg(not(any(unix, target_family = "wasm")))]
+        {
+            directory = cxx_string.to_string_lossy().into_owned().into();
+        }
+        directory
+    }
+
+    #[inline]
+    pub fn trust_region_problem_dump_format_type(&self) -> DumpFormatType {
+        self.inner().trust_region_problem_dump_format_type()
+    }
+
+    #[inline]
+    pub fn check_gradients(&self) -> bool {
+        self.inner().check_gradients()
+    }
+
+    #[inline]
+    pub fn gradient_check_relative_precision(&self) -> f64 {
+        self.inner().gradient_check_relative_precision()
+    }
+
+    #[inline]
+    pub fn gradient_check_numeric_derivative_relative_step_size(&self) -> f64 {
+        self.inner()
+            .gradient_check_numeric_derivative_relative_step_size()
+    }
+
+    #[inline]
+    pub fn update_state_every_iteration(&self) -> bool {
+        self.inner().update_state_every_iteration()
+    }
+
+    /// Names and values of all the options, used by [Debug] and [Display] implementations.
+    fn fields(&self) -> [(&'static str, Box<dyn std::fmt::Debug + '_>); 53] {
+        [
+            ("minimizer_type", Box::new(self.minimizer_type())),
+            (
+                "line_search_direction_type",
+                Box::new(self.line_search_direction_type()),
+            ),
+            ("line_search_type", Box::new(self.line_search_type())),
+            (
+                "nonlinear_conjugate_gradient_type",
+                Box::new(self.nonlinear_conjugate_gradient_type()),
+            ),
+            ("max_lbfgs_rank", Box::new(self.max_lbfgs_rank())),
+            (
+                "use_approximate_eigenvalue_bfgs_scaling",
+                Box::new(self.use_approximate_eigenvalue_bfgs_scaling()),
+            ),
+            (
+                "line_search_interpolation_type",
+                Box::new(self.line_search_interpolation_type()),
+            ),
+            (
+                "min_line_search_step_size",
+                Box::new(self.min_line_search_step_size()),
+            ),
+            (
+                "line_search_sufficient_function_decrease",
+                Box::new(self.line_search_sufficient_function_decrease()),
+            ),
+            (
+                "max_line_search_step_contraction",
+                Box::new(self.max_line_search_step_contraction()),
+            ),
+            (
+                "min_line_search_step_contraction",
+                Box::new(self.min_line_search_step_contraction()),
+            ),
+            (
+                "max_num_line_search_direction_restarts",
+                Box::new(self.max_num_line_search_direction_restarts()),
+            ),
+            (
+                "line_search_sufficient_curvature_decrease",
+                Box::new(self.line_search_sufficient_curvature_decrease()),
+            ),
+            (
+                "max_line_search_step_expansion",
+                Box::new(self.max_line_search_step_expansion()),
+            ),
+            (
+                "trust_region_strategy_type",
+                Box::new(self.trust_region_strategy_type()),
+            ),
+            ("dogleg_type", Box::new(self.dogleg_type())),
+            (
+                "use_nonmonotonic_steps",
+                Box::new(self.use_nonmonotonic_steps()),
+            ),
+            (
+                "max_consecutive_nonmonotonic_steps",
+                Box::new(self.max_consecutive_nonmonotonic_steps()),
+            ),
+            ("max_num_iterations", Box::new(self.max_num_iterations())),
+            (
+                "max_solver_time_in_seconds",
+                Box::new(self.max_solver_time_in_seconds()),
+            ),
+            ("num_threads", Box::new(self.num_threads())),
+            (
+                "initial_trust_region_radius",
+                Box::new(self.initial_trust_region_radius()),
+            ),
+            (
+                "max_trust_region_radius",
+                Box::new(self.max_trust_region_radius()),
+            ),
+            (
+                "min_trust_region_radius",
+                Box::new(self.min_trust_region_radius()),
+            ),
+            (
+                "min_relative_decrease",
+                Box::new(self.min_relative_decrease()),
+            ),
+            ("min_lm_diagonal", Box::new(self.min_lm_diagonal())),
+            ("max_lm_diagonal", Box::new(self.max_lm_diagonal())),
+            (
+                "max_num_consecutive_invalid_steps",
+                Box::new(self.max_num_consecutive_invalid_steps()),
+            ),
+            ("function_tolerance", Box::new(self.function_tolerance())),
+            ("gradient_tolerance", Box::new(self.gradient_tolerance())),
+            ("parameter_tolerance", Box::new(self.parameter_tolerance())),
+            ("linear_solver_type", Box::new(self.linear_solver_type())),
+            ("preconditioner_type", Box::new(self.preconditioner_type())),
+            (
+                "visibility_clustering_type",
+                Box::new(self.visibility_clustering_type()),
+            ),
+            (
+                "num_residual_blocks_for_subset_preconditioner",
+                Box::new(self.residual_blocks_for_subset_preconditioner().len()),
+            ),
+            (
+                "dense_linear_algebra_library_type",
+                Box::new(self.dense_linear_algebra_library_type()),
+            ),
+            (
+                "sparse_linear_algebra_library_type",
+                Box::new(self.sparse_linear_algebra_library_type()),
+            ),
+            (
+                "linear_solver_ordering_type",
+                Box::new(self.linear_solver_ordering_type()),
+            ),
+            (
+                "use_mixed_precision_solves",
+                Box::new(self.use_mixed_precision_solves()),
+            ),
+            (
+                "max_num_refinement_iterations",
+                Box::new(self.max_num_refinement_iterations()),
+            ),
+            (
+                "min_linear_solver_iterations",
+                Box::new(self.min_linear_solver_iterations()),
+            ),
+            (
+                "max_linear_solver_iterations",
+                Box::new(self.max_linear_solver_iterations()),
+            ),
+            ("eta", Box::new(self.eta())),
+            ("jacobi_scaling", Box::new(self.jacobi_scaling())),
+            ("logging_type", Box::new(self.logging_type())),
+            (
+                "minimizer_progress_to_stdout",
+                Box::new(self.minimizer_progress_to_stdout()),
+            ),
+            (
+                "trust_region_minimizer_iterations_to_dump",
+                Box::new(self.trust_region_minimizer_iterations_to_dump()),
+            ),
+            (
+                "trust_region_problem_dump_directory",
+                Box::new(self.trust_region_problem_dump_directory()),
+            ),
+            (
+                "trust_region_problem_dump_format_type",
+                Box::new(self.trust_region_problem_dump_format_type()),
+            ),
+            ("check_gradients", Box::new(self.check_gradients())),
+            (
+                "gradient_check_relative_precision",
+                Box::new(self.gradient_check_relative_precision()),
+            ),
+            (
+                "gradient_check_numeric_derivative_relative_step_size",
+                Box::new(self.gradient_check_numeric_derivative_relative_step_size()),
+            ),
+            (
+                "update_state_every_iteration",
+                Box::new(self.update_state_every_iteration()),
+            ),
+        ]
+    }
+}
+
+impl std::fmt::Debug for SolverOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut debug_struct = f.debug_struct("SolverOptions");
+        for (name, value) in self.fields() {
+            debug_struct.field(name, &value);
+        }
+        debug_struct.finish()
+    }
+}
+
+/// Prints every option on its own line as `name: value`.
+impl std::fmt::Display for SolverOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, (name, value)) in self.fields().iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{name}: {value:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Copies the underlying `ceres::Solver::Options`, iteration callbacks are shared between the
+/// copies.
+///
+/// A shared callback is the same closure with the same captured state: a stateful callback, a
+/// [SolverOptionsBuilder::cancellation_token] or a progress bar keeps its state across the solves
+/// done with any of the copies. Build new options instead of cloning to get independent callbacks.
+impl Clone for SolverOptions {
+    fn clone(&self) -> Self {
+        Self(ffi::clone_solver_options(self.inner()))
+    }
 }
 
 impl Default for SolverOptions {
@@ -32,6 +563,135 @@ impl Default for SolverOptions {
 
 pub struct SolverOptionsBuilder(pub(crate) UniquePtr<ffi::SolverOptions>);
 
+/// Rust-side validation of the options, see [SolverOptionsBuilder::validate].
+fn check_options(options: &ffi::SolverOptions) -> Result<(), SolverOptionsBuildingError> {
+    let non_negative = [
+        ("max_num_iterations", options.max_num_iterations().into()),
+        (
+            "max_solver_time_in_seconds",
+            options.max_solver_time_in_seconds(),
+        ),
+        ("function_tolerance", options.function_tolerance()),
+        ("gradient_tolerance", options.gradient_tolerance()),
+        ("parameter_tolerance", options.parameter_tolerance()),
+    ];
+    for (option, value) in non_negative {
+        if value < 0.0 {
+            return Err(SolverOptionsBuildingError::Negative { option, value });
+        }
+    }
+    if options.num_threads() <= 0 {
+        return Err(SolverOptionsBuildingError::NonPositive {
+            option: "num_threads",
+            value: options.num_threads().into(),
+        });
+    }
+
+    let defaults = ffi::new_solver_options();
+    let minimizer_type = options.minimizer_type();
+    let unused = if minimizer_type == MinimizerType::TRUST_REGION {
+        [
+            (
+                "line_search_direction_type",
+                options.line_search_direction_type() != defaults.line_search_direction_type(),
+            ),
+            (
+                "max_lbfgs_rank",
+                options.max_lbfgs_rank() != defaults.max_lbfgs_rank(),
+            ),
+            (
+                "use_approximate_eigenvalue_bfgs_scaling",
+                options.use_approximate_eigenvalue_bfgs_scaling()
+                    != defaults.use_approximate_eigenvalue_bfgs_scaling(),
+            ),
+        ]
+        .into_iter()
+        .find_map(|(option, is_set)| is_set.then_some(option))
+    } else {
+        [
+            (
+                "trust_region_strategy_type",
+                options.trust_region_strategy_type() != defaults.trust_region_strategy_type(),
+            ),
+            (
+                "dogleg_type",
+                options.dogleg_type() != defaults.dogleg_type(),
+            ),
+        ]
+        .into_iter()
+        .find_map(|(option, is_set)| is_set.then_some(option))
+    };
+    if let Some(option) = unused {
+        return Err(SolverOptionsBuildingError::UnusedByMinimizer {
+            option,
+            minimizer_type,
+        });
+    }
+
+    if minimizer_type == MinimizerType::TRUST_REGION {
+        let positive = [
+            (
+                "initial_trust_region_radius",
+                options.initial_trust_region_radius(),
+            ),
+            ("min_trust_region_radius", options.min_trust_region_radius()),
+            ("eta", options.eta()),
+        ];
+        for (option, value) in positive {
+            if value <= 0.0 {
+                return Err(SolverOptionsBuildingError::NonPositive { option, value });
+            }
+        }
+        let ordered = [
+            (
+                ("min_trust_region_radius", options.min_trust_region_radius()),
+                (
+                    "initial_trust_region_radius",
+                    options.initial_trust_region_radius(),
+                ),
+            ),
+            (
+                (
+                    "initial_trust_region_radius",
+                    options.initial_trust_region_radius(),
+                ),
+                ("max_trust_region_radius", options.max_trust_region_radius()),
+            ),
+            (
+                ("min_lm_diagonal", options.min_lm_diagonal()),
+                ("max_lm_diagonal", options.max_lm_diagonal()),
+            ),
+            (
+                (
+                    "min_linear_solver_iterations",
+                    options.min_linear_solver_iterations().into(),
+                ),
+                (
+                    "max_linear_solver_iterations",
+                    options.max_linear_solver_iterations().into(),
+                ),
+            ),
+        ];
+        for ((lower, lower_value), (upper, upper_value)) in ordered {
+            if lower_value > upper_value {
+                return Err(SolverOptionsBuildingError::WrongOrder {
+                    lower,
+                    lower_value,
+                    upper,
+                    upper_value,
+                });
+            }
+        }
+    } else if options.max_lbfgs_rank() <= 0 {
+        return Err(SolverOptionsBuildingError::NonPositive {
+            option: "max_lbfgs_rank",
+            value: options.max_lbfgs_rank().into(),
+        });
+    }
+
+    Ok(())
+}
+
 impl SolverOptionsBuilder {
     pub fn new() -> Self {
         let slf = Self(ffi::new_solver_options());
@@ -44,7 +704,10 @@ impl SolverOptionsBuilder {
         Ok(SolverOptions(self.0))
     }
 
+    /// Check the options, the obviously inconsistent ones are reported with a dedicated
+    /// [SolverOptionsBuildingError] variant, Ceres' `Solver::Options::IsValid()` checks the rest.
     pub fn validate(&self) -> Result<(), SolverOptionsBuildingError> {
+        check_options(self.inner())?;
         let_cxx_string!(msg = "");
         if self.0.is_valid(msg.as_mut()) {
             Ok(())
@@ -59,6 +722,12 @@ impl SolverOptionsBuilder {
         self.validate().is_ok()
     }
 
+    fn inner(&self) -> &ffi::SolverOptions {
+        self.0
+            .as_ref()
+            .expect("Underlying C++ unique_ptr<SolverOptions> must not hold nullptr")
+    }
+
     fn inner_mut(&mut self) -> Pin<&mut ffi::SolverOptions> {
         self.0
             .as_mut()
@@ -344,6 +1013,57 @@ impl SolverOptionsBuilder {
         self
     }
 
+    #[inline]
+    pub fn linear_solver_ordering_type(
+        mut self,
+        linear_solver_ordering_type: LinearSolverOrderingType,
+    ) -> Self {
+        self.inner_mut()
+            .set_linear_solver_ordering_type(linear_solver_ordering_type);
+        self
+    }
+
+    #[inline]
+    pub fn use_mixed_precision_solves(mut self, yes: bool) -> Self {
+        self.inner_mut().set_use_mixed_precision_solves(yes);
+        self
+    }
+
+    #[inline]
+    pub fn max_num_refinement_iterations(mut self, max_num_refinement_iterations: i32) -> Self {
+        self.inner_mut()
+            .set_max_num_refinement_iterations(max_num_refinement_iterations);
+        self
+    }
+
+    #[inline]
+    pub fn min_linear_solver_iterations(mut self, min_linear_solver_iterations: i32) -> Self {
+        self.inner_mut()
+            .set_min_linear_solver_iterations(min_linear_solver_iterations);
+        self
+    }
+
+    #[inline]
+    pub fn max_linear_solver_iterations(mut self, max_linear_solver_iterations: i32) -> Self {
+        self.inner_mut()
+            .set_max_linear_solver_iterations(max_linear_solver_iterations);
+        self
+    }
+
+    #[inline]
+    pub fn eta(mut self, eta: f64) -> Self {
+        self.inner_mut().set_eta(eta);
+        self
+    }
+
+    #[inline]
+    pub fn jacobi_scaling(mut self, yes: bool) -> Self {
+        self.inner_mut().set_jacobi_scaling(yes);
+        self
+    }
+
+    /// Ceres writes its output to stderr, enable `log` Cargo feature and call
+    /// `logging::redirect_to_log()` to route it into the `log` crate.
     #[inline]
     pub fn logging_type(mut self, logging_type: LoggingType) -> Self {
         self.inner_mut().set_logging_type(logging_type);
@@ -363,6 +1083,11 @@ impl SolverOptionsBuilder {
         self
     }
 
+    /// Directory to dump the problems to.
+    ///
+    /// Ceres keeps the path as a narrow string, so on Unix and WASI the raw bytes of the path are
+    /// passed, while on the other platforms the path is converted to UTF-8, replacing invalid
+    /// characters.
     #[inline]
     pub fn trust_region_problem_dump_directory(mut self, directory: impl AsRef<Path>) -> Self {
         let os_string: &OsStr = directory.as_ref().as_ref();
@@ -377,14 +1102,12 @@ impl SolverOptionsBuilder {
             use std::os::wasi::ffi::OsStrExt;
             bytes = os_string.as_bytes().into();
         }
-        #[cfg(windows)]
+        #[cfg(not(any(unix, target_family = "wasm")))]
         {
-            use std::os::windows::ffi::OsStrExt;
-            bytes = os_string
-                .encode_wide()
-                .flat_map(|c| c.to_le_bytes().into_iter())
-                .collect::<Vec<_>>()
-                .into();
+            bytes = match os_string.to_string_lossy() {
+                Cow::Borrowed(s) => s.as_bytes().into(),
+                Cow::Owned(s) => s.into_bytes().into(),
+            };
         }
         let_cxx_string!(cxx_string = bytes);
         self.inner_mut()
@@ -435,6 +1158,46 @@ impl SolverOptionsBuilder {
         self.inner_mut().set_update_state_every_iteration(yes);
         self
     }
+
+    /// Add a callback to be called after every minimizer iteration, callbacks are called in the
+    /// order they were added.
+    ///
+    /// The callback is shared by all the clones of the options, so its captured state is carried
+    /// over between the solves.
+    pub fn iteration_callback(mut self, callback: impl Into<IterationCallbackType>) -> Self {
+        self.inner_mut()
+            .add_iteration_callback(Box::new(callback.into().into()));
+        self
+    }
+
+    /// Add a cancellation token: once it is set to [true] the solver aborts after the current
+    /// iteration.
+    ///
+    /// It also turns on [SolverOptionsBuilder::update_state_every_iteration], so the parameters
+    /// of the aborted problem hold the values of the last iteration, rather than the initial ones.
+    /// The summary of the aborted solver run reports unusable solution, see
+    /// [SolverSummary::is_solution_usable].
+    ///
+    /// The token stays registered in all the clones of the options, setting it aborts any of them.
+    pub fn cancellation_token(self, token: Arc<AtomicBool>) -> Self {
+        let callback: IterationCallbackType = Box::new(move |_| {
+            if token.load(Ordering::Relaxed) {
+                CallbackReturnType::SOLVER_ABORT
+            } else {
+                CallbackReturnType::SOLVER_CONTINUE
+            }
+        });
+        self.update_state_every_iteration(true)
+            .iteration_callback(callback)
+    }
+}
+
+/// Copies the underlying `ceres::Solver::Options`, iteration callbacks are shared between the
+/// copies, see [SolverOptions] [Clone] implementation.
+impl Clone for SolverOptionsBuilder {
+    fn clone(&self) -> Self {
+        Self(ffi::clone_solver_options(self.inner()))
+    }
 }
 
 impl Default for SolverOptionsBuilder {
@@ -445,6 +1208,10 @@ impl Default for SolverOptionsBuilder {
 
 pub struct SolverSummary(pub(crate) UniquePtr<ffi::SolverSummary>);
 
+// SAFETY: `ceres::Solver::Summary` is a plain value type owning all its data, so it can be moved
+// to another thread.
+unsafe impl Send for SolverSummary {}
+
 impl SolverSummary {
     pub fn new() -> Self {
         Self(ffi::new_solver_summary())
@@ -469,6 +1236,17 @@ impl SolverSummary {
         self.inner().is_solution_usable()
     }
 
+    /// The reason the solver terminated.
+    #[inline]
+    pub fn termination_type(&self) -> TerminationType {
+        self.inner().termination_type()
+    }
+
+    /// Human-readable reason of the solver termination.
+    pub fn message(&self) -> String {
+        self.inner().message().to_string_lossy().into()
+    }
+
     #[inline]
     pub fn initial_cost(&self) -> f64 {
         self.inner().initial_cost()
@@ -520,3 +1298,128 @@ impl Default for SolverSummary {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn getters_return_set_values() {
+        let options = SolverOptions::builder()
+            .max_num_iterations(42)
+            .linear_solver_type(LinearSolverType::DENSE_QR)
+            .function_tolerance(1e-12)
+            .jacobi_scaling(false)
+            .trust_region_minimizer_iterations_to_dump(&[1, 3])
+            .trust_region_problem_dump_directory("dump")
+            .build()
+            .unwrap();
+        assert_eq!(options.max_num_iterations(), 42);
+        assert!(options.linear_solver_type() == LinearSolverType::DENSE_QR);
+        assert_eq!(options.function_tolerance(), 1e-12);
+        assert!(!options.jacobi_scaling());
+        assert!(options.logging_type() == LoggingType::SILENT);
+        assert_eq!(options.trust_region_minimizer_iterations_to_dump(), &[1, 3]);
+        assert_eq!(
+            options.trust_region_problem_dump_directory(),
+            PathBuf::from("dump")
+        );
+        assert!(options
+            .residual_blocks_for_subset_preconditioner()
+            .is_empty());
+    }
+
+    #[test]
+    fn into_builder_keeps_values() {
+        let options = SolverOptions::builder()
+            .max_num_iterations(42)
+            .build()
+            .unwrap()
+            .into_builder()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        assert_eq!(options.max_num_iterations(), 42);
+        assert_eq!(options.num_threads(), 2);
+    }
+
+    #[test]
+    fn clone_is_independent() {
+        let base = SolverOptions::builder().max_num_iterations(42);
+        let tweaked = base.clone().max_num_iterations(7).build().unwrap();
+        let base = base.build().unwrap();
+        let base_copy = base.clone();
+        assert_eq!(base.max_num_iterations(), 42);
+        assert_eq!(base_copy.max_num_iterations(), 42);
+        assert_eq!(tweaked.max_num_iterations(), 7);
+    }
+
+    #[test]
+    fn negative_tolerance_is_reported() {
+        let error = SolverOptions::builder()
+            .function_tolerance(-1.0)
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            SolverOptionsBuildingError::Negative {
+                option: "function_tolerance",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn lbfgs_rank_with_trust_region_is_reported() {
+        let error = SolverOptions::builder()
+            .minimizer_type(MinimizerType::TRUST_REGION)
+            .max_lbfgs_rank(5)
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            SolverOptionsBuildingError::UnusedByMinimizer {
+                option: "max_lbfgs_rank",
+                minimizer_type: MinimizerType::TRUST_REGION,
+            }
+        ));
+        assert!(SolverOptions::builder()
+            .minimizer_type(MinimizerType::LINE_SEARCH)
+            .max_lbfgs_rank(5)
+            .is_valid());
+    }
+
+    #[test]
+    fn inconsistent_trust_region_radii_are_reported() {
+        let error = SolverOptions::builder()
+            .initial_trust_region_radius(10.0)
+            .max_trust_region_radius(1.0)
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            SolverOptionsBuildingError::WrongOrder {
+                lower: "initial_trust_region_radius",
+                upper: "max_trust_region_radius",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn debug_and_display_contain_all_fields() {
+        let options = SolverOptions::builder()
+            .max_num_iterations(42)
+            .linear_solver_type(LinearSolverType::DENSE_QR)
+            .build()
+            .unwrap();
+        let debug = format!("{options:?}");
+        assert!(debug.starts_with("SolverOptions {"));
+        assert!(debug.contains("max_num_iterations: 42"));
+        assert!(debug.contains("linear_solver_type: DENSE_QR"));
+        let display = options.to_string();
+        assert_eq!(display.lines().count(), options.fields().len());
+        assert!(display.contains("max_num_iterations: 42"));
+        assert!(display.contains("logging_type: SILENT"));
+    }
+}
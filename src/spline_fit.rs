@@ -0,0 +1,760 @@
+//! Wrapping [NllsProblem] for non-parametric curve fitting with B-splines.
+//!
+//! [SplineFitProblem] fits a clamped [BSpline] `y = sum_j c_j B_j(x)` to 1-D data when there is
+//! no model for the curve. The spline is defined on the data range `[min(x), max(x)]` by its
+//! degree and interior knots. The coefficients `c_j` are always fitted, and the interior knots are
+//! either fixed or fitted too, see [SplineFitProblemBuilder::free_knots].
+//!
+//! Optional smoothness penalty makes it a P-spline: an extra residual block with
+//! `sqrt(smoothing) * D c` residuals is added to the problem, where `D` is the finite difference
+//! operator of the given order. The differences are taken over the Greville abscissae of the
+//! coefficients, so they are the plain differences for uniform knots far from the boundaries, and
+//! large smoothing pulls the spline to a constant for the first order and to a straight line for
+//! the second order. This way many knots can be used without overfitting.
+//!
+//! Let's smooth a noisy sine:
+//!
+//! ```rust
+//! use ceres_solver::spline_fit::SplineFitProblem;
+//! use ceres_solver::SolverOptions;
+//!
+//! let x: Vec<_> = (0..200).map(|i| 0.05 * i as f64).collect();
+//! // Deterministic "noise"
+//! let y: Vec<_> = x
+//!     .iter()
+//!     .enumerate()
+//!     .map(|(i, &x)| f64::sin(x) + 0.05 * f64::sin(1e3 * i as f64))
+//!     .collect();
+//!
+//! let solution = SplineFitProblem::builder()
+//!     .x(&x)
+//!     .y(&y)
+//!     .uniform_knots(30)
+//!     .smoothing(1.0)
+//!     .build()
+//!     .unwrap()
+//!     .solve(&SolverOptions::default());
+//!
+//! for &x in x.iter() {
+//!     assert!(f64::abs(solution.spline.value(x) - f64::sin(x)) < 0.05);
+//! }
+//! ```
+
+use crate::cost::CostFunctionType;
+use crate::curve_fit::DEFAULT_RELATIVE_STEP_SIZE;
+use crate::error::CurveFitProblemBuildError;
+use crate::loss::LossFunction;
+use crate::nlls_problem::NllsProblem;
+use crate::parameter_block::ParameterBlock;
+use crate::solver::{SolverOptions, SolverSummary};
+
+use std::iter;
+
+/// B-spline curve `y = sum_j c_j B_j(x)` given by its degree, non-decreasing knots `t` and
+/// coefficients `c`. The spline is defined on `[t[degree], t[n]]`, where `n` is the number of
+/// coefficients, outside of it the boundary polynomial pieces are extrapolated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BSpline {
+    degree: usize,
+    knots: Vec<f64>,
+    coefficients: Vec<f64>,
+}
+
+impl BSpline {
+    /// Creates a new B-spline from the full knot vector and the coefficients.
+    ///
+    /// # Panics
+    /// Panics if the number of knots is not `coefficients.len() + degree + 1`, the knots are not
+    /// sorted, or the domain `[knots[degree], knots[coefficients.len()]]` is empty.
+    pub fn new(degree: usize, knots: Vec<f64>, coefficients: Vec<f64>) -> Self {
+        assert_eq!(knots.len(), coefficients.len() + degree + 1);
+        assert!(knots.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(knots[degree] < knots[coefficients.len()]);
+        Self {
+            degree,
+            knots,
+            coefficients,
+        }
+    }
+
+    /// Creates a new clamped B-spline on `[lower, upper]`, which boundary knots are repeated
+    /// `degree + 1` times, so the spline starts at the first coefficient and ends at the last one.
+    ///
+    /// # Panics
+    /// Panics if the number of coefficients is not `interior_knots.len() + degree + 1`, or if the
+    /// knots are not sorted, see [BSpline::new].
+    pub fn clamped(
+        degree: usize,
+        lower: f64,
+        upper: f64,
+        interior_knots: &[f64],
+        coefficients: Vec<f64>,
+    ) -> Self {
+        Self::new(
+            degree,
+            clamped_knots(degree, lower, upper, interior_knots),
+            coefficients,
+        )
+    }
+
+    /// Degree of the spline polynomial pieces.
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    /// Full knot vector.
+    pub fn knots(&self) -> &[f64] {
+        &self.knots
+    }
+
+    /// Coefficients of the basis functions.
+    pub fn coefficients(&self) -> &[f64] {
+        &self.coefficients
+    }
+
+    /// Value of the spline at `x`.
+    pub fn value(&self, x: f64) -> f64 {
+        spline_value(self.degree, &self.knots, &self.coefficients, x)
+    }
+}
+
+fn clamped_knots(degree: usize, lower: f64, upper: f64, interior_knots: &[f64]) -> Vec<f64> {
+    iter::repeat(lower)
+        .take(degree + 1)
+        .chain(interior_knots.iter().copied())
+        .chain(iter::repeat(upper).take(degree + 1))
+        .collect()
+}
+
+fn spline_value(degree: usize, knots: &[f64], coefficients: &[f64], x: f64) -> f64 {
+    let (first, basis) = basis_functions(degree, knots, x);
+    coefficients[first..]
+        .iter()
+        .zip(basis)
+        .map(|(c, b)| c * b)
+        .sum()
+}
+
+/// Non-zero basis functions at `x`, returns the index of the first one and the values of all
+/// `degree + 1` of them. See algorithms A2.1 and A2.2 of "The NURBS Book" by Piegl and Tiller.
+fn basis_functions(degree: usize, knots: &[f64], x: f64) -> (usize, Vec<f64>) {
+    let num_coefficients = knots.len() - degree - 1;
+    let inner = &knots[..num_coefficients];
+    let upper = knots[num_coefficients];
+    // Knot span knots[span] <= x < knots[span + 1] of non-zero length, the boundary spans are
+    // used for the upper boundary point and for extrapolation
+    let span = if x < upper {
+        inner.partition_point(|&t| t <= x)
+    } else {
+        inner.partition_point(|&t| t < upper)
+    }
+    .saturating_sub(1)
+    .max(degree);
+    let mut values = vec![0.0; degree + 1];
+    let mut left = vec![0.0; degree + 1];
+    let mut right = vec![0.0; degree + 1];
+    values[0] = 1.0;
+    for j in 1..=degree {
+        left[j] = x - knots[span + 1 - j];
+        right[j] = knots[span + j] - x;
+        let mut saved = 0.0;
+        for r in 0..j {
+            let temp = values[r] / (right[r + 1] + left[j - r]);
+            values[r] = saved + right[r + 1] * temp;
+            saved = left[j - r] * temp;
+        }
+        values[j] = saved;
+    }
+    (span - degree, values)
+}
+
+/// Interior knots for free-knot fitting. The knots split `[lower, upper]` into intervals
+/// proportional to the parameters, with unity for the last interval, so any non-negative
+/// parameters give sorted knots.
+fn knots_from_gaps(lower: f64, upper: f64, gaps: &[f64]) -> Vec<f64> {
+    let total = gaps.iter().sum::<f64>() + 1.0;
+    gaps.iter()
+        .scan(0.0, |sum, &gap| {
+            *sum += gap;
+            Some(lower + (upper - lower) * *sum / total)
+        })
+        .collect()
+}
+
+/// Inverse of [knots_from_gaps].
+fn gaps_from_knots(lower: f64, upper: f64, interior_knots: &[f64]) -> Vec<f64> {
+    let last = upper - interior_knots.last().copied().unwrap_or(lower);
+    iter::once(lower)
+        .chain(interior_knots.iter().copied())
+        .zip(interior_knots)
+        .map(|(previous, &knot)| (knot - previous) / last)
+        .collect()
+}
+
+/// Greville abscissae, the averages of `degree` successive knots, a spline with coefficients
+/// equal to them is `y = x`.
+fn greville_abscissae(degree: usize, knots: &[f64]) -> Vec<f64> {
+    let num_coefficients = knots.len() - degree - 1;
+    (0..num_coefficients)
+        .map(|j| {
+            if degree == 0 {
+                0.5 * (knots[j] + knots[j + 1])
+            } else {
+                knots[j + 1..=j + degree].iter().sum::<f64>() / degree as f64
+            }
+        })
+        .collect()
+}
+
+/// Divided differences of the coefficients over the Greville abscissae, multiplied by the mean
+/// abscissae spacing to the power of the order, so they are the plain finite differences, e.g.
+/// `[1, -2, 1]` for the second order, if the abscissae are uniform. Row `k` is a stencil for the
+/// coefficients `k..=k + order`.
+fn penalty_stencils(degree: usize, knots: &[f64], order: usize) -> Vec<Vec<f64>> {
+    let abscissae = greville_abscissae(degree, knots);
+    let num_coefficients = abscissae.len();
+    let spacing = (abscissae[num_coefficients - 1] - abscissae[0]) / (num_coefficients - 1) as f64;
+    let mut stencils = vec![vec![1.0]; num_coefficients];
+    for m in 1..=order {
+        stencils = (0..num_coefficients - m)
+            .map(|j| {
+                let scale = m as f64 * spacing / (abscissae[j + m] - abscissae[j]);
+                let mut stencil = vec![0.0; m + 1];
+                for (i, &s) in stencils[j].iter().enumerate() {
+                    stencil[i] -= scale * s;
+                }
+                for (i, &s) in stencils[j + 1].iter().enumerate() {
+                    stencil[i + 1] += scale * s;
+                }
+                stencil
+            })
+            .collect();
+    }
+    stencils
+}
+
+#[derive(Clone, Copy)]
+struct SplineData<'cost> {
+    x: &'cost [f64],
+    y: &'cost [f64],
+    inverse_error: Option<&'cost [f64]>,
+}
+
+impl SplineData<'_> {
+    fn weight(&self, i: usize) -> f64 {
+        self.inverse_error
+            .map_or(1.0, |inverse_error| inverse_error[i])
+    }
+
+    /// Residuals `(y - spline(x)) * inverse_error`.
+    fn residuals(&self, degree: usize, knots: &[f64], coefficients: &[f64], residuals: &mut [f64]) {
+        for (i, (&x, &y)) in self.x.iter().zip(self.y).enumerate() {
+            residuals[i] = self.weight(i) * (y - spline_value(degree, knots, coefficients, x));
+        }
+    }
+}
+
+/// A wrapper for [NllsProblem] to fit a [BSpline] to the data. Create it with
+/// [SplineFitProblem::builder] and then call a destructive method [SplineFitProblem::solve].
+pub struct SplineFitProblem<'cost> {
+    problem: NllsProblem<'cost>,
+    data: SplineData<'cost>,
+    degree: usize,
+    lower: f64,
+    upper: f64,
+    interior_knots: Vec<f64>,
+}
+
+impl<'cost> SplineFitProblem<'cost> {
+    /// Create a [SplineFitProblemBuilder] instance, see its docs for the details.
+    pub fn builder<'param>() -> SplineFitProblemBuilder<'cost, 'param> {
+        SplineFitProblemBuilder::new()
+    }
+
+    /// Cost function of the data, the parameters are the coefficients and optionally the knot
+    /// gaps, see [knots_from_gaps]. The derivatives over the gaps are found with central finite
+    /// differences.
+    fn data_cost_function(
+        data: SplineData<'cost>,
+        degree: usize,
+        (lower, upper): (f64, f64),
+        fixed_knots: Vec<f64>,
+    ) -> CostFunctionType<'cost> {
+        let knots_for = move |parameters: &[&[f64]]| match parameters.get(1) {
+            Some(gaps) => clamped_knots(degree, lower, upper, &knots_from_gaps(lower, upper, gaps)),
+            None => fixed_knots.clone(),
+        };
+        Box::new(move |parameters, residuals, jacobians| {
+            let coefficients = parameters[0];
+            let knots = knots_for(parameters);
+            data.residuals(degree, &knots, coefficients, residuals);
+            let Some(jacobians) = jacobians else {
+                return true;
+            };
+            let (coefficient_jacobian, gap_jacobian) = jacobians.split_at_mut(1);
+            if let Some(d_out) = &mut coefficient_jacobian[0] {
+                for (i, &x) in data.x.iter().enumerate() {
+                    let w = data.weight(i);
+                    let (first, basis) = basis_functions(degree, &knots, x);
+                    d_out[i].fill(0.0);
+                    for (d, b) in d_out[i][first..].iter_mut().zip(basis) {
+                        *d = -w * b;
+                    }
+                }
+            }
+            if let Some(Some(d_out)) = gap_jacobian.first_mut() {
+                let gaps = parameters[1];
+                let mut shifted = gaps.to_vec();
+                let mut plus = vec![0.0; data.x.len()];
+                let mut minus = vec![0.0; data.x.len()];
+                for (l, &gap) in gaps.iter().enumerate() {
+                    let step = if gap == 0.0 {
+                        DEFAULT_RELATIVE_STEP_SIZE
+                    } else {
+                        gap.abs() * DEFAULT_RELATIVE_STEP_SIZE
+                    };
+                    // Forward difference at zero gap to keep the knots sorted
+                    let backward_step = if gap > 0.0 { step } else { 0.0 };
+                    shifted[l] = gap + step;
+                    let knots = knots_for(&[coefficients, &shifted]);
+                    data.residuals(degree, &knots, coefficients, &mut plus);
+                    shifted[l] = gap - backward_step;
+                    let knots = knots_for(&[coefficients, &shifted]);
+                    data.residuals(degree, &knots, coefficients, &mut minus);
+                    shifted[l] = gap;
+                    for (row, (p, m)) in d_out.iter_mut().zip(plus.iter().zip(&minus)) {
+                        row[l] = (p - m) / (step + backward_step);
+                    }
+                }
+            }
+            true
+        })
+    }
+
+    /// Cost function of the smoothness penalty `sqrt(smoothing) * D c`, see [penalty_stencils]
+    /// for `D`.
+    fn penalty_cost_function(stencils: Vec<Vec<f64>>, smoothing: f64) -> CostFunctionType<'cost> {
+        let stencils: Vec<Vec<_>> = stencils
+            .into_iter()
+            .map(|stencil| stencil.into_iter().map(|s| smoothing.sqrt() * s).collect())
+            .collect();
+        Box::new(move |parameters, residuals, jacobians| {
+            let coefficients = parameters[0];
+            for (k, (residual, stencil)) in residuals.iter_mut().zip(&stencils).enumerate() {
+                *residual = stencil
+                    .iter()
+                    .zip(&coefficients[k..])
+                    .map(|(s, c)| s * c)
+                    .sum();
+            }
+            if let Some(jacobians) = jacobians {
+                if let Some(d_out) = &mut jacobians[0] {
+                    for (k, (row, stencil)) in d_out.iter_mut().zip(&stencils).enumerate() {
+                        row.fill(0.0);
+                        row[k..k + stencil.len()].copy_from_slice(stencil);
+                    }
+                }
+            }
+            true
+        })
+    }
+
+    /// Solves the problem and returns the fitted spline.
+    pub fn solve(self, options: &SolverOptions) -> SplineFitProblemSolution {
+        let mut problem = self.problem;
+        let num_data = self.data.x.len();
+        let num_varying = problem.num_varying_parameters();
+        // We know that we have well-defined problem, so we can unwrap
+        let summary = problem.solve_mut(options).unwrap();
+        let mut parameters = problem.into_parameters().into_iter();
+        let coefficients = parameters.next().unwrap();
+        let interior_knots = match parameters.next() {
+            Some(gaps) => knots_from_gaps(self.lower, self.upper, &gaps),
+            None => self.interior_knots,
+        };
+        let spline = BSpline::clamped(
+            self.degree,
+            self.lower,
+            self.upper,
+            &interior_knots,
+            coefficients,
+        );
+        let mut residuals = vec![0.0; num_data];
+        self.data.residuals(
+            spline.degree(),
+            spline.knots(),
+            spline.coefficients(),
+            &mut residuals,
+        );
+        let chi2 = residuals.iter().map(|r| r.powi(2)).sum();
+        let degrees_of_freedom = num_data as i64 - num_varying as i64;
+        let reduced_chi2 = if degrees_of_freedom > 0 {
+            chi2 / degrees_of_freedom as f64
+        } else {
+            f64::INFINITY
+        };
+        SplineFitProblemSolution {
+            spline,
+            summary,
+            chi2,
+            reduced_chi2,
+            degrees_of_freedom,
+        }
+    }
+}
+
+/// A solution for [SplineFitProblem].
+pub struct SplineFitProblemSolution {
+    /// Fitted spline.
+    pub spline: BSpline,
+    /// Solver summary.
+    pub summary: SolverSummary,
+    /// Chi-square, the sum of the squared residuals weighted with the inverse errors. Unlike the
+    /// final cost, it doesn't include the smoothness penalty and the loss function.
+    pub chi2: f64,
+    /// Chi-square divided by the number of degrees of freedom, infinite if the number of degrees
+    /// of freedom is not positive.
+    pub reduced_chi2: f64,
+    /// Number of data points minus the number of the fitted coefficients and knots. The
+    /// smoothness penalty reduces the effective number of parameters, it is not accounted here.
+    pub degrees_of_freedom: i64,
+}
+
+/// Interior knots of [SplineFitProblem].
+#[derive(Clone, Copy, Debug)]
+pub enum SplineKnots<'a> {
+    /// Given interior knots, they must be strictly increasing and inside the data range.
+    Interior(&'a [f64]),
+    /// Given number of interior knots uniformly splitting the data range.
+    Uniform(usize),
+}
+
+/// Builder for [SplineFitProblem], see [spline_fit](crate::spline_fit) module docs for an
+/// example.
+pub struct SplineFitProblemBuilder<'cost, 'param> {
+    /// Independent coordinates for data
+    pub x: Option<&'cost [f64]>,
+    /// Values for data
+    pub y: Option<&'cost [f64]>,
+    /// Optional inverse errors - square root of the weight
+    pub inverse_error: Option<&'cost [f64]>,
+    /// Degree of the spline, cubic by default
+    pub degree: usize,
+    /// Interior knots, or their initial guess if `free_knots` is set
+    pub knots: Option<SplineKnots<'param>>,
+    /// Whether the interior knots are fitted
+    pub free_knots: bool,
+    /// Weight of the smoothness penalty, zero means no penalty
+    pub smoothing: f64,
+    /// Order of the finite differences of the smoothness penalty
+    pub penalty_order: usize,
+    /// Optional loss function
+    pub loss: Option<LossFunction>,
+}
+
+impl<'cost, 'param> SplineFitProblemBuilder<'cost, 'param> {
+    pub fn new() -> Self {
+        Self {
+            x: None,
+            y: None,
+            inverse_error: None,
+            degree: 3,
+            knots: None,
+            free_knots: false,
+            smoothing: 0.0,
+            penalty_order: 2,
+            loss: None,
+        }
+    }
+
+    /// Add independent parameter values for the data points, their range is the spline domain.
+    pub fn x(mut self, x: &'cost [f64]) -> Self {
+        self.x = Some(x);
+        self
+    }
+
+    /// Add values for the data points.
+    pub fn y(mut self, y: &'cost [f64]) -> Self {
+        self.y = Some(y);
+        self
+    }
+
+    /// Add optional inverse errors for the data points. They must be positive, the residual would
+    /// be `(y - spline(x)) * inverse_error`. If not given, unity values are assumed.
+    pub fn inverse_error(mut self, inv_err: &'cost [f64]) -> Self {
+        self.inverse_error = Some(inv_err);
+        self
+    }
+
+    /// Set the degree of the spline, the default is 3 for a cubic spline.
+    pub fn degree(mut self, degree: usize) -> Self {
+        self.degree = degree;
+        self
+    }
+
+    /// Set interior knots, they must be strictly increasing and lie strictly inside the data
+    /// range.
+    pub fn knots(mut self, knots: &'param [f64]) -> Self {
+        self.knots = Some(SplineKnots::Interior(knots));
+        self
+    }
+
+    /// Set the number of interior knots, which uniformly split the data range.
+    pub fn uniform_knots(mut self, num_knots: usize) -> Self {
+        self.knots = Some(SplineKnots::Uniform(num_knots));
+        self
+    }
+
+    /// Fit the interior knots too, the knots given by [SplineFitProblemBuilder::knots] or
+    /// [SplineFitProblemBuilder::uniform_knots] are the initial guess. The knots are
+    /// parametrized by the relative lengths of the intervals between them, so they stay sorted
+    /// and inside the data range. The derivatives over the knots are found numerically, and the
+    /// smoothness penalty is computed for the initial knots.
+    pub fn free_knots(mut self) -> Self {
+        self.free_knots = true;
+        self
+    }
+
+    /// Add the smoothness penalty with the given non-negative weight, see
+    /// [spline_fit](crate::spline_fit) module docs.
+    pub fn smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Set the order of the finite differences of the smoothness penalty, the default is 2.
+    pub fn penalty_order(mut self, order: usize) -> Self {
+        self.penalty_order = order;
+        self
+    }
+
+    /// Add optional loss function for the data, if not given the trivial loss is assumed. The
+    /// loss is not applied to the smoothness penalty.
+    pub fn loss(mut self, loss: LossFunction) -> Self {
+        self.loss = Some(loss);
+        self
+    }
+
+    /// Build the [SplineFitProblem] instance. Returns [Err] if one of the mandatory fields is
+    /// missed, data slices have inconsistent lengths or the knots are invalid.
+    pub fn build(self) -> Result<SplineFitProblem<'cost>, CurveFitProblemBuildError> {
+        let x = self.x.ok_or(CurveFitProblemBuildError::XMissed)?;
+        let y = self.y.ok_or(CurveFitProblemBuildError::YMissed)?;
+        if x.len() != y.len() {
+            return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+        }
+        if let Some(inverse_error) = self.inverse_error {
+            if inverse_error.len() != y.len() {
+                return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+            }
+        }
+        let lower = x.iter().copied().fold(f64::INFINITY, f64::min);
+        let upper = x.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        if lower >= upper {
+            return Err(CurveFitProblemBuildError::DegenerateDataRange);
+        }
+        let interior_knots: Vec<_> =
+            match self.knots.ok_or(CurveFitProblemBuildError::KnotsMissed)? {
+                SplineKnots::Interior(knots) => knots.to_vec(),
+                SplineKnots::Uniform(num_knots) => (1..=num_knots)
+                    .map(|i| lower + (upper - lower) * i as f64 / (num_knots + 1) as f64)
+                    .collect(),
+            };
+        let increasing = iter::once(lower)
+            .chain(interior_knots.iter().copied())
+            .zip(interior_knots.iter().copied().chain(iter::once(upper)))
+            .all(|(previous, next)| previous < next);
+        if !increasing {
+            return Err(CurveFitProblemBuildError::InvalidKnots);
+        }
+        if self.smoothing < 0.0 || self.smoothing.is_nan() {
+            return Err(CurveFitProblemBuildError::NegativeSmoothing);
+        }
+
+        let num_coefficients = interior_knots.len() + self.degree + 1;
+        // Linear in the coefficients, so any initial guess works
+        let mean = y.iter().sum::<f64>() / y.len() as f64;
+        let mut parameters: Vec<ParameterBlock> = vec![vec![mean; num_coefficients].into()];
+        if self.free_knots && !interior_knots.is_empty() {
+            let mut gaps: ParameterBlock = gaps_from_knots(lower, upper, &interior_knots).into();
+            gaps.set_lower_bounds(vec![Some(0.0); interior_knots.len()]);
+            parameters.push(gaps);
+        }
+        let data = SplineData {
+            x,
+            y,
+            inverse_error: self.inverse_error,
+        };
+        let knots = clamped_knots(self.degree, lower, upper, &interior_knots);
+        let mut residual_block = NllsProblem::new().residual_block_builder().set_cost(
+            SplineFitProblem::data_cost_function(data, self.degree, (lower, upper), knots.clone()),
+            y.len(),
+        );
+        if let Some(loss) = self.loss {
+            residual_block = residual_block.set_loss(loss);
+        }
+        let (mut problem, _block_id) = residual_block
+            .set_parameters(parameters)
+            .build_into_problem()
+            .unwrap();
+        if self.smoothing > 0.0 && num_coefficients > self.penalty_order {
+            let stencils = penalty_stencils(self.degree, &knots, self.penalty_order);
+            let num_residuals = stencils.len();
+            problem = problem
+                .residual_block_builder()
+                .set_cost(
+                    SplineFitProblem::penalty_cost_function(stencils, self.smoothing),
+                    num_residuals,
+                )
+                .set_parameters([0])
+                .build_into_problem()
+                .unwrap()
+                .0;
+        }
+        Ok(SplineFitProblem {
+            problem,
+            data,
+            degree: self.degree,
+            lower,
+            upper,
+            interior_knots,
+        })
+    }
+}
+
+impl Default for SplineFitProblemBuilder<'_, '_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn bspline_basis() {
+        let interior_knots = [0.5, 1.0, 1.0, 2.5];
+        let degree = 3;
+        let num_coefficients = interior_knots.len() + degree + 1;
+        // Partition of unity
+        let ones = BSpline::clamped(
+            degree,
+            0.0,
+            3.0,
+            &interior_knots,
+            vec![1.0; num_coefficients],
+        );
+        // Greville abscissae as coefficients give the identity
+        let knots = ones.knots().to_vec();
+        let greville: Vec<_> = (0..num_coefficients)
+            .map(|j| knots[j + 1..=j + degree].iter().sum::<f64>() / degree as f64)
+            .collect();
+        let identity = BSpline::new(degree, knots, greville);
+        for x in [0.0, 0.3, 0.5, 0.99, 1.0, 1.7, 2.5, 2.9, 3.0] {
+            assert_abs_diff_eq!(ones.value(x), 1.0, epsilon = 1e-12);
+            assert_abs_diff_eq!(identity.value(x), x, epsilon = 1e-12);
+        }
+        assert_abs_diff_eq!(
+            &knots_from_gaps(0.0, 3.0, &gaps_from_knots(0.0, 3.0, &interior_knots))[..],
+            &interior_knots[..],
+            epsilon = 1e-12
+        );
+        // Straight line has no penalty
+        let stencils = penalty_stencils(degree, identity.knots(), 2);
+        assert_eq!(stencils.len(), num_coefficients - 2);
+        for (k, stencil) in stencils.iter().enumerate() {
+            let penalty: f64 = stencil
+                .iter()
+                .zip(&identity.coefficients()[k..])
+                .map(|(s, c)| s * c)
+                .sum();
+            assert_abs_diff_eq!(penalty, 0.0, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn fixed_knots() {
+        let x: Vec<_> = (0..100).map(|i| 0.1 * i as f64).collect();
+        let y: Vec<_> = x.iter().map(|&x| f64::sin(x)).collect();
+
+        let solution = SplineFitProblem::builder()
+            .x(&x)
+            .y(&y)
+            .uniform_knots(8)
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        for (&x, &y) in x.iter().zip(&y) {
+            assert_abs_diff_eq!(solution.spline.value(x), y, epsilon = 1e-2);
+        }
+        assert_eq!(solution.spline.coefficients().len(), 12);
+        assert_eq!(solution.degrees_of_freedom, 88);
+
+        let error = SplineFitProblem::builder()
+            .x(&x)
+            .y(&y)
+            .knots(&[5.0, 2.0])
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(error, CurveFitProblemBuildError::InvalidKnots));
+
+        let error = SplineFitProblem::builder()
+            .x(&[1.0, 1.0])
+            .y(&[0.0, 1.0])
+            .uniform_knots(1)
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            CurveFitProblemBuildError::DegenerateDataRange
+        ));
+    }
+
+    #[test]
+    fn smoothing_penalty() {
+        // A line with alternating perturbations
+        let x: Vec<_> = (0..50).map(|i| i as f64).collect();
+        let y: Vec<_> = x
+            .iter()
+            .map(|&x| 2.0 * x + 1.0 + if x % 2.0 == 0.0 { 0.5 } else { -0.5 })
+            .collect();
+
+        let solution = SplineFitProblem::builder()
+            .x(&x)
+            .y(&y)
+            .uniform_knots(20)
+            .smoothing(1e8)
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        // Large penalty of the second differences gives a straight line
+        for &x in x.iter() {
+            assert_abs_diff_eq!(solution.spline.value(x), 2.0 * x + 1.0, epsilon = 0.1);
+        }
+    }
+
+    #[test]
+    fn free_knots() {
+        let x: Vec<_> = (0..=100).map(|i| 0.01 * i as f64).collect();
+        let y: Vec<_> = x.iter().map(|&x| f64::abs(x - 0.3)).collect();
+
+        let solution = SplineFitProblem::builder()
+            .x(&x)
+            .y(&y)
+            .degree(1)
+            .knots(&[0.5])
+            .free_knots()
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        let knots = solution.spline.knots();
+        assert_eq!(knots.len(), 5);
+        assert_abs_diff_eq!(knots[2], 0.3, epsilon = 1e-4);
+        assert_eq!(solution.degrees_of_freedom, 97);
+    }
+}
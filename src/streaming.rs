@@ -0,0 +1,518 @@
+//! Curve fitting of datasets too large to keep in memory.
+//!
+//! [StreamingCurveFitProblem] fits a model like
+//! [CurveFitProblem1D](crate::curve_fit::CurveFitProblem1D), but it doesn't own the data. The data
+//! points are read from a [CurveDataSource] chunk by chunk every time the residuals are evaluated,
+//! so only a single chunk is in memory at once. Every chunk is a separate residual block, so the
+//! data is split into the residual blocks automatically, see
+//! [StreamingCurveFitProblemBuilder::chunk_size].
+//!
+//! The source is either a closure wrapped into [FnDataSource], e.g. generating the data or copying
+//! it from a memory-mapped file, or a binary file read with [BinaryFileSource]. The solver
+//! evaluates the residuals a few times per iteration, so the source should be fast enough to be
+//! read many times.
+//!
+//! Let's fit a line to a million points generated on the fly:
+//!
+//! ```rust
+//! use ceres_solver::curve_fit::CurveFunctionType;
+//! use ceres_solver::streaming::{FnDataSource, StreamingCurveFitProblem};
+//! use ceres_solver::SolverOptions;
+//!
+//! // y = a * x + b
+//! fn line(x: f64, parameters: &[f64], y: &mut f64, jacobians: Option<&mut [Option<f64>]>) -> bool {
+//!     *y = parameters[0] * x + parameters[1];
+//!     if let Some(jacobians) = jacobians {
+//!         if let Some(d_da) = &mut jacobians[0] {
+//!             *d_da = x;
+//!         }
+//!         if let Some(d_db) = &mut jacobians[1] {
+//!             *d_db = 1.0;
+//!         }
+//!     }
+//!     true
+//! }
+//!
+//! let source = FnDataSource::new(1_000_000, |offset, x, y, _inverse_error| {
+//!     for (i, (x, y)) in x.iter_mut().zip(y.iter_mut()).enumerate() {
+//!         *x = 1e-6 * (offset + i) as f64;
+//!         *y = 3.0 * *x - 1.0;
+//!     }
+//!     true
+//! });
+//!
+//! let func: CurveFunctionType = Box::new(line);
+//! let problem = StreamingCurveFitProblem::builder()
+//!     .func(func)
+//!     .source(source)
+//!     .chunk_size(100_000)
+//!     .parameters(&[1.0, 0.0])
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(problem.num_chunks(), 10);
+//!
+//! let solution = problem.solve(&SolverOptions::default());
+//! assert!(f64::abs(solution.parameters[0] - 3.0) < 1e-6);
+//! assert!(f64::abs(solution.parameters[1] + 1.0) < 1e-6);
+//! ```
+
+use crate::cost::CostFunctionType;
+use crate::curve_fit::{
+    autodiff_curve_function, solve_fit_problem, AutoDiffCurveModel, CurveFitProblemSolution,
+    CurveFunctionType,
+};
+use crate::error::CurveFitProblemBuildError;
+use crate::loss::{LossFunction, LossFunctionWrapper};
+use crate::nlls_problem::NllsProblem;
+use crate::parameter_block::{ParameterBlock, ParameterBlockOrIndex};
+use crate::solver::SolverOptions;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::rc::Rc;
+
+/// Default number of data points per chunk, see [StreamingCurveFitProblemBuilder::chunk_size].
+pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// Source of the data points for [StreamingCurveFitProblem].
+pub trait CurveDataSource {
+    /// Total number of the data points, it must not change.
+    fn num_points(&self) -> usize;
+
+    /// Reads the data points starting at `offset` into `x`, `y` and `inverse_error`, all of them
+    /// have the length of the chunk. `inverse_error` is filled with unity values, so it may be
+    /// left as is if the errors are unknown. Returns [false] if the data cannot be read, so the
+    /// solver treats the evaluation as failed.
+    fn read(&self, offset: usize, x: &mut [f64], y: &mut [f64], inverse_error: &mut [f64]) -> bool;
+}
+
+/// [CurveDataSource] given by a closure with the same arguments as [CurveDataSource::read].
+pub struct FnDataSource<F> {
+    num_points: usize,
+    read: F,
+}
+
+impl<F> FnDataSource<F>
+where
+    F: Fn(usize, &mut [f64], &mut [f64], &mut [f64]) -> bool,
+{
+    /// Creates a new source of `num_points` data points.
+    pub fn new(num_points: usize, read: F) -> Self {
+        Self { num_points, read }
+    }
+}
+
+impl<F> CurveDataSource for FnDataSource<F>
+where
+    F: Fn(usize, &mut [f64], &mut [f64], &mut [f64]) -> bool,
+{
+    fn num_points(&self) -> usize {
+        self.num_points
+    }
+
+    fn read(&self, offset: usize, x: &mut [f64], y: &mut [f64], inverse_error: &mut [f64]) -> bool {
+        (self.read)(offset, x, y, inverse_error)
+    }
+}
+
+/// [CurveDataSource] reading a binary file of little-endian [f64] records `(x, y)`, or
+/// `(x, y, inverse_error)`. The file is kept open and read chunk by chunk, so it must not be
+/// modified while the problem is alive.
+pub struct BinaryFileSource {
+    file: RefCell<File>,
+    num_points: usize,
+    with_inverse_error: bool,
+}
+
+impl BinaryFileSource {
+    /// Opens the file, `with_inverse_error` tells whether the records have the third value.
+    /// Returns [Err] if the file cannot be opened or its size is not a multiple of the record
+    /// size.
+    pub fn open(path: impl AsRef<Path>, with_inverse_error: bool) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let record_size = Self::record_size(with_inverse_error) as u64;
+        let file_size = file.metadata()?.len();
+        if file_size % record_size != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file size is not a multiple of the record size",
+            ));
+        }
+        Ok(Self {
+            file: RefCell::new(file),
+            num_points: (file_size / record_size) as usize,
+            with_inverse_error,
+        })
+    }
+
+    fn record_size(with_inverse_error: bool) -> usize {
+        let num_values = if with_inverse_error { 3 } else { 2 };
+        num_values * std::mem::size_of::<f64>()
+    }
+}
+
+impl CurveDataSource for BinaryFileSource {
+    fn num_points(&self) -> usize {
+        self.num_points
+    }
+
+    fn read(&self, offset: usize, x: &mut [f64], y: &mut [f64], inverse_error: &mut [f64]) -> bool {
+        let record_size = Self::record_size(self.with_inverse_error);
+        let mut bytes = vec![0; x.len() * record_size];
+        let mut file = self.file.borrow_mut();
+        if file
+            .seek(SeekFrom::Start((offset * record_size) as u64))
+            .and_then(|_| file.read_exact(&mut bytes))
+            .is_err()
+        {
+            return false;
+        }
+        for (i, record) in bytes.chunks_exact(record_size).enumerate() {
+            let value =
+                |k: usize| f64::from_le_bytes(record[8 * k..8 * (k + 1)].try_into().unwrap());
+            x[i] = value(0);
+            y[i] = value(1);
+            if self.with_inverse_error {
+                inverse_error[i] = value(2);
+            }
+        }
+        true
+    }
+}
+
+/// A wrapper for [NllsProblem] to fit a curve to the data read from a [CurveDataSource] chunk by
+/// chunk, see [streaming](crate::streaming) module docs. Create it with
+/// [StreamingCurveFitProblem::builder] and then call a destructive method
+/// [StreamingCurveFitProblem::solve].
+pub struct StreamingCurveFitProblem<'cost> {
+    problem: NllsProblem<'cost>,
+}
+
+impl<'cost> StreamingCurveFitProblem<'cost> {
+    /// Create a [StreamingCurveFitProblemBuilder] instance, see its docs for the details.
+    pub fn builder<'param>() -> StreamingCurveFitProblemBuilder<'cost, 'param> {
+        StreamingCurveFitProblemBuilder::new()
+    }
+
+    /// Number of the chunks, each of them is a residual block.
+    pub fn num_chunks(&self) -> usize {
+        self.problem.num_residual_blocks()
+    }
+
+    fn cost_function(
+        source: Rc<dyn CurveDataSource + 'cost>,
+        offset: usize,
+        len: usize,
+        func: Rc<CurveFunctionType>,
+    ) -> CostFunctionType<'cost> {
+        Box::new(move |parameters, residuals, mut jacobians| {
+            // The chunk is read on every evaluation and dropped after it
+            let mut x = vec![0.0; len];
+            let mut y = vec![0.0; len];
+            let mut inverse_error = vec![1.0; len];
+            if !source.read(offset, &mut x, &mut y, &mut inverse_error) {
+                return false;
+            }
+            let parameters: Vec<_> = parameters.iter().map(|p| p[0]).collect();
+            let mut point_jacobians: Option<Vec<Option<f64>>> =
+                jacobians.as_ref().map(|jacobians| {
+                    jacobians
+                        .iter()
+                        .map(|jacobian| jacobian.as_ref().map(|_| 0.0))
+                        .collect()
+                });
+            let mut value = 0.0;
+            for i in 0..len {
+                if !func(
+                    x[i],
+                    &parameters,
+                    &mut value,
+                    point_jacobians.as_deref_mut(),
+                ) {
+                    return false;
+                }
+                let w = inverse_error[i];
+                residuals[i] = w * (y[i] - value);
+                if let (Some(jacobians), Some(point_jacobians)) =
+                    (jacobians.as_mut(), point_jacobians.as_ref())
+                {
+                    for (d_out, &d_in) in jacobians.iter_mut().zip(point_jacobians) {
+                        if let (Some(d_out), Some(d_in)) = (d_out.as_mut(), d_in) {
+                            d_out[i][0] = -w * d_in;
+                        }
+                    }
+                }
+            }
+            true
+        })
+    }
+
+    /// Solves the problem and returns a solution for the parameters. The data is not kept, so
+    /// [CurveFitProblemSolution::y_fit] and [CurveFitProblemSolution::residuals] are [None].
+    pub fn solve(self, options: &SolverOptions) -> CurveFitProblemSolution {
+        solve_fit_problem(self.problem, &[], None, options, false)
+    }
+}
+
+/// Builder for [StreamingCurveFitProblem], see [streaming](crate::streaming) module docs for an
+/// example.
+pub struct StreamingCurveFitProblemBuilder<'cost, 'param> {
+    /// Model function
+    pub func: Option<CurveFunctionType>,
+    /// Source of the data points
+    pub source: Option<Box<dyn CurveDataSource + 'cost>>,
+    /// Number of the data points per chunk, [DEFAULT_CHUNK_SIZE] if not given
+    pub chunk_size: Option<usize>,
+    /// Initial parameters' guess
+    pub parameters: Option<&'param [f64]>,
+    /// Optional lower bounds for parameters
+    pub lower_bounds: Option<&'param [Option<f64>]>,
+    /// Optional upper bounds for parameters
+    pub upper_bounds: Option<&'param [Option<f64>]>,
+    /// Constant parameters, they will not be optimized.
+    pub constant_parameters: Option<&'param [usize]>,
+    /// Optional loss function, shared by all the chunks
+    pub loss: Option<LossFunction>,
+}
+
+impl<'cost, 'param> StreamingCurveFitProblemBuilder<'cost, 'param> {
+    pub fn new() -> Self {
+        Self {
+            func: None,
+            source: None,
+            chunk_size: None,
+            parameters: None,
+            lower_bounds: None,
+            upper_bounds: None,
+            constant_parameters: None,
+            loss: None,
+        }
+    }
+
+    /// Add model function, see
+    /// [CurveFitProblem1D::new](crate::curve_fit::CurveFitProblem1D::new) for its arguments.
+    pub fn func(mut self, func: impl Into<CurveFunctionType>) -> Self {
+        self.func = Some(func.into());
+        self
+    }
+
+    /// Add model function generic over [Scalar](crate::autodiff::Scalar) differentiated
+    /// automatically, see [AutoDiffCurveModel].
+    pub fn autodiff_func(self, model: impl AutoDiffCurveModel + 'static) -> Self {
+        self.func(autodiff_curve_function(model))
+    }
+
+    /// Add the source of the data points.
+    pub fn source(mut self, source: impl CurveDataSource + 'cost) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Set the number of the data points per chunk, the last chunk may be shorter. Larger chunks
+    /// mean fewer reads of the source and fewer residual blocks, but more memory per evaluation.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    /// Add initial parameter guess slice, it is copied to the [StreamingCurveFitProblem]
+    /// instance.
+    pub fn parameters(mut self, parameters: &'param [f64]) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+
+    /// Add optional lower bounds for parameters, in the same order as parameters themselves. If
+    /// some parameter has no lower bound, use [None].
+    pub fn lower_bounds(mut self, lower_bounds: &'param [Option<f64>]) -> Self {
+        self.lower_bounds = Some(lower_bounds);
+        self
+    }
+
+    /// Add optional upper bounds for parameters, in the same order as parameters themselves. If
+    /// some parameter has no upper bound, use [None].
+    pub fn upper_bounds(mut self, upper_bounds: &'param [Option<f64>]) -> Self {
+        self.upper_bounds = Some(upper_bounds);
+        self
+    }
+
+    /// Make parameters constant, i.e. they will not be fitted.
+    pub fn constant(mut self, indexes: &'param [usize]) -> Self {
+        self.constant_parameters = Some(indexes);
+        self
+    }
+
+    /// Add optional loss function, if not given the trivial loss is assumed.
+    pub fn loss(mut self, loss: LossFunction) -> Self {
+        self.loss = Some(loss);
+        self
+    }
+
+    /// Build the [StreamingCurveFitProblem] instance. Returns [Err] if one of the mandatory
+    /// fields is missed, the source is empty or the chunk size is zero. The source is not read.
+    pub fn build(self) -> Result<StreamingCurveFitProblem<'cost>, CurveFitProblemBuildError> {
+        let func = Rc::new(self.func.ok_or(CurveFitProblemBuildError::FuncMissed)?);
+        let source: Rc<dyn CurveDataSource + 'cost> = self
+            .source
+            .ok_or(CurveFitProblemBuildError::SourceMissed)?
+            .into();
+        let chunk_size = self.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+        if chunk_size == 0 {
+            return Err(CurveFitProblemBuildError::ZeroChunkSize);
+        }
+        let num_points = source.num_points();
+        if num_points == 0 {
+            return Err(CurveFitProblemBuildError::EmptySource);
+        }
+        let mut nlls_parameters: Vec<ParameterBlock> = self
+            .parameters
+            .ok_or(CurveFitProblemBuildError::ParametersMissed)?
+            .iter()
+            .map(|&p| vec![p].into())
+            .collect();
+        if let Some(lower_bounds) = self.lower_bounds {
+            if lower_bounds.len() != nlls_parameters.len() {
+                return Err(CurveFitProblemBuildError::LowerBoundarySizeMismatch);
+            }
+            for (parameter, &lb) in nlls_parameters.iter_mut().zip(lower_bounds) {
+                if lb.is_some() {
+                    parameter.set_lower_bounds(vec![lb]);
+                }
+            }
+        }
+        if let Some(upper_bounds) = self.upper_bounds {
+            if upper_bounds.len() != nlls_parameters.len() {
+                return Err(CurveFitProblemBuildError::UpperBoundarySizeMismatch);
+            }
+            for (parameter, &ub) in nlls_parameters.iter_mut().zip(upper_bounds) {
+                if ub.is_some() {
+                    parameter.set_upper_bounds(vec![ub]);
+                }
+            }
+        }
+        let num_parameters = nlls_parameters.len();
+        let loss = self.loss.map(|loss| LossFunctionWrapper::new(Some(loss)));
+        let mut new_parameters = Some(nlls_parameters);
+        let mut problem = NllsProblem::new();
+        for offset in (0..num_points).step_by(chunk_size) {
+            let len = chunk_size.min(num_points - offset);
+            // The parameter blocks are added with the first residual block and reused later
+            let block_parameters: Vec<ParameterBlockOrIndex> = match new_parameters.take() {
+                Some(parameters) => parameters.into_iter().map(Into::into).collect(),
+                None => (0..num_parameters).map(Into::into).collect(),
+            };
+            let mut residual_block = problem.residual_block_builder().set_cost(
+                StreamingCurveFitProblem::cost_function(
+                    Rc::clone(&source),
+                    offset,
+                    len,
+                    Rc::clone(&func),
+                ),
+                len,
+            );
+            if let Some(loss) = &loss {
+                residual_block = residual_block.set_loss_wrapper(loss.clone());
+            }
+            problem = residual_block
+                .set_parameters(block_parameters)
+                .build_into_problem()
+                .unwrap()
+                .0;
+        }
+        if let Some(indexes) = self.constant_parameters {
+            for &i_param in indexes {
+                problem.set_parameter_block_constant(i_param)?;
+            }
+        }
+        Ok(StreamingCurveFitProblem { problem })
+    }
+}
+
+impl Default for StreamingCurveFitProblemBuilder<'_, '_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::autodiff::Scalar;
+
+    use approx::assert_abs_diff_eq;
+    use std::io::Write;
+
+    struct Exponent;
+
+    impl AutoDiffCurveModel for Exponent {
+        fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+            parameters[0] * (-x * parameters[1]).exp()
+        }
+    }
+
+    fn data(i: usize) -> (f64, f64, f64) {
+        let x = 1e-3 * i as f64;
+        let y = 2.0 * f64::exp(-0.7 * x) + 1e-3 * f64::sin(1e3 * x);
+        let inverse_error = 1.0 + 0.5 * f64::cos(x);
+        (x, y, inverse_error)
+    }
+
+    #[test]
+    fn fn_source_chunks() {
+        let source = FnDataSource::new(10_500, |offset, x, y, inverse_error| {
+            for i in 0..x.len() {
+                (x[i], y[i], inverse_error[i]) = data(offset + i);
+            }
+            true
+        });
+        let problem = StreamingCurveFitProblem::builder()
+            .autodiff_func(Exponent)
+            .source(source)
+            .chunk_size(1000)
+            .parameters(&[1.0, 1.0])
+            .build()
+            .unwrap();
+        // The last chunk is shorter
+        assert_eq!(problem.num_chunks(), 11);
+        let solution = problem.solve(&SolverOptions::default());
+        assert_eq!(solution.degrees_of_freedom, 10_498);
+        assert_abs_diff_eq!(&solution.parameters[..], &[2.0, 0.7][..], epsilon = 1e-4);
+    }
+
+    #[test]
+    fn binary_file_source() {
+        let path =
+            std::env::temp_dir().join(format!("ceres-solver-streaming-{}.bin", std::process::id()));
+        {
+            let mut file = File::create(&path).unwrap();
+            for i in 0..5001 {
+                let (x, y, inverse_error) = data(i);
+                for value in [x, y, inverse_error] {
+                    file.write_all(&value.to_le_bytes()).unwrap();
+                }
+            }
+        }
+        let source = BinaryFileSource::open(&path, true).unwrap();
+        assert_eq!(source.num_points(), 5001);
+        let (mut x, mut y, mut inverse_error) = ([0.0; 2], [0.0; 2], [1.0; 2]);
+        assert!(source.read(10, &mut x, &mut y, &mut inverse_error));
+        assert_eq!((x[1], y[1], inverse_error[1]), data(11));
+        assert!(!source.read(5000, &mut x, &mut y, &mut inverse_error));
+
+        let solution = StreamingCurveFitProblem::builder()
+            .autodiff_func(Exponent)
+            .source(source)
+            .parameters(&[1.0, 1.0])
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        assert_abs_diff_eq!(&solution.parameters[..], &[2.0, 0.7][..], epsilon = 1e-3);
+
+        // Records without inverse errors don't fit the file size
+        assert!(BinaryFileSource::open(&path, false).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
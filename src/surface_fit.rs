@@ -0,0 +1,502 @@
+//! Wrapping [NllsProblem] for 2-D surface fit problems.
+//!
+//! [SurfaceFitProblem] fits a model `z = f(x, y; parameters)` to scattered `(x, y, z)` data
+//! points or to `z` values on a regular grid, e.g. a point spread function to an image or a
+//! surface to a topography map. Like [CurveFitProblem1D](crate::curve_fit::CurveFitProblem1D), it
+//! treats every parameter as a separate scalar and returns [CurveFitProblemSolution], where
+//! [CurveFitProblemSolution::y_fit] holds the model `z` values.
+//!
+//! Let's fit a Gaussian spot on a constant background to a 2-D image:
+//!
+//! ```rust
+//! use ceres_solver::surface_fit::{SurfaceFitProblem, SurfaceFunctionType};
+//! use ceres_solver::SolverOptions;
+//!
+//! // z = a exp(-((x - x0)^2 + (y - y0)^2) / (2 s^2)) + b
+//! fn gaussian(
+//!     x: f64,
+//!     y: f64,
+//!     parameters: &[f64],
+//!     z: &mut f64,
+//!     jacobians: Option<&mut [Option<f64>]>,
+//! ) -> bool {
+//!     let &[a, x0, y0, s, b]: &[f64; 5] = parameters.try_into().unwrap();
+//!     let (dx, dy) = (x - x0, y - y0);
+//!     let r2 = dx.powi(2) + dy.powi(2);
+//!     let e = f64::exp(-0.5 * r2 / s.powi(2));
+//!     *z = a * e + b;
+//!     if let Some(jacobians) = jacobians {
+//!         let derivatives = [
+//!             e,
+//!             a * e * dx / s.powi(2),
+//!             a * e * dy / s.powi(2),
+//!             a * e * r2 / s.powi(3),
+//!             1.0,
+//!         ];
+//!         for (jacobian, derivative) in jacobians.iter_mut().zip(derivatives) {
+//!             if let Some(jacobian) = jacobian {
+//!                 *jacobian = derivative;
+//!             }
+//!         }
+//!     }
+//!     true
+//! }
+//!
+//! let true_parameters = [10.0, 12.3, 15.6, 2.5, 1.0];
+//!
+//! // 32x24 pixel image, z has a row per y value
+//! let x: Vec<_> = (0..32).map(|i| i as f64).collect();
+//! let y: Vec<_> = (0..24).map(|j| j as f64).collect();
+//! let z: Vec<_> = y
+//!     .iter()
+//!     .flat_map(|&y| x.iter().map(move |&x| (x, y)))
+//!     .map(|(x, y)| {
+//!         let mut z = 0.0;
+//!         gaussian(x, y, &true_parameters, &mut z, None);
+//!         z
+//!     })
+//!     .collect();
+//!
+//! let func: SurfaceFunctionType = Box::new(gaussian);
+//! let solution = SurfaceFitProblem::builder()
+//!     .func(func)
+//!     .grid(&x, &y, &z)
+//!     .parameters(&[8.0, 13.0, 15.0, 2.0, 0.5])
+//!     .lower_bounds(&[Some(0.0), None, None, Some(0.1), None])
+//!     .build()
+//!     .unwrap()
+//!     .solve(&SolverOptions::default());
+//!
+//! for (true_value, value) in true_parameters.iter().zip(solution.parameters.iter()) {
+//!     assert!(f64::abs(true_value - value) < 1e-6);
+//! }
+//! ```
+
+use crate::cost::CostFunctionType;
+use crate::curve_fit::{solve_fit_problem, CurveFitProblemSolution};
+use crate::error::CurveFitProblemBuildError;
+use crate::loss::LossFunction;
+use crate::nlls_problem::NllsProblem;
+use crate::parameter_block::ParameterBlock;
+use crate::solver::SolverOptions;
+
+/// Model function of [SurfaceFitProblem], it accepts `x`, `y`, the parameters, a mutable reference
+/// to output the `z` value and optional jacobians, see [SurfaceFitProblem::new].
+pub type SurfaceFunctionType =
+    Box<dyn Fn(f64, f64, &[f64], &mut f64, Option<&mut [Option<f64>]>) -> bool>;
+
+/// A wrapper for [NllsProblem] to fit a surface `z = f(x, y; parameters)` to the data. Create it
+/// with [SurfaceFitProblem::new] for scattered data points or with [SurfaceFitProblem::builder]
+/// for more options, and then call a destructive method [SurfaceFitProblem::solve].
+pub struct SurfaceFitProblem<'cost> {
+    problem: NllsProblem<'cost>,
+    z: &'cost [f64],
+    inverse_error: Option<&'cost [f64]>,
+}
+
+impl<'cost> SurfaceFitProblem<'cost> {
+    /// Creates a new instance of the `SurfaceFitProblem` for scattered data points. If you need
+    /// more control over the problem, or your data is on a grid, use [SurfaceFitProblem::builder]
+    /// instead.
+    ///
+    /// # Arguments
+    /// - func - a function describing a surface. It must return [false] if it cannot calculate
+    ///   the value, or [true] otherwise. It accepts the following parameters:
+    ///   - x, y - independent coordinates.
+    ///   - parameters - a slice for the current value of the problem parameters.
+    ///   - z - a mutable reference to output the function value.
+    ///   - jacobians - derivatives of the value over the parameters, the slice has length of
+    ///     `parameters.len()`. It (or any of its component) can be [None], which means that the
+    ///     solver doesn't need it.
+    /// - x, y - independent coordinates of the data points.
+    /// - z - values of the data points.
+    /// - parameters - initial parameters.
+    ///
+    /// # Panics
+    /// Panics if `x`, `y` and `z` have different sizes.
+    pub fn new(
+        func: impl Into<SurfaceFunctionType>,
+        x: &[f64],
+        y: &[f64],
+        z: &'cost [f64],
+        parameters: &[f64],
+    ) -> Self {
+        assert_eq!(x.len(), z.len());
+        assert_eq!(y.len(), z.len());
+        let points = x.iter().copied().zip(y.iter().copied()).collect();
+        let nlls_parameters: Vec<_> = parameters.iter().map(|&p| vec![p]).collect();
+        let (problem, _block_id) = NllsProblem::new()
+            .residual_block_builder()
+            .set_cost(Self::cost_function(points, z, None, func.into()), z.len())
+            .set_parameters(nlls_parameters)
+            .build_into_problem()
+            .unwrap();
+        Self {
+            problem,
+            z,
+            inverse_error: None,
+        }
+    }
+
+    /// Create a [SurfaceFitProblemBuilder] instance, see its docs for the details.
+    pub fn builder<'param>() -> SurfaceFitProblemBuilder<'cost, 'param> {
+        SurfaceFitProblemBuilder::new()
+    }
+
+    fn cost_function(
+        points: Vec<(f64, f64)>,
+        z: &'cost [f64],
+        inverse_error: Option<&'cost [f64]>,
+        func: SurfaceFunctionType,
+    ) -> CostFunctionType<'cost> {
+        Box::new(move |parameters, residuals, mut jacobians| {
+            let parameters: Vec<_> = parameters.iter().map(|p| p[0]).collect();
+            let mut point_jacobians: Option<Vec<Option<f64>>> =
+                jacobians.as_ref().map(|jacobians| {
+                    jacobians
+                        .iter()
+                        .map(|jacobian| jacobian.as_ref().map(|_| 0.0))
+                        .collect()
+                });
+            let mut value = 0.0;
+            for (i, (&(x, y), &z)) in points.iter().zip(z).enumerate() {
+                if !func(
+                    x,
+                    y,
+                    &parameters,
+                    &mut value,
+                    point_jacobians.as_deref_mut(),
+                ) {
+                    return false;
+                }
+                let w = inverse_error.map_or(1.0, |inverse_error| inverse_error[i]);
+                residuals[i] = w * (z - value);
+                if let (Some(jacobians), Some(point_jacobians)) =
+                    (jacobians.as_mut(), point_jacobians.as_ref())
+                {
+                    for (d_out, &d_in) in jacobians.iter_mut().zip(point_jacobians) {
+                        if let (Some(d_out), Some(d_in)) = (d_out.as_mut(), d_in) {
+                            d_out[i][0] = -w * d_in;
+                        }
+                    }
+                }
+            }
+            true
+        })
+    }
+
+    /// Solves the problem and returns a solution for the parameters.
+    pub fn solve(self, options: &SolverOptions) -> CurveFitProblemSolution {
+        solve_fit_problem(self.problem, self.z, self.inverse_error, options, false)
+    }
+
+    /// Solves the problem like [SurfaceFitProblem::solve] and also fills
+    /// [CurveFitProblemSolution::y_fit] with the model `z` values and
+    /// [CurveFitProblemSolution::residuals] with the residuals at the solution.
+    pub fn solve_with_residuals(self, options: &SolverOptions) -> CurveFitProblemSolution {
+        solve_fit_problem(self.problem, self.z, self.inverse_error, options, true)
+    }
+}
+
+/// Builder for [SurfaceFitProblem], see [surface_fit](crate::surface_fit) module docs for an
+/// example.
+pub struct SurfaceFitProblemBuilder<'cost, 'param> {
+    /// Model function
+    pub func: Option<SurfaceFunctionType>,
+    /// Independent x coordinates of the data points, or of the grid columns if `grid` is set
+    pub x: Option<&'cost [f64]>,
+    /// Independent y coordinates of the data points, or of the grid rows if `grid` is set
+    pub y: Option<&'cost [f64]>,
+    /// Values for data, row-major with a row per y value if `grid` is set
+    pub z: Option<&'cost [f64]>,
+    /// Whether the data is on a grid
+    pub grid: bool,
+    /// Optional inverse errors of z - square root of the weight
+    pub inverse_error: Option<&'cost [f64]>,
+    /// Initial parameters' guess
+    pub parameters: Option<&'param [f64]>,
+    /// Optional lower bounds for parameters
+    pub lower_bounds: Option<&'param [Option<f64>]>,
+    /// Optional upper bounds for parameters
+    pub upper_bounds: Option<&'param [Option<f64>]>,
+    /// Constant parameters, they will not be optimized.
+    pub constant_parameters: Option<&'param [usize]>,
+    /// Optional loss function
+    pub loss: Option<LossFunction>,
+}
+
+impl<'cost, 'param> SurfaceFitProblemBuilder<'cost, 'param> {
+    pub fn new() -> Self {
+        Self {
+            func: None,
+            x: None,
+            y: None,
+            z: None,
+            grid: false,
+            inverse_error: None,
+            parameters: None,
+            lower_bounds: None,
+            upper_bounds: None,
+            constant_parameters: None,
+            loss: None,
+        }
+    }
+
+    /// Add model function, see [SurfaceFitProblem::new] for its arguments.
+    pub fn func(mut self, func: impl Into<SurfaceFunctionType>) -> Self {
+        self.func = Some(func.into());
+        self
+    }
+
+    /// Add scattered data points, all the slices must have the same length.
+    pub fn points(mut self, x: &'cost [f64], y: &'cost [f64], z: &'cost [f64]) -> Self {
+        self.x = Some(x);
+        self.y = Some(y);
+        self.z = Some(z);
+        self.grid = false;
+        self
+    }
+
+    /// Add data on a grid given by its `x` and `y` axes. `z` is row-major with a row per `y`
+    /// value, so the value at `(x[i], y[j])` is `z[j * x.len() + i]`, like pixels of an image.
+    pub fn grid(mut self, x: &'cost [f64], y: &'cost [f64], z: &'cost [f64]) -> Self {
+        self.x = Some(x);
+        self.y = Some(y);
+        self.z = Some(z);
+        self.grid = true;
+        self
+    }
+
+    /// Add optional inverse errors for `z` values, in the same order as `z`. They must be
+    /// positive, the residual would be `(z - model(x, y)) * inverse_error`. If not given, unity
+    /// values are assumed.
+    pub fn inverse_error(mut self, inv_err: &'cost [f64]) -> Self {
+        self.inverse_error = Some(inv_err);
+        self
+    }
+
+    /// Add initial parameter guess slice, it is copied to the [SurfaceFitProblem] instance.
+    pub fn parameters(mut self, parameters: &'param [f64]) -> Self {
+        self.parameters = Some(parameters);
+        self
+    }
+
+    /// Add optional lower bounds for parameters, in the same order as parameters themselves. If
+    /// some parameter has no lower bound, use [None].
+    pub fn lower_bounds(mut self, lower_bounds: &'param [Option<f64>]) -> Self {
+        self.lower_bounds = Some(lower_bounds);
+        self
+    }
+
+    /// Add optional upper bounds for parameters, in the same order as parameters themselves. If
+    /// some parameter has no upper bound, use [None].
+    pub fn upper_bounds(mut self, upper_bounds: &'param [Option<f64>]) -> Self {
+        self.upper_bounds = Some(upper_bounds);
+        self
+    }
+
+    /// Make parameters constant, i.e. they will not be fitted.
+    pub fn constant(mut self, indexes: &'param [usize]) -> Self {
+        self.constant_parameters = Some(indexes);
+        self
+    }
+
+    /// Add optional loss function, if not given the trivial loss is assumed.
+    pub fn loss(mut self, loss: LossFunction) -> Self {
+        self.loss = Some(loss);
+        self
+    }
+
+    /// Build the [SurfaceFitProblem] instance. Returns [Err] if one of the mandatory fields is
+    /// missed or data slices have inconsistent lengths.
+    pub fn build(self) -> Result<SurfaceFitProblem<'cost>, CurveFitProblemBuildError> {
+        let func = self.func.ok_or(CurveFitProblemBuildError::FuncMissed)?;
+        let x = self.x.ok_or(CurveFitProblemBuildError::XMissed)?;
+        let y = self.y.ok_or(CurveFitProblemBuildError::YMissed)?;
+        let z = self.z.ok_or(CurveFitProblemBuildError::ZMissed)?;
+        let points: Vec<_> = if self.grid {
+            y.iter()
+                .flat_map(|&y| x.iter().map(move |&x| (x, y)))
+                .collect()
+        } else {
+            if x.len() != y.len() {
+                return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+            }
+            x.iter().copied().zip(y.iter().copied()).collect()
+        };
+        let n_obs = z.len();
+        if points.len() != n_obs {
+            return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+        }
+        if let Some(inverse_error) = self.inverse_error {
+            if inverse_error.len() != n_obs {
+                return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+            }
+        }
+        let mut nlls_parameters: Vec<ParameterBlock> = self
+            .parameters
+            .ok_or(CurveFitProblemBuildError::ParametersMissed)?
+            .iter()
+            .map(|&p| vec![p].into())
+            .collect();
+        if let Some(lower_bounds) = self.lower_bounds {
+            if lower_bounds.len() != nlls_parameters.len() {
+                return Err(CurveFitProblemBuildError::LowerBoundarySizeMismatch);
+            }
+            for (parameter, &lb) in nlls_parameters.iter_mut().zip(lower_bounds) {
+                if lb.is_some() {
+                    parameter.set_lower_bounds(vec![lb]);
+                }
+            }
+        }
+        if let Some(upper_bounds) = self.upper_bounds {
+            if upper_bounds.len() != nlls_parameters.len() {
+                return Err(CurveFitProblemBuildError::UpperBoundarySizeMismatch);
+            }
+            for (parameter, &ub) in nlls_parameters.iter_mut().zip(upper_bounds) {
+                if ub.is_some() {
+                    parameter.set_upper_bounds(vec![ub]);
+                }
+            }
+        }
+        let mut residual_block = NllsProblem::new().residual_block_builder().set_cost(
+            SurfaceFitProblem::cost_function(points, z, self.inverse_error, func),
+            n_obs,
+        );
+        if let Some(loss) = self.loss {
+            residual_block = residual_block.set_loss(loss);
+        }
+        let (mut problem, _block_id) = residual_block
+            .set_parameters(nlls_parameters)
+            .build_into_problem()
+            .unwrap();
+        if let Some(indexes) = self.constant_parameters {
+            for &i_param in indexes {
+                problem.set_parameter_block_constant(i_param)?;
+            }
+        }
+        Ok(SurfaceFitProblem {
+            problem,
+            z,
+            inverse_error: self.inverse_error,
+        })
+    }
+}
+
+impl Default for SurfaceFitProblemBuilder<'_, '_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use approx::assert_abs_diff_eq;
+
+    // z = a x + b y + c
+    fn plane(
+        x: f64,
+        y: f64,
+        parameters: &[f64],
+        z: &mut f64,
+        jacobians: Option<&mut [Option<f64>]>,
+    ) -> bool {
+        *z = parameters[0] * x + parameters[1] * y + parameters[2];
+        if let Some(jacobians) = jacobians {
+            for (jacobian, derivative) in jacobians.iter_mut().zip([x, y, 1.0]) {
+                if let Some(jacobian) = jacobian {
+                    *jacobian = derivative;
+                }
+            }
+        }
+        true
+    }
+
+    const TRUE_PARAMETERS: [f64; 3] = [2.0, -3.0, 0.5];
+
+    fn plane_value(x: f64, y: f64) -> f64 {
+        let mut z = 0.0;
+        plane(x, y, &TRUE_PARAMETERS, &mut z, None);
+        z
+    }
+
+    #[test]
+    fn scattered_points() {
+        let x: Vec<_> = (0..50).map(|i| f64::sin(i as f64)).collect();
+        let y: Vec<_> = (0..50).map(|i| f64::cos(3.0 * i as f64)).collect();
+        let z: Vec<_> = x.iter().zip(&y).map(|(&x, &y)| plane_value(x, y)).collect();
+
+        let func: SurfaceFunctionType = Box::new(plane);
+        let solution = SurfaceFitProblem::new(func, &x, &y, &z, &[0.0, 0.0, 0.0])
+            .solve_with_residuals(&SolverOptions::default());
+        assert_abs_diff_eq!(
+            &solution.parameters[..],
+            &TRUE_PARAMETERS[..],
+            epsilon = 1e-8
+        );
+        assert_eq!(solution.degrees_of_freedom, 47);
+        assert_abs_diff_eq!(&solution.y_fit.unwrap()[..], &z[..], epsilon = 1e-8);
+    }
+
+    #[test]
+    fn grid() {
+        let x = [0.0, 1.0, 2.0, 3.0];
+        let y = [10.0, 20.0];
+        let z: Vec<_> = y
+            .iter()
+            .flat_map(|&y| x.iter().map(move |&x| plane_value(x, y)))
+            .collect();
+        let inverse_error = vec![2.0; z.len()];
+
+        let func: SurfaceFunctionType = Box::new(plane);
+        let solution = SurfaceFitProblem::builder()
+            .func(func)
+            .grid(&x, &y, &z)
+            .inverse_error(&inverse_error)
+            .parameters(&[0.0, 0.0, 0.0])
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        assert_abs_diff_eq!(
+            &solution.parameters[..],
+            &TRUE_PARAMETERS[..],
+            epsilon = 1e-8
+        );
+
+        let func: SurfaceFunctionType = Box::new(plane);
+        let error = SurfaceFitProblem::builder()
+            .func(func)
+            .grid(&x, &y[..1], &z)
+            .parameters(&[0.0, 0.0, 0.0])
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            CurveFitProblemBuildError::DataSizesDontMatch
+        ));
+    }
+
+    #[test]
+    fn bounds_and_constant_parameters() {
+        let x: Vec<_> = (0..20).map(|i| i as f64).collect();
+        let y: Vec<_> = (0..20).map(|i| (i % 3) as f64).collect();
+        let z: Vec<_> = x.iter().zip(&y).map(|(&x, &y)| plane_value(x, y)).collect();
+
+        let func: SurfaceFunctionType = Box::new(plane);
+        let solution = SurfaceFitProblem::builder()
+            .func(func)
+            .points(&x, &y, &z)
+            .parameters(&[0.0, 0.0, 1.0])
+            .upper_bounds(&[Some(1.0), None, None])
+            .constant(&[2])
+            .build()
+            .unwrap()
+            .solve(&SolverOptions::default());
+        assert_abs_diff_eq!(solution.parameters[0], 1.0, epsilon = 1e-8);
+        assert_eq!(solution.parameters[2], 1.0);
+        assert_eq!(solution.degrees_of_freedom, 18);
+    }
+}
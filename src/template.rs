@@ -0,0 +1,236 @@
+//! Reusable curve fit problems for many datasets of the same size, see
+//! [CurveFitProblem1DBuilder::build_template](crate::curve_fit::CurveFitProblem1DBuilder::build_template).
+//!
+//! Building a [CurveFitProblem1D](crate::curve_fit::CurveFitProblem1D) creates the cost
+//! function, the parameter blocks, their bounds and manifolds, which may take longer than solving
+//! a small problem. [CurveFitTemplate] builds the problem once, and every
+//! [CurveFitTemplate::fit] copies the new data into it, resets the parameters to the initial
+//! guess and solves it again, e.g. for frames of a data stream.
+//!
+//! ```rust
+//! use ceres_solver::curve_fit::{AutoDiffCurveModel, CurveFitProblem1D};
+//! use ceres_solver::autodiff::Scalar;
+//! use ceres_solver::SolverOptions;
+//!
+//! // y = a exp(-b x)
+//! struct Decay;
+//!
+//! impl AutoDiffCurveModel for Decay {
+//!     fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+//!         parameters[0] * (-x * parameters[1]).exp()
+//!     }
+//! }
+//!
+//! let x: Vec<_> = (0..50).map(|i| 0.1 * i as f64).collect();
+//! let y = vec![0.0; x.len()];
+//! let mut template = CurveFitProblem1D::builder()
+//!     .autodiff_func(Decay)
+//!     .x(&x)
+//!     .y(&y)
+//!     .parameters(&[1.0, 1.0])
+//!     .lower_bounds(&[Some(0.0), Some(0.0)])
+//!     .build_template(SolverOptions::default())
+//!     .unwrap();
+//!
+//! for frame in 1..=10 {
+//!     let rate = 0.1 * frame as f64;
+//!     let y: Vec<_> = x.iter().map(|&x| 3.0 * f64::exp(-rate * x)).collect();
+//!     let solution = template.fit(&x, &y, None).unwrap();
+//!     assert!(f64::abs(solution.parameters[1] - rate) < 1e-6);
+//! }
+//! ```
+
+use crate::curve_fit::{
+    solve_problem, to_natural_parameters, CurveFitProblemSolution, CurveFunctionType,
+    ParameterTransform,
+};
+use crate::error::CurveFitProblemBuildError;
+use crate::nlls_problem::NllsProblem;
+use crate::solver::SolverOptions;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Data of the current fit of [CurveFitTemplate], shared with its model function.
+pub(crate) struct TemplateData {
+    pub(crate) x: Vec<f64>,
+    pub(crate) y: Vec<f64>,
+    pub(crate) inverse_error: Vec<f64>,
+}
+
+/// Wraps the model into a function of the data point index returning the negated weighted
+/// residual `(model(x) - y) * inverse_error`. The problem built with it for zero y values and
+/// unity inverse errors has the residuals `(y - model(x)) * inverse_error` for the current data.
+pub(crate) fn template_function(
+    data: Rc<RefCell<TemplateData>>,
+    func: CurveFunctionType,
+) -> CurveFunctionType {
+    Box::new(move |index, parameters, value, mut jacobians| {
+        let data = data.borrow();
+        let i = index as usize;
+        if !func(data.x[i], parameters, value, jacobians.as_deref_mut()) {
+            return false;
+        }
+        let w = data.inverse_error[i];
+        *value = w * (*value - data.y[i]);
+        for jacobian in jacobians.into_iter().flatten().flatten() {
+            *jacobian *= w;
+        }
+        true
+    })
+}
+
+/// A curve fit problem built once and solved for many datasets of the same size, see
+/// [template](crate::template) module docs. Create it with
+/// [CurveFitProblem1DBuilder::build_template](crate::curve_fit::CurveFitProblem1DBuilder::build_template).
+pub struct CurveFitTemplate {
+    problem: NllsProblem<'static>,
+    data: Rc<RefCell<TemplateData>>,
+    initial_parameters: Vec<f64>,
+    transforms: Vec<(usize, ParameterTransform)>,
+    options: SolverOptions,
+}
+
+impl CurveFitTemplate {
+    pub(crate) fn new(
+        problem: NllsProblem<'static>,
+        data: Rc<RefCell<TemplateData>>,
+        transforms: Vec<(usize, ParameterTransform)>,
+        options: SolverOptions,
+    ) -> Self {
+        let initial_parameters = problem
+            .parameter_blocks()
+            .iter()
+            .map(|block| block.values()[0])
+            .collect();
+        Self {
+            problem,
+            data,
+            initial_parameters,
+            transforms,
+            options,
+        }
+    }
+
+    /// Number of the data points of every dataset.
+    pub fn num_points(&self) -> usize {
+        self.data.borrow().x.len()
+    }
+
+    /// Solver options used by every fit.
+    pub fn options(&self) -> &SolverOptions {
+        &self.options
+    }
+
+    /// Fits the new data starting from the initial guess of the template. `inverse_error` has the
+    /// same meaning as [CurveFitProblem1DBuilder::inverse_error](crate::curve_fit::CurveFitProblem1DBuilder::inverse_error),
+    /// unity values are used if it is [None]. Returns [Err] if the data sizes differ from
+    /// [CurveFitTemplate::num_points].
+    pub fn fit(
+        &mut self,
+        x: &[f64],
+        y: &[f64],
+        inverse_error: Option<&[f64]>,
+    ) -> Result<CurveFitProblemSolution, CurveFitProblemBuildError> {
+        {
+            let mut data = self.data.borrow_mut();
+            let n_obs = data.x.len();
+            if x.len() != n_obs
+                || y.len() != n_obs
+                || inverse_error.map_or(false, |inverse_error| inverse_error.len() != n_obs)
+            {
+                return Err(CurveFitProblemBuildError::DataSizesDontMatch);
+            }
+            data.x.copy_from_slice(x);
+            data.y.copy_from_slice(y);
+            match inverse_error {
+                Some(inverse_error) => data.inverse_error.copy_from_slice(inverse_error),
+                None => data.inverse_error.fill(1.0),
+            }
+        }
+        for (i, &value) in self.initial_parameters.iter().enumerate() {
+            // We know that all the parameter blocks exist and have a single component
+            self.problem
+                .set_parameter_block_values(i, &[value])
+                .unwrap();
+        }
+        let mut solution = solve_problem(
+            &mut self.problem,
+            y,
+            inverse_error,
+            None,
+            &self.options,
+            false,
+            false,
+        );
+        to_natural_parameters(&self.transforms, &mut solution.parameters);
+        Ok(solution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::autodiff::Scalar;
+    use crate::curve_fit::{AutoDiffCurveModel, CurveFitProblem1D};
+    use crate::error::CurveFitProblemBuildError;
+    use crate::solver::SolverOptions;
+
+    use approx::assert_abs_diff_eq;
+
+    // y = a sin(b x) + c
+    struct Sine;
+
+    impl AutoDiffCurveModel for Sine {
+        fn model<T: Scalar>(&self, x: T, parameters: &[T]) -> T {
+            parameters[0] * (parameters[1] * x).sin() + parameters[2]
+        }
+    }
+
+    #[test]
+    fn template_matches_problem() {
+        let x: Vec<_> = (0..40).map(|i| 0.1 * i as f64).collect();
+        let inverse_error: Vec<_> = x.iter().map(|&x| 1.0 + x).collect();
+        let mut template = CurveFitProblem1D::builder()
+            .autodiff_func(Sine)
+            .x(&x)
+            .y(vec![0.0; x.len()])
+            .parameters(&[1.0, 2.0, 0.0])
+            .lower_bounds(&[Some(0.0), Some(1.0), None])
+            .constant(&[1])
+            .build_template(SolverOptions::default())
+            .unwrap();
+        assert_eq!(template.num_points(), 40);
+
+        for frame in 0..5 {
+            let amplitude = 0.5 + frame as f64;
+            let y: Vec<_> = x
+                .iter()
+                .map(|&x| amplitude * f64::sin(2.0 * x) - 1.0 + 0.01 * f64::cos(17.0 * x))
+                .collect();
+            let solution = template.fit(&x, &y, Some(&inverse_error)).unwrap();
+            let expected = CurveFitProblem1D::builder()
+                .autodiff_func(Sine)
+                .x(&x)
+                .y(&y)
+                .inverse_error(&inverse_error)
+                .parameters(&[1.0, 2.0, 0.0])
+                .lower_bounds(&[Some(0.0), Some(1.0), None])
+                .constant(&[1])
+                .build()
+                .unwrap()
+                .solve(&SolverOptions::default());
+            assert_abs_diff_eq!(
+                &solution.parameters[..],
+                &expected.parameters[..],
+                epsilon = 1e-8
+            );
+            assert_abs_diff_eq!(solution.chi2, expected.chi2, epsilon = 1e-10);
+            assert_eq!(solution.degrees_of_freedom, 38);
+        }
+
+        assert!(matches!(
+            template.fit(&x[1..], &x[1..], None),
+            Err(CurveFitProblemBuildError::DataSizesDontMatch)
+        ));
+    }
+}
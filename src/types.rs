@@ -2,6 +2,106 @@
 
 pub type JacobianType<'a> = Option<&'a mut [Option<&'a mut [&'a mut [f64]]>]>;
 
+/// Convenience wrapper of [JacobianType] for cost functions, writing to jacobian blocks which
+/// are not requested by the solver is a no-op.
+///
+/// ```rust
+/// use ceres_solver::types::JacobianView;
+/// use ceres_solver::CostFunctionType;
+///
+/// // r = [a x + b - y] for two points
+/// let cost: CostFunctionType = Box::new(|parameters, residuals, jacobians| {
+///     let (a, b) = (parameters[0][0], parameters[1][0]);
+///     let points = [(0.0, 1.0), (1.0, 3.0)];
+///     let mut jacobians = JacobianView::new(jacobians);
+///     for (i, &(x, y)) in points.iter().enumerate() {
+///         residuals[i] = a * x + b - y;
+///         jacobians.set(0, i, 0, x);
+///     }
+///     jacobians.fill_block(1, &[[1.0], [1.0]]);
+///     true
+/// });
+/// ```
+pub struct JacobianView<'a> {
+    inner: JacobianType<'a>,
+}
+
+impl<'a> JacobianView<'a> {
+    pub fn new(jacobians: JacobianType<'a>) -> Self {
+        Self { inner: jacobians }
+    }
+
+    /// Whether any jacobian is requested.
+    pub fn is_requested(&self) -> bool {
+        self.inner
+            .as_ref()
+            .map_or(false, |blocks| blocks.iter().any(Option::is_some))
+    }
+
+    /// Whether the jacobian of the given parameter block is requested.
+    pub fn is_block_requested(&self, param_idx: usize) -> bool {
+        self.inner
+            .as_ref()
+            .and_then(|blocks| blocks.get(param_idx))
+            .map_or(false, Option::is_some)
+    }
+
+    /// Jacobian of the parameter block as residual rows, [None] if it is not requested.
+    pub fn block(&mut self, param_idx: usize) -> Option<&mut [&'a mut [f64]]> {
+        self.inner.as_mut()?.get_mut(param_idx)?.as_deref_mut()
+    }
+
+    /// Set the derivative of the residual over the parameter component, no-op if the jacobian of
+    /// the parameter block is not requested.
+    ///
+    /// # Panics
+    /// If the residual or the component index is out of bounds.
+    pub fn set(&mut self, param_idx: usize, residual_idx: usize, component_idx: usize, value: f64) {
+        if let Some(block) = self.block(param_idx) {
+            block[residual_idx][component_idx] = value;
+        }
+    }
+
+    /// Copy the jacobian of the parameter block from the residual rows, no-op if it is not
+    /// requested.
+    ///
+    /// # Panics
+    /// If the matrix shape doesn't match the number of residuals and the parameter block size.
+    pub fn fill_block<R>(&mut self, param_idx: usize, matrix: &[R])
+    where
+        R: AsRef<[f64]>,
+    {
+        if let Some(block) = self.block(param_idx) {
+            assert_eq!(
+                block.len(),
+                matrix.len(),
+                "Number of matrix rows must be equal to the number of residuals"
+            );
+            for (row, values) in block.iter_mut().zip(matrix) {
+                row.copy_from_slice(values.as_ref());
+            }
+        }
+    }
+
+    /// Iterator over the requested jacobians with the parameter block indices.
+    pub fn requested_blocks(&mut self) -> impl Iterator<Item = (usize, &mut [&'a mut [f64]])> {
+        self.inner
+            .iter_mut()
+            .flat_map(|blocks| blocks.iter_mut().enumerate())
+            .filter_map(|(param_idx, block)| Some((param_idx, block.as_deref_mut()?)))
+    }
+
+    pub fn into_inner(self) -> JacobianType<'a> {
+        self.inner
+    }
+}
+
+impl<'a> From<JacobianType<'a>> for JacobianView<'a> {
+    fn from(jacobians: JacobianType<'a>) -> Self {
+        Self::new(jacobians)
+    }
+}
+
 pub(crate) enum Either<A, B> {
     Left(A),
     Right(B),
@@ -21,3 +121,36 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jacobian_view() {
+        let mut d_dx = [[0.0; 2]; 3];
+        let mut d_dx_rows: Vec<&mut [f64]> = d_dx.iter_mut().map(|row| &mut row[..]).collect();
+        let mut blocks = [Some(&mut d_dx_rows[..]), None];
+        let mut view = JacobianView::new(Some(&mut blocks));
+        assert!(view.is_requested());
+        assert!(view.is_block_requested(0));
+        assert!(!view.is_block_requested(1));
+        assert!(!view.is_block_requested(2));
+
+        view.fill_block(0, &[[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]]);
+        view.set(0, 2, 1, -6.0);
+        // Not requested
+        view.set(1, 0, 0, 1.0);
+        view.fill_block(1, &[[1.0]]);
+        let requested: Vec<_> = view
+            .requested_blocks()
+            .map(|(param_idx, block)| (param_idx, block.len()))
+            .collect();
+        assert_eq!(requested, [(0, 3)]);
+        assert_eq!(d_dx, [[1.0, 2.0], [3.0, 4.0], [5.0, -6.0]]);
+
+        let view = JacobianView::new(None);
+        assert!(!view.is_requested());
+        assert!(!view.is_block_requested(0));
+    }
+}